@@ -0,0 +1,97 @@
+//! Heap held by cached certificate paths before and after interning.
+//!
+//! Run with `cargo bench --bench cache_memory`. Simulates a fleet where each
+//! path holds a leaf certificate plus its intermediate, spread over a few
+//! backends, and reports the live heap of the certificate objects as a scan
+//! produces them and once their paths are interned the way the cache does.
+
+use chrono::Utc;
+use doomsday_rs::cache::PathInterner;
+use doomsday_rs::types::{CacheObject, PathObject};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+
+struct CountingAllocator;
+
+static LIVE_BYTES: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        LIVE_BYTES.fetch_add(layout.size(), Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        LIVE_BYTES.fetch_sub(layout.size(), Ordering::Relaxed);
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+const BACKENDS: [&str; 3] = [
+    "vault-production",
+    "credhub-production",
+    "opsmgr-production",
+];
+
+/// Two certificates per path, every string freshly allocated as a backend
+/// accessor returns it.
+fn scanned_fleet(paths: usize) -> Vec<CacheObject> {
+    let mut objects = Vec::with_capacity(paths * 2);
+    for i in 0..paths {
+        let backend = BACKENDS[i % BACKENDS.len()];
+        let path = format!("secret/deployments/service-{:06}/tls/certificate", i);
+        for role in ["leaf", "intermediate"] {
+            objects.push(CacheObject {
+                subject: format!("CN={}-{}.example.com", role, i),
+                not_before: None,
+                not_after: Utc::now(),
+                sha1: format!("{:040x}", i * 2 + (role == "leaf") as usize),
+                paths: vec![PathObject::new(backend.to_string(), path.clone())],
+                metadata: Default::default(),
+            });
+        }
+    }
+    objects
+}
+
+fn path_bytes(paths: usize) -> (usize, usize, f64) {
+    let baseline = LIVE_BYTES.load(Ordering::Relaxed);
+    let mut objects = scanned_fleet(paths);
+    let scanned = LIVE_BYTES.load(Ordering::Relaxed) - baseline;
+
+    let start = Instant::now();
+    let interner = PathInterner::new();
+    for object in &mut objects {
+        for path in &mut object.paths {
+            interner.intern(path);
+        }
+    }
+    let elapsed_ms = start.elapsed().as_secs_f64() * 1000.0;
+    let interned = LIVE_BYTES.load(Ordering::Relaxed) - baseline;
+
+    drop(objects);
+    drop(interner);
+    (scanned, interned, elapsed_ms)
+}
+
+fn main() {
+    println!(
+        "{:>8} {:>14} {:>14} {:>10} {:>10}",
+        "paths", "scanned (KiB)", "interned (KiB)", "saved", "intern ms"
+    );
+    for paths in [1_000, 10_000, 100_000] {
+        let (scanned, interned, elapsed_ms) = path_bytes(paths);
+        println!(
+            "{:>8} {:>14} {:>14} {:>9.1}% {:>10.1}",
+            paths,
+            scanned / 1024,
+            interned / 1024,
+            100.0 * (1.0 - interned as f64 / scanned as f64),
+            elapsed_ms
+        );
+    }
+}
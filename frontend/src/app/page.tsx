@@ -49,6 +49,7 @@ export default function Dashboard() {
     expired: groupedCertificates.expired.length,
     expiring_soon: groupedCertificates.expiring_soon.length,
     ok: groupedCertificates.ok.length,
+    not_yet_valid: groupedCertificates.not_yet_valid.length,
   };
 
   const handleRefresh = async () => {
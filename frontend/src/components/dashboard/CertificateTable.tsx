@@ -58,7 +58,14 @@ export function CertificateTable({ certificates, loading = false }: CertificateT
           comparison = new Date(a.not_after).getTime() - new Date(b.not_after).getTime();
           break;
         case 'status':
-          const statusOrder = { [CertStatus.EXPIRED]: 0, [CertStatus.EXPIRING_SOON]: 1, [CertStatus.OK]: 2 };
+          const statusOrder: Record<CertStatus, number> = {
+            [CertStatus.REVOKED]: 0,
+            [CertStatus.EXPIRED]: 1,
+            [CertStatus.EXPIRING_SOON]: 2,
+            [CertStatus.NOT_YET_VALID]: 3,
+            [CertStatus.OK]: 4,
+            [CertStatus.SUPERSEDED]: 5,
+          };
           comparison = statusOrder[a.status] - statusOrder[b.status];
           break;
       }
@@ -115,6 +122,7 @@ export function CertificateTable({ certificates, loading = false }: CertificateT
             <option value={CertStatus.EXPIRED}>Expired</option>
             <option value={CertStatus.EXPIRING_SOON}>Expiring Soon</option>
             <option value={CertStatus.OK}>OK</option>
+            <option value={CertStatus.NOT_YET_VALID}>Not Yet Valid</option>
           </select>
         </div>
       </div>
@@ -175,7 +183,8 @@ export function CertificateTable({ certificates, loading = false }: CertificateT
                     variant={
                       cert.status === CertStatus.OK
                         ? 'success'
-                        : cert.status === CertStatus.EXPIRING_SOON
+                        : cert.status === CertStatus.EXPIRING_SOON ||
+                          cert.status === CertStatus.NOT_YET_VALID
                         ? 'warning'
                         : 'error'
                     }
@@ -183,9 +192,27 @@ export function CertificateTable({ certificates, loading = false }: CertificateT
                   >
                     <span>{getStatusIcon(cert.status)}</span>
                     <span className="capitalize">
-                      {cert.status === CertStatus.EXPIRING_SOON ? 'Expiring Soon' : cert.status}
+                      {cert.status.replace(/_/g, ' ')}
                     </span>
                   </Badge>
+                  {cert.stale && (
+                    <Badge
+                      variant="secondary"
+                      className="mt-1"
+                      title="Every backend serving this certificate has been failing; it may be out of date"
+                    >
+                      stale
+                    </Badge>
+                  )}
+                  {cert.acknowledged && (
+                    <Badge
+                      variant="outline"
+                      className="mt-1"
+                      title={cert.acknowledged.comment || 'Notifications are silenced'}
+                    >
+                      acked until {formatDateTime(cert.acknowledged.until)}
+                    </Badge>
+                  )}
                 </td>
                 <td className="px-6 py-4">
                   <div className="text-sm font-medium text-gray-900">
@@ -201,6 +228,11 @@ export function CertificateTable({ certificates, loading = false }: CertificateT
                     <div className="text-xs text-gray-500">
                       {formatRelativeTime(cert.not_after)}
                     </div>
+                    {cert.status === CertStatus.NOT_YET_VALID && cert.not_before && (
+                      <div className="text-xs text-purple-700">
+                        valid from {formatDateTime(cert.not_before)}
+                      </div>
+                    )}
                   </div>
                 </td>
                 <td className="px-6 py-4">
@@ -1,6 +1,6 @@
 import { Card, CardContent, CardHeader, CardTitle } from '@/components/ui/card';
 import { CacheStats } from '@/types';
-import { Shield, AlertTriangle, Clock, CheckCircle } from 'lucide-react';
+import { Shield, AlertTriangle, Clock, CheckCircle, Hourglass } from 'lucide-react';
 
 interface StatsCardsProps {
   stats: CacheStats;
@@ -10,8 +10,8 @@ interface StatsCardsProps {
 export function StatsCards({ stats, loading = false }: StatsCardsProps) {
   if (loading) {
     return (
-      <div className="grid grid-cols-1 md:grid-cols-2 lg:grid-cols-4 gap-6">
-        {[...Array(4)].map((_, i) => (
+      <div className="grid grid-cols-1 md:grid-cols-2 lg:grid-cols-5 gap-6">
+        {[...Array(5)].map((_, i) => (
           <Card key={i} className="animate-pulse">
             <CardHeader className="pb-2">
               <div className="h-4 bg-gray-200 rounded w-3/4"></div>
@@ -54,10 +54,17 @@ export function StatsCards({ stats, loading = false }: StatsCardsProps) {
       color: 'text-green-600',
       bgColor: 'bg-green-50',
     },
+    {
+      title: 'Not Yet Valid',
+      value: stats.not_yet_valid ?? 0,
+      icon: Hourglass,
+      color: 'text-purple-600',
+      bgColor: 'bg-purple-50',
+    },
   ];
 
   return (
-    <div className="grid grid-cols-1 md:grid-cols-2 lg:grid-cols-4 gap-6">
+    <div className="grid grid-cols-1 md:grid-cols-2 lg:grid-cols-5 gap-6">
       {cards.map((card) => {
         const Icon = card.icon;
         return (
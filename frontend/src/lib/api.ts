@@ -1,11 +1,20 @@
 import { 
   CacheItem, 
+  CertificateChain, 
   InfoResponse, 
   AuthRequest, 
   AuthResponse, 
   SchedulerInfo, 
   RefreshRequest, 
-  PopulateStats 
+  PopulateStats,
+  RotationEvent,
+  CacheChanges,
+  CaImpact,
+  AlertAck,
+  NotificationDelivery,
+  DeadLetter,
+  NotificationTestResult,
+  HookRun
 } from '@/types';
 
 const API_BASE = process.env.NEXT_PUBLIC_API_URL || '/api';
@@ -71,15 +80,102 @@ export const api = {
   getCertificates: (params?: {
     beyond?: string;
     within?: string;
+    backends?: string[];
   }): Promise<CacheItem[]> => {
     const searchParams = new URLSearchParams();
     if (params?.beyond) searchParams.set('beyond', params.beyond);
     if (params?.within) searchParams.set('within', params.within);
+    params?.backends?.forEach((backend) => searchParams.append('backend', backend));
     
     const query = searchParams.toString();
     return apiRequest(`/cache${query ? `?${query}` : ''}`);
   },
 
+  searchCertificates: (q: string): Promise<CacheItem[]> =>
+    apiRequest(`/cache/search?q=${encodeURIComponent(q)}`),
+
+  getCertificate: (sha1: string): Promise<CacheItem> =>
+    apiRequest(`/cache/${encodeURIComponent(sha1)}`),
+
+  getCertificateChain: (sha1: string): Promise<CertificateChain> =>
+    apiRequest(`/cache/${encodeURIComponent(sha1)}/chain`),
+
+  getCacheChanges: (since?: string): Promise<CacheChanges> =>
+    apiRequest(`/cache/changes${since ? `?since=${encodeURIComponent(since)}` : ''}`),
+
+  getCaImpact: (within?: string): Promise<CaImpact[]> =>
+    apiRequest(`/cache/ca-impact${within ? `?within=${encodeURIComponent(within)}` : ''}`),
+
+  getRotationHistory: (params?: { backend?: string; path?: string; limit?: number }): Promise<RotationEvent[]> => {
+    const searchParams = new URLSearchParams();
+    if (params?.backend) searchParams.set('backend', params.backend);
+    if (params?.path) searchParams.set('path', params.path);
+    if (params?.limit) searchParams.set('limit', params.limit.toString());
+
+    const query = searchParams.toString();
+    return apiRequest(`/history${query ? `?${query}` : ''}`);
+  },
+
+  getNotificationHistory: (params?: {
+    backend?: string;
+    sha1?: string;
+    status?: NotificationDelivery['status'];
+    limit?: number;
+  }): Promise<NotificationDelivery[]> => {
+    const searchParams = new URLSearchParams();
+    if (params?.backend) searchParams.set('backend', params.backend);
+    if (params?.sha1) searchParams.set('sha1', params.sha1);
+    if (params?.status) searchParams.set('status', params.status);
+    if (params?.limit) searchParams.set('limit', params.limit.toString());
+
+    const query = searchParams.toString();
+    return apiRequest(`/notifications/history${query ? `?${query}` : ''}`);
+  },
+
+  getDeadLetters: (params?: { backend?: string; limit?: number }): Promise<DeadLetter[]> => {
+    const searchParams = new URLSearchParams();
+    if (params?.backend) searchParams.set('backend', params.backend);
+    if (params?.limit) searchParams.set('limit', params.limit.toString());
+
+    const query = searchParams.toString();
+    return apiRequest(`/notifications/dead-letters${query ? `?${query}` : ''}`);
+  },
+
+  sendTestNotification: (backend?: string): Promise<NotificationTestResult[]> => {
+    const query = backend ? `?backend=${encodeURIComponent(backend)}` : '';
+    return apiRequest(`/notifications/test${query}`, {
+      method: 'POST',
+    });
+  },
+
+  getHookHistory: (params?: { hook?: string; sha1?: string; limit?: number }): Promise<HookRun[]> => {
+    const searchParams = new URLSearchParams();
+    if (params?.hook) searchParams.set('hook', params.hook);
+    if (params?.sha1) searchParams.set('sha1', params.sha1);
+    if (params?.limit) searchParams.set('limit', params.limit.toString());
+
+    const query = searchParams.toString();
+    return apiRequest(`/hooks/history${query ? `?${query}` : ''}`);
+  },
+
+  // Alert acknowledgements
+  getAlertAcks: (): Promise<AlertAck[]> =>
+    apiRequest('/alerts/acks'),
+
+  acknowledgeAlerts: (sha1: string, params?: { snooze?: string; comment?: string }): Promise<AlertAck> => {
+    const searchParams = new URLSearchParams();
+    if (params?.snooze) searchParams.set('snooze', params.snooze);
+    if (params?.comment) searchParams.set('comment', params.comment);
+
+    const query = searchParams.toString();
+    return apiRequest(`/alerts/${encodeURIComponent(sha1)}/ack${query ? `?${query}` : ''}`, {
+      method: 'POST',
+    });
+  },
+
+  removeAlertAck: (sha1: string): Promise<AlertAck> =>
+    apiRequest(`/alerts/${encodeURIComponent(sha1)}/ack`, { method: 'DELETE' }),
+
   refreshCache: (request?: RefreshRequest): Promise<PopulateStats> =>
     apiRequest('/cache/refresh', {
       method: 'POST',
@@ -89,6 +185,12 @@ export const api = {
   // Scheduler endpoint
   getSchedulerInfo: (): Promise<SchedulerInfo> =>
     apiRequest('/scheduler'),
+
+  pauseScheduler: (): Promise<SchedulerInfo> =>
+    apiRequest('/scheduler/pause', { method: 'POST' }),
+
+  resumeScheduler: (): Promise<SchedulerInfo> =>
+    apiRequest('/scheduler/resume', { method: 'POST' }),
 };
 
 export { ApiError };
@@ -60,7 +60,8 @@ export function getCertificateStatus(daysUntilExpiry: number): CertStatus {
 export function addStatusToCertificates(certificates: CacheItem[]): CertificateWithStatus[] {
   return certificates.map(cert => {
     const daysUntilExpiry = getDaysUntilExpiry(cert.not_after);
-    const status = getCertificateStatus(daysUntilExpiry);
+    // The server computes status centrally; only fall back for older servers
+    const status = cert.status ?? getCertificateStatus(daysUntilExpiry);
     
     return {
       ...cert,
@@ -77,7 +78,10 @@ export function getStatusColor(status: CertStatus): string {
     case CertStatus.EXPIRING_SOON:
       return 'text-orange-700 bg-orange-50 border-orange-200';
     case CertStatus.EXPIRED:
+    case CertStatus.REVOKED:
       return 'text-red-700 bg-red-50 border-red-200';
+    case CertStatus.NOT_YET_VALID:
+      return 'text-purple-700 bg-purple-50 border-purple-200';
     default:
       return 'text-gray-700 bg-gray-50 border-gray-200';
   }
@@ -91,6 +95,12 @@ export function getStatusIcon(status: CertStatus): string {
       return '⏰';
     case CertStatus.EXPIRED:
       return '⚠️';
+    case CertStatus.REVOKED:
+      return '⛔';
+    case CertStatus.NOT_YET_VALID:
+      return '🕓';
+    case CertStatus.SUPERSEDED:
+      return '🔁';
     default:
       return '❓';
   }
@@ -134,6 +144,7 @@ export function groupCertificatesByStatus(certificates: CertificateWithStatus[])
     expired: certificates.filter(cert => cert.status === CertStatus.EXPIRED),
     expiring_soon: certificates.filter(cert => cert.status === CertStatus.EXPIRING_SOON),
     ok: certificates.filter(cert => cert.status === CertStatus.OK),
+    not_yet_valid: certificates.filter(cert => cert.status === CertStatus.NOT_YET_VALID),
   };
 
   return groups;
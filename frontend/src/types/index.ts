@@ -1,7 +1,120 @@
 export interface CacheItem {
+  sha1?: string;
   subject: string;
+  not_before?: string;
   not_after: string;
   paths: PathObject[];
+  status?: CertStatus;
+  stale?: boolean;
+  labels?: Record<string, string>;
+  acknowledged?: AlertAck | null;
+  issuer?: string;
+  serial_number?: string;
+  subject_alt_names?: string[];
+  fingerprint_sha256?: string;
+  key_algorithm?: string;
+  key_size?: number;
+  key_usage?: string[];
+  ext_key_usage?: string[];
+  is_ca?: boolean;
+  subject_key_id?: string;
+  authority_key_id?: string;
+}
+
+export interface AlertAck {
+  sha1: string;
+  subject: string;
+  acknowledged_at: string;
+  until: string;
+  comment?: string | null;
+}
+
+export interface NotificationDelivery {
+  attempted_at: string;
+  backend: string;
+  title: string;
+  urgency: 'low' | 'normal' | 'high' | 'critical';
+  certificates: { sha1: string; subject: string }[];
+  attempt: number;
+  status: 'sent' | 'failed' | 'held';
+  error?: string | null;
+}
+
+export interface DeadLetter {
+  failed_at: string;
+  backend: string;
+  title: string;
+  body: string;
+  urgency: NotificationDelivery['urgency'];
+  certificates: NotificationDelivery['certificates'];
+  attempts: number;
+  error: string;
+}
+
+export interface NotificationTestResult {
+  backend: string;
+  recipients: string[];
+  success: boolean;
+  error: string | null;
+}
+
+export interface HookRun {
+  ran_at: string;
+  hook: string;
+  action: 'script' | 'webhook' | 'task';
+  sha1: string;
+  subject: string;
+  success: boolean;
+  error: string | null;
+}
+
+export interface ChainLink extends CacheItem {
+  outlives_issuer: boolean;
+}
+
+export interface CertificateChain {
+  chain: ChainLink[];
+  complete: boolean;
+  issued: CacheItem[];
+}
+
+export interface CaImpact extends CacheItem {
+  leaves: number;
+  intermediates: number;
+  leaves_outliving: number;
+}
+
+export interface CacheChange {
+  at: string;
+  added: CacheItem[];
+  removed: CacheItem[];
+  renewed: CertificateRenewal[];
+}
+
+export interface CacheChanges {
+  complete: boolean;
+  changes: CacheChange[];
+}
+
+export interface CertificateRenewal {
+  backend: string;
+  path: string;
+  subject: string;
+  old_sha1: string;
+  old_not_after: string;
+  new_sha1: string;
+  new_not_after: string;
+}
+
+export interface RotationEvent {
+  rotated_at: string;
+  backend: string;
+  path: string;
+  subject: string;
+  old_sha1: string;
+  old_not_after: string;
+  new_sha1: string;
+  new_not_after: string;
 }
 
 export interface PathObject {
@@ -18,6 +131,25 @@ export interface PopulateStats {
 export interface InfoResponse {
   version: string;
   auth_required: boolean;
+  degraded?: boolean;
+  last_refresh?: RefreshSummary | null;
+}
+
+export interface RefreshSummary {
+  finished_at: string;
+  duration_ms: number;
+  num_certs: number;
+  num_paths: number;
+  backends: BackendRefreshStats[];
+}
+
+export interface BackendRefreshStats {
+  name: string;
+  finished_at: string;
+  duration_ms: number;
+  num_certs: number;
+  num_paths: number;
+  error: string | null;
 }
 
 export interface AuthRequest {
@@ -34,6 +166,20 @@ export interface SchedulerInfo {
   workers: number;
   pending_tasks: number;
   running_tasks: number;
+  paused: boolean;
+  task_stats: TaskTypeStats[];
+}
+
+export interface TaskTypeStats {
+  task_type: string;
+  backend: string;
+  succeeded: number;
+  failed: number;
+  total_duration_ms: number;
+  avg_duration_ms: number;
+  p50_duration_ms: number;
+  p95_duration_ms: number;
+  p99_duration_ms: number;
 }
 
 export interface RefreshRequest {
@@ -45,12 +191,19 @@ export interface CacheStats {
   ok: number;
   expiring_soon: number;
   expired: number;
+  not_yet_valid?: number;
+  revoked?: number;
+  superseded?: number;
+  stale?: number;
 }
 
 export enum CertStatus {
   OK = 'ok',
   EXPIRING_SOON = 'expiring_soon', 
   EXPIRED = 'expired',
+  NOT_YET_VALID = 'not_yet_valid',
+  REVOKED = 'revoked',
+  SUPERSEDED = 'superseded',
 }
 
 export interface CertificateWithStatus extends CacheItem {
@@ -0,0 +1,289 @@
+use crate::json_store::JsonStore;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+
+/// Reminders fall due this many minutes early, so a daily digest is not
+/// pushed back a day by the time the previous run took to send.
+const REMINDER_SLACK_MINUTES: i64 = 60;
+
+/// How often to remind about a certificate that was already alerted at the
+/// same stage. The rule with the smallest `within` covering the certificate
+/// applies; a rule without `within` covers every certificate.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ReminderRule {
+    /// Days until expiry at or below which the rule applies.
+    pub within: Option<i64>,
+    /// Days between reminders.
+    pub every: u64,
+}
+
+impl ReminderRule {
+    pub fn validate(&self) -> crate::Result<()> {
+        if self.every == 0 {
+            return Err(crate::DoomsdayError::config(
+                "Reminder interval (every) must be at least 1 day",
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Time between reminders for a certificate with `days_left` until expiry,
+/// or `None` if it is only alerted once per stage.
+pub fn reminder_interval(rules: &[ReminderRule], days_left: i64) -> Option<Duration> {
+    rules
+        .iter()
+        .filter(|rule| rule.within.is_none_or(|within| days_left <= within))
+        .min_by_key(|rule| rule.within.unwrap_or(i64::MAX))
+        .map(|rule| Duration::days(rule.every as i64))
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct AlertRecord {
+    stage: String,
+    sent_at: DateTime<Utc>,
+}
+
+/// The stage (e.g. `expired`) each certificate was last alerted at, by SHA1,
+/// so scheduled digests only repeat an alert when a reminder is due. Kept
+/// across restarts when backed by a file, so a restart does not re-send
+/// everything.
+#[derive(Debug, Clone, Default)]
+pub struct AlertLog {
+    records: JsonStore<HashMap<String, AlertRecord>>,
+}
+
+impl AlertLog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn load(path: PathBuf) -> crate::Result<Self> {
+        let records: JsonStore<HashMap<String, AlertRecord>> = JsonStore::load(path.clone())?;
+        tracing::info!(
+            "Loaded {} alerted certificates from {}",
+            records.read().len(),
+            path.display()
+        );
+        Ok(AlertLog { records })
+    }
+
+    /// Whether a certificate should be alerted at `stage`: it has not been
+    /// alerted at that stage yet, or `reminder` has passed since it was.
+    pub fn is_due(
+        &self,
+        sha1: &str,
+        stage: &str,
+        reminder: Option<Duration>,
+        now: DateTime<Utc>,
+    ) -> bool {
+        let records = self.records.read();
+        let Some(record) = records.get(sha1).filter(|r| r.stage == stage) else {
+            return true;
+        };
+        reminder.is_some_and(|every| {
+            now - record.sent_at >= every - Duration::minutes(REMINDER_SLACK_MINUTES)
+        })
+    }
+
+    pub fn record<'a>(
+        &self,
+        sha1s: impl IntoIterator<Item = &'a str>,
+        stage: &str,
+        now: DateTime<Utc>,
+    ) -> crate::Result<()> {
+        self.records.update(|records| {
+            for sha1 in sha1s {
+                records.insert(
+                    sha1.to_string(),
+                    AlertRecord {
+                        stage: stage.to_string(),
+                        sent_at: now,
+                    },
+                );
+            }
+        })
+    }
+
+    /// Forgets certificates outside `alerting`, so one that needs alerting
+    /// again later (e.g. after its status thresholds change) is alerted
+    /// afresh.
+    pub fn retain(&self, alerting: &HashSet<String>) -> crate::Result<()> {
+        if self
+            .records
+            .read()
+            .keys()
+            .all(|sha1| alerting.contains(sha1))
+        {
+            return Ok(());
+        }
+        self.records
+            .update(|records| records.retain(|sha1, _| alerting.contains(sha1)))
+    }
+
+    /// Writes records not yet saved. Blocks on the disk.
+    pub fn flush(&self) -> crate::Result<()> {
+        self.records.flush()
+    }
+}
+
+/// An operator's acknowledgement of a certificate, silencing its
+/// notifications until `until`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AlertAck {
+    pub sha1: String,
+    pub subject: String,
+    pub acknowledged_at: DateTime<Utc>,
+    pub until: DateTime<Utc>,
+    pub comment: Option<String>,
+}
+
+/// Acknowledged certificates, by SHA1. Acks lapse on their own at `until`.
+#[derive(Debug, Clone, Default)]
+pub struct AlertAcks {
+    entries: JsonStore<HashMap<String, AlertAck>>,
+}
+
+impl AlertAcks {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn load(path: PathBuf) -> crate::Result<Self> {
+        let entries: JsonStore<HashMap<String, AlertAck>> = JsonStore::load(path.clone())?;
+        tracing::info!(
+            "Loaded {} alert acknowledgements from {}",
+            entries.read().len(),
+            path.display()
+        );
+        Ok(AlertAcks { entries })
+    }
+
+    /// Records `ack`, replacing any earlier one for the certificate, and
+    /// drops acks that have lapsed.
+    pub fn acknowledge(&self, ack: AlertAck) -> crate::Result<()> {
+        tracing::info!(
+            "Alerts for {} ({}) acknowledged until {}",
+            ack.sha1,
+            ack.subject,
+            ack.until
+        );
+        let now = Utc::now();
+        self.entries.update(|entries| {
+            entries.retain(|_, existing| existing.until > now);
+            entries.insert(ack.sha1.clone(), ack);
+        })
+    }
+
+    pub fn remove(&self, sha1: &str) -> crate::Result<Option<AlertAck>> {
+        if !self.entries.read().contains_key(sha1) {
+            return Ok(None);
+        }
+        self.entries.update(|entries| entries.remove(sha1))
+    }
+
+    /// The certificate's ack, unless it has lapsed.
+    pub fn active(&self, sha1: &str, now: DateTime<Utc>) -> Option<AlertAck> {
+        self.entries
+            .read()
+            .get(sha1)
+            .filter(|ack| ack.until > now)
+            .cloned()
+    }
+
+    /// Acks that have not lapsed, soonest to lapse first.
+    pub fn list(&self) -> Vec<AlertAck> {
+        let now = Utc::now();
+        let mut acks: Vec<AlertAck> = self
+            .entries
+            .read()
+            .values()
+            .filter(|ack| ack.until > now)
+            .cloned()
+            .collect();
+        acks.sort_by_key(|ack| ack.until);
+        acks
+    }
+
+    /// Writes acks not yet saved. Blocks on the disk.
+    pub fn flush(&self) -> crate::Result<()> {
+        self.entries.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reminder_cadence() {
+        // Every 3 days, daily in the final week
+        let rules = vec![
+            ReminderRule {
+                within: None,
+                every: 3,
+            },
+            ReminderRule {
+                within: Some(7),
+                every: 1,
+            },
+        ];
+        assert_eq!(reminder_interval(&rules, 20), Some(Duration::days(3)));
+        assert_eq!(reminder_interval(&rules, 7), Some(Duration::days(1)));
+        assert_eq!(reminder_interval(&rules, -2), Some(Duration::days(1)));
+        assert_eq!(reminder_interval(&[], 5), None);
+
+        let log = AlertLog::new();
+        let sent = Utc::now();
+        assert!(log.is_due("abc", "expiring_soon", None, sent));
+        log.record(["abc"], "expiring_soon", sent).unwrap();
+
+        // Identical alerts are not repeated until a reminder is due
+        assert!(!log.is_due("abc", "expiring_soon", None, sent + Duration::days(30)));
+        let daily = Some(Duration::days(1));
+        assert!(!log.is_due("abc", "expiring_soon", daily, sent + Duration::hours(12)));
+        assert!(log.is_due(
+            "abc",
+            "expiring_soon",
+            daily,
+            sent + Duration::hours(23) + Duration::minutes(59)
+        ));
+        // Reaching a new stage alerts straight away
+        assert!(log.is_due("abc", "expired", None, sent + Duration::hours(1)));
+
+        log.retain(&HashSet::new()).unwrap();
+        assert!(log.is_due("abc", "expiring_soon", None, sent));
+    }
+
+    #[test]
+    fn test_acks_lapse() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("acks.json");
+        let now = Utc::now();
+        let ack = |sha1: &str, until| AlertAck {
+            sha1: sha1.to_string(),
+            subject: "CN=example.com".to_string(),
+            acknowledged_at: now,
+            until,
+            comment: Some("rotation scheduled".to_string()),
+        };
+
+        let acks = AlertAcks::load(path.clone()).unwrap();
+        acks.acknowledge(ack("abc", now + Duration::days(3)))
+            .unwrap();
+        acks.acknowledge(ack("def", now - Duration::minutes(1)))
+            .unwrap();
+
+        assert!(acks.active("abc", now).is_some());
+        assert!(acks.active("abc", now + Duration::days(4)).is_none());
+        assert!(acks.active("def", now).is_none());
+
+        let reloaded = AlertAcks::load(path).unwrap();
+        assert_eq!(reloaded.list().len(), 1);
+        assert!(reloaded.remove("abc").unwrap().is_some());
+        assert!(reloaded.list().is_empty());
+    }
+}
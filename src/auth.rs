@@ -1,10 +1,11 @@
-use crate::config::AuthConfig;
+use crate::config::{AuthConfig, AuthFallbackConfig};
 use crate::types::{AuthRequest, AuthResponse};
 use async_trait::async_trait;
 use chrono::{DateTime, Duration, Utc};
 use dashmap::DashMap;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 use uuid::Uuid;
 
@@ -13,10 +14,24 @@ pub trait AuthProvider: Send + Sync {
     async fn authenticate(&self, request: &AuthRequest) -> crate::Result<AuthResponse>;
     async fn validate_token(&self, token: &str) -> crate::Result<bool>;
     async fn revoke_token(&self, token: &str) -> crate::Result<()>;
+    /// Whether the (already validated) token may perform administrative actions.
+    async fn is_admin(&self, token: &str) -> crate::Result<bool>;
     fn requires_auth(&self) -> bool;
 }
 
 pub fn create_auth_provider(config: &AuthConfig) -> crate::Result<Arc<dyn AuthProvider>> {
+    let primary = create_primary_auth_provider(config)?;
+
+    match &config.fallback {
+        Some(fallback) => {
+            tracing::info!("Break-glass authentication fallback configured");
+            Ok(Arc::new(FallbackAuthProvider::new(primary, fallback)?))
+        }
+        None => Ok(primary),
+    }
+}
+
+fn create_primary_auth_provider(config: &AuthConfig) -> crate::Result<Arc<dyn AuthProvider>> {
     tracing::info!("Creating authentication provider: {}", config.auth_type);
 
     match config.auth_type.as_str() {
@@ -63,6 +78,10 @@ impl AuthProvider for NopAuthProvider {
         Ok(())
     }
 
+    async fn is_admin(&self, _token: &str) -> crate::Result<bool> {
+        Ok(true) // Everyone is an administrator without authentication
+    }
+
     fn requires_auth(&self) -> bool {
         false
     }
@@ -79,6 +98,7 @@ struct SessionInfo {
 #[derive(Debug)]
 pub struct UserPassAuthProvider {
     users: HashMap<String, String>, // username -> password hash
+    admins: HashSet<String>,        // empty means every user is an admin
     sessions: Arc<DashMap<String, SessionInfo>>,
     session_timeout: Duration,
     refresh_on_use: bool,
@@ -92,12 +112,18 @@ impl UserPassAuthProvider {
     ) -> Self {
         UserPassAuthProvider {
             users,
+            admins: HashSet::new(),
             sessions: Arc::new(DashMap::new()),
             session_timeout,
             refresh_on_use,
         }
     }
 
+    pub fn with_admins(mut self, admins: HashSet<String>) -> Self {
+        self.admins = admins;
+        self
+    }
+
     pub fn from_config(properties: &HashMap<String, serde_yaml::Value>) -> crate::Result<Self> {
         tracing::debug!("Configuring username/password authentication from properties");
 
@@ -108,7 +134,7 @@ impl UserPassAuthProvider {
                 crate::DoomsdayError::config("userpass auth requires users configuration")
             })?;
 
-        let mut users = HashMap::new();
+        let mut plaintext_users = HashMap::new();
         tracing::info!(
             "Configuring {} users for authentication",
             users_config.len()
@@ -122,16 +148,11 @@ impl UserPassAuthProvider {
                 .as_str()
                 .ok_or_else(|| crate::DoomsdayError::config("Password must be a string"))?;
 
-            tracing::debug!("Hashing password for user: {}", username_str);
-            // Hash the password
-            let password_hash = bcrypt::hash(password_str, bcrypt::DEFAULT_COST).map_err(|e| {
-                crate::DoomsdayError::auth(format!("Failed to hash password: {}", e))
-            })?;
-
-            users.insert(username_str.to_string(), password_hash);
-            tracing::debug!("User {} configured successfully", username_str);
+            plaintext_users.insert(username_str.to_string(), password_str.to_string());
         }
 
+        let users = Self::hash_passwords(&plaintext_users)?;
+
         let session_timeout_minutes = properties
             .get("session_timeout")
             .and_then(|v| v.as_u64())
@@ -142,17 +163,62 @@ impl UserPassAuthProvider {
             .and_then(|v| v.as_bool())
             .unwrap_or(true);
 
+        let admins: HashSet<String> = match properties.get("admins") {
+            Some(value) => value
+                .as_sequence()
+                .ok_or_else(|| crate::DoomsdayError::config("admins must be a list of usernames"))?
+                .iter()
+                .map(|v| {
+                    v.as_str().map(|s| s.to_string()).ok_or_else(|| {
+                        crate::DoomsdayError::config("Admin username must be a string")
+                    })
+                })
+                .collect::<crate::Result<_>>()?,
+            None => HashSet::new(),
+        };
+
+        for admin in &admins {
+            if !users.contains_key(admin) {
+                return Err(crate::DoomsdayError::config(format!(
+                    "Admin {} is not a configured user",
+                    admin
+                )));
+            }
+        }
+
         tracing::info!(
-            "Authentication configured: session_timeout={}min, refresh_on_use={}",
+            "Authentication configured: session_timeout={}min, refresh_on_use={}, admins={}",
             session_timeout_minutes,
-            refresh_on_use
+            refresh_on_use,
+            if admins.is_empty() {
+                "all users".to_string()
+            } else {
+                admins.len().to_string()
+            }
         );
 
         Ok(UserPassAuthProvider::new(
             users,
             Duration::minutes(session_timeout_minutes as i64),
             refresh_on_use,
-        ))
+        )
+        .with_admins(admins))
+    }
+
+    fn hash_passwords(users: &HashMap<String, String>) -> crate::Result<HashMap<String, String>> {
+        let mut hashed = HashMap::new();
+
+        for (username, password) in users {
+            tracing::debug!("Hashing password for user: {}", username);
+            let password_hash = bcrypt::hash(password, bcrypt::DEFAULT_COST).map_err(|e| {
+                crate::DoomsdayError::auth(format!("Failed to hash password: {}", e))
+            })?;
+
+            hashed.insert(username.clone(), password_hash);
+            tracing::debug!("User {} configured successfully", username);
+        }
+
+        Ok(hashed)
     }
 
     fn cleanup_expired_sessions(&self) {
@@ -256,7 +322,285 @@ impl AuthProvider for UserPassAuthProvider {
         Ok(())
     }
 
+    async fn is_admin(&self, token: &str) -> crate::Result<bool> {
+        let Some(session) = self.sessions.get(token) else {
+            return Ok(false);
+        };
+
+        if session.expires_at < Utc::now() {
+            return Ok(false);
+        }
+
+        Ok(self.admins.is_empty() || self.admins.contains(&session.username))
+    }
+
     fn requires_auth(&self) -> bool {
         true
     }
 }
+
+/// Wraps the primary provider with break-glass credentials that only become
+/// usable once the primary has failed twice in a row with something other
+/// than a credential rejection (i.e. it is unreachable). The in-process
+/// providers only ever accept or reject, so behind them the fallback stays
+/// unused. Every fallback use is written to the `audit` log target.
+pub struct FallbackAuthProvider {
+    primary: Arc<dyn AuthProvider>,
+    local_users: Option<UserPassAuthProvider>,
+    emergency_token_hash: Option<Vec<u8>>,
+}
+
+impl FallbackAuthProvider {
+    pub fn new(primary: Arc<dyn AuthProvider>, config: &AuthFallbackConfig) -> crate::Result<Self> {
+        let local_users = if config.users.is_empty() {
+            None
+        } else {
+            let users = UserPassAuthProvider::hash_passwords(&config.users)?;
+            let session_timeout = Duration::minutes(config.session_timeout.unwrap_or(60) as i64);
+            Some(UserPassAuthProvider::new(users, session_timeout, false))
+        };
+
+        let emergency_token_hash = config
+            .emergency_token
+            .as_deref()
+            .map(|token| Sha256::digest(token.as_bytes()).to_vec());
+
+        Ok(FallbackAuthProvider {
+            primary,
+            local_users,
+            emergency_token_hash,
+        })
+    }
+
+    /// Errors that mean the primary provider answered and said no; these must
+    /// never unlock the fallback.
+    fn is_rejection(error: &crate::DoomsdayError) -> bool {
+        matches!(
+            error,
+            crate::DoomsdayError::Auth(_) | crate::DoomsdayError::PermissionDenied(_)
+        )
+    }
+
+    fn matches_emergency_token(&self, token: &str) -> bool {
+        let Some(expected) = &self.emergency_token_hash else {
+            return false;
+        };
+
+        // Compare fixed-length digests so the check does not leak the token length
+        let actual = Sha256::digest(token.as_bytes());
+        expected
+            .iter()
+            .zip(actual.iter())
+            .fold(0u8, |acc, (a, b)| acc | (a ^ b))
+            == 0
+    }
+}
+
+#[async_trait]
+impl AuthProvider for FallbackAuthProvider {
+    async fn authenticate(&self, request: &AuthRequest) -> crate::Result<AuthResponse> {
+        let error = match self.primary.authenticate(request).await {
+            Ok(response) => return Ok(response),
+            Err(e) if Self::is_rejection(&e) => return Err(e),
+            Err(e) => e,
+        };
+
+        tracing::warn!(
+            "Primary auth provider failed ({}), retrying once for user {}",
+            error,
+            request.username
+        );
+
+        let error = match self.primary.authenticate(request).await {
+            Ok(response) => return Ok(response),
+            Err(e) if Self::is_rejection(&e) => return Err(e),
+            Err(e) => e,
+        };
+
+        let local_users = self.local_users.as_ref().ok_or_else(|| {
+            crate::DoomsdayError::auth(format!("Primary auth provider unavailable: {}", error))
+        })?;
+
+        tracing::warn!(
+            target: "audit",
+            "BREAK-GLASS: primary auth provider unreachable ({}), attempting fallback login for user {}",
+            error,
+            request.username
+        );
+
+        let result = local_users.authenticate(request).await;
+        match &result {
+            Ok(_) => tracing::warn!(
+                target: "audit",
+                "BREAK-GLASS: fallback login GRANTED for user {}",
+                request.username
+            ),
+            Err(_) => tracing::warn!(
+                target: "audit",
+                "BREAK-GLASS: fallback login DENIED for user {}",
+                request.username
+            ),
+        }
+        result
+    }
+
+    async fn validate_token(&self, token: &str) -> crate::Result<bool> {
+        let mut primary_result = self.primary.validate_token(token).await;
+        if matches!(&primary_result, Err(e) if !Self::is_rejection(e)) {
+            primary_result = self.primary.validate_token(token).await;
+        }
+
+        if let Ok(true) = primary_result {
+            return Ok(true);
+        }
+
+        // Sessions issued by the fallback stay valid until they expire
+        if let Some(local_users) = &self.local_users {
+            if local_users.validate_token(token).await? {
+                tracing::info!(target: "audit", "BREAK-GLASS: request authorized by fallback session");
+                return Ok(true);
+            }
+        }
+
+        match primary_result {
+            Err(e) if !Self::is_rejection(&e) => {
+                if self.matches_emergency_token(token) {
+                    tracing::warn!(
+                        target: "audit",
+                        "BREAK-GLASS: primary auth provider unreachable ({}), request authorized with emergency token",
+                        e
+                    );
+                    return Ok(true);
+                }
+                Err(e)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn revoke_token(&self, token: &str) -> crate::Result<()> {
+        if let Some(local_users) = &self.local_users {
+            local_users.revoke_token(token).await?;
+        }
+        self.primary.revoke_token(token).await
+    }
+
+    async fn is_admin(&self, token: &str) -> crate::Result<bool> {
+        // Break-glass access exists to let operators fix things, so it is
+        // always administrative
+        if let Some(local_users) = &self.local_users {
+            if local_users.is_admin(token).await? {
+                return Ok(true);
+            }
+        }
+
+        match self.primary.is_admin(token).await {
+            Err(e) if !Self::is_rejection(&e) && self.matches_emergency_token(token) => {
+                tracing::warn!(
+                    target: "audit",
+                    "BREAK-GLASS: emergency token used for an administrative action"
+                );
+                Ok(true)
+            }
+            result => result,
+        }
+    }
+
+    fn requires_auth(&self) -> bool {
+        self.primary.requires_auth()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct UnreachableAuthProvider;
+
+    #[async_trait]
+    impl AuthProvider for UnreachableAuthProvider {
+        async fn authenticate(&self, _request: &AuthRequest) -> crate::Result<AuthResponse> {
+            Err(crate::DoomsdayError::backend("connection refused"))
+        }
+
+        async fn validate_token(&self, _token: &str) -> crate::Result<bool> {
+            Err(crate::DoomsdayError::backend("connection refused"))
+        }
+
+        async fn revoke_token(&self, _token: &str) -> crate::Result<()> {
+            Ok(())
+        }
+
+        async fn is_admin(&self, _token: &str) -> crate::Result<bool> {
+            Err(crate::DoomsdayError::backend("connection refused"))
+        }
+
+        fn requires_auth(&self) -> bool {
+            true
+        }
+    }
+
+    fn fallback_config() -> AuthFallbackConfig {
+        AuthFallbackConfig {
+            emergency_token: Some("break-glass".to_string()),
+            users: HashMap::new(),
+            session_timeout: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_fallback_behind_userpass_stays_unused() {
+        let config: AuthConfig = serde_yaml::from_str(
+            r#"type: userpass
+properties:
+  users: {admin: "secret"}
+fallback:
+  emergency_token: break-glass
+  users: {breakglass: "local"}
+"#,
+        )
+        .unwrap();
+        let provider = create_auth_provider(&config).unwrap();
+        assert!(provider.requires_auth());
+
+        let login = |username: &str, password: &str| AuthRequest {
+            username: username.to_string(),
+            password: password.to_string(),
+        };
+        let session = provider
+            .authenticate(&login("admin", "secret"))
+            .await
+            .unwrap();
+        assert!(provider.validate_token(&session.token).await.unwrap());
+        assert!(provider.is_admin(&session.token).await.unwrap());
+
+        // userpass always answers, so its rejections stand
+        assert!(provider
+            .authenticate(&login("admin", "wrong"))
+            .await
+            .is_err());
+        assert!(provider
+            .authenticate(&login("breakglass", "local"))
+            .await
+            .is_err());
+        assert!(!provider.validate_token("break-glass").await.unwrap());
+        assert!(!provider.is_admin("break-glass").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_emergency_token_only_when_primary_unreachable() {
+        let unreachable =
+            FallbackAuthProvider::new(Arc::new(UnreachableAuthProvider), &fallback_config())
+                .unwrap();
+        assert!(unreachable.validate_token("break-glass").await.unwrap());
+        assert!(unreachable.validate_token("wrong").await.is_err());
+
+        let users = HashMap::from([("admin".to_string(), "unused-hash".to_string())]);
+        let healthy = FallbackAuthProvider::new(
+            Arc::new(UserPassAuthProvider::new(users, Duration::minutes(5), true)),
+            &fallback_config(),
+        )
+        .unwrap();
+        assert!(!healthy.validate_token("break-glass").await.unwrap());
+    }
+}
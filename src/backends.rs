@@ -1,70 +1,67 @@
-use crate::config::BackendConfig;
+use crate::config::{BackendConfig, BackendKind};
 use crate::storage::{
     credhub::CredHubAccessor, opsmgr::OpsMgrAccessor, tlsclient::TlsClientAccessor,
     vault::VaultAccessor, Accessor,
 };
 use std::sync::Arc;
+use std::time::Duration;
 
-pub fn create_accessor(config: &BackendConfig) -> crate::Result<Arc<dyn Accessor>> {
+pub fn create_accessor(
+    config: &BackendConfig,
+    request_timeout: Duration,
+) -> crate::Result<Arc<dyn Accessor>> {
     tracing::info!(
         "Creating accessor for backend '{}' of type '{}'",
         config.name,
-        config.backend_type
+        config.backend_type()
     );
 
-    match config.backend_type.as_str() {
-        "vault" => {
+    match &config.kind {
+        BackendKind::Vault(properties) => {
             tracing::debug!("Initializing Vault accessor for backend: {}", config.name);
-            let accessor = VaultAccessor::from_config(config.name.clone(), &config.properties)?;
+            let accessor =
+                VaultAccessor::from_config(config.name.clone(), properties, request_timeout)?;
             tracing::info!(
                 "Vault accessor created successfully for backend: {}",
                 config.name
             );
             Ok(Arc::new(accessor))
         }
-        "credhub" => {
+        BackendKind::CredHub(properties) => {
             tracing::debug!("Initializing CredHub accessor for backend: {}", config.name);
-            let accessor = CredHubAccessor::from_config(config.name.clone(), &config.properties)?;
+            let accessor =
+                CredHubAccessor::from_config(config.name.clone(), properties, request_timeout)?;
             tracing::info!(
                 "CredHub accessor created successfully for backend: {}",
                 config.name
             );
             Ok(Arc::new(accessor))
         }
-        "opsmgr" => {
+        BackendKind::OpsMgr(properties) => {
             tracing::debug!(
                 "Initializing Ops Manager accessor for backend: {}",
                 config.name
             );
-            let accessor = OpsMgrAccessor::from_config(config.name.clone(), &config.properties)?;
+            let accessor =
+                OpsMgrAccessor::from_config(config.name.clone(), properties, request_timeout)?;
             tracing::info!(
                 "Ops Manager accessor created successfully for backend: {}",
                 config.name
             );
             Ok(Arc::new(accessor))
         }
-        "tlsclient" => {
+        BackendKind::TlsClient(properties) => {
             tracing::debug!(
                 "Initializing TLS Client accessor for backend: {}",
                 config.name
             );
-            let accessor = TlsClientAccessor::from_config(config.name.clone(), &config.properties)?;
+            let accessor =
+                TlsClientAccessor::from_config(config.name.clone(), properties, request_timeout)?;
             tracing::info!(
                 "TLS Client accessor created successfully for backend: {}",
                 config.name
             );
             Ok(Arc::new(accessor))
         }
-        _ => {
-            tracing::error!(
-                "Unknown backend type '{}' for backend '{}'",
-                config.backend_type,
-                config.name
-            );
-            Err(crate::DoomsdayError::config(format!(
-                "Unknown backend type: {}",
-                config.backend_type
-            )))
-        }
     }
 }
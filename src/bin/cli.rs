@@ -1,23 +1,115 @@
 use clap::{Arg, ArgMatches, Command};
-use doomsday_rs::config::{ClientConfig, ClientTarget};
+use doomsday_rs::cache::{CaImpact, CacheDiffPreview, DuplicateReport};
+use doomsday_rs::config::{ClientConfig, ClientTarget, StatusThresholds};
 use doomsday_rs::duration::DurationParser;
-use doomsday_rs::types::{AuthRequest, CacheItem};
+use doomsday_rs::storage::tlsclient::{StartTls, TlsClientAccessor};
+use doomsday_rs::types::{
+    AuthRequest, AuthResponse, CacheItem, CacheObject, CertificateData, CertificateStatus,
+    NotificationTestResult, PathObject, TaskInfo, TaskStatus,
+};
 use reqwest::Client;
+use serde::Serialize;
 use serde_json;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::io::{IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
 use tabled::{
-    settings::{Style, Width},
+    settings::{
+        object::{Cell, Columns},
+        Color, Disable, Style, Width,
+    },
     Table, Tabled,
 };
 
+/// Whether tables are colored. Set once in `main`: only when stdout is a
+/// terminal, and never with `--no-color` or `NO_COLOR` set.
+static COLOR: AtomicBool = AtomicBool::new(false);
+
+/// `--timeout` and `--retries`, which take precedence over the target's own.
+static REQUEST_OVERRIDES: OnceLock<(Option<Duration>, Option<u32>)> = OnceLock::new();
+
+/// Tokens from logging in again during this command, by target name.
+static RENEWED_TOKENS: Mutex<BTreeMap<String, String>> = Mutex::new(BTreeMap::new());
+
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+const DEFAULT_RETRIES: u32 = 2;
+const PING_TIMEOUT: Duration = Duration::from_secs(5);
+/// Wait before the first retry, doubled for each one after.
+const RETRY_BACKOFF: Duration = Duration::from_millis(500);
+
+/// How commands print their results. `json` and `yaml` print the data as
+/// the server returned it, for scripts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum OutputFormat {
+    Table,
+    Json,
+    Yaml,
+}
+
+impl OutputFormat {
+    fn from_matches(matches: &ArgMatches) -> Self {
+        match matches.get_one::<String>("output").map(String::as_str) {
+            Some("json") => OutputFormat::Json,
+            Some("yaml") => OutputFormat::Yaml,
+            _ => OutputFormat::Table,
+        }
+    }
+
+    /// Prints `value` and returns true unless the output is a table, which
+    /// the caller renders itself.
+    fn print<T: Serialize>(self, value: &T) -> anyhow::Result<bool> {
+        match self {
+            OutputFormat::Table => return Ok(false),
+            OutputFormat::Json => println!("{}", serde_json::to_string_pretty(value)?),
+            OutputFormat::Yaml => print!("{}", serde_yaml::to_string(value)?),
+        }
+        Ok(true)
+    }
+}
+
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     let app = Command::new("doomsday")
         .version(doomsday_rs::version::VERSION)
         .about("Doomsday certificate monitoring CLI")
+        .arg(
+            Arg::new("output")
+                .short('o')
+                .long("output")
+                .global(true)
+                .value_parser(["table", "json", "yaml"])
+                .default_value("table")
+                .help("Output format"),
+        )
+        .arg(
+            Arg::new("no-color")
+                .long("no-color")
+                .global(true)
+                .action(clap::ArgAction::SetTrue)
+                .help("Don't color table output"),
+        )
+        .arg(
+            Arg::new("timeout")
+                .long("timeout")
+                .global(true)
+                .value_parser(parse_timeout)
+                .help("Give up on a request after this long, e.g. 10s or 2m (default 30s)"),
+        )
+        .arg(
+            Arg::new("retries")
+                .long("retries")
+                .global(true)
+                .value_parser(clap::value_parser!(u32))
+                .help("Retries when the server can't be reached (default 2)"),
+        )
         .subcommand(
             Command::new("target")
                 .about("Set target doomsday server")
+                .args_conflicts_with_subcommands(true)
+                .subcommand_negates_reqs(true)
                 .arg(Arg::new("name").required(true).help("Target name"))
                 .arg(Arg::new("address").required(true).help("Server address"))
                 .arg(
@@ -25,9 +117,65 @@ async fn main() -> anyhow::Result<()> {
                         .long("skip-verify")
                         .action(clap::ArgAction::SetTrue)
                         .help("Skip TLS verification"),
+                )
+                .arg(
+                    Arg::new("ca-cert")
+                        .long("ca-cert")
+                        .value_parser(clap::value_parser!(PathBuf))
+                        .help("PEM bundle of CAs to trust for this server"),
+                )
+                .arg(
+                    Arg::new("client-cert")
+                        .long("client-cert")
+                        .value_parser(clap::value_parser!(PathBuf))
+                        .help("PEM client certificate for mutual TLS"),
+                )
+                .arg(
+                    Arg::new("client-key")
+                        .long("client-key")
+                        .value_parser(clap::value_parser!(PathBuf))
+                        .requires("client-cert")
+                        .help("PEM client key, if not in the client certificate file"),
+                )
+                .arg(
+                    Arg::new("proxy")
+                        .long("proxy")
+                        .value_name("URL")
+                        .help("Proxy to reach this server through, instead of HTTPS_PROXY"),
+                )
+                .subcommand(
+                    Command::new("use")
+                        .about("Switch the current target")
+                        .arg(Arg::new("name").required(true).help("Target name")),
+                )
+                .subcommand(
+                    Command::new("rename")
+                        .about("Rename a target")
+                        .arg(Arg::new("name").required(true).help("Target name"))
+                        .arg(Arg::new("new-name").required(true).help("New target name")),
+                )
+                .subcommand(
+                    Command::new("delete")
+                        .about("Remove a target")
+                        .arg(Arg::new("name").required(true).help("Target name"))
+                        .arg(
+                            Arg::new("force")
+                                .long("force")
+                                .action(clap::ArgAction::SetTrue)
+                                .help("Delete even if it is the current target"),
+                        ),
+                ),
+        )
+        .subcommand(
+            Command::new("targets")
+                .about("List configured targets")
+                .arg(
+                    Arg::new("ping")
+                        .long("ping")
+                        .action(clap::ArgAction::SetTrue)
+                        .help("Check whether each server is reachable, and its version"),
                 ),
         )
-        .subcommand(Command::new("targets").about("List configured targets"))
         .subcommand(
             Command::new("auth")
                 .about("Authenticate with server")
@@ -42,6 +190,12 @@ async fn main() -> anyhow::Result<()> {
                         .short('p')
                         .long("password")
                         .help("Password"),
+                )
+                .arg(
+                    Arg::new("remember")
+                        .long("remember")
+                        .action(clap::ArgAction::SetTrue)
+                        .help("Save the credentials to log in again when the session expires"),
                 ),
         )
         .subcommand(
@@ -56,9 +210,161 @@ async fn main() -> anyhow::Result<()> {
                     Arg::new("within")
                         .long("within")
                         .help("Show certificates expiring within duration"),
+                )
+                .arg(
+                    Arg::new("backend")
+                        .long("backend")
+                        .action(clap::ArgAction::Append)
+                        .help("Only show certificates from this backend (repeatable)"),
+                )
+                .arg(
+                    Arg::new("label")
+                        .long("label")
+                        .action(clap::ArgAction::Append)
+                        .help("Only show certificates with this key=value label (repeatable)"),
+                )
+                .arg(
+                    Arg::new("match")
+                        .long("match")
+                        .help("Only show certificates whose subject or a SAN matches this regex"),
+                )
+                .arg(
+                    Arg::new("expired-only")
+                        .long("expired-only")
+                        .action(clap::ArgAction::SetTrue)
+                        .help("Only show expired certificates"),
+                )
+                .arg(
+                    Arg::new("sort-by")
+                        .long("sort-by")
+                        .value_parser(["expiry", "subject", "status", "paths"])
+                        .default_value("expiry")
+                        .help("Sort certificates by this column"),
+                )
+                .arg(
+                    Arg::new("reverse")
+                        .long("reverse")
+                        .action(clap::ArgAction::SetTrue)
+                        .help("Reverse the sort order"),
+                )
+                .arg(
+                    Arg::new("watch")
+                        .long("watch")
+                        .help("Redraw every interval (e.g. 5s, 1m) until interrupted"),
+                ),
+        )
+        .subcommand(
+            Command::new("export")
+                .about("Download the full inventory to a file")
+                .arg(
+                    Arg::new("format")
+                        .long("format")
+                        .value_parser(["csv", "json", "prometheus"])
+                        .default_value("json")
+                        .help("Export format"),
+                )
+                .arg(
+                    Arg::new("out")
+                        .long("out")
+                        .value_parser(clap::value_parser!(PathBuf))
+                        .help("File to write, standard output if not given"),
+                ),
+        )
+        .subcommand(
+            Command::new("scan-files")
+                .about("Parse local PEM, DER or bundle files without a server")
+                .arg(
+                    Arg::new("paths")
+                        .required(true)
+                        .num_args(1..)
+                        .value_parser(clap::value_parser!(PathBuf))
+                        .help("Certificate files, or directories to search"),
+                ),
+        )
+        .subcommand(
+            Command::new("probe")
+                .about("Show the certificate and chain an endpoint serves, without a backend")
+                .arg(
+                    Arg::new("address")
+                        .required(true)
+                        .help("host:port to connect to (port defaults to 443)"),
+                )
+                .arg(
+                    Arg::new("starttls")
+                        .long("starttls")
+                        .value_parser(["smtp"])
+                        .help("Upgrade a plaintext connection with STARTTLS first"),
+                )
+                .arg(
+                    Arg::new("server-name")
+                        .long("server-name")
+                        .help("SNI name to send, if not the host"),
+                ),
+        )
+        .subcommand(
+            Command::new("check")
+                .about(
+                    "Exit non-zero when certificates expire within a window, e.g. to gate deploys",
+                )
+                .arg(
+                    Arg::new("fail-within")
+                        .long("fail-within")
+                        .required(true)
+                        .help("Fail when a certificate expires within this duration (e.g. 30d)"),
+                )
+                .arg(
+                    Arg::new("backend")
+                        .long("backend")
+                        .action(clap::ArgAction::Append)
+                        .help("Only check certificates from this backend (repeatable)"),
+                )
+                .arg(
+                    Arg::new("match")
+                        .long("match")
+                        .help("Only check certificates whose subject or a path matches this regex"),
+                ),
+        )
+        .subcommand(
+            Command::new("show")
+                .about("Show a certificate's details and every path it was found at")
+                .arg(
+                    Arg::new("certificate")
+                        .required(true)
+                        .help("SHA1 fingerprint, path (optionally backend:path) or subject"),
+                ),
+        )
+        .subcommand(
+            Command::new("dashboard")
+                .about("Show certificate dashboard")
+                .arg(
+                    Arg::new("watch")
+                        .long("watch")
+                        .help("Redraw every interval (e.g. 5s, 1m) until interrupted"),
+                ),
+        )
+        .subcommand(
+            Command::new("duplicates")
+                .about(
+                    "Show certificates copied to several paths and names with several certificates",
+                )
+                .arg(
+                    Arg::new("min-paths")
+                        .long("min-paths")
+                        .value_parser(clap::value_parser!(usize))
+                        .help(
+                            "Only report certificates found at this many paths or more (default 2)",
+                        ),
+                ),
+        )
+        .subcommand(
+            Command::new("ca-impact")
+                .about("Show CAs nearing expiry and how many certificates chain to them")
+                .arg(
+                    Arg::new("within")
+                        .long("within")
+                        .help("Only CAs expiring within this duration (default 90d)"),
                 ),
         )
-        .subcommand(Command::new("dashboard").about("Show certificate dashboard"))
         .subcommand(
             Command::new("refresh")
                 .about("Refresh certificate cache")
@@ -66,22 +372,117 @@ async fn main() -> anyhow::Result<()> {
                     Arg::new("backends")
                         .long("backends")
                         .help("Comma-separated list of backends to refresh"),
+                )
+                .arg(
+                    Arg::new("dry-run")
+                        .long("dry-run")
+                        .action(clap::ArgAction::SetTrue)
+                        .help("Show what a refresh would change without applying it"),
+                ),
+        )
+        .subcommand(
+            Command::new("notify-test")
+                .about("Send a test notification via each notification backend")
+                .arg(
+                    Arg::new("backend")
+                        .long("backend")
+                        .help("Only test the notification backend with this name"),
                 ),
         )
         .subcommand(Command::new("info").about("Show server information"))
-        .subcommand(Command::new("scheduler").about("Show scheduler information"));
+        .subcommand(
+            Command::new("scheduler")
+                .about("Show scheduler information")
+                .subcommand(
+                    Command::new("pause")
+                        .about("Pause scheduled refreshes (e.g. during maintenance)"),
+                )
+                .subcommand(Command::new("resume").about("Resume scheduled refreshes")),
+        )
+        .subcommand(
+            Command::new("tasks")
+                .about("Inspect and cancel scheduler tasks")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("list")
+                        .about("List recent tasks, newest first")
+                        .arg(
+                            Arg::new("status")
+                                .long("status")
+                                .value_parser([
+                                    "pending",
+                                    "running",
+                                    "completed",
+                                    "failed",
+                                    "cancelled",
+                                ])
+                                .help("Only show tasks in this state"),
+                        )
+                        .arg(
+                            Arg::new("limit")
+                                .long("limit")
+                                .value_parser(clap::value_parser!(usize))
+                                .default_value("20")
+                                .help("Number of tasks to show"),
+                        ),
+                )
+                .subcommand(
+                    Command::new("show")
+                        .about("Show a task, including its last error")
+                        .arg(Arg::new("id").required(true).help("Task ID")),
+                )
+                .subcommand(
+                    Command::new("cancel")
+                        .about("Cancel a pending or running task")
+                        .arg(Arg::new("id").required(true).help("Task ID")),
+                ),
+        );
 
     let matches = app.get_matches();
 
+    // Global arguments are propagated to the subcommand
+    let global = matches
+        .subcommand()
+        .map_or(&matches, |(_, sub_matches)| sub_matches);
+    let output = OutputFormat::from_matches(global);
+    COLOR.store(
+        !global.get_flag("no-color")
+            && std::env::var_os("NO_COLOR").is_none()
+            && std::io::stdout().is_terminal(),
+        Ordering::Relaxed,
+    );
+    let _ = REQUEST_OVERRIDES.set((
+        global.get_one::<Duration>("timeout").copied(),
+        global.get_one::<u32>("retries").copied(),
+    ));
+
     match matches.subcommand() {
         Some(("target", sub_matches)) => handle_target(sub_matches).await,
-        Some(("targets", _)) => handle_targets().await,
+        Some(("targets", sub_matches)) => handle_targets(sub_matches, output).await,
         Some(("auth", sub_matches)) => handle_auth(sub_matches).await,
-        Some(("list", sub_matches)) => handle_list(sub_matches).await,
-        Some(("dashboard", _)) => handle_dashboard().await,
-        Some(("refresh", sub_matches)) => handle_refresh(sub_matches).await,
-        Some(("info", _)) => handle_info().await,
-        Some(("scheduler", _)) => handle_scheduler().await,
+        Some(("list", sub_matches)) => handle_list(sub_matches, output).await,
+        Some(("export", sub_matches)) => handle_export(sub_matches).await,
+        Some(("scan-files", sub_matches)) => handle_scan_files(sub_matches, output),
+        Some(("probe", sub_matches)) => handle_probe(sub_matches, output).await,
+        Some(("check", sub_matches)) => handle_check(sub_matches, output).await,
+        Some(("show", sub_matches)) => handle_show(sub_matches, output).await,
+        Some(("dashboard", sub_matches)) => handle_dashboard(sub_matches, output).await,
+        Some(("duplicates", sub_matches)) => handle_duplicates(sub_matches, output).await,
+        Some(("ca-impact", sub_matches)) => handle_ca_impact(sub_matches, output).await,
+        Some(("refresh", sub_matches)) => handle_refresh(sub_matches, output).await,
+        Some(("notify-test", sub_matches)) => handle_notify_test(sub_matches, output).await,
+        Some(("info", _)) => handle_info(output).await,
+        Some(("tasks", sub_matches)) => match sub_matches.subcommand() {
+            Some(("list", list_matches)) => handle_tasks_list(list_matches, output).await,
+            Some(("show", show_matches)) => handle_task(show_matches, false, output).await,
+            Some(("cancel", cancel_matches)) => handle_task(cancel_matches, true, output).await,
+            _ => unreachable!(),
+        },
+        Some(("scheduler", sub_matches)) => match sub_matches.subcommand() {
+            Some(("pause", _)) => handle_scheduler_pause(true, output).await,
+            Some(("resume", _)) => handle_scheduler_pause(false, output).await,
+            _ => handle_scheduler(output).await,
+        },
         _ => {
             println!("Use --help for usage information");
             Ok(())
@@ -90,9 +491,28 @@ async fn main() -> anyhow::Result<()> {
 }
 
 async fn handle_target(matches: &ArgMatches) -> anyhow::Result<()> {
+    match matches.subcommand() {
+        Some(("use", sub_matches)) => return handle_target_use(sub_matches),
+        Some(("rename", sub_matches)) => return handle_target_rename(sub_matches),
+        Some(("delete", sub_matches)) => return handle_target_delete(sub_matches),
+        _ => {}
+    }
+
     let name = matches.get_one::<String>("name").unwrap();
     let address = matches.get_one::<String>("address").unwrap();
     let skip_verify = matches.get_flag("skip-verify");
+    // Stored absolute, so the CLI works from any directory
+    let file = |arg: &str| {
+        matches
+            .get_one::<PathBuf>(arg)
+            .map(|path| {
+                std::fs::canonicalize(path)
+                    .map_err(|e| anyhow::anyhow!("Failed to read {}: {}", path.display(), e))
+            })
+            .transpose()
+    };
+    let (ca_cert, client_cert, client_key) =
+        (file("ca-cert")?, file("client-cert")?, file("client-key")?);
 
     let mut config = ClientConfig::load()?;
 
@@ -102,7 +522,21 @@ async fn handle_target(matches: &ArgMatches) -> anyhow::Result<()> {
         skip_verify,
         token: None,
         token_expires: None,
+        ca_cert,
+        client_cert,
+        client_key,
+        proxy: matches.get_one::<String>("proxy").cloned(),
+        // Given with the target, --timeout and --retries become its defaults
+        timeout: matches
+            .get_one::<Duration>("timeout")
+            .map(Duration::as_secs),
+        retries: matches.get_one::<u32>("retries").copied(),
+        credentials: None,
+        secret_store: None,
     };
+    // Catch unusable certificates or proxies now rather than on the next
+    // command
+    create_client(&target)?;
 
     config.targets.insert(name.clone(), target);
     config.current_target = Some(name.clone());
@@ -112,8 +546,84 @@ async fn handle_target(matches: &ArgMatches) -> anyhow::Result<()> {
     Ok(())
 }
 
-async fn handle_targets() -> anyhow::Result<()> {
+fn handle_target_use(matches: &ArgMatches) -> anyhow::Result<()> {
+    let name = matches.get_one::<String>("name").unwrap();
+    let mut config = ClientConfig::load()?;
+    config.use_target(name)?;
+    config.save()?;
+
+    println!("✅ Now using target '{}'", name);
+    Ok(())
+}
+
+fn handle_target_rename(matches: &ArgMatches) -> anyhow::Result<()> {
+    let name = matches.get_one::<String>("name").unwrap();
+    let new_name = matches.get_one::<String>("new-name").unwrap();
+    let mut config = ClientConfig::load()?;
+    config.rename_target(name, new_name)?;
+    config.save()?;
+
+    println!("✅ Target '{}' renamed to '{}'", name, new_name);
+    Ok(())
+}
+
+fn handle_target_delete(matches: &ArgMatches) -> anyhow::Result<()> {
+    let name = matches.get_one::<String>("name").unwrap();
+    let mut config = ClientConfig::load()?;
+    let was_current = config.current_target.as_deref() == Some(name.as_str());
+    config.delete_target(name, matches.get_flag("force"))?;
+    config.save()?;
+
+    println!("✅ Target '{}' deleted", name);
+    if was_current {
+        println!("No target is current now. Use 'doomsday target use <name>' to pick one.");
+    }
+    Ok(())
+}
+
+async fn handle_targets(matches: &ArgMatches, output: OutputFormat) -> anyhow::Result<()> {
     let config = ClientConfig::load()?;
+    let ping = matches.get_flag("ping");
+
+    // Tokens stay out of the output
+    #[derive(Serialize)]
+    struct TargetEntry<'a> {
+        name: &'a str,
+        address: &'a str,
+        current: bool,
+        skip_verify: bool,
+        authenticated: bool,
+        #[serde(flatten, skip_serializing_if = "Option::is_none")]
+        ping: Option<PingResult>,
+    }
+
+    let mut targets: Vec<&ClientTarget> = config.targets.values().collect();
+    targets.sort_by_key(|target| &target.name);
+    let pings = if ping {
+        futures::future::join_all(targets.iter().map(|target| ping_target(target)))
+            .await
+            .into_iter()
+            .map(Some)
+            .collect()
+    } else {
+        vec![None; targets.len()]
+    };
+
+    let entries: Vec<TargetEntry> = targets
+        .iter()
+        .zip(pings)
+        .map(|(target, ping)| TargetEntry {
+            name: &target.name,
+            address: &target.address,
+            current: config.current_target.as_ref() == Some(&target.name),
+            skip_verify: target.skip_verify,
+            authenticated: target.token.is_some(),
+            ping,
+        })
+        .collect();
+    if output.print(&entries)? {
+        return Ok(());
+    }
 
     if config.targets.is_empty() {
         println!("No targets configured. Use 'doomsday target' to add one.");
@@ -130,36 +640,100 @@ async fn handle_targets() -> anyhow::Result<()> {
         current: String,
         #[tabled(rename = "Skip Verify")]
         skip_verify: String,
+        #[tabled(rename = "Reachable")]
+        reachable: String,
+        #[tabled(rename = "Version")]
+        version: String,
     }
 
-    let mut rows = Vec::new();
-    for (name, target) in &config.targets {
-        let current = if config.current_target.as_ref() == Some(name) {
-            "✓".to_string()
-        } else {
-            "".to_string()
-        };
-
-        let skip_verify = if target.skip_verify {
-            "✓".to_string()
-        } else {
-            "".to_string()
-        };
-
-        rows.push(TargetRow {
-            name: name.clone(),
-            address: target.address.clone(),
-            current,
-            skip_verify,
-        });
-    }
+    let check = |flag: bool| if flag { "✓" } else { "" }.to_string();
+    let rows: Vec<TargetRow> = entries
+        .into_iter()
+        .map(|entry| {
+            let (reachable, version) = match entry.ping {
+                Some(PingResult {
+                    reachable: true,
+                    version,
+                    latency_ms,
+                    ..
+                }) => (format!("✅ {}ms", latency_ms), version.unwrap_or_default()),
+                Some(PingResult { error, .. }) => {
+                    (format!("❌ {}", error.unwrap_or_default()), String::new())
+                }
+                None => (String::new(), String::new()),
+            };
+            TargetRow {
+                name: entry.name.to_string(),
+                address: entry.address.to_string(),
+                current: check(entry.current),
+                skip_verify: check(entry.skip_verify),
+                reachable,
+                version,
+            }
+        })
+        .collect();
 
     let mut table = Table::new(rows);
     table.with(Style::rounded()).with(Width::wrap(120));
+    if !ping {
+        table.with(Disable::column(Columns::new(4..)));
+    }
     println!("{}", table);
     Ok(())
 }
 
+/// Whether a target's server answered `/v1/info`, and how quickly.
+#[derive(Debug, Clone, Serialize)]
+struct PingResult {
+    reachable: bool,
+    version: Option<String>,
+    latency_ms: u128,
+    error: Option<String>,
+}
+
+/// Fetches `/v1/info` once, without retrying, within `--timeout` or
+/// `PING_TIMEOUT`.
+async fn ping_target(target: &ClientTarget) -> PingResult {
+    let timeout = REQUEST_OVERRIDES
+        .get()
+        .and_then(|(timeout, _)| *timeout)
+        .unwrap_or(PING_TIMEOUT);
+    let started = std::time::Instant::now();
+    let result = async {
+        let response = create_client(target)?
+            .get(format!("{}/v1/info", target.address))
+            .timeout(timeout)
+            .send()
+            .await
+            .map_err(|e| match e {
+                e if e.is_timeout() => anyhow::anyhow!("timed out"),
+                e if e.is_connect() => anyhow::anyhow!("could not connect"),
+                e => anyhow::anyhow!(e.without_url()),
+            })?;
+        if !response.status().is_success() {
+            return Err(anyhow::anyhow!("{}", response.status()));
+        }
+        Ok(response.json::<doomsday_rs::types::InfoResponse>().await?)
+    }
+    .await;
+
+    let latency_ms = started.elapsed().as_millis();
+    match result {
+        Ok(info) => PingResult {
+            reachable: true,
+            version: Some(info.version),
+            latency_ms,
+            error: None,
+        },
+        Err(e) => PingResult {
+            reachable: false,
+            version: None,
+            latency_ms,
+            error: Some(e.to_string()),
+        },
+    }
+}
+
 async fn handle_auth(matches: &ArgMatches) -> anyhow::Result<()> {
     let mut config = ClientConfig::load()?;
     let target = config
@@ -167,40 +741,17 @@ async fn handle_auth(matches: &ArgMatches) -> anyhow::Result<()> {
         .ok_or_else(|| anyhow::anyhow!("No target configured. Use 'doomsday target' first."))?
         .clone();
 
-    let username = if let Some(username) = matches.get_one::<String>("username") {
-        username.clone()
-    } else {
-        print!("Username: ");
-        let mut input = String::new();
-        std::io::stdin().read_line(&mut input)?;
-        input.trim().to_string()
-    };
-
-    let password = if let Some(password) = matches.get_one::<String>("password") {
-        password.clone()
-    } else {
-        rpassword::prompt_password("Password: ")?
-    };
-
-    let client = create_client(target.skip_verify);
-    let auth_request = AuthRequest { username, password };
-
-    let response = client
-        .post(&format!("{}/v1/auth", target.address))
-        .json(&auth_request)
-        .send()
-        .await?;
-
-    if !response.status().is_success() {
-        return Err(anyhow::anyhow!("Authentication failed"));
-    }
-
-    let auth_response: doomsday_rs::types::AuthResponse = response.json().await?;
+    let credentials = prompt_credentials(
+        matches.get_one::<String>("username"),
+        matches.get_one::<String>("password"),
+    )?;
+    let auth_response = login(&target, &credentials).await?;
 
     // Update target with token
     if let Some(target_mut) = config.targets.get_mut(&target.name) {
         target_mut.token = Some(auth_response.token);
         target_mut.token_expires = Some(auth_response.expires_at);
+        target_mut.credentials = matches.get_flag("remember").then_some(credentials);
     }
 
     config.save()?;
@@ -209,13 +760,20 @@ async fn handle_auth(matches: &ArgMatches) -> anyhow::Result<()> {
     Ok(())
 }
 
-async fn handle_list(matches: &ArgMatches) -> anyhow::Result<()> {
+async fn handle_list(matches: &ArgMatches, output: OutputFormat) -> anyhow::Result<()> {
+    match matches.get_one::<String>("watch") {
+        Some(interval) => watch(interval, output, || print_list(matches, output)).await,
+        None => print_list(matches, output).await,
+    }
+}
+
+async fn print_list(matches: &ArgMatches, output: OutputFormat) -> anyhow::Result<()> {
     let config = ClientConfig::load()?;
     let target = config
         .current_target()
         .ok_or_else(|| anyhow::anyhow!("No target configured"))?;
 
-    let client = create_client(target.skip_verify);
+    let client = create_client(target)?;
     let mut url = format!("{}/v1/cache", target.address);
 
     let mut params = vec![];
@@ -225,6 +783,25 @@ async fn handle_list(matches: &ArgMatches) -> anyhow::Result<()> {
     if let Some(within) = matches.get_one::<String>("within") {
         params.push(format!("within={}", within));
     }
+    if let Some(backends) = matches.get_many::<String>("backend") {
+        for backend in backends {
+            params.push(format!("backend={}", backend));
+        }
+    }
+    if let Some(labels) = matches.get_many::<String>("label") {
+        for label in labels {
+            params.push(format!("label={}", urlencoding::encode(label)));
+        }
+    }
+    let expired_only = matches.get_flag("expired-only");
+    if expired_only {
+        params.push("status=expired".to_string());
+    }
+    // The server has no regex filter
+    let pattern = matches
+        .get_one::<String>("match")
+        .map(|pattern| regex::Regex::new(pattern))
+        .transpose()?;
 
     if !params.is_empty() {
         url.push('?');
@@ -236,7 +813,7 @@ async fn handle_list(matches: &ArgMatches) -> anyhow::Result<()> {
         request = request.header("X-Doomsday-Token", token);
     }
 
-    let response = request.send().await?;
+    let response = send(target, request).await?;
     if !response.status().is_success() {
         return Err(anyhow::anyhow!(
             "Failed to fetch certificates: {}",
@@ -244,11 +821,42 @@ async fn handle_list(matches: &ArgMatches) -> anyhow::Result<()> {
         ));
     }
 
-    let certificates: Vec<CacheItem> = response.json().await?;
+    let mut certificates: Vec<CacheItem> = response.json().await?;
+    // Servers that predate the status filter ignore it
+    if expired_only {
+        certificates.retain(|cert| cert.status == CertificateStatus::Expired);
+    }
+    if let Some(pattern) = &pattern {
+        certificates.retain(|cert| {
+            pattern.is_match(&cert.subject)
+                || cert
+                    .metadata
+                    .subject_alt_names
+                    .iter()
+                    .any(|san| pattern.is_match(san))
+        });
+    }
+
+    match matches.get_one::<String>("sort-by").map(String::as_str) {
+        Some("subject") => certificates.sort_by(|a, b| a.subject.cmp(&b.subject)),
+        Some("status") => certificates.sort_by_key(|cert| cert.status.as_str()),
+        Some("paths") => certificates.sort_by_key(|cert| cert.paths.len()),
+        _ => certificates.sort_by_key(|cert| cert.not_after),
+    }
+    if matches.get_flag("reverse") {
+        certificates.reverse();
+    }
+
+    if !output.print(&certificates)? {
+        print_certificates(certificates);
+    }
+    Ok(())
+}
 
+fn print_certificates(certificates: Vec<CacheItem>) {
     if certificates.is_empty() {
         println!("No certificates found");
-        return Ok(());
+        return;
     }
 
     #[derive(Tabled)]
@@ -259,10 +867,13 @@ async fn handle_list(matches: &ArgMatches) -> anyhow::Result<()> {
         expires: String,
         #[tabled(rename = "Time Until")]
         time_until: String,
+        #[tabled(rename = "Status")]
+        status: String,
         #[tabled(rename = "Paths")]
         paths: String,
     }
 
+    let expiries: Vec<_> = certificates.iter().map(|cert| cert.not_after).collect();
     let mut rows = Vec::new();
     for cert in certificates {
         let expires = cert.not_after.format("%Y-%m-%d %H:%M UTC").to_string();
@@ -273,129 +884,992 @@ async fn handle_list(matches: &ArgMatches) -> anyhow::Result<()> {
             subject: cert.subject,
             expires,
             time_until,
+            status: cert.status.to_string(),
             paths,
         });
     }
 
     let mut table = Table::new(rows);
     table.with(Style::rounded()).with(Width::wrap(120));
+    color_time_until(&mut table, 2, &expiries);
     println!("{}", table);
-    Ok(())
 }
 
-async fn handle_dashboard() -> anyhow::Result<()> {
+/// Colors the "Time Until" cell of each row, given each row's expiry: red
+/// once expired, yellow while expiring soon, green otherwise.
+fn color_time_until(table: &mut Table, column: usize, expiries: &[chrono::DateTime<chrono::Utc>]) {
+    if !COLOR.load(Ordering::Relaxed) {
+        return;
+    }
+
+    let now = chrono::Utc::now();
+    let soon = chrono::Duration::days(StatusThresholds::default().expiring_soon_days);
+    for (row, not_after) in expiries.iter().enumerate() {
+        let color = if *not_after <= now {
+            Color::FG_RED
+        } else if *not_after - now <= soon {
+            Color::FG_YELLOW
+        } else {
+            Color::FG_GREEN
+        };
+        // Row 0 is the header
+        table.modify(Cell::new(row + 1, column), color);
+    }
+}
+
+/// Streams `/v1/cache/export` to a file, which only appears once the
+/// download has completed.
+async fn handle_export(matches: &ArgMatches) -> anyhow::Result<()> {
     let config = ClientConfig::load()?;
     let target = config
         .current_target()
         .ok_or_else(|| anyhow::anyhow!("No target configured"))?;
+    let format = matches.get_one::<String>("format").unwrap();
 
-    let client = create_client(target.skip_verify);
-    let mut request = client.get(&format!("{}/v1/cache", target.address));
-
+    let client = create_client(target)?;
+    let mut request = client.get(&format!(
+        "{}/v1/cache/export?format={}",
+        target.address, format
+    ));
     if let Some(token) = &target.token {
         request = request.header("X-Doomsday-Token", token);
     }
 
-    let response = request.send().await?;
+    let mut response = send(target, request).await?;
     if !response.status().is_success() {
         return Err(anyhow::anyhow!(
-            "Failed to fetch certificates: {}",
+            "Failed to export inventory: {}",
             response.status()
         ));
     }
 
-    let certificates: Vec<CacheItem> = response.json().await?;
-
-    let now = chrono::Utc::now();
-    let mut expired = 0;
-    let mut expiring_soon = 0;
-    let mut ok = 0;
-
-    for cert in &certificates {
-        let days_until_expiry = (cert.not_after - now).num_days();
+    let Some(out) = matches.get_one::<PathBuf>("out") else {
+        let mut stdout = std::io::stdout().lock();
+        while let Some(chunk) = response.chunk().await? {
+            stdout.write_all(&chunk)?;
+        }
+        return Ok(());
+    };
 
-        if days_until_expiry < 0 {
-            expired += 1;
-        } else if days_until_expiry <= 30 {
-            expiring_soon += 1;
-        } else {
-            ok += 1;
+    let mut partial = out.clone().into_os_string();
+    partial.push(".part");
+    let partial = PathBuf::from(partial);
+    let file = std::fs::File::create(&partial)
+        .map_err(|e| anyhow::anyhow!("Failed to create {}: {}", partial.display(), e))?;
+    let mut file = std::io::BufWriter::new(file);
+    let mut written = 0;
+    let result: anyhow::Result<()> = async {
+        while let Some(chunk) = response.chunk().await? {
+            file.write_all(&chunk)?;
+            written += chunk.len();
         }
+        file.flush()?;
+        Ok(())
     }
+    .await;
+    if let Err(e) = result {
+        let _ = std::fs::remove_file(&partial);
+        return Err(e);
+    }
+    std::fs::rename(&partial, out)?;
 
-    println!("🔒 Doomsday Certificate Dashboard");
-    println!("═══════════════════════════════════");
-    println!();
-    println!("⚠️  Expired:        {} certificates", expired);
     println!(
-        "⏰ Expiring Soon:   {} certificates (within 30 days)",
-        expiring_soon
+        "✅ Exported inventory as {} to {} ({} bytes)",
+        format,
+        out.display(),
+        written
     );
-    println!("✅ OK:              {} certificates", ok);
-    println!("📊 Total:           {} certificates", certificates.len());
+    Ok(())
+}
+
+/// Parses certificate files locally, without a server. Directories are
+/// searched recursively, skipping files that hold no certificates.
+fn handle_scan_files(matches: &ArgMatches, output: OutputFormat) -> anyhow::Result<()> {
+    let mut objects: HashMap<String, CacheObject> = HashMap::new();
+    let mut failed = 0;
+    for path in matches.get_many::<PathBuf>("paths").unwrap() {
+        let files = if path.is_dir() {
+            walk_dir(path)?
+        } else {
+            vec![path.clone()]
+        };
+        let explicit = !path.is_dir();
+
+        for file in files {
+            let parsed = std::fs::read(&file)
+                .map_err(anyhow::Error::from)
+                .and_then(|data| Ok(CertificateData::parse_file(&data)?));
+            let certificates = match parsed {
+                Ok(certificates) => certificates,
+                // Directories hold keys, configs and the like alongside certificates
+                Err(_) if !explicit => continue,
+                Err(e) => {
+                    eprintln!("❌ {}: {}", file.display(), e);
+                    failed += 1;
+                    continue;
+                }
+            };
+
+            for cert in certificates {
+                let location = PathObject::new("file", file.display().to_string());
+                match objects.get_mut(&cert.fingerprint_sha1) {
+                    Some(object) => object.paths.push(location),
+                    None => {
+                        let object = CacheObject::from_certificate(cert, location);
+                        objects.insert(object.sha1.clone(), object);
+                    }
+                }
+            }
+        }
+    }
 
+    let now = chrono::Utc::now();
+    let thresholds = StatusThresholds::default();
+    let mut certificates: Vec<CacheItem> = objects
+        .values()
+        .map(|object| {
+            CacheItem::new(
+                object,
+                CertificateStatus::evaluate(object, false, now, &thresholds),
+            )
+        })
+        .collect();
+    certificates.sort_by_key(|cert| cert.not_after);
+
+    if !output.print(&certificates)? {
+        print_certificates(certificates);
+    }
+    if failed > 0 {
+        return Err(anyhow::anyhow!("{} file(s) could not be parsed", failed));
+    }
     Ok(())
 }
 
-async fn handle_refresh(matches: &ArgMatches) -> anyhow::Result<()> {
+/// Every file below `dir`, in a stable order.
+fn walk_dir(dir: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let mut entries: Vec<PathBuf> = std::fs::read_dir(dir)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<_, _>>()?;
+    entries.sort();
+
+    let mut files = Vec::new();
+    for entry in entries {
+        if entry.is_dir() {
+            files.extend(walk_dir(&entry)?);
+        } else {
+            files.push(entry);
+        }
+    }
+    Ok(files)
+}
+
+/// `doomsday check` exit code when matching certificates expire within the
+/// window.
+const CHECK_EXPIRING: i32 = 2;
+/// `doomsday check` exit code when some have already expired.
+const CHECK_EXPIRED: i32 = 3;
+
+async fn handle_check(matches: &ArgMatches, output: OutputFormat) -> anyhow::Result<()> {
     let config = ClientConfig::load()?;
     let target = config
         .current_target()
         .ok_or_else(|| anyhow::anyhow!("No target configured"))?;
 
-    let client = create_client(target.skip_verify);
-
-    let refresh_request = if let Some(backends_str) = matches.get_one::<String>("backends") {
-        let backends: Vec<String> = backends_str
-            .split(',')
-            .map(|s| s.trim().to_string())
-            .collect();
-        doomsday_rs::types::RefreshRequest {
-            backends: Some(backends),
+    // The server ignores windows it cannot parse, which must not pass a gate
+    let within = matches.get_one::<String>("fail-within").unwrap();
+    DurationParser::parse(within)?;
+    let pattern = matches
+        .get_one::<String>("match")
+        .map(|pattern| regex::Regex::new(pattern))
+        .transpose()?;
+
+    let mut params = vec![format!("within={}", urlencoding::encode(within))];
+    if let Some(backends) = matches.get_many::<String>("backend") {
+        for backend in backends {
+            params.push(format!("backend={}", urlencoding::encode(backend)));
         }
-    } else {
-        doomsday_rs::types::RefreshRequest { backends: None }
-    };
-
-    let mut request = client
-        .post(&format!("{}/v1/cache/refresh", target.address))
-        .json(&refresh_request);
+    }
 
+    let client = create_client(target)?;
+    let mut request = client.get(&format!("{}/v1/cache?{}", target.address, params.join("&")));
     if let Some(token) = &target.token {
         request = request.header("X-Doomsday-Token", token);
     }
 
-    println!("🔄 Refreshing certificate cache...");
-
-    let response = request.send().await?;
+    let response = send(target, request).await?;
     if !response.status().is_success() {
         return Err(anyhow::anyhow!(
-            "Failed to refresh cache: {}",
+            "Failed to fetch certificates: {}",
             response.status()
         ));
     }
 
-    let stats: doomsday_rs::types::PopulateStats = response.json().await?;
+    let certificates: Vec<CacheItem> = response.json().await?;
+    let mut offenders: Vec<CacheItem> = certificates
+        .into_iter()
+        .filter(|cert| cert.status != CertificateStatus::Superseded)
+        .filter(|cert| {
+            pattern.as_ref().is_none_or(|pattern| {
+                pattern.is_match(&cert.subject)
+                    || cert.paths.iter().any(|p| {
+                        pattern.is_match(&p.path)
+                            || pattern.is_match(&format!("{}:{}", p.backend, p.path))
+                    })
+            })
+        })
+        .collect();
+    offenders.sort_by_key(|cert| cert.not_after);
+
+    if !output.print(&offenders)? {
+        print_check(&offenders, within);
+    }
 
-    println!("✅ Refresh complete");
-    println!("   Certificates: {}", stats.num_certs);
+    let now = chrono::Utc::now();
+    if offenders.iter().any(|cert| cert.not_after <= now) {
+        std::process::exit(CHECK_EXPIRED);
+    }
+    if !offenders.is_empty() {
+        std::process::exit(CHECK_EXPIRING);
+    }
+    Ok(())
+}
+
+fn print_check(offenders: &[CacheItem], within: &str) {
+    if offenders.is_empty() {
+        println!("✅ No certificates expire within {}", within);
+        return;
+    }
+
+    #[derive(Tabled)]
+    struct OffenderRow {
+        #[tabled(rename = "Subject")]
+        subject: String,
+        #[tabled(rename = "Expires")]
+        expires: String,
+        #[tabled(rename = "Time Until")]
+        time_until: String,
+        #[tabled(rename = "Paths")]
+        paths: String,
+    }
+
+    let rows: Vec<OffenderRow> = offenders
+        .iter()
+        .map(|cert| OffenderRow {
+            subject: cert.subject.clone(),
+            expires: cert.not_after.format("%Y-%m-%d %H:%M UTC").to_string(),
+            time_until: DurationParser::format_human(DurationParser::until_expiry(cert.not_after)),
+            paths: cert
+                .paths
+                .iter()
+                .map(|p| format!("{}:{}", p.backend, p.path))
+                .collect::<Vec<_>>()
+                .join("\n"),
+        })
+        .collect();
+    let mut table = Table::new(rows);
+    table.with(Style::rounded()).with(Width::wrap(120));
+    let expiries: Vec<_> = offenders.iter().map(|cert| cert.not_after).collect();
+    color_time_until(&mut table, 2, &expiries);
+    println!(
+        "❌ {} certificate(s) expire within {}\n",
+        offenders.len(),
+        within
+    );
+    println!("{}", table);
+}
+
+async fn handle_show(matches: &ArgMatches, output: OutputFormat) -> anyhow::Result<()> {
+    let config = ClientConfig::load()?;
+    let target = config
+        .current_target()
+        .ok_or_else(|| anyhow::anyhow!("No target configured"))?;
+    let query = matches.get_one::<String>("certificate").unwrap();
+
+    let client = create_client(target)?;
+    let fetch = |url: String| {
+        let mut request = client.get(url);
+        if let Some(token) = &target.token {
+            request = request.header("X-Doomsday-Token", token);
+        }
+        send(target, request)
+    };
+
+    // A fingerprint, then a path, then anything the search matches
+    let fingerprint = query.replace(':', "").to_lowercase();
+    let certificates: Vec<CacheItem> =
+        if fingerprint.len() == 40 && fingerprint.chars().all(|c| c.is_ascii_hexdigit()) {
+            let response = fetch(format!("{}/v1/cache/{}", target.address, fingerprint)).await?;
+            match response.status() {
+                reqwest::StatusCode::NOT_FOUND => Vec::new(),
+                status if !status.is_success() => {
+                    return Err(anyhow::anyhow!("Failed to fetch certificate: {}", status));
+                }
+                _ => vec![response.json().await?],
+            }
+        } else {
+            let response = fetch(format!("{}/v1/cache", target.address)).await?;
+            if !response.status().is_success() {
+                return Err(anyhow::anyhow!(
+                    "Failed to fetch certificates: {}",
+                    response.status()
+                ));
+            }
+            let all: Vec<CacheItem> = response.json().await?;
+            let at_path: Vec<CacheItem> = all
+                .into_iter()
+                .filter(|cert| {
+                    cert.paths.iter().any(|p| {
+                        *p.path == **query || format!("{}:{}", p.backend, p.path) == *query
+                    })
+                })
+                .collect();
+
+            if at_path.is_empty() {
+                let url = format!(
+                    "{}/v1/cache/search?q={}",
+                    target.address,
+                    urlencoding::encode(query)
+                );
+                let response = fetch(url).await?;
+                if !response.status().is_success() {
+                    return Err(anyhow::anyhow!(
+                        "Failed to search certificates: {}",
+                        response.status()
+                    ));
+                }
+                response.json().await?
+            } else {
+                at_path
+            }
+        };
+
+    if certificates.is_empty() {
+        return Err(anyhow::anyhow!("No certificate matches '{}'", query));
+    }
+    if output.print(&certificates)? {
+        return Ok(());
+    }
+
+    match certificates.as_slice() {
+        [cert] => {
+            print_certificate(cert);
+            Ok(())
+        }
+        _ => {
+            #[derive(Tabled)]
+            struct MatchRow {
+                #[tabled(rename = "Subject")]
+                subject: String,
+                #[tabled(rename = "SHA1")]
+                sha1: String,
+                #[tabled(rename = "Expires")]
+                expires: String,
+            }
+
+            let rows: Vec<MatchRow> = certificates
+                .iter()
+                .map(|cert| MatchRow {
+                    subject: cert.subject.clone(),
+                    sha1: cert.sha1.clone(),
+                    expires: cert.not_after.format("%Y-%m-%d").to_string(),
+                })
+                .collect();
+            let mut table = Table::new(rows);
+            table.with(Style::rounded()).with(Width::wrap(120));
+            println!("{}", table);
+            Err(anyhow::anyhow!(
+                "{} certificates match '{}'; pass a SHA1 to pick one",
+                certificates.len(),
+                query
+            ))
+        }
+    }
+}
+
+fn print_certificate(cert: &CacheItem) {
+    let metadata = &cert.metadata;
+    let or_unknown = |value: &str| {
+        if value.is_empty() {
+            "unknown".to_string()
+        } else {
+            value.to_string()
+        }
+    };
+
+    println!("📜 {}", cert.subject);
+    println!("════════════════════════════════");
+    println!("Issuer:           {}", or_unknown(&metadata.issuer));
+    println!("Serial:           {}", or_unknown(&metadata.serial_number));
+    if !metadata.subject_alt_names.is_empty() {
+        println!(
+            "SANs:             {}",
+            metadata.subject_alt_names.join(", ")
+        );
+    }
+    println!("SHA1:             {}", cert.sha1);
+    println!(
+        "SHA256:           {}",
+        or_unknown(&metadata.fingerprint_sha256)
+    );
+    if !metadata.key_algorithm.is_empty() {
+        match metadata.key_size {
+            0 => println!("Key:              {}", metadata.key_algorithm),
+            bits => println!("Key:              {} {}", metadata.key_algorithm, bits),
+        }
+    }
+    if metadata.is_ca {
+        println!("CA:               yes");
+    }
+    if let Some(not_before) = cert.not_before {
+        println!(
+            "Not Before:       {}",
+            not_before.format("%Y-%m-%d %H:%M UTC")
+        );
+    }
+    println!(
+        "Not After:        {} ({})",
+        cert.not_after.format("%Y-%m-%d %H:%M UTC"),
+        DurationParser::format_human(DurationParser::until_expiry(cert.not_after))
+    );
+    println!("Status:           {}", cert.status);
+    if !cert.labels.is_empty() {
+        let mut labels: Vec<String> = cert
+            .labels
+            .iter()
+            .map(|(key, value)| format!("{}={}", key, value))
+            .collect();
+        labels.sort();
+        println!("Labels:           {}", labels.join(", "));
+    }
+
+    println!();
+    println!("📍 Paths ({})", cert.paths.len());
+    for path in &cert.paths {
+        println!("  {}:{}", path.backend, path.path);
+    }
+}
+
+/// Handshakes with an endpoint the way a `tlsclient` backend would and
+/// prints what it served.
+async fn handle_probe(matches: &ArgMatches, output: OutputFormat) -> anyhow::Result<()> {
+    let address = matches.get_one::<String>("address").unwrap();
+    let (host, port) = match address.rsplit_once(':') {
+        Some((host, port)) if !port.contains(']') => (host, port.parse::<u16>()?),
+        _ => (address.as_str(), 443),
+    };
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    let starttls = matches
+        .get_one::<String>("starttls")
+        .map(|protocol| protocol.parse::<StartTls>())
+        .transpose()?;
+    let server_name = matches.get_one::<String>("server-name").map(String::as_str);
+
+    let timeout = REQUEST_OVERRIDES
+        .get()
+        .and_then(|(timeout, _)| *timeout)
+        .unwrap_or(DEFAULT_TIMEOUT);
+    let cert = TlsClientAccessor::probe(host, port, server_name, starttls, timeout)
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("{} served no certificate", address))?;
+    if output.print(&cert)? {
+        return Ok(());
+    }
+
+    let chain = cert.chain.clone();
+    let object =
+        CacheObject::from_certificate(cert, PathObject::new("probe", format!("{}:{}", host, port)));
+    let status = CertificateStatus::evaluate(
+        &object,
+        false,
+        chrono::Utc::now(),
+        &StatusThresholds::default(),
+    );
+    print_certificate(&CacheItem::new(&object, status));
+
+    if !chain.is_empty() {
+        println!();
+        println!("🔗 Chain ({})", chain.len());
+        for issuer in &chain {
+            println!(
+                "  {} (expires {}, {})",
+                issuer.subject,
+                issuer.not_after.format("%Y-%m-%d %H:%M UTC"),
+                DurationParser::format_human(DurationParser::until_expiry(issuer.not_after))
+            );
+        }
+    }
+    Ok(())
+}
+
+async fn handle_dashboard(matches: &ArgMatches, output: OutputFormat) -> anyhow::Result<()> {
+    match matches.get_one::<String>("watch") {
+        Some(interval) => watch(interval, output, || print_dashboard(output)).await,
+        None => print_dashboard(output).await,
+    }
+}
+
+async fn print_dashboard(output: OutputFormat) -> anyhow::Result<()> {
+    let config = ClientConfig::load()?;
+    let target = config
+        .current_target()
+        .ok_or_else(|| anyhow::anyhow!("No target configured"))?;
+
+    let client = create_client(target)?;
+    let mut request = client.get(&format!("{}/v1/cache", target.address));
+
+    if let Some(token) = &target.token {
+        request = request.header("X-Doomsday-Token", token);
+    }
+
+    let response = send(target, request).await?;
+    if !response.status().is_success() {
+        return Err(anyhow::anyhow!(
+            "Failed to fetch certificates: {}",
+            response.status()
+        ));
+    }
+
+    let certificates: Vec<CacheItem> = response.json().await?;
+
+    let count = |status: CertificateStatus| {
+        certificates
+            .iter()
+            .filter(|cert| cert.status == status)
+            .count()
+    };
+
+    #[derive(Serialize)]
+    struct Dashboard {
+        expired: usize,
+        expiring_soon: usize,
+        ok: usize,
+        not_yet_valid: usize,
+        superseded: usize,
+        revoked: usize,
+        total: usize,
+    }
+
+    let dashboard = Dashboard {
+        expired: count(CertificateStatus::Expired),
+        expiring_soon: count(CertificateStatus::ExpiringSoon),
+        ok: count(CertificateStatus::Ok),
+        not_yet_valid: count(CertificateStatus::NotYetValid),
+        superseded: count(CertificateStatus::Superseded),
+        revoked: count(CertificateStatus::Revoked),
+        total: certificates.len(),
+    };
+    if output.print(&dashboard)? {
+        return Ok(());
+    }
+
+    println!("🔒 Doomsday Certificate Dashboard");
+    println!("═══════════════════════════════════");
+    println!();
+    println!(
+        "⚠️  Expired:        {} certificates",
+        count(CertificateStatus::Expired)
+    );
+    println!(
+        "⏰ Expiring Soon:   {} certificates",
+        count(CertificateStatus::ExpiringSoon)
+    );
+    println!(
+        "✅ OK:              {} certificates",
+        count(CertificateStatus::Ok)
+    );
+    for (label, status) in [
+        ("🕓 Not Yet Valid:  ", CertificateStatus::NotYetValid),
+        ("🔁 Superseded:     ", CertificateStatus::Superseded),
+        ("⛔ Revoked:        ", CertificateStatus::Revoked),
+    ] {
+        let n = count(status);
+        if n > 0 {
+            println!("{} {} certificates", label, n);
+        }
+    }
+    println!("📊 Total:           {} certificates", certificates.len());
+
+    Ok(())
+}
+
+async fn handle_duplicates(matches: &ArgMatches, output: OutputFormat) -> anyhow::Result<()> {
+    let config = ClientConfig::load()?;
+    let target = config
+        .current_target()
+        .ok_or_else(|| anyhow::anyhow!("No target configured"))?;
+
+    let client = create_client(target)?;
+    let mut url = format!("{}/v1/cache/duplicates", target.address);
+    if let Some(min_paths) = matches.get_one::<usize>("min-paths") {
+        url.push_str(&format!("?min_paths={}", min_paths));
+    }
+
+    let mut request = client.get(&url);
+    if let Some(token) = &target.token {
+        request = request.header("X-Doomsday-Token", token);
+    }
+
+    let response = send(target, request).await?;
+    if !response.status().is_success() {
+        return Err(anyhow::anyhow!(
+            "Failed to fetch duplicate report: {}",
+            response.status()
+        ));
+    }
+
+    let report: DuplicateReport = response.json().await?;
+    if output.print(&report)? {
+        return Ok(());
+    }
+
+    if report.copies.is_empty() && report.collisions.is_empty() {
+        println!("No duplicate certificates found");
+        return Ok(());
+    }
+
+    #[derive(Tabled)]
+    struct CopyRow {
+        #[tabled(rename = "Subject")]
+        subject: String,
+        #[tabled(rename = "Expires")]
+        expires: String,
+        #[tabled(rename = "Copies")]
+        copies: usize,
+        #[tabled(rename = "Paths")]
+        paths: String,
+    }
+
+    #[derive(Tabled)]
+    struct CollisionRow {
+        #[tabled(rename = "Name")]
+        name: String,
+        #[tabled(rename = "Subject")]
+        subject: String,
+        #[tabled(rename = "Expires")]
+        expires: String,
+        #[tabled(rename = "Paths")]
+        paths: String,
+    }
+
+    let format_paths = |cert: &CacheItem| {
+        cert.paths
+            .iter()
+            .map(|p| format!("{}:{}", p.backend, p.path))
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+
+    if !report.copies.is_empty() {
+        println!("📋 Certificates at several paths\n");
+        let rows: Vec<CopyRow> = report
+            .copies
+            .iter()
+            .map(|cert| CopyRow {
+                subject: cert.subject.clone(),
+                expires: cert.not_after.format("%Y-%m-%d").to_string(),
+                copies: cert.paths.len(),
+                paths: format_paths(cert),
+            })
+            .collect();
+        let mut table = Table::new(rows);
+        table.with(Style::rounded()).with(Width::wrap(120));
+        println!("{}\n", table);
+    }
+
+    if !report.collisions.is_empty() {
+        println!("👥 Names with several certificates\n");
+        let rows: Vec<CollisionRow> = report
+            .collisions
+            .iter()
+            .flat_map(|collision| {
+                collision.certificates.iter().map(|cert| CollisionRow {
+                    name: collision.name.clone(),
+                    subject: cert.subject.clone(),
+                    expires: cert.not_after.format("%Y-%m-%d").to_string(),
+                    paths: format_paths(cert),
+                })
+            })
+            .collect();
+        let mut table = Table::new(rows);
+        table.with(Style::rounded()).with(Width::wrap(120));
+        println!("{}", table);
+    }
+
+    Ok(())
+}
+
+async fn handle_ca_impact(matches: &ArgMatches, output: OutputFormat) -> anyhow::Result<()> {
+    let config = ClientConfig::load()?;
+    let target = config
+        .current_target()
+        .ok_or_else(|| anyhow::anyhow!("No target configured"))?;
+
+    let client = create_client(target)?;
+    let mut url = format!("{}/v1/cache/ca-impact", target.address);
+    if let Some(within) = matches.get_one::<String>("within") {
+        url.push_str(&format!("?within={}", urlencoding::encode(within)));
+    }
+
+    let mut request = client.get(&url);
+    if let Some(token) = &target.token {
+        request = request.header("X-Doomsday-Token", token);
+    }
+
+    let response = send(target, request).await?;
+    if !response.status().is_success() {
+        return Err(anyhow::anyhow!(
+            "Failed to fetch CA impact report: {}",
+            response.status()
+        ));
+    }
+
+    let report: Vec<CaImpact> = response.json().await?;
+    if output.print(&report)? {
+        return Ok(());
+    }
+
+    if report.is_empty() {
+        println!("No CA certificates expiring in that window");
+        return Ok(());
+    }
+
+    #[derive(Tabled)]
+    struct ImpactRow {
+        #[tabled(rename = "CA")]
+        subject: String,
+        #[tabled(rename = "Expires")]
+        expires: String,
+        #[tabled(rename = "Time Until")]
+        time_until: String,
+        #[tabled(rename = "Leaves")]
+        leaves: usize,
+        #[tabled(rename = "Outliving")]
+        leaves_outliving: usize,
+        #[tabled(rename = "Intermediates")]
+        intermediates: usize,
+    }
+
+    let expiries: Vec<_> = report
+        .iter()
+        .map(|impact| impact.certificate.not_after)
+        .collect();
+    let rows: Vec<ImpactRow> = report
+        .into_iter()
+        .map(|impact| ImpactRow {
+            expires: impact
+                .certificate
+                .not_after
+                .format("%Y-%m-%d %H:%M UTC")
+                .to_string(),
+            time_until: DurationParser::format_human(DurationParser::until_expiry(
+                impact.certificate.not_after,
+            )),
+            subject: impact.certificate.subject,
+            leaves: impact.leaves,
+            leaves_outliving: impact.leaves_outliving,
+            intermediates: impact.intermediates,
+        })
+        .collect();
+
+    let mut table = Table::new(rows);
+    table.with(Style::rounded()).with(Width::wrap(120));
+    color_time_until(&mut table, 2, &expiries);
+    println!("{}", table);
+    Ok(())
+}
+
+async fn handle_refresh(matches: &ArgMatches, output: OutputFormat) -> anyhow::Result<()> {
+    let config = ClientConfig::load()?;
+    let target = config
+        .current_target()
+        .ok_or_else(|| anyhow::anyhow!("No target configured"))?;
+
+    let client = create_client(target)?;
+
+    let refresh_request = if let Some(backends_str) = matches.get_one::<String>("backends") {
+        let backends: Vec<String> = backends_str
+            .split(',')
+            .map(|s| s.trim().to_string())
+            .collect();
+        doomsday_rs::types::RefreshRequest {
+            backends: Some(backends),
+        }
+    } else {
+        doomsday_rs::types::RefreshRequest { backends: None }
+    };
+
+    let dry_run = matches.get_flag("dry-run");
+    let mut url = format!("{}/v1/cache/refresh", target.address);
+    if dry_run {
+        url.push_str("?dry_run=true");
+    }
+
+    let mut request = client.post(&url).json(&refresh_request);
+
+    if let Some(token) = &target.token {
+        request = request.header("X-Doomsday-Token", token);
+    }
+
+    // Keep structured output parseable
+    if output == OutputFormat::Table {
+        if dry_run {
+            println!("🔍 Computing refresh preview (dry run)...");
+        } else {
+            println!("🔄 Refreshing certificate cache...");
+        }
+    }
+
+    let response = send(target, request).await?;
+    if !response.status().is_success() {
+        return Err(anyhow::anyhow!(
+            "Failed to refresh cache: {}",
+            response.status()
+        ));
+    }
+
+    if dry_run {
+        let preview: CacheDiffPreview = response.json().await?;
+        if !output.print(&preview)? {
+            print_refresh_preview(&preview);
+        }
+        return Ok(());
+    }
+
+    let stats: doomsday_rs::types::PopulateStats = response.json().await?;
+    if output.print(&stats)? {
+        return Ok(());
+    }
+
+    println!("✅ Refresh complete");
+    println!("   Certificates: {}", stats.num_certs);
     println!("   Paths:        {}", stats.num_paths);
     println!("   Duration:     {}ms", stats.duration_ms);
 
     Ok(())
 }
 
-async fn handle_info() -> anyhow::Result<()> {
+fn print_refresh_preview(preview: &CacheDiffPreview) {
+    if preview.is_empty() {
+        println!(
+            "✅ No changes ({} certificates unchanged)",
+            preview.unchanged
+        );
+        return;
+    }
+
+    println!("📋 Refresh preview (nothing was applied)");
+    println!("   Added:     {}", preview.added.len());
+    println!("   Removed:   {}", preview.removed.len());
+    println!("   Renewed:   {}", preview.renewed.len());
+    println!("   Unchanged: {}", preview.unchanged);
+
+    for cert in &preview.added {
+        println!(
+            "  + {} (expires {})",
+            cert.subject,
+            cert.not_after.format("%Y-%m-%d")
+        );
+    }
+    for cert in &preview.removed {
+        println!(
+            "  - {} (expires {})",
+            cert.subject,
+            cert.not_after.format("%Y-%m-%d")
+        );
+    }
+    for renewal in &preview.renewed {
+        println!(
+            "  ~ {} at {}:{} ({} -> {})",
+            renewal.subject,
+            renewal.backend,
+            renewal.path,
+            renewal.old_not_after.format("%Y-%m-%d"),
+            renewal.new_not_after.format("%Y-%m-%d")
+        );
+    }
+}
+
+async fn handle_notify_test(matches: &ArgMatches, output: OutputFormat) -> anyhow::Result<()> {
     let config = ClientConfig::load()?;
     let target = config
         .current_target()
         .ok_or_else(|| anyhow::anyhow!("No target configured"))?;
 
-    let client = create_client(target.skip_verify);
-    let response = client
-        .get(&format!("{}/v1/info", target.address))
-        .send()
-        .await?;
+    let client = create_client(target)?;
+    let mut request = client.post(&format!("{}/v1/notifications/test", target.address));
+    if let Some(backend) = matches.get_one::<String>("backend") {
+        request = request.query(&[("backend", backend)]);
+    }
+    if let Some(token) = &target.token {
+        request = request.header("X-Doomsday-Token", token);
+    }
+
+    let response = send(target, request).await?;
+    if response.status() == reqwest::StatusCode::NOT_FOUND {
+        return Err(anyhow::anyhow!(
+            "No matching notification backends are configured"
+        ));
+    }
+    if !response.status().is_success() {
+        return Err(anyhow::anyhow!(
+            "Failed to send test notifications: {}",
+            response.status()
+        ));
+    }
+
+    let results: Vec<NotificationTestResult> = response.json().await?;
+    if !output.print(&results)? {
+        print_notify_test(&results);
+    }
+
+    let failed = results.iter().filter(|result| !result.success).count();
+    if failed > 0 {
+        return Err(anyhow::anyhow!(
+            "{} of {} notification backend(s) failed",
+            failed,
+            results.len()
+        ));
+    }
+
+    Ok(())
+}
+
+fn print_notify_test(results: &[NotificationTestResult]) {
+    #[derive(Tabled)]
+    struct TestRow {
+        #[tabled(rename = "")]
+        status: String,
+        #[tabled(rename = "Backend")]
+        backend: String,
+        #[tabled(rename = "Recipients")]
+        recipients: String,
+        #[tabled(rename = "Error")]
+        error: String,
+    }
+
+    let rows: Vec<TestRow> = results
+        .iter()
+        .map(|result| TestRow {
+            status: if result.success { "✅" } else { "❌" }.to_string(),
+            backend: result.backend.clone(),
+            recipients: result.recipients.join("\n"),
+            error: result.error.clone().unwrap_or_default(),
+        })
+        .collect();
+    let mut table = Table::new(rows);
+    table.with(Style::rounded()).with(Width::wrap(120));
+    println!("🧪 Test notifications\n");
+    println!("{}", table);
+}
+
+async fn handle_info(output: OutputFormat) -> anyhow::Result<()> {
+    let config = ClientConfig::load()?;
+    let target = config
+        .current_target()
+        .ok_or_else(|| anyhow::anyhow!("No target configured"))?;
+
+    let client = create_client(target)?;
+    let response = send(target, client.get(&format!("{}/v1/info", target.address))).await?;
 
     if !response.status().is_success() {
         return Err(anyhow::anyhow!(
@@ -405,6 +1879,9 @@ async fn handle_info() -> anyhow::Result<()> {
     }
 
     let info: doomsday_rs::types::InfoResponse = response.json().await?;
+    if output.print(&info)? {
+        return Ok(());
+    }
 
     println!("🔒 Doomsday Server Information");
     println!("════════════════════════════════");
@@ -418,24 +1895,51 @@ async fn handle_info() -> anyhow::Result<()> {
         }
     );
     println!("Target:           {} ({})", target.name, target.address);
+    if info.degraded {
+        println!("Status:           ⚠️  DEGRADED (one or more backends failed to initialize)");
+    }
+    match &info.last_refresh {
+        Some(summary) => {
+            let ago = chrono::Utc::now() - summary.finished_at;
+            println!(
+                "Last refresh:     {} ago, {} backends, {} failing",
+                DurationParser::format_human(ago),
+                summary.backends.len(),
+                summary.failing_backends()
+            );
+            for backend in &summary.backends {
+                match &backend.error {
+                    Some(error) => println!("  ❌ {}: {}", backend.name, error),
+                    None => println!(
+                        "  ✅ {}: {} certs, {} paths in {}ms",
+                        backend.name, backend.num_certs, backend.num_paths, backend.duration_ms
+                    ),
+                }
+                for failure in &backend.list_failures {
+                    println!("     ⚠️  not listed: {}: {}", failure.path, failure.error);
+                }
+            }
+        }
+        None => println!("Last refresh:     never"),
+    }
 
     Ok(())
 }
 
-async fn handle_scheduler() -> anyhow::Result<()> {
+async fn handle_scheduler(output: OutputFormat) -> anyhow::Result<()> {
     let config = ClientConfig::load()?;
     let target = config
         .current_target()
         .ok_or_else(|| anyhow::anyhow!("No target configured"))?;
 
-    let client = create_client(target.skip_verify);
+    let client = create_client(target)?;
     let mut request = client.get(&format!("{}/v1/scheduler", target.address));
 
     if let Some(token) = &target.token {
         request = request.header("X-Doomsday-Token", token);
     }
 
-    let response = request.send().await?;
+    let response = send(target, request).await?;
     if !response.status().is_success() {
         return Err(anyhow::anyhow!(
             "Failed to get scheduler info: {}",
@@ -444,22 +1948,555 @@ async fn handle_scheduler() -> anyhow::Result<()> {
     }
 
     let info: doomsday_rs::types::SchedulerInfo = response.json().await?;
+    if output.print(&info)? {
+        return Ok(());
+    }
 
     println!("⚙️  Scheduler Information");
     println!("════════════════════════");
     println!("Workers:        {}", info.workers);
     println!("Pending Tasks:  {}", info.pending_tasks);
     println!("Running Tasks:  {}", info.running_tasks);
+    println!(
+        "Refreshes:      {}",
+        if info.paused {
+            "⏸️  paused"
+        } else {
+            "active"
+        }
+    );
+
+    if info.task_stats.is_empty() {
+        return Ok(());
+    }
+
+    #[derive(Tabled)]
+    struct TaskStatsRow {
+        #[tabled(rename = "Task")]
+        task_type: String,
+        #[tabled(rename = "Backend")]
+        backend: String,
+        #[tabled(rename = "OK")]
+        succeeded: u64,
+        #[tabled(rename = "Failed")]
+        failed: u64,
+        #[tabled(rename = "Avg")]
+        avg: String,
+        #[tabled(rename = "p95")]
+        p95: String,
+    }
+
+    let rows: Vec<TaskStatsRow> = info
+        .task_stats
+        .into_iter()
+        .map(|stats| TaskStatsRow {
+            task_type: stats.task_type,
+            backend: stats.backend,
+            succeeded: stats.succeeded,
+            failed: stats.failed,
+            avg: format!("{}ms", stats.avg_duration_ms),
+            p95: format!("{}ms", stats.p95_duration_ms),
+        })
+        .collect();
+
+    let mut table = Table::new(rows);
+    table.with(Style::rounded());
+    println!();
+    println!("{}", table);
 
     Ok(())
 }
 
-fn create_client(skip_verify: bool) -> Client {
-    let mut client_builder = reqwest::Client::builder();
+async fn handle_scheduler_pause(pause: bool, output: OutputFormat) -> anyhow::Result<()> {
+    let config = ClientConfig::load()?;
+    let target = config
+        .current_target()
+        .ok_or_else(|| anyhow::anyhow!("No target configured"))?;
 
-    if skip_verify {
+    let action = if pause { "pause" } else { "resume" };
+    let client = create_client(target)?;
+    let mut request = client.post(&format!("{}/v1/scheduler/{}", target.address, action));
+
+    if let Some(token) = &target.token {
+        request = request.header("X-Doomsday-Token", token);
+    }
+
+    let response = send(target, request).await?;
+    if !response.status().is_success() {
+        return Err(anyhow::anyhow!(
+            "Failed to {} scheduler: {}",
+            action,
+            response.status()
+        ));
+    }
+
+    let info: doomsday_rs::types::SchedulerInfo = response.json().await?;
+    if output.print(&info)? {
+        return Ok(());
+    }
+    if info.paused {
+        println!("⏸️  Scheduled refreshes paused");
+    } else {
+        println!("▶️  Scheduled refreshes resumed");
+    }
+
+    Ok(())
+}
+
+async fn handle_tasks_list(matches: &ArgMatches, output: OutputFormat) -> anyhow::Result<()> {
+    let config = ClientConfig::load()?;
+    let target = config
+        .current_target()
+        .ok_or_else(|| anyhow::anyhow!("No target configured"))?;
+
+    let mut url = format!(
+        "{}/v1/scheduler/tasks?limit={}",
+        target.address,
+        matches.get_one::<usize>("limit").unwrap()
+    );
+    if let Some(status) = matches.get_one::<String>("status") {
+        url.push_str(&format!("&status={}", status));
+    }
+
+    let client = create_client(target)?;
+    let mut request = client.get(&url);
+    if let Some(token) = &target.token {
+        request = request.header("X-Doomsday-Token", token);
+    }
+
+    let response = send(target, request).await?;
+    if !response.status().is_success() {
+        return Err(anyhow::anyhow!(
+            "Failed to list tasks: {}",
+            response.status()
+        ));
+    }
+
+    let tasks: Vec<TaskInfo> = response.json().await?;
+    if output.print(&tasks)? {
+        return Ok(());
+    }
+
+    if tasks.is_empty() {
+        println!("No tasks found");
+        return Ok(());
+    }
+
+    #[derive(Tabled)]
+    struct TaskRow {
+        #[tabled(rename = "ID")]
+        id: String,
+        #[tabled(rename = "Task")]
+        task: String,
+        #[tabled(rename = "Backend")]
+        backend: String,
+        #[tabled(rename = "Status")]
+        status: String,
+        #[tabled(rename = "Attempts")]
+        attempts: u32,
+        #[tabled(rename = "Created")]
+        created: String,
+        #[tabled(rename = "Error")]
+        error: String,
+    }
+
+    let rows: Vec<TaskRow> = tasks
+        .into_iter()
+        .map(|task| TaskRow {
+            id: task.id,
+            task: task.task.kind().to_string(),
+            backend: task.task.backend_name().to_string(),
+            status: format!("{:?}", task.status),
+            attempts: task.attempts,
+            created: task
+                .created_at
+                .with_timezone(&chrono::Local)
+                .format("%Y-%m-%d %H:%M:%S")
+                .to_string(),
+            error: task.error.unwrap_or_default(),
+        })
+        .collect();
+
+    let mut table = Table::new(rows);
+    table.with(Style::rounded()).with(Width::wrap(160));
+    println!("{}", table);
+    Ok(())
+}
+
+/// Shows a task, cancelling it first if asked to.
+async fn handle_task(
+    matches: &ArgMatches,
+    cancel: bool,
+    output: OutputFormat,
+) -> anyhow::Result<()> {
+    let config = ClientConfig::load()?;
+    let target = config
+        .current_target()
+        .ok_or_else(|| anyhow::anyhow!("No target configured"))?;
+    let id = matches.get_one::<String>("id").unwrap();
+
+    let client = create_client(target)?;
+    let url = format!(
+        "{}/v1/scheduler/tasks/{}",
+        target.address,
+        urlencoding::encode(id)
+    );
+    let mut request = if cancel {
+        client.post(&format!("{}/cancel", url))
+    } else {
+        client.get(&url)
+    };
+    if let Some(token) = &target.token {
+        request = request.header("X-Doomsday-Token", token);
+    }
+
+    let response = send(target, request).await?;
+    match response.status() {
+        reqwest::StatusCode::NOT_FOUND => return Err(anyhow::anyhow!("No task with ID {}", id)),
+        reqwest::StatusCode::CONFLICT => {
+            return Err(anyhow::anyhow!("Task {} has already finished", id))
+        }
+        status if !status.is_success() => {
+            return Err(anyhow::anyhow!("Failed to get task: {}", status))
+        }
+        _ => {}
+    }
+
+    let task: TaskInfo = response.json().await?;
+    if output.print(&task)? {
+        return Ok(());
+    }
+
+    if cancel {
+        match task.status {
+            TaskStatus::Running => println!("🛑 Stopping running task {}", task.id),
+            _ => println!("🛑 Task {} cancelled", task.id),
+        }
+        return Ok(());
+    }
+
+    let time = |at: Option<chrono::DateTime<chrono::Utc>>| {
+        at.map(|at| {
+            at.with_timezone(&chrono::Local)
+                .format("%Y-%m-%d %H:%M:%S")
+                .to_string()
+        })
+    };
+    println!("📋 Task {}", task.id);
+    println!("════════════════════════════════");
+    println!("Task:         {}", task.task.kind());
+    println!("Backend:      {}", task.task.backend_name());
+    println!("Status:       {:?}", task.status);
+    println!("Attempts:     {}", task.attempts);
+    println!("Created:      {}", time(Some(task.created_at)).unwrap());
+    if let Some(started) = time(task.started_at) {
+        println!("Started:      {}", started);
+    }
+    if let Some(completed) = time(task.completed_at) {
+        println!("Completed:    {}", completed);
+    }
+    if let Some(retry) = time(task.next_retry_at) {
+        println!("Next Retry:   {}", retry);
+    }
+    if let Some(error) = &task.error {
+        println!("Error:        {}", error);
+    }
+    if let Some(stats) = &task.stats {
+        println!(
+            "Refreshed:    {} certificates at {} paths in {}ms",
+            stats.num_certs, stats.num_paths, stats.duration_ms
+        );
+    }
+    Ok(())
+}
+
+/// Clears the screen and reruns `render` every `interval` until Ctrl+C. A
+/// failed run is shown and retried at the next interval.
+async fn watch<F, Fut>(interval: &str, output: OutputFormat, mut render: F) -> anyhow::Result<()>
+where
+    F: FnMut() -> Fut,
+    Fut: std::future::Future<Output = anyhow::Result<()>>,
+{
+    if output != OutputFormat::Table {
+        return Err(anyhow::anyhow!("--watch only works with table output"));
+    }
+    let period = DurationParser::parse(interval)?.to_std()?;
+
+    loop {
+        print!("\x1B[2J\x1B[H");
+        println!(
+            "🔄 Every {} · updated {} · Ctrl+C to stop\n",
+            interval,
+            chrono::Local::now().format("%H:%M:%S")
+        );
+        if let Err(e) = render().await {
+            println!("❌ {}", e);
+        }
+
+        tokio::select! {
+            _ = tokio::time::sleep(period) => {}
+            _ = tokio::signal::ctrl_c() => return Ok(()),
+        }
+    }
+}
+
+fn create_client(target: &ClientTarget) -> anyhow::Result<Client> {
+    let timeout = REQUEST_OVERRIDES
+        .get()
+        .and_then(|(timeout, _)| *timeout)
+        .or(target.timeout.map(Duration::from_secs))
+        .unwrap_or(DEFAULT_TIMEOUT);
+    let mut client_builder = reqwest::Client::builder().timeout(timeout);
+
+    if target.skip_verify {
         client_builder = client_builder.danger_accept_invalid_certs(true);
     }
 
-    client_builder.build().unwrap()
+    if let Some(path) = &target.ca_cert {
+        let pem = read_pem(path)?;
+        for cert in reqwest::Certificate::from_pem_bundle(&pem)? {
+            client_builder = client_builder.add_root_certificate(cert);
+        }
+    }
+
+    if let Some(path) = &target.client_cert {
+        let mut pem = read_pem(path)?;
+        if let Some(key) = &target.client_key {
+            pem.push(b'\n');
+            pem.extend(read_pem(key)?);
+        }
+        // PEM identities are only supported by the rustls backend
+        client_builder = client_builder
+            .use_rustls_tls()
+            .identity(reqwest::Identity::from_pem(&pem)?);
+    }
+
+    // reqwest uses HTTPS_PROXY, HTTP_PROXY and NO_PROXY unless a proxy is
+    // set here, so NO_PROXY still applies to a target's own proxy
+    if let Some(proxy) = &target.proxy {
+        let proxy = reqwest::Proxy::all(proxy)
+            .map_err(|e| anyhow::anyhow!("Invalid proxy {}: {}", proxy, e))?
+            .no_proxy(reqwest::NoProxy::from_env());
+        client_builder = client_builder.proxy(proxy);
+    }
+
+    Ok(client_builder.build()?)
+}
+
+/// Prompts for whichever of the username and password were not given.
+fn prompt_credentials(
+    username: Option<&String>,
+    password: Option<&String>,
+) -> anyhow::Result<AuthRequest> {
+    let username = match username {
+        Some(username) => username.clone(),
+        None => {
+            // On stderr, so that structured output stays parseable
+            eprint!("Username: ");
+            let mut input = String::new();
+            std::io::stdin().read_line(&mut input)?;
+            input.trim().to_string()
+        }
+    };
+    let password = match password {
+        Some(password) => password.clone(),
+        None => rpassword::prompt_password("Password: ")?,
+    };
+    Ok(AuthRequest { username, password })
+}
+
+async fn login(target: &ClientTarget, credentials: &AuthRequest) -> anyhow::Result<AuthResponse> {
+    let client = create_client(target)?;
+    let request = client
+        .post(&format!("{}/v1/auth", target.address))
+        .json(credentials)
+        .build()?;
+    let response = execute(target, &client, request).await?;
+
+    if !response.status().is_success() {
+        return Err(anyhow::anyhow!("Authentication failed"));
+    }
+    Ok(response.json().await?)
+}
+
+/// Logs in again with the target's saved credentials, or ones typed in at
+/// a prompt, and saves the new token.
+async fn reauthenticate(target: &ClientTarget) -> anyhow::Result<String> {
+    if target.credentials.is_none() && !std::io::stdin().is_terminal() {
+        return Err(anyhow::anyhow!(
+            "Session for target '{}' has expired. Run 'doomsday auth' to log in again.",
+            target.name
+        ));
+    }
+
+    eprintln!(
+        "🔑 Session for target '{}' has expired, logging in again",
+        target.name
+    );
+    let credentials = match &target.credentials {
+        Some(credentials) => credentials.clone(),
+        None => prompt_credentials(None, None)?,
+    };
+    let auth_response = login(target, &credentials).await?;
+
+    let mut config = ClientConfig::load()?;
+    if let Some(target_mut) = config.targets.get_mut(&target.name) {
+        target_mut.token = Some(auth_response.token.clone());
+        target_mut.token_expires = Some(auth_response.expires_at);
+    }
+    config.save()?;
+
+    RENEWED_TOKENS
+        .lock()
+        .unwrap()
+        .insert(target.name.clone(), auth_response.token.clone());
+    Ok(auth_response.token)
+}
+
+/// Sends a request to the target's server. When the server turns it away
+/// because the session has expired, logs in again and resends it.
+async fn send(
+    target: &ClientTarget,
+    request: reqwest::RequestBuilder,
+) -> anyhow::Result<reqwest::Response> {
+    let (client, request) = request.build_split();
+    let mut request = request?;
+    let renewed = RENEWED_TOKENS.lock().unwrap().get(&target.name).cloned();
+    if let Some(token) = &renewed {
+        request
+            .headers_mut()
+            .insert("X-Doomsday-Token", token.parse()?);
+    }
+
+    let resend = request.try_clone();
+    let response = execute(target, &client, request).await?;
+
+    let expired = renewed.is_none()
+        && target
+            .token_expires
+            .is_some_and(|expires| expires <= chrono::Utc::now());
+    let Some(mut request) =
+        resend.filter(|_| expired && response.status() == reqwest::StatusCode::UNAUTHORIZED)
+    else {
+        return Ok(response);
+    };
+
+    let token = reauthenticate(target).await?;
+    request
+        .headers_mut()
+        .insert("X-Doomsday-Token", token.parse()?);
+    Ok(execute(target, &client, request).await?)
+}
+
+/// Sends a request, retrying with backoff while the server can't be
+/// reached. Reads are also retried when they time out or the server answers
+/// 502, 503 or 504; anything else may already have run on the server then,
+/// such as a refresh, so it is not sent twice.
+async fn execute(
+    target: &ClientTarget,
+    client: &Client,
+    request: reqwest::Request,
+) -> reqwest::Result<reqwest::Response> {
+    let retries = REQUEST_OVERRIDES
+        .get()
+        .and_then(|(_, retries)| *retries)
+        .or(target.retries)
+        .unwrap_or(DEFAULT_RETRIES);
+
+    let idempotent = matches!(
+        *request.method(),
+        reqwest::Method::GET | reqwest::Method::HEAD
+    );
+
+    let mut delay = RETRY_BACKOFF;
+    for attempt in 1..=retries {
+        // Streamed bodies can't be sent twice
+        let Some(retry) = request.try_clone() else {
+            break;
+        };
+        let result = client.execute(retry).await;
+        let failure = match &result {
+            Ok(response) if idempotent => match response.status() {
+                reqwest::StatusCode::BAD_GATEWAY
+                | reqwest::StatusCode::SERVICE_UNAVAILABLE
+                | reqwest::StatusCode::GATEWAY_TIMEOUT => response.status().to_string(),
+                _ => return result,
+            },
+            // Never reached the server
+            Err(e) if e.is_connect() => "could not connect".to_string(),
+            Err(e) if idempotent && e.is_timeout() => "timed out".to_string(),
+            _ => return result,
+        };
+
+        eprintln!(
+            "⚠️  Request to {} failed ({}), retrying in {:?} ({}/{})",
+            target.address, failure, delay, attempt, retries
+        );
+        tokio::time::sleep(delay).await;
+        delay *= 2;
+    }
+    client.execute(request).await
+}
+
+/// A duration such as `10s` or `2m`, for request timeouts.
+fn parse_timeout(value: &str) -> Result<Duration, String> {
+    DurationParser::parse(value)
+        .map_err(|e| e.to_string())?
+        .to_std()
+        .map_err(|e| e.to_string())
+}
+
+fn read_pem(path: &Path) -> anyhow::Result<Vec<u8>> {
+    std::fs::read(path).map_err(|e| anyhow::anyhow!("Failed to read {}: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::routing::get;
+    use axum::Router;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_only_reads_are_retried_after_reaching_the_server() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = format!("http://{}", listener.local_addr().unwrap());
+        let hits = Arc::new(AtomicU32::new(0));
+        let unavailable = {
+            let hits = hits.clone();
+            move || {
+                let hits = hits.clone();
+                async move {
+                    hits.fetch_add(1, Ordering::SeqCst);
+                    axum::http::StatusCode::SERVICE_UNAVAILABLE
+                }
+            }
+        };
+        let router = Router::new().route(
+            "/v1/cache/refresh",
+            get(unavailable.clone()).post(unavailable),
+        );
+        tokio::spawn(async move { axum::serve(listener, router).await });
+
+        let target: ClientTarget = serde_json::from_value(serde_json::json!({
+            "name": "local",
+            "address": address,
+            "skip_verify": false,
+            "retries": 2,
+        }))
+        .unwrap();
+        let client = Client::new();
+        let url = format!("{}/v1/cache/refresh", address);
+
+        // The refresh may have been scheduled, so it is not asked for again
+        let request = client.post(&url).build().unwrap();
+        let response = execute(&target, &client, request).await.unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(hits.swap(0, Ordering::SeqCst), 1);
+
+        let request = client.get(&url).build().unwrap();
+        execute(&target, &client, request).await.unwrap();
+        assert_eq!(hits.load(Ordering::SeqCst), 3);
+    }
 }
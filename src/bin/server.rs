@@ -1,17 +1,18 @@
-use clap::{Arg, Command};
-use doomsday_rs::config::Config;
+use clap::{Arg, ArgMatches, Command};
+use doomsday_rs::config::{Config, ConfigFormat};
+use doomsday_rs::core::Core;
 use doomsday_rs::server::DoomsdayServer;
+use doomsday_rs::types::CacheItem;
+use std::path::Path;
 use tracing_subscriber;
 
+/// The commented example config `init` writes.
+const EXAMPLE_CONFIG: &str = include_str!("../../ddayconfig.yml.example");
+/// The example's admin user, given a random password by `init`.
+const EXAMPLE_ADMIN: &str = "admin: \"change-me\"  # `doomsday-server init` generates a random one";
+
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    tracing_subscriber::fmt::init();
-
-    tracing::info!(
-        "Starting Doomsday Certificate Monitor Server v{}",
-        doomsday_rs::version::VERSION
-    );
-
     let matches = Command::new("doomsday-server")
         .version(doomsday_rs::version::VERSION)
         .about("Doomsday certificate monitoring server")
@@ -21,22 +22,81 @@ async fn main() -> anyhow::Result<()> {
                 .long("config")
                 .value_name("FILE")
                 .help("Configuration file path")
-                .default_value("ddayconfig.yml"),
+                .default_value("ddayconfig.yml")
+                .global(true),
+        )
+        .arg(
+            Arg::new("format")
+                .long("format")
+                .value_name("FORMAT")
+                .value_parser(["yaml", "toml", "json"])
+                .help("Configuration file format (default: from the file extension, else YAML)")
+                .global(true),
+        )
+        .subcommand(
+            Command::new("init")
+                .about("Write a commented example configuration to start from")
+                .arg(
+                    Arg::new("force")
+                        .long("force")
+                        .action(clap::ArgAction::SetTrue)
+                        .help("Overwrite an existing file"),
+                ),
+        )
+        .subcommand(
+            Command::new("validate").about("Check the configuration without starting the server"),
+        )
+        .subcommand(
+            Command::new("notify")
+                .about("Send the scheduled notifications once and exit, e.g. from cron")
+                .arg(
+                    Arg::new("server")
+                        .long("server")
+                        .value_name("URL")
+                        .help("Use the cache of a running server instead of scanning the backends"),
+                )
+                .arg(
+                    Arg::new("token")
+                        .long("token")
+                        .requires("server")
+                        .help("Session token for the running server"),
+                ),
         )
         .get_matches();
 
     let config_path = matches.get_one::<String>("config").unwrap();
+    let format = matches
+        .get_one::<String>("format")
+        .map(|format| format.parse::<ConfigFormat>())
+        .transpose()?;
+    if let Some(init_matches) = matches.subcommand_matches("init") {
+        return init(config_path, format, init_matches.get_flag("force"));
+    }
+    if matches.subcommand_matches("validate").is_some() {
+        return validate(config_path, format);
+    }
+
+    tracing_subscriber::fmt::init();
+
+    if let Some(notify_matches) = matches.subcommand_matches("notify") {
+        return notify(config_path, format, notify_matches).await;
+    }
+
+    tracing::info!(
+        "Starting Doomsday Certificate Monitor Server v{}",
+        doomsday_rs::version::VERSION
+    );
     tracing::info!("Loading configuration from: {}", config_path);
 
-    let config = if std::path::Path::new(config_path).exists() {
+    let config = if Path::new(config_path).exists() {
         tracing::info!("Configuration file found, loading...");
-        Config::from_file(config_path)?
+        Config::from_file(config_path, format)?
     } else {
         tracing::warn!(
-            "Config file {} not found, using default configuration",
+            "Config file {} not found, using default configuration and DOOMSDAY__* environment variables",
             config_path
         );
-        Config::default()
+        Config::from_env()?
     };
 
     tracing::info!("Validating configuration...");
@@ -51,3 +111,116 @@ async fn main() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+/// Loads the config, scans the backends or fetches a running server's
+/// cache, and sends the scheduled notifications once.
+async fn notify(
+    config_path: &str,
+    format: Option<ConfigFormat>,
+    matches: &ArgMatches,
+) -> anyhow::Result<()> {
+    let config = load_config(config_path, format)?;
+    config.validate()?;
+
+    let certificates = match matches.get_one::<String>("server") {
+        Some(server) => {
+            tracing::info!("Fetching certificates from {}", server);
+            let mut request =
+                reqwest::Client::new().get(format!("{}/v1/cache", server.trim_end_matches('/')));
+            if let Some(token) = matches.get_one::<String>("token") {
+                request = request.header("X-Doomsday-Token", token);
+            }
+            let response = request.send().await?;
+            if !response.status().is_success() {
+                return Err(anyhow::anyhow!(
+                    "Failed to fetch certificates from {}: {}",
+                    server,
+                    response.status()
+                ));
+            }
+            Some(response.json::<Vec<CacheItem>>().await?)
+        }
+        None => None,
+    };
+
+    let count = Core::notify_once(config, certificates).await?;
+    tracing::info!("Checked {} certificates for scheduled notifications", count);
+    Ok(())
+}
+
+/// Writes the example config with a random password for its admin user.
+fn init(config_path: &str, format: Option<ConfigFormat>, force: bool) -> anyhow::Result<()> {
+    let path = Path::new(config_path);
+    if format.unwrap_or_else(|| ConfigFormat::from_path(path)) != ConfigFormat::Yaml {
+        return Err(anyhow::anyhow!(
+            "init writes YAML; pass a .yml path with -c"
+        ));
+    }
+    if path.exists() && !force {
+        return Err(anyhow::anyhow!(
+            "{} already exists; use --force to overwrite it",
+            config_path
+        ));
+    }
+
+    let password = uuid::Uuid::new_v4().simple().to_string();
+    let content = EXAMPLE_CONFIG.replace(EXAMPLE_ADMIN, &format!("admin: \"{}\"", password));
+    std::fs::write(path, content)?;
+    // Holds the admin password
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))?;
+    }
+
+    println!("✅ Wrote {}", config_path);
+    println!("   Log in as 'admin' with password: {}", password);
+    println!(
+        "   Fill in your backends, then run 'doomsday-server -c {} validate'",
+        config_path
+    );
+    Ok(())
+}
+
+/// Reports every problem with the config and exits non-zero if there are
+/// any. Nothing is logged, so the report is not buried in startup logs.
+fn validate(config_path: &str, format: Option<ConfigFormat>) -> anyhow::Result<()> {
+    let config_path = if Path::new(config_path).exists() {
+        config_path
+    } else {
+        "Environment config"
+    };
+    let config = match load_config(config_path, format) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("❌ {}: {}", config_path, e);
+            std::process::exit(1);
+        }
+    };
+
+    let problems = Core::check_config(&config);
+    if problems.is_empty() {
+        println!(
+            "✅ {} is valid ({} backends)",
+            config_path,
+            config.backends.len()
+        );
+        return Ok(());
+    }
+
+    eprintln!("❌ {} has {} problem(s):", config_path, problems.len());
+    for problem in &problems {
+        eprintln!("  - {}", problem);
+    }
+    std::process::exit(1);
+}
+
+/// The config file, or without one the defaults and any `DOOMSDAY__*`
+/// environment overrides.
+fn load_config(config_path: &str, format: Option<ConfigFormat>) -> doomsday_rs::Result<Config> {
+    if Path::new(config_path).exists() {
+        Config::from_file(config_path, format)
+    } else {
+        Config::from_env()
+    }
+}
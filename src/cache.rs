@@ -1,13 +1,241 @@
-use crate::types::{CacheItem, CacheObject, PathObject};
+use crate::alerts::AlertAcks;
+use crate::config::StatusThresholds;
+use crate::ignore::IgnoreList;
+use crate::json_store::JsonStore;
+use crate::labels::LabelRules;
+use crate::types::{BackendRefreshStats, CacheItem, CacheObject, CertificateStatus, PathObject};
 use chrono::{DateTime, Utc};
-use dashmap::DashMap;
+use dashmap::{DashMap, DashSet};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use std::sync::Arc;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::{Arc, Mutex, RwLock};
+use tokio::sync::broadcast;
+
+/// Capacity of the cache event channel; slow subscribers beyond this lag and
+/// miss events rather than blocking cache updates.
+const EVENT_CHANNEL_CAPACITY: usize = 1024;
 
 #[derive(Debug, Clone)]
 pub struct Cache {
     inner: Arc<DashMap<String, CacheObject>>,
+    index: Arc<SearchIndex>,
+    paths: Arc<PathInterner>,
+    events: broadcast::Sender<CacheEvent>,
+    ignored: IgnoreList,
+    labels: LabelRules,
+    acks: AlertAcks,
+    thresholds: Arc<RwLock<StatusThresholds>>,
+    /// Backends whose refreshes are failing, and since when.
+    failing_since: Arc<RwLock<HashMap<String, DateTime<Utc>>>>,
+    expiry_alerts: Arc<Mutex<ExpiryAlerts>>,
+}
+
+/// Thresholds already reported, as `(sha1, days)`. Kept across restarts when
+/// backed by a file, so a restart does not announce every crossing again.
+pub type CrossedThresholds = JsonStore<HashSet<(String, i64)>>;
+
+/// Days-until-expiry thresholds that raise `ThresholdCrossed`, and the ones
+/// already reported for each certificate.
+#[derive(Debug, Default)]
+struct ExpiryAlerts {
+    thresholds: Vec<i64>,
+    crossed: CrossedThresholds,
+}
+
+/// Shares backend names and paths between the certificates that carry them,
+/// so each distinct string is stored once however many certificates (or
+/// chain certificates at the same path) refer to it.
+#[derive(Debug, Default)]
+pub struct PathInterner {
+    strings: DashSet<Arc<str>>,
+}
+
+impl PathInterner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn intern(&self, path: &mut PathObject) {
+        path.backend = self.intern_str(&path.backend);
+        path.path = self.intern_str(&path.path);
+    }
+
+    fn intern_str(&self, value: &Arc<str>) -> Arc<str> {
+        if let Some(existing) = self.strings.get(&**value) {
+            return existing.key().clone();
+        }
+        self.strings.insert(value.clone());
+        value.clone()
+    }
+
+    /// Forgets strings that only the interner still holds.
+    pub fn prune(&self) {
+        self.strings.retain(|value| Arc::strong_count(value) > 1);
+    }
+
+    pub fn len(&self) -> usize {
+        self.strings.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.strings.is_empty()
+    }
+
+    fn clear(&self) {
+        self.strings.clear();
+    }
+}
+
+/// Lowercased search terms (subject, subject CN, SAN DNS names, issuer and
+/// issuer CN) mapped to the cache keys of the certificates carrying them, so
+/// searches don't scan the whole cache.
+#[derive(Debug, Default)]
+struct SearchIndex {
+    terms: DashMap<String, HashSet<String>>,
+}
+
+impl SearchIndex {
+    fn terms(object: &CacheObject) -> HashSet<String> {
+        let mut terms = HashSet::new();
+        for name in [&object.subject, &object.metadata.issuer] {
+            if name.is_empty() {
+                continue;
+            }
+            terms.insert(name.to_lowercase());
+            if let Some(cn) = common_name(name) {
+                terms.insert(cn.to_lowercase());
+            }
+        }
+        for san in &object.metadata.subject_alt_names {
+            terms.insert(san.to_lowercase());
+        }
+        terms
+    }
+
+    fn add(&self, key: &str, object: &CacheObject) {
+        for term in Self::terms(object) {
+            self.terms.entry(term).or_default().insert(key.to_string());
+        }
+    }
+
+    fn remove(&self, key: &str, object: &CacheObject) {
+        for term in Self::terms(object) {
+            if let Some(mut keys) = self.terms.get_mut(&term) {
+                keys.remove(key);
+            }
+            self.terms.remove_if(&term, |_, keys| keys.is_empty());
+        }
+    }
+
+    fn get(&self, term: &str) -> HashSet<String> {
+        self.terms
+            .get(term)
+            .map(|keys| keys.clone())
+            .unwrap_or_default()
+    }
+
+    /// Keys matching `query` exactly, or through a wildcard SAN one label up.
+    fn lookup(&self, query: &str) -> HashSet<String> {
+        let query = query.trim().to_lowercase();
+        let mut keys = self.get(&query);
+        if let Some((_, parent)) = query.split_once('.') {
+            keys.extend(self.get(&format!("*.{}", parent)));
+        }
+        keys
+    }
+
+    fn clear(&self) {
+        self.terms.clear();
+    }
+}
+
+/// The CN component of a distinguished name such as `C=US, CN=example.com`.
+fn common_name(name: &str) -> Option<&str> {
+    name.split(',')
+        .map(str::trim)
+        .find_map(|part| part.strip_prefix("CN="))
+}
+
+/// Chains longer than this are assumed to loop (e.g. cross-signed CAs).
+const MAX_CHAIN_DEPTH: usize = 16;
+
+/// Everything needed to compute `CertificateStatus` consistently for a
+/// snapshot of the cache.
+struct StatusContext {
+    now: DateTime<Utc>,
+    thresholds: StatusThresholds,
+    superseded: HashSet<String>,
+    stale_backends: HashSet<String>,
+    labels: LabelRules,
+    acks: AlertAcks,
+}
+
+impl StatusContext {
+    fn item(&self, object: &CacheObject) -> CacheItem {
+        let superseded = self.superseded.contains(&object.sha1);
+        let status = CertificateStatus::evaluate(object, superseded, self.now, &self.thresholds);
+        CacheItem {
+            stale: is_stale(object, &self.stale_backends),
+            labels: self.labels.labels_for(object),
+            acknowledged: self.acks.active(&object.sha1, self.now),
+            ..CacheItem::new(object, status)
+        }
+    }
+}
+
+/// Whether every path the certificate was found at belongs to a stale backend.
+fn is_stale(object: &CacheObject, stale_backends: &HashSet<String>) -> bool {
+    !stale_backends.is_empty()
+        && !object.paths.is_empty()
+        && object
+            .paths
+            .iter()
+            .all(|p| stale_backends.contains(&*p.backend))
+}
+
+/// Change notifications emitted by the cache (and by expiry threshold checks)
+/// for subscribers such as outbound webhooks.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum CacheEvent {
+    CertificateAdded {
+        certificate: CacheObject,
+    },
+    CertificateRemoved {
+        certificate: CacheObject,
+    },
+    ThresholdCrossed {
+        certificate: CacheObject,
+        threshold_days: i64,
+    },
+    /// `certificate` replaced `old_sha1` at a path and expires later.
+    CertificateRotated {
+        certificate: CacheObject,
+        backend: String,
+        path: String,
+        old_sha1: String,
+        old_not_after: DateTime<Utc>,
+    },
+}
+
+impl CacheEvent {
+    pub fn name(&self) -> &'static str {
+        match self {
+            CacheEvent::CertificateAdded { .. } => "certificate_added",
+            CacheEvent::CertificateRemoved { .. } => "certificate_removed",
+            CacheEvent::ThresholdCrossed { .. } => "threshold_crossed",
+            CacheEvent::CertificateRotated { .. } => "certificate_rotated",
+        }
+    }
+
+    pub fn certificate(&self) -> &CacheObject {
+        match self {
+            CacheEvent::CertificateAdded { certificate }
+            | CacheEvent::CertificateRemoved { certificate }
+            | CacheEvent::ThresholdCrossed { certificate, .. }
+            | CacheEvent::CertificateRotated { certificate, .. } => certificate,
+        }
+    }
 }
 
 impl Default for Cache {
@@ -18,9 +246,256 @@ impl Default for Cache {
 
 impl Cache {
     pub fn new() -> Self {
+        let (events, _) = broadcast::channel(EVENT_CHANNEL_CAPACITY);
         Cache {
             inner: Arc::new(DashMap::new()),
+            index: Arc::new(SearchIndex::default()),
+            paths: Arc::new(PathInterner::new()),
+            events,
+            ignored: IgnoreList::new(),
+            labels: LabelRules::new(),
+            acks: AlertAcks::new(),
+            thresholds: Arc::new(RwLock::new(StatusThresholds::default())),
+            failing_since: Arc::new(RwLock::new(HashMap::new())),
+            expiry_alerts: Arc::new(Mutex::new(ExpiryAlerts::default())),
+        }
+    }
+
+    pub fn set_status_thresholds(&self, thresholds: StatusThresholds) {
+        *self.thresholds.write().unwrap() = thresholds;
+    }
+
+    pub fn status_thresholds(&self) -> StatusThresholds {
+        *self.thresholds.read().unwrap()
+    }
+
+    /// Tracks which backends are failing so their certificates can be
+    /// flagged once the data is older than `status.stale_after_hours`.
+    pub fn record_backend_refresh(&self, backends: &[BackendRefreshStats]) {
+        let mut failing_since = self.failing_since.write().unwrap();
+        for stats in backends {
+            if stats.error.is_none() {
+                if failing_since.remove(&stats.name).is_some() {
+                    tracing::info!("Backend {} recovered", stats.name);
+                }
+                continue;
+            }
+
+            let since = *failing_since
+                .entry(stats.name.clone())
+                .or_insert(stats.finished_at);
+            if self.is_stale_since(since, Utc::now()) {
+                tracing::warn!(
+                    "Backend {} has been failing since {}, its certificates are stale",
+                    stats.name,
+                    since
+                );
+            }
+        }
+    }
+
+    pub fn failing_since(&self, backend: &str) -> Option<DateTime<Utc>> {
+        self.failing_since.read().unwrap().get(backend).copied()
+    }
+
+    pub fn is_backend_stale(&self, backend: &str) -> bool {
+        self.failing_since(backend)
+            .is_some_and(|since| self.is_stale_since(since, Utc::now()))
+    }
+
+    fn is_stale_since(&self, since: DateTime<Utc>, now: DateTime<Utc>) -> bool {
+        self.status_thresholds()
+            .stale_after_hours
+            .is_some_and(|hours| now - since > chrono::Duration::hours(hours))
+    }
+
+    fn stale_backends(&self, now: DateTime<Utc>) -> HashSet<String> {
+        self.failing_since
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|(_, since)| self.is_stale_since(**since, now))
+            .map(|(name, _)| name.clone())
+            .collect()
+    }
+
+    /// Certificates for which a newer, currently valid certificate with the
+    /// same subject is also cached.
+    fn superseded(&self, now: DateTime<Utc>) -> HashSet<String> {
+        let mut newest_valid: HashMap<String, DateTime<Utc>> = HashMap::new();
+        for entry in self.inner.iter() {
+            let obj = entry.value();
+            if obj.not_before.is_some_and(|not_before| not_before > now) {
+                continue;
+            }
+            let newest = newest_valid
+                .entry(obj.subject.clone())
+                .or_insert(obj.not_after);
+            if obj.not_after > *newest {
+                *newest = obj.not_after;
+            }
         }
+
+        self.inner
+            .iter()
+            .filter(|entry| {
+                newest_valid
+                    .get(&entry.value().subject)
+                    .is_some_and(|newest| entry.value().not_after < *newest)
+            })
+            .map(|entry| entry.key().clone())
+            .collect()
+    }
+
+    fn status_context(&self) -> StatusContext {
+        let now = Utc::now();
+        StatusContext {
+            now,
+            thresholds: self.status_thresholds(),
+            superseded: self.superseded(now),
+            stale_backends: self.stale_backends(now),
+            labels: self.labels.clone(),
+            acks: self.acks.clone(),
+        }
+    }
+
+    pub fn with_ignore_list(ignored: IgnoreList) -> Self {
+        Cache {
+            ignored,
+            ..Self::new()
+        }
+    }
+
+    pub fn ignore_list(&self) -> &IgnoreList {
+        &self.ignored
+    }
+
+    pub fn label_rules(&self) -> &LabelRules {
+        &self.labels
+    }
+
+    /// Replaces the (empty) acknowledgements, e.g. with ones loaded from disk.
+    pub fn with_alert_acks(self, acks: AlertAcks) -> Self {
+        Cache { acks, ..self }
+    }
+
+    pub fn alert_acks(&self) -> &AlertAcks {
+        &self.acks
+    }
+
+    /// Replaces the (empty) reported thresholds, e.g. with ones loaded from
+    /// disk.
+    pub fn with_crossed_thresholds(self, crossed: CrossedThresholds) -> Self {
+        self.expiry_alerts.lock().unwrap().crossed = crossed;
+        self
+    }
+
+    pub fn crossed_thresholds(&self) -> CrossedThresholds {
+        self.expiry_alerts.lock().unwrap().crossed.clone()
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<CacheEvent> {
+        self.events.subscribe()
+    }
+
+    pub fn set_event_thresholds(&self, thresholds: &[i64]) {
+        let mut thresholds = thresholds.to_vec();
+        thresholds.sort_unstable();
+        thresholds.dedup();
+        self.expiry_alerts.lock().unwrap().thresholds = thresholds;
+    }
+
+    /// Emits one `ThresholdCrossed` per certificate for the tightest threshold
+    /// it has newly crossed, and returns how many were emitted. Each threshold
+    /// is reported once per certificate.
+    pub fn check_thresholds(&self) -> usize {
+        let now = Utc::now();
+        let objects = self.list_objects();
+
+        let events = {
+            let alerts = self.expiry_alerts.lock().unwrap();
+            let crossed = alerts.crossed.read();
+
+            let mut events = Vec::new();
+            let mut reported = Vec::new();
+            for object in &objects {
+                let days_until_expiry = (object.not_after - now).num_days();
+                let reached: Vec<i64> = alerts
+                    .thresholds
+                    .iter()
+                    .copied()
+                    .filter(|threshold| days_until_expiry <= *threshold)
+                    .filter(|threshold| !crossed.contains(&(object.sha1.clone(), *threshold)))
+                    .collect();
+
+                if let Some(tightest) = reached.first() {
+                    events.push(CacheEvent::ThresholdCrossed {
+                        certificate: object.clone(),
+                        threshold_days: *tightest,
+                    });
+                    reported.extend(reached.into_iter().map(|t| (object.sha1.clone(), t)));
+                }
+            }
+            drop(crossed);
+
+            if !reported.is_empty() {
+                if let Err(e) = alerts.crossed.update(|crossed| crossed.extend(reported)) {
+                    tracing::error!("Failed to save crossed thresholds: {}", e);
+                }
+            }
+            events
+        };
+
+        let count = events.len();
+        for event in events {
+            self.emit(event);
+        }
+        count
+    }
+
+    /// Drops the thresholds reported for certificates no longer cached, so
+    /// one that comes back is announced afresh. Only removals prune, so a
+    /// partly refilled cache after a restart keeps the others' state.
+    fn forget_crossed_thresholds(&self, sha1s: &[&str]) {
+        let alerts = self.expiry_alerts.lock().unwrap();
+        if !alerts
+            .crossed
+            .read()
+            .iter()
+            .any(|(sha1, _)| sha1s.contains(&sha1.as_str()))
+        {
+            return;
+        }
+        if let Err(e) = alerts
+            .crossed
+            .update(|crossed| crossed.retain(|(sha1, _)| !sha1s.contains(&sha1.as_str())))
+        {
+            tracing::error!("Failed to save crossed thresholds: {}", e);
+        }
+    }
+
+    fn emit(&self, event: CacheEvent) {
+        if self.ignored.matches(event.certificate()) {
+            return;
+        }
+        // Having no subscribers is the normal case when nothing is configured
+        let _ = self.events.send(event);
+    }
+
+    /// Drops a certificate from the cache. Unless it is also ignored it will
+    /// reappear on the next refresh if a backend still reports it.
+    pub fn purge(&self, sha1: &str) -> Option<CacheObject> {
+        let removed = self.remove(sha1)?;
+        self.forget_crossed_thresholds(&[sha1]);
+        tracing::info!(
+            "Purged certificate from cache: {} ({})",
+            removed.subject,
+            sha1
+        );
+        self.emit(CacheEvent::CertificateRemoved {
+            certificate: removed.clone(),
+        });
+        Some(removed)
     }
 
     pub fn get(&self, sha1: &str) -> Option<CacheObject> {
@@ -28,11 +503,28 @@ impl Cache {
     }
 
     pub fn insert(&self, sha1: String, object: CacheObject) {
-        self.inner.insert(sha1, object);
+        self.put(sha1, object);
     }
 
     pub fn remove(&self, sha1: &str) -> Option<CacheObject> {
-        self.inner.remove(sha1).map(|(_, obj)| obj)
+        let (_, removed) = self.inner.remove(sha1)?;
+        self.index.remove(sha1, &removed);
+        Some(removed)
+    }
+
+    /// Inserts or replaces an entry, keeping the search index in step.
+    fn put(&self, sha1: String, mut object: CacheObject) -> Option<CacheObject> {
+        for path in &mut object.paths {
+            self.paths.intern(path);
+        }
+        let previous = self.inner.insert(sha1.clone(), object);
+        if let Some(previous) = &previous {
+            self.index.remove(&sha1, previous);
+        }
+        if let Some(current) = self.inner.get(&sha1) {
+            self.index.add(&sha1, current.value());
+        }
+        previous
     }
 
     pub fn len(&self) -> usize {
@@ -45,18 +537,19 @@ impl Cache {
 
     pub fn clear(&self) {
         self.inner.clear();
+        self.index.clear();
+        self.paths.clear();
     }
 
     pub fn list(&self) -> Vec<CacheItem> {
+        let context = self.status_context();
         let mut items = Vec::new();
 
         for entry in self.inner.iter() {
-            let obj = entry.value();
-            items.push(CacheItem {
-                subject: obj.subject.clone(),
-                not_after: obj.not_after,
-                paths: obj.paths.clone(),
-            });
+            if self.ignored.matches(entry.value()) {
+                continue;
+            }
+            items.push(context.item(entry.value()));
         }
 
         // Sort by expiry date
@@ -65,6 +558,381 @@ impl Cache {
         items
     }
 
+    /// The certificates `list` returns, in the same order, but each built
+    /// only when the iterator reaches it, so a large inventory can be
+    /// streamed without holding every item at once. Certificates removed in
+    /// the meantime are skipped.
+    pub fn items(&self) -> impl Iterator<Item = CacheItem> + Send + 'static {
+        let mut keys: Vec<(DateTime<Utc>, String)> = self
+            .inner
+            .iter()
+            .filter(|entry| !self.ignored.matches(entry.value()))
+            .map(|entry| (entry.value().not_after, entry.key().clone()))
+            .collect();
+        keys.sort();
+
+        let context = self.status_context();
+        let inner = self.inner.clone();
+        keys.into_iter().filter_map(move |(_, sha1)| {
+            let object = inner.get(&sha1)?;
+            Some(context.item(object.value()))
+        })
+    }
+
+    pub fn find_by_path(&self, backend: &str, path: &str) -> Vec<CacheObject> {
+        self.inner
+            .iter()
+            .filter(|entry| {
+                entry
+                    .value()
+                    .paths
+                    .iter()
+                    .any(|p| &*p.backend == backend && &*p.path == path)
+            })
+            .map(|entry| entry.value().clone())
+            .collect()
+    }
+
+    /// Every cached certificate, ignored ones included, for persisting.
+    pub fn snapshot(&self) -> Vec<CacheObject> {
+        self.inner
+            .iter()
+            .map(|entry| entry.value().clone())
+            .collect()
+    }
+
+    /// Loads a previously saved snapshot without emitting change events.
+    pub fn restore(&self, objects: Vec<CacheObject>) {
+        for object in objects {
+            self.put(object.sha1.clone(), object);
+        }
+    }
+
+    /// Replaces the contents with a snapshot taken elsewhere (e.g. by another
+    /// replica) without emitting change events.
+    pub fn replace_all(&self, objects: Vec<CacheObject>) {
+        let keep: HashSet<String> = objects.iter().map(|o| o.sha1.clone()).collect();
+        let stale: Vec<String> = self
+            .inner
+            .iter()
+            .filter(|entry| !keep.contains(entry.key()))
+            .map(|entry| entry.key().clone())
+            .collect();
+        for sha1 in stale {
+            self.remove(&sha1);
+        }
+        self.restore(objects);
+        self.paths.prune();
+    }
+
+    /// A single certificate as `list` would return it.
+    pub fn item(&self, object: &CacheObject) -> CacheItem {
+        self.item_for(object, Utc::now(), &self.status_thresholds())
+    }
+
+    /// Certificates whose subject, subject CN, SAN DNS names, issuer or issuer
+    /// CN match `query` case-insensitively, including wildcard SANs covering
+    /// it. Served from the search index rather than a scan of the cache.
+    pub fn search(&self, query: &str) -> Vec<CacheItem> {
+        let now = Utc::now();
+        let thresholds = self.status_thresholds();
+
+        let mut items: Vec<CacheItem> = self
+            .index
+            .lookup(query)
+            .into_iter()
+            .filter_map(|sha1| self.get(&sha1))
+            .filter(|object| !self.ignored.matches(object))
+            .map(|object| self.item_for(&object, now, &thresholds))
+            .collect();
+
+        items.sort_by_key(|item| item.not_after);
+        tracing::debug!(
+            "Search for '{}' matched {} certificates",
+            query,
+            items.len()
+        );
+        items
+    }
+
+    /// Status of a single certificate without evaluating the whole cache.
+    fn item_for(
+        &self,
+        object: &CacheObject,
+        now: DateTime<Utc>,
+        thresholds: &StatusThresholds,
+    ) -> CacheItem {
+        let superseded = self.is_superseded(object, now);
+        CacheItem {
+            stale: is_stale(object, &self.stale_backends(now)),
+            labels: self.labels.labels_for(object),
+            acknowledged: self.acks.active(&object.sha1, now),
+            ..CacheItem::new(
+                object,
+                CertificateStatus::evaluate(object, superseded, now, thresholds),
+            )
+        }
+    }
+
+    /// Whether `issuer` can have issued `object`: its subject is the issuer
+    /// name and, when both carry them, the key identifiers agree.
+    fn issued_by(object: &CacheObject, issuer: &CacheObject) -> bool {
+        let (aki, ski) = (
+            &object.metadata.authority_key_id,
+            &issuer.metadata.subject_key_id,
+        );
+        issuer.sha1 != object.sha1
+            && issuer.subject == object.metadata.issuer
+            && (aki.is_empty() || ski.is_empty() || aki == ski)
+    }
+
+    /// The cached CA that issued `object`, preferring the currently valid
+    /// one that expires last when a CA has been renewed.
+    fn issuer_of(&self, object: &CacheObject, now: DateTime<Utc>) -> Option<CacheObject> {
+        if object.metadata.issuer.is_empty() || object.metadata.issuer == object.subject {
+            return None;
+        }
+
+        self.index
+            .get(&object.metadata.issuer.to_lowercase())
+            .iter()
+            .filter_map(|sha1| self.get(sha1))
+            .filter(|candidate| Self::issued_by(object, candidate))
+            .max_by_key(|candidate| {
+                let valid = candidate
+                    .not_before
+                    .is_none_or(|not_before| not_before <= now);
+                (valid, candidate.not_after)
+            })
+    }
+
+    /// The certificate and the cached CAs above it, plus the certificates it
+    /// issued. `None` if `sha1` is not cached.
+    pub fn chain(&self, sha1: &str) -> Option<CertificateChain> {
+        let now = Utc::now();
+        let thresholds = self.status_thresholds();
+        let start = self.get(sha1)?;
+
+        let mut links = vec![start.clone()];
+        let mut seen = HashSet::from([start.sha1.clone()]);
+        while links.len() < MAX_CHAIN_DEPTH {
+            let Some(issuer) = self.issuer_of(links.last().unwrap(), now) else {
+                break;
+            };
+            if !seen.insert(issuer.sha1.clone()) {
+                break;
+            }
+            links.push(issuer);
+        }
+
+        let top = links.last().unwrap();
+        let complete = !top.metadata.issuer.is_empty() && top.metadata.issuer == top.subject;
+
+        let chain = links
+            .iter()
+            .enumerate()
+            .map(|(i, object)| ChainLink {
+                outlives_issuer: links
+                    .get(i + 1)
+                    .is_some_and(|issuer| issuer.not_after < object.not_after),
+                certificate: self.item_for(object, now, &thresholds),
+            })
+            .collect();
+
+        let mut issued: Vec<CacheItem> = self
+            .index
+            .get(&start.subject.to_lowercase())
+            .iter()
+            .filter_map(|sha1| self.get(sha1))
+            .filter(|object| Self::issued_by(object, &start))
+            .map(|object| self.item_for(&object, now, &thresholds))
+            .collect();
+        issued.sort_by_key(|item| item.not_after);
+
+        Some(CertificateChain {
+            chain,
+            complete,
+            issued,
+        })
+    }
+
+    /// CA certificates expiring within `within` (or already expired), with
+    /// the cached certificates that depend on them, highest impact first.
+    /// Each certificate is counted under the issuers `chain` would show for
+    /// it, so a renewed CA takes over the dependents of the one it replaces.
+    pub fn ca_impact(&self, within: chrono::Duration) -> Vec<CaImpact> {
+        let now = Utc::now();
+        let thresholds = self.status_thresholds();
+        let deadline = now + within;
+        let objects = self.list_objects();
+
+        let mut issuers: HashMap<String, Option<CacheObject>> = HashMap::new();
+        let mut impact: HashMap<String, CaImpact> = HashMap::new();
+        for object in &objects {
+            let mut current = object.clone();
+            let mut seen = HashSet::from([current.sha1.clone()]);
+            while seen.len() < MAX_CHAIN_DEPTH {
+                let issuer = issuers
+                    .entry(current.sha1.clone())
+                    .or_insert_with(|| self.issuer_of(&current, now))
+                    .clone();
+                let Some(issuer) = issuer else {
+                    break;
+                };
+                if !seen.insert(issuer.sha1.clone()) {
+                    break;
+                }
+
+                if issuer.not_after <= deadline {
+                    let entry = impact
+                        .entry(issuer.sha1.clone())
+                        .or_insert_with(|| CaImpact {
+                            certificate: self.item_for(&issuer, now, &thresholds),
+                            leaves: 0,
+                            intermediates: 0,
+                            leaves_outliving: 0,
+                        });
+                    if object.metadata.is_ca {
+                        entry.intermediates += 1;
+                    } else {
+                        entry.leaves += 1;
+                        if object.not_after > issuer.not_after {
+                            entry.leaves_outliving += 1;
+                        }
+                    }
+                }
+                current = issuer;
+            }
+        }
+
+        // CAs nothing cached depends on still need rotating
+        for object in &objects {
+            if object.metadata.is_ca
+                && object.not_after <= deadline
+                && !impact.contains_key(&object.sha1)
+            {
+                let item = self.item_for(object, now, &thresholds);
+                if item.status != CertificateStatus::Superseded {
+                    impact.insert(
+                        object.sha1.clone(),
+                        CaImpact {
+                            certificate: item,
+                            leaves: 0,
+                            intermediates: 0,
+                            leaves_outliving: 0,
+                        },
+                    );
+                }
+            }
+        }
+
+        let mut report: Vec<CaImpact> = impact.into_values().collect();
+        report.sort_by(|a, b| {
+            b.leaves
+                .cmp(&a.leaves)
+                .then(b.intermediates.cmp(&a.intermediates))
+                .then(a.certificate.not_after.cmp(&b.certificate.not_after))
+        });
+        report
+    }
+
+    /// `superseded` for a single certificate, looking up same-subject
+    /// certificates through the index.
+    fn is_superseded(&self, object: &CacheObject, now: DateTime<Utc>) -> bool {
+        self.index
+            .get(&object.subject.to_lowercase())
+            .iter()
+            .filter_map(|sha1| self.inner.get(sha1))
+            .any(|other| {
+                other.subject == object.subject
+                    && other.not_before.is_none_or(|not_before| not_before <= now)
+                    && other.not_after > object.not_after
+            })
+    }
+
+    /// Certificates that will need rotating together: the same certificate
+    /// found at `min_paths` or more paths, and distinct current certificates
+    /// issued for the same name (subject CN or SAN).
+    pub fn duplicates(&self, min_paths: usize) -> DuplicateReport {
+        let items = self.list();
+
+        let mut copies: Vec<CacheItem> = items
+            .iter()
+            .filter(|item| item.paths.len() >= min_paths.max(2))
+            .cloned()
+            .collect();
+        copies.sort_by_key(|item| std::cmp::Reverse(item.paths.len()));
+
+        // Superseded certificates are on their way out, not colliding
+        let mut by_name: HashMap<String, Vec<CacheItem>> = HashMap::new();
+        for item in items
+            .iter()
+            .filter(|item| item.status != CertificateStatus::Superseded)
+        {
+            let mut names: HashSet<String> = item
+                .metadata
+                .subject_alt_names
+                .iter()
+                .map(|san| san.to_lowercase())
+                .collect();
+            names.insert(
+                common_name(&item.subject)
+                    .unwrap_or(&item.subject)
+                    .to_lowercase(),
+            );
+            for name in names {
+                by_name.entry(name).or_default().push(item.clone());
+            }
+        }
+
+        let mut collisions: Vec<NameCollision> = by_name
+            .into_iter()
+            .filter(|(_, certificates)| certificates.len() > 1)
+            .map(|(name, certificates)| NameCollision { name, certificates })
+            .collect();
+        collisions.sort_by(|a, b| a.name.cmp(&b.name));
+
+        DuplicateReport { copies, collisions }
+    }
+
+    pub fn list_objects(&self) -> Vec<CacheObject> {
+        self.inner
+            .iter()
+            .filter(|entry| !self.ignored.matches(entry.value()))
+            .map(|entry| entry.value().clone())
+            .collect()
+    }
+
+    /// Whether `backend` serves a certificate that expires within `within`
+    /// and has not already been superseded by a renewal.
+    pub fn has_expiring(&self, backend: &str, within: chrono::Duration) -> bool {
+        let now = Utc::now();
+        let deadline = now + within;
+        self.list().iter().any(|item| {
+            item.not_after > now
+                && item.not_after <= deadline
+                && !matches!(item.status, CertificateStatus::Superseded)
+                && item.paths.iter().any(|p| &*p.backend == backend)
+        })
+    }
+
+    /// The cached paths of `backend` below any of `dirs`, such as the
+    /// directories a listing could not read, so a scan keeps them rather
+    /// than pruning them as deleted.
+    pub fn paths_under(&self, backend: &str, dirs: &[String]) -> HashSet<PathObject> {
+        let prefixes: Vec<String> = dirs
+            .iter()
+            .map(|dir| format!("{}/", dir.trim_end_matches('/')))
+            .collect();
+        self.inner
+            .iter()
+            .flat_map(|entry| entry.value().paths.clone())
+            .filter(|p| {
+                &*p.backend == backend && prefixes.iter().any(|prefix| p.path.starts_with(prefix))
+            })
+            .collect()
+    }
+
     pub fn list_filtered<F>(&self, filter: F) -> Vec<CacheItem>
     where
         F: Fn(&CacheItem) -> bool,
@@ -83,6 +951,11 @@ impl Cache {
         for sha1 in &diff.removed {
             if let Some(removed_obj) = self.remove(sha1) {
                 tracing::debug!("Removed certificate from cache: {}", removed_obj.subject);
+                if !diff.added.contains_key(sha1) {
+                    self.emit(CacheEvent::CertificateRemoved {
+                        certificate: removed_obj,
+                    });
+                }
             }
         }
 
@@ -93,29 +966,203 @@ impl Cache {
                 object.subject,
                 sha1
             );
-            self.insert(sha1, object);
+            let is_new =
+                self.put(sha1.clone(), object.clone()).is_none() && !diff.removed.contains(&sha1);
+            if is_new {
+                self.emit(CacheEvent::CertificateAdded {
+                    certificate: object,
+                });
+            }
         }
 
+        let gone: Vec<&str> = diff
+            .removed
+            .iter()
+            .filter(|sha1| !self.inner.contains_key(sha1.as_str()))
+            .map(String::as_str)
+            .collect();
+        self.forget_crossed_thresholds(&gone);
+        self.paths.prune();
+        self.check_thresholds();
         tracing::debug!("Cache update completed, new size: {}", self.len());
         Ok(())
     }
 
-    pub fn get_stats(&self) -> CacheStats {
-        let now = Utc::now();
-        let mut stats = CacheStats::default();
+    /// Publishes `certificate_rotated` for each of `renewals`, already
+    /// applied, that moved the path's expiry later.
+    pub fn publish_rotations(&self, renewals: &[CertificateRenewal]) {
+        for renewal in renewals
+            .iter()
+            .filter(|r| r.new_not_after > r.old_not_after)
+        {
+            let Some(certificate) = self.get(&renewal.new_sha1) else {
+                continue;
+            };
+            self.emit(CacheEvent::CertificateRotated {
+                certificate,
+                backend: renewal.backend.clone(),
+                path: renewal.path.clone(),
+                old_sha1: renewal.old_sha1.clone(),
+                old_not_after: renewal.old_not_after,
+            });
+        }
+    }
 
+    /// Certificates that applying `diff` would replace at a path with a
+    /// different certificate. Where several certificates share a path (e.g. a
+    /// leaf and its chain) the replacement is matched by subject.
+    pub fn rotations(&self, diff: &CacheDiff) -> Vec<CertificateRenewal> {
+        let touched: HashSet<&PathObject> =
+            diff.added.values().flat_map(|o| o.paths.iter()).collect();
+
+        let mut before: HashMap<PathObject, Vec<CacheObject>> = HashMap::new();
         for entry in self.inner.iter() {
-            let obj = entry.value();
-            stats.total += 1;
+            for path in &entry.value().paths {
+                if touched.contains(path) {
+                    before
+                        .entry(path.clone())
+                        .or_default()
+                        .push(entry.value().clone());
+                }
+            }
+        }
 
-            let days_until_expiry = (obj.not_after - now).num_days();
+        let mut rotations = Vec::new();
+        for (sha1, object) in &diff.added {
+            for path in &object.paths {
+                let Some(old) = before.get(path) else {
+                    continue;
+                };
+                if old.iter().any(|o| &o.sha1 == sha1) {
+                    continue;
+                }
 
-            if days_until_expiry < 0 {
-                stats.expired += 1;
-            } else if days_until_expiry <= 30 {
-                stats.expiring_soon += 1;
-            } else {
-                stats.ok += 1;
+                let leaving: Vec<&CacheObject> = old
+                    .iter()
+                    .filter(|o| {
+                        diff.removed.contains(&o.sha1)
+                            || diff
+                                .added
+                                .get(&o.sha1)
+                                .is_some_and(|n| !n.paths.contains(path))
+                    })
+                    .collect();
+                let replaced = leaving.iter().find(|o| o.subject == object.subject).or(
+                    if leaving.len() == 1 {
+                        leaving.first()
+                    } else {
+                        None
+                    },
+                );
+
+                if let Some(replaced) = replaced {
+                    rotations.push(CertificateRenewal {
+                        backend: path.backend.to_string(),
+                        path: path.path.to_string(),
+                        subject: object.subject.clone(),
+                        old_sha1: replaced.sha1.clone(),
+                        old_not_after: replaced.not_after,
+                        new_sha1: sha1.clone(),
+                        new_not_after: object.not_after,
+                    });
+                }
+            }
+        }
+
+        rotations
+    }
+
+    pub fn preview_diff(&self, diff: &CacheDiff) -> CacheDiffPreview {
+        let context = self.status_context();
+
+        // A new certificate replacing another at a path is a renewal rather
+        // than a brand new entry, and its predecessor did not just disappear
+        let renewed = self.rotations(diff);
+        let new_sha1s: HashSet<&str> = renewed.iter().map(|r| r.new_sha1.as_str()).collect();
+        let old_sha1s: HashSet<&str> = renewed.iter().map(|r| r.old_sha1.as_str()).collect();
+
+        let mut preview = CacheDiffPreview::default();
+        for (sha1, object) in &diff.added {
+            if self.inner.contains_key(sha1) {
+                preview.unchanged += 1;
+            } else if !new_sha1s.contains(sha1.as_str()) {
+                preview.added.push(context.item(object));
+            }
+        }
+
+        for sha1 in &diff.removed {
+            if diff.added.contains_key(sha1) || old_sha1s.contains(sha1.as_str()) {
+                continue;
+            }
+            if let Some(old) = self.get(sha1) {
+                preview.removed.push(context.item(&old));
+            }
+        }
+
+        preview.added.sort_by_key(|item| item.not_after);
+        preview.removed.sort_by_key(|item| item.not_after);
+        preview.renewed = renewed;
+        preview
+    }
+
+    /// Reconciles a fresh scan of the `scanned` backends with the cache.
+    /// Paths those backends no longer report are pruned, paths on other
+    /// backends are carried over, and an entry is only removed once it has no
+    /// paths left. Paths in `unreadable` failed to fetch, so they keep their
+    /// previous certificate instead of being treated as deleted.
+    pub fn diff_scan(
+        &self,
+        mut found: HashMap<String, CacheObject>,
+        scanned: &HashSet<String>,
+        unreadable: &HashSet<PathObject>,
+    ) -> CacheDiff {
+        let mut removed = Vec::new();
+
+        for entry in self.inner.iter() {
+            let (sha1, existing) = entry.pair();
+            let retained: Vec<PathObject> = existing
+                .paths
+                .iter()
+                .filter(|p| !scanned.contains(&*p.backend) || unreadable.contains(p))
+                .cloned()
+                .collect();
+
+            if let Some(object) = found.get_mut(sha1) {
+                for path in retained {
+                    if !object.paths.contains(&path) {
+                        object.paths.push(path);
+                    }
+                }
+            } else if retained.is_empty() {
+                removed.push(sha1.clone());
+            } else if retained.len() < existing.paths.len() {
+                let mut object = existing.clone();
+                object.paths = retained;
+                found.insert(sha1.clone(), object);
+            }
+        }
+
+        CacheDiff {
+            added: found,
+            removed,
+        }
+    }
+
+    pub fn get_stats(&self) -> CacheStats {
+        let mut stats = CacheStats::default();
+
+        for item in self.list() {
+            stats.total += 1;
+            match item.status {
+                CertificateStatus::Ok => stats.ok += 1,
+                CertificateStatus::ExpiringSoon => stats.expiring_soon += 1,
+                CertificateStatus::Expired => stats.expired += 1,
+                CertificateStatus::NotYetValid => stats.not_yet_valid += 1,
+                CertificateStatus::Revoked => stats.revoked += 1,
+                CertificateStatus::Superseded => stats.superseded += 1,
+            }
+            if item.stale {
+                stats.stale += 1;
             }
         }
 
@@ -142,12 +1189,169 @@ impl CacheDiff {
     }
 }
 
+/// Certificates that will need rotating together, see `Cache::duplicates`.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct DuplicateReport {
+    /// Certificates present at several paths, most copies first.
+    pub copies: Vec<CacheItem>,
+    /// Names shared by more than one current certificate.
+    pub collisions: Vec<NameCollision>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NameCollision {
+    pub name: String,
+    /// Soonest expiry first.
+    pub certificates: Vec<CacheItem>,
+}
+
+/// A certificate's position in its CA hierarchy, as far as the issuers are
+/// cached.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CertificateChain {
+    /// The certificate first, then each issuer up towards the root.
+    pub chain: Vec<ChainLink>,
+    /// Whether the chain ends at a cached self-signed root.
+    pub complete: bool,
+    /// Cached certificates issued by this one.
+    pub issued: Vec<CacheItem>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChainLink {
+    #[serde(flatten)]
+    pub certificate: CacheItem,
+    /// The CA that issued this certificate expires before it does.
+    pub outlives_issuer: bool,
+}
+
+/// A CA nearing expiry and what its rotation affects, see `Cache::ca_impact`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CaImpact {
+    #[serde(flatten)]
+    pub certificate: CacheItem,
+    /// Leaf certificates chaining to this CA, directly or through
+    /// intermediates.
+    pub leaves: usize,
+    /// Intermediate CAs below this one.
+    pub intermediates: usize,
+    /// Leaves that expire after this CA and so will stop validating first.
+    pub leaves_outliving: usize,
+}
+
+/// What a single refresh changed in the cache.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CacheChange {
+    pub at: DateTime<Utc>,
+    pub added: Vec<CacheItem>,
+    pub removed: Vec<CacheItem>,
+    pub renewed: Vec<CertificateRenewal>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CacheChanges {
+    /// Whether every change since the requested time is included. False when
+    /// older changes were dropped or happened before the server started.
+    pub complete: bool,
+    pub changes: Vec<CacheChange>,
+}
+
+/// The most recent cache changes, oldest first.
+#[derive(Debug)]
+pub struct ChangeLog {
+    changes: VecDeque<CacheChange>,
+    /// Changes up to this time may be missing from the log.
+    known_after: DateTime<Utc>,
+}
+
+impl ChangeLog {
+    pub fn new() -> Self {
+        ChangeLog {
+            changes: VecDeque::new(),
+            known_after: Utc::now(),
+        }
+    }
+
+    pub fn record(&mut self, preview: CacheDiffPreview, capacity: usize) {
+        if preview.added.is_empty() && preview.removed.is_empty() && preview.renewed.is_empty() {
+            return;
+        }
+
+        self.changes.push_back(CacheChange {
+            at: Utc::now(),
+            added: preview.added,
+            removed: preview.removed,
+            renewed: preview.renewed,
+        });
+        while self.changes.len() > capacity {
+            if let Some(dropped) = self.changes.pop_front() {
+                self.known_after = dropped.at;
+            }
+        }
+    }
+
+    /// Changes made after `since`, or every retained change.
+    pub fn since(&self, since: Option<DateTime<Utc>>) -> CacheChanges {
+        CacheChanges {
+            complete: since.is_some_and(|since| since >= self.known_after),
+            changes: self
+                .changes
+                .iter()
+                .filter(|change| since.is_none_or(|since| change.at > since))
+                .cloned()
+                .collect(),
+        }
+    }
+}
+
+impl Default for ChangeLog {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Human-oriented view of a `CacheDiff` relative to the current cache contents,
+/// used to preview a refresh without applying it.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct CacheDiffPreview {
+    pub added: Vec<CacheItem>,
+    pub removed: Vec<CacheItem>,
+    pub renewed: Vec<CertificateRenewal>,
+    pub unchanged: usize,
+}
+
+impl CacheDiffPreview {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.renewed.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CertificateRenewal {
+    pub backend: String,
+    pub path: String,
+    pub subject: String,
+    pub old_sha1: String,
+    pub old_not_after: DateTime<Utc>,
+    pub new_sha1: String,
+    pub new_not_after: DateTime<Utc>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct CacheStats {
     pub total: usize,
     pub ok: usize,
     pub expiring_soon: usize,
     pub expired: usize,
+    #[serde(default)]
+    pub not_yet_valid: usize,
+    #[serde(default)]
+    pub revoked: usize,
+    #[serde(default)]
+    pub superseded: usize,
+    /// Certificates flagged stale, counted in addition to their status.
+    #[serde(default)]
+    pub stale: usize,
 }
 
 impl CacheStats {
@@ -159,17 +1363,20 @@ impl CacheStats {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::ignore::IgnoredCertificate;
     use chrono::Duration;
 
     fn create_test_object(subject: &str, days_from_now: i64) -> CacheObject {
         CacheObject {
             subject: subject.to_string(),
+            not_before: None,
             not_after: Utc::now() + Duration::days(days_from_now),
             sha1: format!("sha1_{}", subject),
             paths: vec![PathObject {
-                backend: "test".to_string(),
-                path: format!("/test/{}", subject),
+                backend: "test".into(),
+                path: format!("/test/{}", subject).into(),
             }],
+            metadata: Default::default(),
         }
     }
 
@@ -210,6 +1417,46 @@ mod tests {
         assert_eq!(stats.ok, 1);
     }
 
+    #[test]
+    fn test_certificate_status() {
+        let cache = Cache::new();
+        cache.set_status_thresholds(StatusThresholds {
+            expiring_soon_days: 10,
+            stale_after_hours: None,
+        });
+
+        let mut old = create_test_object("renewed.com", 5);
+        old.sha1 = "old".to_string();
+        let mut renewed = create_test_object("renewed.com", 90);
+        renewed.sha1 = "renewed".to_string();
+        let mut future = create_test_object("future.com", 400);
+        future.not_before = Some(Utc::now() + Duration::days(1));
+        let soon = create_test_object("soon.com", 15);
+
+        for object in [old, renewed, future, soon] {
+            cache.insert(object.sha1.clone(), object);
+        }
+
+        let status_of = |sha1: &str| {
+            cache
+                .list()
+                .into_iter()
+                .find(|item| item.sha1 == sha1)
+                .unwrap()
+                .status
+        };
+        assert_eq!(status_of("old"), CertificateStatus::Superseded);
+        assert_eq!(status_of("renewed"), CertificateStatus::Ok);
+        assert_eq!(status_of("sha1_future.com"), CertificateStatus::NotYetValid);
+        // 15 days out is beyond the configured 10 day threshold
+        assert_eq!(status_of("sha1_soon.com"), CertificateStatus::Ok);
+
+        let stats = cache.get_stats();
+        assert_eq!(stats.superseded, 1);
+        assert_eq!(stats.not_yet_valid, 1);
+        assert_eq!(stats.ok, 2);
+    }
+
     #[test]
     fn test_cache_list_filtered() {
         let cache = Cache::new();
@@ -222,4 +1469,604 @@ mod tests {
         assert_eq!(filtered.len(), 1);
         assert_eq!(filtered[0].subject, "a.com");
     }
+
+    #[test]
+    fn test_has_expiring() {
+        let cache = Cache::new();
+        let soon = create_test_object("soon.com", 3);
+        let expired = create_test_object("expired.com", -1);
+        cache.insert(soon.sha1.clone(), soon);
+        cache.insert(expired.sha1.clone(), expired);
+
+        assert!(cache.has_expiring("test", Duration::days(7)));
+        assert!(!cache.has_expiring("test", Duration::days(2)));
+        assert!(!cache.has_expiring("other", Duration::days(7)));
+
+        // A renewal with the same subject supersedes the expiring certificate
+        let mut renewed = create_test_object("soon.com", 90);
+        renewed.sha1 = "renewed".to_string();
+        cache.insert(renewed.sha1.clone(), renewed);
+        assert!(!cache.has_expiring("test", Duration::days(7)));
+    }
+
+    #[test]
+    fn test_paths_are_shared_between_certificates() {
+        let cache = Cache::new();
+        let leaf = create_test_object("leaf", 30);
+        let mut ca = create_test_object("ca", 365);
+        // Same strings, separately allocated, as a scan would produce them
+        ca.paths = vec![PathObject::new(
+            "test".to_string(),
+            "/test/leaf".to_string(),
+        )];
+
+        let (leaf_sha1, ca_sha1) = (leaf.sha1.clone(), ca.sha1.clone());
+        let diff = CacheDiff {
+            added: HashMap::from([(leaf_sha1.clone(), leaf), (ca_sha1.clone(), ca)]),
+            removed: vec![],
+        };
+        cache.update_from_diff(diff).unwrap();
+
+        let (leaf, ca) = (cache.get(&leaf_sha1).unwrap(), cache.get(&ca_sha1).unwrap());
+        assert!(Arc::ptr_eq(&leaf.paths[0].path, &ca.paths[0].path));
+        assert!(Arc::ptr_eq(&leaf.paths[0].backend, &ca.paths[0].backend));
+        assert_eq!(cache.paths.len(), 2);
+
+        // Strings no certificate refers to any more are released
+        let diff = CacheDiff {
+            added: HashMap::new(),
+            removed: vec![leaf_sha1, ca_sha1],
+        };
+        drop((leaf, ca));
+        cache.update_from_diff(diff).unwrap();
+        assert!(cache.paths.is_empty());
+    }
+
+    #[test]
+    fn test_change_log_keeps_recent_changes() {
+        let mut log = ChangeLog::new();
+        let start = log.known_after;
+        let change = |subject: &str| CacheDiffPreview {
+            added: vec![CacheItem::new(
+                &create_test_object(subject, 30),
+                CertificateStatus::Ok,
+            )],
+            ..Default::default()
+        };
+
+        // Refreshes that changed nothing are not recorded
+        log.record(CacheDiffPreview::default(), 2);
+        assert!(log.since(None).changes.is_empty());
+
+        log.record(change("a"), 2);
+        log.record(change("b"), 2);
+        let all = log.since(Some(start));
+        assert!(all.complete);
+        assert_eq!(all.changes.len(), 2);
+
+        // Dropping the oldest change makes older queries incomplete
+        log.record(change("c"), 2);
+        let all = log.since(Some(start));
+        assert!(!all.complete);
+        assert_eq!(all.changes[0].added[0].subject, "b");
+
+        let latest = log.since(Some(all.changes[0].at));
+        assert!(latest.complete);
+        assert_eq!(latest.changes.len(), 1);
+        assert_eq!(latest.changes[0].added[0].subject, "c");
+    }
+
+    #[test]
+    fn test_failing_backends_mark_certificates_stale() {
+        let only_failing = create_test_object("failing.com", 30);
+        let mut also_healthy = create_test_object("both.com", 30);
+        also_healthy.paths.push(PathObject {
+            backend: "other".into(),
+            path: "/other/both.com".into(),
+        });
+        let setup = || {
+            let cache = Cache::new();
+            cache.set_status_thresholds(StatusThresholds {
+                stale_after_hours: Some(1),
+                ..Default::default()
+            });
+            cache.insert(only_failing.sha1.clone(), only_failing.clone());
+            cache.insert(also_healthy.sha1.clone(), also_healthy.clone());
+            cache
+        };
+        let failure = |hours_ago: i64| BackendRefreshStats {
+            finished_at: Utc::now() - Duration::hours(hours_ago),
+            error: Some("connection refused".to_string()),
+            ..BackendRefreshStats::new("test")
+        };
+
+        // Failing, but not yet for longer than the TTL
+        let cache = setup();
+        cache.record_backend_refresh(&[failure(0)]);
+        assert!(cache.failing_since("test").is_some());
+        assert!(cache.list().iter().all(|item| !item.stale));
+
+        // The first failure is what counts
+        let cache = setup();
+        cache.record_backend_refresh(&[failure(2)]);
+        cache.record_backend_refresh(&[failure(0)]);
+        assert!(cache.is_backend_stale("test"));
+        let stale: Vec<String> = cache
+            .list()
+            .into_iter()
+            .filter(|item| item.stale)
+            .map(|item| item.sha1)
+            .collect();
+        assert_eq!(stale, vec![only_failing.sha1.clone()]);
+        assert_eq!(cache.get_stats().stale, 1);
+
+        cache.record_backend_refresh(&[BackendRefreshStats::new("test")]);
+        assert!(!cache.is_backend_stale("test"));
+        assert_eq!(cache.get_stats().stale, 0);
+    }
+
+    #[test]
+    fn test_ignored_certificates_are_hidden() {
+        let cache = Cache::new();
+        let mut events = cache.subscribe();
+
+        let old = create_test_object("old.com", -10);
+        cache.insert(old.sha1.clone(), old.clone());
+        cache.insert("2".to_string(), create_test_object("ok.com", 100));
+        cache
+            .ignore_list()
+            .ignore(IgnoredCertificate {
+                sha1: old.sha1.clone(),
+                subject: "old.com".to_string(),
+                ignored_at: Utc::now(),
+                reason: None,
+                expires_at: None,
+            })
+            .unwrap();
+
+        assert_eq!(cache.list().len(), 1);
+        assert_eq!(cache.list_objects().len(), 1);
+        assert_eq!(cache.get_stats().expired, 0);
+
+        cache.purge(&old.sha1).unwrap();
+        assert!(events.try_recv().is_err());
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[test]
+    fn test_update_from_diff_emits_events() {
+        let cache = Cache::new();
+        let mut events = cache.subscribe();
+
+        let existing = create_test_object("existing.com", 30);
+        let gone = create_test_object("gone.com", 30);
+        cache.insert(existing.sha1.clone(), existing.clone());
+        cache.insert(gone.sha1.clone(), gone.clone());
+
+        let fresh = create_test_object("new.com", 90);
+        let mut diff = CacheDiff::new();
+        diff.added.insert(existing.sha1.clone(), existing);
+        diff.added.insert(fresh.sha1.clone(), fresh);
+        diff.removed = vec![gone.sha1.clone()];
+        cache.update_from_diff(diff).unwrap();
+
+        let mut names = vec![];
+        while let Ok(event) = events.try_recv() {
+            names.push(event.name());
+        }
+        names.sort();
+        assert_eq!(names, vec!["certificate_added", "certificate_removed"]);
+    }
+
+    #[test]
+    fn test_rotation_events() {
+        let cache = Cache::new();
+        let old = create_test_object("rotated.com", 3);
+        cache.insert(old.sha1.clone(), old.clone());
+        let mut events = cache.subscribe();
+
+        let mut renewed = create_test_object("rotated.com", 90);
+        renewed.sha1 = "renewed".to_string();
+        renewed.paths = old.paths.clone();
+        let mut diff = CacheDiff::new();
+        diff.added.insert(renewed.sha1.clone(), renewed);
+        diff.removed = vec![old.sha1.clone()];
+
+        let renewals = cache.rotations(&diff);
+        cache.update_from_diff(diff).unwrap();
+        cache.publish_rotations(&renewals);
+        // A replacement expiring sooner is no cause for closure
+        let mut downgrade = renewals[0].clone();
+        downgrade.new_not_after = downgrade.old_not_after - Duration::days(1);
+        cache.publish_rotations(&[downgrade]);
+
+        let mut rotated = vec![];
+        while let Ok(event) = events.try_recv() {
+            if let CacheEvent::CertificateRotated { old_sha1, .. } = event {
+                rotated.push(old_sha1);
+            }
+        }
+        assert_eq!(rotated, vec![old.sha1]);
+    }
+
+    #[test]
+    fn test_ca_impact() {
+        let cache = Cache::new();
+        let cert = |name: &str, issuer: &str, days: i64, is_ca: bool| {
+            let mut object = create_test_object(name, days);
+            object.subject = format!("CN={}", name);
+            object.metadata.issuer = format!("CN={}", issuer);
+            object.metadata.is_ca = is_ca;
+            object
+        };
+
+        let root = cert("Root CA", "Root CA", 3650, true);
+        let intermediate = cert("Intermediate CA", "Root CA", 20, true);
+        let unused = cert("Unused CA", "Unused CA", 15, true);
+        for object in [
+            root.clone(),
+            intermediate.clone(),
+            unused.clone(),
+            cert("a.example.com", "Intermediate CA", 90, false),
+            cert("b.example.com", "Intermediate CA", 10, false),
+        ] {
+            cache.insert(object.sha1.clone(), object);
+        }
+
+        let report = cache.ca_impact(Duration::days(30));
+        assert_eq!(report.len(), 2);
+        assert_eq!(report[0].certificate.sha1, intermediate.sha1);
+        assert_eq!(report[0].leaves, 2);
+        assert_eq!(report[0].leaves_outliving, 1);
+        assert_eq!(report[1].certificate.sha1, unused.sha1);
+        assert_eq!(report[1].leaves, 0);
+
+        // The root counts leaves through the intermediate
+        let report = cache.ca_impact(Duration::days(4000));
+        assert_eq!(report[0].certificate.sha1, root.sha1);
+        assert_eq!(report[0].leaves, 2);
+        assert_eq!(report[0].intermediates, 1);
+    }
+
+    #[test]
+    fn test_threshold_crossings_are_published_once() {
+        let cache = Cache::new();
+        cache.set_event_thresholds(&[30, 7, 1]);
+        let mut events = cache.subscribe();
+
+        let soon = create_test_object("soon.com", 5);
+        let mut diff = CacheDiff::new();
+        diff.added.insert(soon.sha1.clone(), soon);
+        diff.added
+            .insert("later".to_string(), create_test_object("later.com", 90));
+        cache.update_from_diff(diff).unwrap();
+
+        let mut crossed = vec![];
+        while let Ok(event) = events.try_recv() {
+            if let CacheEvent::ThresholdCrossed {
+                certificate,
+                threshold_days,
+            } = event
+            {
+                crossed.push((certificate.subject, threshold_days));
+            }
+        }
+        assert_eq!(crossed, vec![("soon.com".to_string(), 7)]);
+        assert_eq!(cache.check_thresholds(), 0);
+    }
+
+    #[test]
+    fn test_threshold_crossings_survive_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("thresholds.json");
+        let soon = create_test_object("soon.com", 5);
+        let other = create_test_object("other.com", 3);
+
+        let cache =
+            Cache::new().with_crossed_thresholds(CrossedThresholds::load(path.clone()).unwrap());
+        cache.set_event_thresholds(&[7]);
+        let mut diff = CacheDiff::new();
+        diff.added.insert(soon.sha1.clone(), soon.clone());
+        diff.added.insert(other.sha1.clone(), other.clone());
+        cache.update_from_diff(diff).unwrap();
+
+        // After a restart, one backend refilling the cache neither announces
+        // its certificate again nor forgets the others'
+        let cache =
+            Cache::new().with_crossed_thresholds(CrossedThresholds::load(path.clone()).unwrap());
+        cache.set_event_thresholds(&[7]);
+        let mut diff = CacheDiff::new();
+        diff.added.insert(soon.sha1.clone(), soon.clone());
+        cache.update_from_diff(diff).unwrap();
+        assert_eq!(cache.crossed_thresholds().read().len(), 2);
+
+        let mut diff = CacheDiff::new();
+        diff.added.insert(other.sha1.clone(), other.clone());
+        cache.update_from_diff(diff).unwrap();
+        assert_eq!(cache.check_thresholds(), 0);
+
+        // A certificate that goes away is announced afresh if it returns
+        let mut diff = CacheDiff::new();
+        diff.removed.push(soon.sha1.clone());
+        cache.update_from_diff(diff).unwrap();
+        let mut diff = CacheDiff::new();
+        diff.added.insert(soon.sha1.clone(), soon);
+        let mut events = cache.subscribe();
+        cache.update_from_diff(diff).unwrap();
+        assert!(std::iter::from_fn(|| events.try_recv().ok())
+            .any(|event| matches!(event, CacheEvent::ThresholdCrossed { .. })));
+    }
+
+    #[test]
+    fn test_preview_diff() {
+        let cache = Cache::new();
+
+        let kept = create_test_object("kept.com", 30);
+        let old = create_test_object("renewed.com", 5);
+        let gone = create_test_object("gone.com", 10);
+        cache.insert(kept.sha1.clone(), kept.clone());
+        cache.insert(old.sha1.clone(), old.clone());
+        cache.insert(gone.sha1.clone(), gone.clone());
+
+        let mut renewed = create_test_object("renewed.com", 365);
+        renewed.sha1 = "sha1_renewed_new".to_string();
+        let fresh = create_test_object("new.com", 90);
+
+        let mut diff = CacheDiff::new();
+        diff.added.insert(kept.sha1.clone(), kept);
+        diff.added.insert(renewed.sha1.clone(), renewed);
+        diff.added.insert(fresh.sha1.clone(), fresh);
+        diff.removed = vec![old.sha1.clone(), gone.sha1.clone()];
+
+        let preview = cache.preview_diff(&diff);
+        assert_eq!(preview.unchanged, 1);
+        assert_eq!(preview.added.len(), 1);
+        assert_eq!(preview.added[0].subject, "new.com");
+        assert_eq!(preview.removed.len(), 1);
+        assert_eq!(preview.removed[0].subject, "gone.com");
+        assert_eq!(preview.renewed.len(), 1);
+        assert_eq!(preview.renewed[0].old_sha1, old.sha1);
+
+        // Previewing must not modify the cache
+        assert_eq!(cache.len(), 3);
+    }
+
+    #[test]
+    fn test_diff_scan_prunes_stale_entries_and_paths() {
+        let cache = Cache::new();
+        let path = |backend: &str, path: &str| PathObject {
+            backend: backend.into(),
+            path: path.into(),
+        };
+
+        let kept = create_test_object("kept.com", 30);
+        let gone = create_test_object("gone.com", 30);
+        let flaky = create_test_object("flaky.com", 30);
+        let mut shared = create_test_object("shared.com", 30);
+        shared.paths.push(path("other", "/other/shared.com"));
+        let mut elsewhere = create_test_object("elsewhere.com", 30);
+        elsewhere.paths = vec![path("other", "/other/elsewhere.com")];
+        for object in [&kept, &gone, &flaky, &shared, &elsewhere] {
+            cache.insert(object.sha1.clone(), object.clone());
+        }
+
+        // Only "test" was scanned: it still has kept.com, flaky.com failed to
+        // fetch, and gone.com and shared.com were deleted from it
+        let mut found = HashMap::new();
+        found.insert(kept.sha1.clone(), kept.clone());
+        let scanned = HashSet::from(["test".to_string()]);
+        let unreadable = HashSet::from([flaky.paths[0].clone()]);
+
+        let diff = cache.diff_scan(found, &scanned, &unreadable);
+        assert_eq!(diff.removed, vec![gone.sha1.clone()]);
+        assert!(diff.added.contains_key(&kept.sha1));
+        assert!(!diff.added.contains_key(&flaky.sha1));
+        assert!(!diff.added.contains_key(&elsewhere.sha1));
+        assert_eq!(
+            diff.added[&shared.sha1].paths,
+            vec![path("other", "/other/shared.com")]
+        );
+
+        cache.update_from_diff(diff).unwrap();
+        assert_eq!(cache.len(), 4);
+        assert!(cache.get(&gone.sha1).is_none());
+        assert!(cache.get(&flaky.sha1).is_some());
+        assert_eq!(cache.get(&shared.sha1).unwrap().paths.len(), 1);
+    }
+
+    #[test]
+    fn test_paths_under_unlisted_dirs() {
+        let cache = Cache::new();
+        let mut object = create_test_object("nested.com", 30);
+        object.paths = vec![
+            PathObject::new("test", "certs/team-a/api"),
+            PathObject::new("test", "certs/team-ab/api"),
+            PathObject::new("test", "certs/team-b/api"),
+            PathObject::new("other", "certs/team-a/api"),
+        ];
+        cache.insert(object.sha1.clone(), object);
+
+        let kept = cache.paths_under("test", &["certs/team-a/".to_string()]);
+        assert_eq!(
+            kept,
+            HashSet::from([PathObject::new("test", "certs/team-a/api")])
+        );
+        assert!(cache.paths_under("test", &[]).is_empty());
+    }
+
+    #[test]
+    fn test_search_uses_index() {
+        let cache = Cache::new();
+
+        let mut api = create_test_object("api", 30);
+        api.subject = "O=Example, CN=api.example.com".to_string();
+        api.metadata.issuer = "O=Example, CN=Example Issuing CA".to_string();
+        api.metadata.subject_alt_names = vec!["api.example.com".to_string()];
+        let mut wildcard = create_test_object("wildcard", 60);
+        wildcard.subject = "CN=*.example.com".to_string();
+        wildcard.metadata.subject_alt_names =
+            vec!["*.example.com".to_string(), "example.com".to_string()];
+        let other = create_test_object("other.org", 90);
+        for object in [&api, &wildcard, &other] {
+            cache.insert(object.sha1.clone(), object.clone());
+        }
+
+        let sha1s = |query: &str| -> Vec<String> {
+            cache
+                .search(query)
+                .into_iter()
+                .map(|item| item.sha1)
+                .collect()
+        };
+
+        // Exact SAN plus the wildcard covering it, soonest expiry first
+        assert_eq!(
+            sha1s("API.example.com"),
+            vec![api.sha1.clone(), wildcard.sha1.clone()]
+        );
+        assert_eq!(sha1s("www.example.com"), vec![wildcard.sha1.clone()]);
+        assert_eq!(sha1s("example issuing ca"), vec![api.sha1.clone()]);
+        assert!(sha1s("missing.example.net").is_empty());
+
+        // Replaced and removed entries drop out of the index
+        let mut renamed = api.clone();
+        renamed.metadata.subject_alt_names = vec!["api2.example.com".to_string()];
+        renamed.subject = "CN=api2.example.com".to_string();
+        cache.insert(api.sha1.clone(), renamed);
+        assert_eq!(sha1s("api.example.com"), vec![wildcard.sha1.clone()]);
+        cache.remove(&wildcard.sha1);
+        assert!(sha1s("www.example.com").is_empty());
+        assert_eq!(sha1s("api2.example.com"), vec![api.sha1.clone()]);
+    }
+
+    #[test]
+    fn test_chain_walks_issuers() {
+        let cache = Cache::new();
+        let cert = |name: &str, issuer: &str, days: i64| {
+            let mut object = create_test_object(name, days);
+            object.subject = format!("CN={}", name);
+            object.metadata.issuer = format!("CN={}", issuer);
+            object
+        };
+
+        let root = cert("Root CA", "Root CA", 3650);
+        let mut intermediate = cert("Intermediate CA", "Root CA", 20);
+        intermediate.metadata.subject_key_id = "aa".to_string();
+        let mut leaf = cert("leaf.example.com", "Intermediate CA", 90);
+        leaf.metadata.authority_key_id = "aa".to_string();
+        // Same name as the real issuer, but a different key
+        let mut impostor = cert("Intermediate CA", "Other Root", 400);
+        impostor.sha1 = "sha1_impostor".to_string();
+        impostor.metadata.subject_key_id = "ff".to_string();
+        for object in [&root, &intermediate, &leaf, &impostor] {
+            cache.insert(object.sha1.clone(), object.clone());
+        }
+
+        let chain = cache.chain(&leaf.sha1).unwrap();
+        let sha1s: Vec<&str> = chain
+            .chain
+            .iter()
+            .map(|link| link.certificate.sha1.as_str())
+            .collect();
+        assert_eq!(sha1s, [&leaf.sha1, &intermediate.sha1, &root.sha1]);
+        assert!(chain.complete);
+        // The leaf outlives the intermediate that issued it
+        assert!(chain.chain[0].outlives_issuer);
+        assert!(!chain.chain[1].outlives_issuer);
+        assert!(!chain.chain[2].outlives_issuer);
+
+        let issued = cache.chain(&intermediate.sha1).unwrap().issued;
+        assert_eq!(issued.len(), 1);
+        assert_eq!(issued[0].sha1, leaf.sha1);
+
+        // Without its issuer cached the chain stops at the leaf
+        cache.remove(&intermediate.sha1);
+        let chain = cache.chain(&leaf.sha1).unwrap();
+        assert_eq!(chain.chain.len(), 1);
+        assert!(!chain.complete);
+
+        assert!(cache.chain("missing").is_none());
+    }
+
+    #[test]
+    fn test_duplicates_report() {
+        let cache = Cache::new();
+
+        let mut copied = create_test_object("copied.com", 30);
+        copied.paths.push(PathObject {
+            backend: "other".into(),
+            path: "/other/copied.com".into(),
+        });
+        let mut a = create_test_object("a", 30);
+        a.subject = "O=Team A, CN=a.example.com".to_string();
+        a.metadata.subject_alt_names = vec!["shared.example.com".to_string()];
+        let mut b = create_test_object("b", 60);
+        b.subject = "O=Team B, CN=b.example.com".to_string();
+        b.metadata.subject_alt_names = vec!["SHARED.example.com".to_string()];
+        // A renewal of a.example.com supersedes the old one instead of colliding
+        let mut old_a = a.clone();
+        old_a.sha1 = "sha1_old_a".to_string();
+        old_a.not_after = Utc::now() + Duration::days(2);
+        for object in [&copied, &a, &b, &old_a] {
+            cache.insert(object.sha1.clone(), object.clone());
+        }
+
+        let report = cache.duplicates(2);
+        assert_eq!(report.copies.len(), 1);
+        assert_eq!(report.copies[0].sha1, copied.sha1);
+        assert!(cache.duplicates(3).copies.is_empty());
+
+        assert_eq!(report.collisions.len(), 1);
+        assert_eq!(report.collisions[0].name, "shared.example.com");
+        let sha1s: Vec<&str> = report.collisions[0]
+            .certificates
+            .iter()
+            .map(|item| item.sha1.as_str())
+            .collect();
+        assert_eq!(sha1s, [a.sha1.as_str(), b.sha1.as_str()]);
+    }
+
+    #[test]
+    fn test_rotations_match_replacements_by_path() {
+        let cache = Cache::new();
+        let at_leaf_path = |object: &mut CacheObject| {
+            object.paths = vec![PathObject {
+                backend: "test".into(),
+                path: "/test/leaf".into(),
+            }];
+        };
+
+        let mut leaf = create_test_object("leaf", 5);
+        at_leaf_path(&mut leaf);
+        let mut ca = create_test_object("ca", 365);
+        at_leaf_path(&mut ca);
+        let untouched = create_test_object("untouched", 30);
+        for object in [&leaf, &ca, &untouched] {
+            cache.insert(object.sha1.clone(), object.clone());
+        }
+
+        // The leaf is renewed in place; the CA at the same path is unchanged
+        let mut renewed = leaf.clone();
+        renewed.sha1 = "sha1_leaf_new".to_string();
+        renewed.not_after = Utc::now() + Duration::days(90);
+        let found = HashMap::from([
+            (renewed.sha1.clone(), renewed.clone()),
+            (ca.sha1.clone(), ca.clone()),
+            (untouched.sha1.clone(), untouched.clone()),
+        ]);
+        let scanned = HashSet::from(["test".to_string()]);
+        let diff = cache.diff_scan(found, &scanned, &HashSet::new());
+
+        let rotations = cache.rotations(&diff);
+        assert_eq!(rotations.len(), 1);
+        assert_eq!(rotations[0].path, "/test/leaf");
+        assert_eq!(rotations[0].old_sha1, leaf.sha1);
+        assert_eq!(rotations[0].new_sha1, renewed.sha1);
+
+        // Nothing rotates when a scan finds the same certificates again
+        cache.update_from_diff(diff).unwrap();
+        let found = HashMap::from([(renewed.sha1.clone(), renewed.clone())]);
+        let diff = cache.diff_scan(found, &HashSet::new(), &HashSet::new());
+        assert!(cache.rotations(&diff).is_empty());
+    }
 }
@@ -0,0 +1,204 @@
+use crate::types::CacheObject;
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use aes_gcm::{Aes256Gcm, Nonce};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+/// Leads every encrypted snapshot, followed by the nonce and the ciphertext.
+const ENCRYPTED_MAGIC: &[u8] = b"DDAYENC1";
+const NONCE_LEN: usize = 12;
+
+/// File-backed snapshot of the certificate cache, reloaded on startup so the
+/// cache is not empty while the initial populate runs.
+#[derive(Debug)]
+pub struct CacheStore {
+    path: PathBuf,
+    // Serializes writers so an older snapshot never overwrites a newer one
+    lock: Mutex<()>,
+    cipher: Option<SnapshotCipher>,
+}
+
+/// AES-256-GCM over the whole snapshot. Kept out of `Debug` output.
+struct SnapshotCipher(Aes256Gcm);
+
+impl std::fmt::Debug for SnapshotCipher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("SnapshotCipher(..)")
+    }
+}
+
+impl SnapshotCipher {
+    fn encrypt(&self, plaintext: &[u8]) -> crate::Result<Vec<u8>> {
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .0
+            .encrypt(
+                &nonce,
+                Payload {
+                    msg: plaintext,
+                    aad: ENCRYPTED_MAGIC,
+                },
+            )
+            .map_err(|_| crate::DoomsdayError::cache("Failed to encrypt cache snapshot"))?;
+
+        let mut out = Vec::with_capacity(ENCRYPTED_MAGIC.len() + NONCE_LEN + ciphertext.len());
+        out.extend_from_slice(ENCRYPTED_MAGIC);
+        out.extend_from_slice(&nonce);
+        out.extend_from_slice(&ciphertext);
+        Ok(out)
+    }
+
+    /// `data` without the leading magic.
+    fn decrypt(&self, data: &[u8]) -> crate::Result<Vec<u8>> {
+        if data.len() < NONCE_LEN {
+            return Err(crate::DoomsdayError::cache(
+                "Encrypted cache snapshot is truncated",
+            ));
+        }
+        let (nonce, ciphertext) = data.split_at(NONCE_LEN);
+        self.0
+            .decrypt(
+                Nonce::from_slice(nonce),
+                Payload {
+                    msg: ciphertext,
+                    aad: ENCRYPTED_MAGIC,
+                },
+            )
+            .map_err(|_| {
+                crate::DoomsdayError::cache(
+                    "Failed to decrypt cache snapshot (wrong key or corrupted file)",
+                )
+            })
+    }
+}
+
+impl CacheStore {
+    pub fn new(path: PathBuf) -> Self {
+        CacheStore {
+            path,
+            lock: Mutex::new(()),
+            cipher: None,
+        }
+    }
+
+    /// A store whose snapshots are encrypted with `key`. Unencrypted
+    /// snapshots left by earlier versions are still loaded, and encrypted on
+    /// the next save.
+    pub fn encrypted(path: PathBuf, key: &[u8; 32]) -> Self {
+        CacheStore {
+            cipher: Some(SnapshotCipher(Aes256Gcm::new(key.into()))),
+            ..Self::new(path)
+        }
+    }
+
+    pub fn load(&self) -> crate::Result<Vec<CacheObject>> {
+        if !self.path.exists() {
+            return Ok(Vec::new());
+        }
+
+        let content = fs::read(&self.path)?;
+        let content = match (content.strip_prefix(ENCRYPTED_MAGIC), &self.cipher) {
+            (Some(data), Some(cipher)) => cipher.decrypt(data)?,
+            (Some(_), None) => {
+                return Err(crate::DoomsdayError::config(
+                    "Cache snapshot is encrypted but cache.encryption is not configured",
+                ))
+            }
+            (None, Some(_)) => {
+                tracing::info!("Cache snapshot is not encrypted yet, it will be on the next save");
+                content
+            }
+            (None, None) => content,
+        };
+
+        let objects: Vec<CacheObject> = serde_json::from_slice(&content)?;
+        tracing::info!(
+            "Loaded {} cached certificates from {}",
+            objects.len(),
+            self.path.display()
+        );
+        Ok(objects)
+    }
+
+    pub fn save(&self, mut objects: Vec<CacheObject>) -> crate::Result<()> {
+        let _guard = self.lock.lock().unwrap();
+
+        objects.sort_by(|a, b| a.sha1.cmp(&b.sha1));
+
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let mut content = serde_json::to_vec(&objects)?;
+        if let Some(cipher) = &self.cipher {
+            content = cipher.encrypt(&content)?;
+        }
+
+        // Write then rename so a crash mid-write cannot truncate the snapshot
+        let tmp = self.path.with_extension("json.tmp");
+        fs::write(&tmp, content)?;
+        fs::rename(&tmp, &self.path)?;
+        tracing::debug!(
+            "Saved {} cached certificates to {}",
+            objects.len(),
+            self.path.display()
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::PathObject;
+    use chrono::Utc;
+
+    fn object() -> CacheObject {
+        CacheObject {
+            subject: "CN=example.com".to_string(),
+            not_before: None,
+            not_after: Utc::now(),
+            sha1: "abc".to_string(),
+            paths: vec![PathObject {
+                backend: "vault".into(),
+                path: "secret/example".into(),
+            }],
+            metadata: Default::default(),
+        }
+    }
+
+    #[test]
+    fn test_snapshot_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = CacheStore::new(dir.path().join("cache.json"));
+        assert!(store.load().unwrap().is_empty());
+
+        store.save(vec![object()]).unwrap();
+
+        let loaded = store.load().unwrap();
+        assert_eq!(loaded.len(), 1);
+        assert_eq!(loaded[0].sha1, "abc");
+        assert_eq!(&*loaded[0].paths[0].backend, "vault");
+    }
+
+    #[test]
+    fn test_encrypted_snapshot() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cache.json");
+
+        // A plaintext snapshot from before encryption was enabled still loads
+        CacheStore::new(path.clone()).save(vec![object()]).unwrap();
+        let store = CacheStore::encrypted(path.clone(), &[7; 32]);
+        assert_eq!(store.load().unwrap().len(), 1);
+
+        store.save(vec![object()]).unwrap();
+        let raw = fs::read(&path).unwrap();
+        assert!(raw.starts_with(ENCRYPTED_MAGIC));
+        assert!(!String::from_utf8_lossy(&raw).contains("secret/example"));
+        assert_eq!(store.load().unwrap()[0].sha1, "abc");
+
+        assert!(CacheStore::new(path.clone()).load().is_err());
+        assert!(CacheStore::encrypted(path, &[8; 32]).load().is_err());
+    }
+}
@@ -0,0 +1,101 @@
+use crate::config::ClientTarget;
+use crate::types::AuthRequest;
+use serde::{Deserialize, Serialize};
+
+/// The keyring service the CLI's entries are filed under, one per target.
+const SERVICE: &str = "doomsday";
+
+/// Where a target's token and saved credentials are kept.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SecretStore {
+    /// The OS keychain: macOS Keychain, Windows Credential Manager or the
+    /// Secret Service on Linux.
+    Keyring,
+    /// The CLI config file, used when no keyring is available.
+    File,
+}
+
+/// A target's keyring entry.
+#[derive(Serialize, Deserialize)]
+struct Secrets {
+    token: Option<String>,
+    credentials: Option<AuthRequest>,
+}
+
+/// Moves the target's token and saved credentials into the OS keyring, so
+/// the config file is written without them. Without a usable keyring they
+/// stay in the file, and the target is marked to keep using it.
+pub fn stash(target: &mut ClientTarget) {
+    if target.token.is_none() && target.credentials.is_none() {
+        return;
+    }
+    if target.secret_store == Some(SecretStore::File) {
+        return;
+    }
+
+    let secrets = Secrets {
+        token: target.token.take(),
+        credentials: target.credentials.take(),
+    };
+    match store(&target.name, &secrets) {
+        Ok(()) => target.secret_store = Some(SecretStore::Keyring),
+        Err(e) => {
+            eprintln!(
+                "⚠️  OS keyring unavailable ({}), keeping the token for target '{}' in the config file",
+                e, target.name
+            );
+            target.token = secrets.token;
+            target.credentials = secrets.credentials;
+            target.secret_store = Some(SecretStore::File);
+        }
+    }
+}
+
+/// Reads back the token and credentials of a target kept in the keyring.
+/// Without them the target is left logged out.
+pub fn restore(target: &mut ClientTarget) {
+    if target.secret_store != Some(SecretStore::Keyring) {
+        return;
+    }
+    match load(&target.name) {
+        Ok(secrets) => {
+            target.token = secrets.token;
+            target.credentials = secrets.credentials;
+        }
+        Err(e) => eprintln!(
+            "⚠️  Could not read the token for target '{}' from the OS keyring: {}",
+            target.name, e
+        ),
+    }
+}
+
+/// Removes a target's keyring entry, once it is deleted or renamed.
+pub fn forget(target: &ClientTarget) {
+    if target.secret_store != Some(SecretStore::Keyring) {
+        return;
+    }
+    let removed = keyring::Entry::new(SERVICE, &target.name).and_then(|e| e.delete_credential());
+    if let Err(e) = removed {
+        if !matches!(e, keyring::Error::NoEntry) {
+            eprintln!(
+                "⚠️  Could not remove the token for target '{}' from the OS keyring: {}",
+                target.name, e
+            );
+        }
+    }
+}
+
+fn store(name: &str, secrets: &Secrets) -> Result<(), String> {
+    let secret = serde_json::to_string(secrets).map_err(|e| e.to_string())?;
+    keyring::Entry::new(SERVICE, name)
+        .and_then(|entry| entry.set_password(&secret))
+        .map_err(|e| e.to_string())
+}
+
+fn load(name: &str) -> Result<Secrets, String> {
+    let secret = keyring::Entry::new(SERVICE, name)
+        .and_then(|entry| entry.get_password())
+        .map_err(|e| e.to_string())?;
+    serde_json::from_str(&secret).map_err(|e| e.to_string())
+}
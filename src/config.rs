@@ -1,74 +1,1346 @@
+use crate::alerts::ReminderRule;
+use crate::client_secrets::{self, SecretStore};
+use crate::config_layers::{env_overrides, ConfigLayers};
+use crate::ignore::IgnoreRule;
+use crate::labels::LabelRule;
+use crate::notifications::NotificationUrgency;
+use crate::quiet::{MaintenanceWindow, QuietHours};
+use crate::schedule::Schedule;
+use crate::storage::tlsclient::StartTls;
+use crate::types::{AuthRequest, Task};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// Events published by the cache, as named in `events` filters.
+pub const CACHE_EVENTS: &[&str] = &[
+    "certificate_added",
+    "certificate_removed",
+    "threshold_crossed",
+    "certificate_rotated",
+];
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct Config {
+    /// Further config files, or globs of them, merged into this one.
+    #[serde(default)]
+    pub include: Vec<String>,
+    #[serde(default)]
     pub backends: Vec<BackendConfig>,
     pub server: ServerConfig,
     pub notifications: Option<NotificationConfig>,
+    #[serde(default)]
+    pub pins: Vec<PinConfig>,
+    pub webhooks: Option<WebhookConfig>,
+    /// When `threshold_crossed` events are raised for webhooks and
+    /// notifications.
+    pub events: Option<EventConfig>,
+    #[serde(default)]
+    pub status: StatusThresholds,
+    #[serde(default)]
+    pub task_retry: RetryPolicy,
+    #[serde(default)]
+    pub task_timeouts: TaskTimeouts,
+    #[serde(default)]
+    pub scheduler: SchedulerConfig,
+    /// Server-wide tuning of backend scans and where state is kept.
+    #[serde(default)]
+    pub settings: SettingsConfig,
+    pub adaptive_refresh: Option<AdaptiveRefreshConfig>,
+    #[serde(default)]
+    pub cache: CacheConfig,
+    /// Rules hiding certificates from listings, stats and notifications.
+    #[serde(default)]
+    pub ignore: Vec<IgnoreRule>,
+    /// Rules attaching labels to certificates by backend, path or subject.
+    #[serde(default)]
+    pub labels: Vec<LabelRule>,
+    /// Actions run for certificates nearing expiry, e.g. to start a
+    /// rotation pipeline.
+    #[serde(default)]
+    pub hooks: Vec<HookConfig>,
+    /// Backends left out because they could not be parsed, kept to be
+    /// reported when `server.skip_invalid_backends` is set.
+    #[serde(skip)]
+    pub invalid_backends: Vec<InvalidBackend>,
+}
+
+/// A backend that could not be parsed, e.g. for an unknown `type` or a
+/// misspelled property.
+#[derive(Debug, Clone)]
+pub struct InvalidBackend {
+    /// Its `name`, or its place in `backends` without one.
+    pub name: String,
+    pub backend_type: String,
+    pub error: String,
+}
+
+/// Formats the server config may be written in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigFormat {
+    Yaml,
+    Toml,
+    Json,
+}
+
+impl ConfigFormat {
+    /// `.toml` and `.json` files are read as such; anything else is YAML.
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("toml") => ConfigFormat::Toml,
+            Some(ext) if ext.eq_ignore_ascii_case("json") => ConfigFormat::Json,
+            _ => ConfigFormat::Yaml,
+        }
+    }
+
+    pub fn deserialize<T: serde::de::DeserializeOwned>(&self, content: &str) -> crate::Result<T> {
+        let value = match self {
+            ConfigFormat::Yaml => serde_yaml::from_str(content)?,
+            ConfigFormat::Toml => toml::from_str(content)?,
+            ConfigFormat::Json => serde_json::from_str(content)?,
+        };
+        Ok(value)
+    }
+}
+
+impl FromStr for ConfigFormat {
+    type Err = crate::DoomsdayError;
+
+    fn from_str(s: &str) -> crate::Result<Self> {
+        match s.to_lowercase().as_str() {
+            "yaml" | "yml" => Ok(ConfigFormat::Yaml),
+            "toml" => Ok(ConfigFormat::Toml),
+            "json" => Ok(ConfigFormat::Json),
+            _ => Err(crate::DoomsdayError::invalid_input(format!(
+                "Unknown config format: {} (expected yaml, toml or json)",
+                s
+            ))),
+        }
+    }
+}
+
+/// Snapshotting of the certificate cache to `server.data_dir`, and sharing
+/// it between replicas.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CacheConfig {
+    /// Reload the last snapshot on startup and keep it up to date.
+    #[serde(default)]
+    pub persist: bool,
+    /// Seconds between snapshots while running.
+    #[serde(default = "default_snapshot_interval")]
+    pub snapshot_interval: u64,
+    pub shared: Option<SharedCacheConfig>,
+    /// Number of refreshes whose changes are kept for `/v1/cache/changes`.
+    #[serde(default = "default_change_history")]
+    pub change_history: usize,
+    /// Encrypt the snapshot on disk.
+    pub encryption: Option<SnapshotEncryptionConfig>,
+}
+
+/// AES-256-GCM key for the cache snapshot: 32 bytes, base64 encoded, taken
+/// from exactly one of the sources.
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SnapshotEncryptionConfig {
+    pub key: Option<String>,
+    /// Environment variable holding the key.
+    pub key_env: Option<String>,
+    /// File holding the key, e.g. one written by a KMS or secrets agent.
+    pub key_file: Option<String>,
+}
+
+impl std::fmt::Debug for SnapshotEncryptionConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SnapshotEncryptionConfig")
+            .field("key", &self.key.as_ref().map(|_| "<redacted>"))
+            .field("key_env", &self.key_env)
+            .field("key_file", &self.key_file)
+            .finish()
+    }
+}
+
+impl SnapshotEncryptionConfig {
+    pub fn validate(&self) -> crate::Result<()> {
+        let sources = [&self.key, &self.key_env, &self.key_file]
+            .iter()
+            .filter(|source| source.is_some())
+            .count();
+        if sources != 1 {
+            return Err(crate::DoomsdayError::config(
+                "cache.encryption requires exactly one of key, key_env or key_file",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Reads and decodes the key from its configured source.
+    pub fn load_key(&self) -> crate::Result<[u8; 32]> {
+        use base64::prelude::*;
+
+        let encoded = match (&self.key, &self.key_env, &self.key_file) {
+            (Some(key), _, _) => key.clone(),
+            (_, Some(var), _) => std::env::var(var).map_err(|_| {
+                crate::DoomsdayError::config(format!(
+                    "Cache encryption key variable {} is not set",
+                    var
+                ))
+            })?,
+            (_, _, Some(file)) => fs::read_to_string(file).map_err(|e| {
+                crate::DoomsdayError::config(format!(
+                    "Failed to read cache encryption key from {}: {}",
+                    file, e
+                ))
+            })?,
+            _ => {
+                return Err(crate::DoomsdayError::config(
+                    "No cache encryption key configured",
+                ))
+            }
+        };
+
+        BASE64_STANDARD
+            .decode(encoded.trim())
+            .ok()
+            .and_then(|bytes| <[u8; 32]>::try_from(bytes).ok())
+            .ok_or_else(|| {
+                crate::DoomsdayError::config(
+                    "Cache encryption key must be 32 bytes, base64 encoded",
+                )
+            })
+    }
+}
+
+/// A Redis instance holding the cache for all replicas. Only the replica
+/// holding the refresh lease scans backends.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SharedCacheConfig {
+    /// `redis://[user:password@]host[:port][/db]`
+    pub url: String,
+    /// Prefix for the keys doomsday stores.
+    #[serde(default = "default_shared_key_prefix")]
+    pub key_prefix: String,
+    /// Seconds between publishing (or loading) the shared cache.
+    #[serde(default = "default_shared_sync_interval")]
+    pub sync_interval: u64,
+    /// Seconds the refresh lease outlives a replica that stops renewing it.
+    #[serde(default = "default_shared_lease_ttl")]
+    pub lease_ttl: u64,
+}
+
+fn default_shared_key_prefix() -> String {
+    "doomsday".to_string()
+}
+
+fn default_shared_sync_interval() -> u64 {
+    30
+}
+
+fn default_shared_lease_ttl() -> u64 {
+    90
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        CacheConfig {
+            persist: false,
+            snapshot_interval: default_snapshot_interval(),
+            shared: None,
+            change_history: default_change_history(),
+            encryption: None,
+        }
+    }
+}
+
+fn default_snapshot_interval() -> u64 {
+    300
+}
+
+fn default_change_history() -> usize {
+    100
+}
+
+/// Refreshes backends more often while they serve certificates close to
+/// expiry, so last-minute rotations show up quickly.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AdaptiveRefreshConfig {
+    /// Backends with a certificate expiring within this many days qualify.
+    pub within_days: i64,
+    /// Minutes between refreshes of qualifying backends.
+    pub interval: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SchedulerConfig {
+    /// Number of tasks (e.g. backend refreshes) that may run at once.
+    #[serde(default = "default_scheduler_workers")]
+    pub workers: usize,
+}
+
+impl Default for SchedulerConfig {
+    fn default() -> Self {
+        SchedulerConfig {
+            workers: default_scheduler_workers(),
+        }
+    }
+}
+
+fn default_scheduler_workers() -> usize {
+    4
+}
+
+/// Tuning for backend scans and the location of persisted state. Expiry
+/// thresholds and the scheduler's worker count are set under `status` and
+/// `scheduler`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SettingsConfig {
+    /// Certificate fetches in flight per backend, unless the backend sets
+    /// its own `max_concurrent_requests`.
+    #[serde(default = "default_max_concurrent_requests")]
+    pub max_concurrent_requests: usize,
+    /// Paths fetched per batch during a scan, bounding the work queued at
+    /// once for backends with many paths.
+    #[serde(default = "default_fetch_chunk_size")]
+    pub fetch_chunk_size: usize,
+    /// Seconds a single request to a storage backend may take, including a
+    /// `tlsclient` handshake.
+    #[serde(default = "default_request_timeout")]
+    pub request_timeout: u64,
+    /// Cache snapshot file. Relative paths are under `server.data_dir`.
+    #[serde(default = "default_cache_path")]
+    pub cache_path: String,
+    /// Scheduler task history file. Relative paths are under
+    /// `server.data_dir`.
+    #[serde(default = "default_task_store_path")]
+    pub task_store_path: String,
+}
+
+impl SettingsConfig {
+    pub fn request_timeout(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.request_timeout)
+    }
+}
+
+impl Default for SettingsConfig {
+    fn default() -> Self {
+        SettingsConfig {
+            max_concurrent_requests: default_max_concurrent_requests(),
+            fetch_chunk_size: default_fetch_chunk_size(),
+            request_timeout: default_request_timeout(),
+            cache_path: default_cache_path(),
+            task_store_path: default_task_store_path(),
+        }
+    }
+}
+
+fn default_max_concurrent_requests() -> usize {
+    50
+}
+
+fn default_fetch_chunk_size() -> usize {
+    1000
+}
+
+fn default_request_timeout() -> u64 {
+    30
+}
+
+fn default_cache_path() -> String {
+    "cache.json".to_string()
+}
+
+fn default_task_store_path() -> String {
+    "tasks.json".to_string()
+}
+
+/// Seconds a scheduler task may run before it is cancelled, per task type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TaskTimeouts {
+    #[serde(default = "default_refresh_backend_timeout")]
+    pub refresh_backend: u64,
+    #[serde(default = "default_renew_auth_token_timeout")]
+    pub renew_auth_token: u64,
+    #[serde(default = "default_run_hook_timeout")]
+    pub run_hook: u64,
+}
+
+impl TaskTimeouts {
+    pub fn for_task(&self, task: &Task) -> std::time::Duration {
+        let seconds = match task {
+            Task::RefreshBackend { .. } => self.refresh_backend,
+            Task::RenewAuthToken { .. } => self.renew_auth_token,
+            Task::RunHook { .. } => self.run_hook,
+        };
+        std::time::Duration::from_secs(seconds)
+    }
+}
+
+impl Default for TaskTimeouts {
+    fn default() -> Self {
+        TaskTimeouts {
+            refresh_backend: default_refresh_backend_timeout(),
+            renew_auth_token: default_renew_auth_token_timeout(),
+            run_hook: default_run_hook_timeout(),
+        }
+    }
+}
+
+fn default_refresh_backend_timeout() -> u64 {
+    300
+}
+
+fn default_renew_auth_token_timeout() -> u64 {
+    60
+}
+
+fn default_run_hook_timeout() -> u64 {
+    300
+}
+
+/// How failed backend refresh tasks and notification deliveries are
+/// retried.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RetryPolicy {
+    /// Total attempts, including the first; 1 disables retries.
+    #[serde(default = "default_max_attempts")]
+    pub max_attempts: u32,
+    /// Seconds to wait before the first retry. Doubles with every attempt.
+    #[serde(default = "default_initial_backoff")]
+    pub initial_backoff: u64,
+    /// Upper bound in seconds for the wait between attempts.
+    #[serde(default = "default_max_backoff")]
+    pub max_backoff: u64,
+}
+
+impl RetryPolicy {
+    /// Backoff before retrying a task that has failed `attempt` times, before
+    /// jitter is applied.
+    pub fn backoff(&self, attempt: u32) -> std::time::Duration {
+        let exponent = attempt.saturating_sub(1).min(31);
+        let seconds = self
+            .initial_backoff
+            .saturating_mul(1u64 << exponent)
+            .min(self.max_backoff);
+        std::time::Duration::from_secs(seconds)
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: default_max_attempts(),
+            initial_backoff: default_initial_backoff(),
+            max_backoff: default_max_backoff(),
+        }
+    }
+}
+
+fn default_max_attempts() -> u32 {
+    5
+}
+
+fn default_initial_backoff() -> u64 {
+    30
+}
+
+fn default_max_backoff() -> u64 {
+    600
+}
+
+/// Thresholds used to classify certificates into a `CertificateStatus`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct StatusThresholds {
+    /// Certificates expiring within this many days are `expiring_soon`.
+    #[serde(default = "default_expiring_soon_days")]
+    pub expiring_soon_days: i64,
+    /// Certificates served only by backends that have been failing for longer
+    /// than this many hours are flagged as stale. Never when unset.
+    #[serde(default)]
+    pub stale_after_hours: Option<i64>,
+}
+
+impl Default for StatusThresholds {
+    fn default() -> Self {
+        StatusThresholds {
+            expiring_soon_days: default_expiring_soon_days(),
+            stale_after_hours: None,
+        }
+    }
+}
+
+fn default_expiring_soon_days() -> i64 {
+    30
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EventConfig {
+    /// Days-until-expiry thresholds that trigger a `threshold_crossed` event.
+    #[serde(default = "default_event_thresholds")]
+    pub thresholds: Vec<i64>,
+    /// How often (in minutes) certificates are checked against the
+    /// thresholds between refreshes.
+    #[serde(default = "default_event_check_interval")]
+    pub check_interval: u64,
+}
+
+impl Default for EventConfig {
+    fn default() -> Self {
+        EventConfig {
+            thresholds: default_event_thresholds(),
+            check_interval: default_event_check_interval(),
+        }
+    }
+}
+
+fn default_event_thresholds() -> Vec<i64> {
+    vec![30, 7, 1]
+}
+
+fn default_event_check_interval() -> u64 {
+    60
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WebhookConfig {
+    /// Superseded by `events.thresholds`; still used when `events` is unset.
+    pub thresholds: Option<Vec<i64>>,
+    /// Superseded by `events.check_interval`; still used when `events` is unset.
+    pub check_interval: Option<u64>,
+    pub endpoints: Vec<WebhookEndpointConfig>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WebhookEndpointConfig {
+    pub name: String,
+    pub url: String,
+    /// Shared secret used to sign payloads (HMAC-SHA256).
+    pub secret: Option<String>,
+    /// Event names to deliver; all events are delivered when empty.
+    #[serde(default)]
+    pub events: Vec<String>,
+    pub max_retries: Option<u32>,
+    /// Only deliver events for certificates carrying all of these labels.
+    #[serde(default)]
+    pub labels: HashMap<String, String>,
+}
+
+impl WebhookEndpointConfig {
+    pub fn accepts(&self, event_name: &str) -> bool {
+        self.events.is_empty() || self.events.iter().any(|e| e == event_name)
+    }
+}
+
+/// Runs `action` once for each certificate matching the selectors when it
+/// comes within `within` days of expiry.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct HookConfig {
+    pub name: String,
+    pub within: i64,
+    /// Labels the certificate must carry.
+    #[serde(default)]
+    pub labels: HashMap<String, String>,
+    /// Storage backends the certificate was found on.
+    #[serde(default)]
+    pub backends: Vec<String>,
+    /// Regex matched against the certificate's paths.
+    pub path: Option<String>,
+    pub action: HookAction,
+}
+
+/// What a hook does. Each is given the certificate as JSON.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase", deny_unknown_fields)]
+pub enum HookAction {
+    /// Runs `command` with the JSON on stdin.
+    Script {
+        command: String,
+        #[serde(default)]
+        args: Vec<String>,
+        /// Seconds before the command is killed.
+        #[serde(default = "default_run_hook_timeout")]
+        timeout: u64,
+    },
+    /// POSTs the JSON to `url`, signed like webhook events when `secret`
+    /// is set.
+    Webhook { url: String, secret: Option<String> },
+    /// Runs `command` as a scheduler task, retried under `task_retry` and
+    /// limited by `task_timeouts.run_hook`.
+    Task {
+        command: String,
+        #[serde(default)]
+        args: Vec<String>,
+    },
+}
+
+impl HookAction {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            HookAction::Script { .. } => "script",
+            HookAction::Webhook { .. } => "webhook",
+            HookAction::Task { .. } => "task",
+        }
+    }
+}
+
+impl HookConfig {
+    pub fn validate(&self) -> crate::Result<()> {
+        if self.within < 0 {
+            return Err(crate::DoomsdayError::config(format!(
+                "Hook {} within must not be negative",
+                self.name
+            )));
+        }
+
+        if let Some(path) = &self.path {
+            regex::Regex::new(path).map_err(|e| {
+                crate::DoomsdayError::config(format!(
+                    "Invalid path regex for hook {}: {}",
+                    self.name, e
+                ))
+            })?;
+        }
+
+        match &self.action {
+            HookAction::Script { command, .. } | HookAction::Task { command, .. }
+                if command.is_empty() =>
+            {
+                Err(crate::DoomsdayError::config(format!(
+                    "Hook {} requires a command",
+                    self.name
+                )))
+            }
+            HookAction::Script { timeout: 0, .. } => Err(crate::DoomsdayError::config(format!(
+                "Hook {} timeout must be at least one second",
+                self.name
+            ))),
+            HookAction::Webhook { url, .. } => {
+                url::Url::parse(url).map_err(|e| {
+                    crate::DoomsdayError::config(format!(
+                        "Invalid URL for hook {}: {}",
+                        self.name, e
+                    ))
+                })?;
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Expected fingerprints for a critical certificate location. A refresh that
+/// observes any other certificate at this location raises an alert.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PinConfig {
+    pub backend: String,
+    pub path: String,
+    /// Accepted SHA1 fingerprints, hex encoded (colons and case are ignored).
+    pub fingerprints: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "RawBackendConfig")]
 pub struct BackendConfig {
-    #[serde(rename = "type")]
-    pub backend_type: String,
     pub name: String,
+    /// `false` keeps the backend configured but skips creating, refreshing
+    /// and scheduling it.
+    #[serde(default = "default_backend_enabled")]
+    pub enabled: bool,
     pub refresh_interval: Option<u64>, // minutes
-    pub properties: HashMap<String, serde_yaml::Value>,
+    /// Cron expression for refreshes, as an alternative to `refresh_interval`.
+    pub refresh_cron: Option<String>,
+    /// Maximum certificate fetches in flight against this backend at once.
+    pub max_concurrent_requests: Option<usize>,
+    /// The backend's `type` and its `properties`.
+    #[serde(flatten)]
+    pub kind: BackendKind,
+}
+
+/// A backend as written, before unknown keys are rejected.
+#[derive(Deserialize)]
+struct RawBackendConfig {
+    name: String,
+    #[serde(default = "default_backend_enabled")]
+    enabled: bool,
+    refresh_interval: Option<u64>,
+    refresh_cron: Option<String>,
+    max_concurrent_requests: Option<usize>,
+    #[serde(flatten)]
+    kind: BackendKind,
+    #[serde(flatten)]
+    unknown: UnknownFields,
+}
+
+impl TryFrom<RawBackendConfig> for BackendConfig {
+    type Error = String;
+
+    fn try_from(raw: RawBackendConfig) -> Result<Self, Self::Error> {
+        deny_unknown_fields(
+            &raw.unknown,
+            &[
+                "name",
+                "enabled",
+                "type",
+                "properties",
+                "refresh_interval",
+                "refresh_cron",
+                "max_concurrent_requests",
+            ],
+        )?;
+        Ok(BackendConfig {
+            name: raw.name,
+            enabled: raw.enabled,
+            refresh_interval: raw.refresh_interval,
+            refresh_cron: raw.refresh_cron,
+            max_concurrent_requests: raw.max_concurrent_requests,
+            kind: raw.kind,
+        })
+    }
+}
+
+fn default_backend_enabled() -> bool {
+    true
+}
+
+/// Keys left over after deserializing a struct with a flattened field.
+/// serde cannot combine `flatten` with `deny_unknown_fields`, so such
+/// structs collect the rest here and reject it themselves.
+pub(crate) type UnknownFields = BTreeMap<String, serde::de::IgnoredAny>;
+
+/// Fails on the first unknown key, worded like serde's own
+/// `deny_unknown_fields` error.
+pub(crate) fn deny_unknown_fields(
+    unknown: &UnknownFields,
+    expected: &[&str],
+) -> Result<(), String> {
+    match unknown.keys().next() {
+        Some(field) => Err(format!(
+            "unknown field `{}`, expected one of {}",
+            field,
+            expected
+                .iter()
+                .map(|name| format!("`{}`", name))
+                .collect::<Vec<_>>()
+                .join(", ")
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Each storage backend type with the properties it takes. Unknown
+/// properties are rejected when the config is loaded.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", content = "properties", rename_all = "lowercase")]
+pub enum BackendKind {
+    Vault(Box<VaultBackendConfig>),
+    CredHub(CredHubBackendConfig),
+    OpsMgr(OpsMgrBackendConfig),
+    TlsClient(TlsClientBackendConfig),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct VaultBackendConfig {
+    pub url: String,
+    /// Static token, used unless `auth` is `approle`.
+    pub token: Option<String>,
+    /// File holding the token instead, such as a Vault Agent sink. Read
+    /// again when it changes or Vault rejects the token.
+    pub token_file: Option<PathBuf>,
+    #[serde(default)]
+    pub auth: VaultAuthMethod,
+    pub role_id: Option<String>,
+    pub secret_id: Option<String>,
+    /// File holding the secret ID, read again at every login so it can be
+    /// rotated, e.g. by a Vault agent.
+    pub secret_id_file: Option<PathBuf>,
+    /// Where the AppRole auth method is mounted.
+    #[serde(default = "default_vault_approle_mount")]
+    pub approle_mount: String,
+    /// The KV mount to list, `secret` unless `mounts` is set.
+    pub mount_path: Option<String>,
+    /// Where under the mount to start listing secrets, `/` by default.
+    pub secret_path: Option<String>,
+    /// Version of the mount's KV secrets engine, 1 or 2. Detected from the
+    /// mount when unset.
+    pub kv_version: Option<u8>,
+    /// Several mounts to list instead of `mount_path`. Their secrets' paths
+    /// start with the mount they are in.
+    #[serde(default)]
+    pub mounts: Vec<VaultMountConfig>,
+    /// Directories listed at once while walking `secret_path`.
+    #[serde(default = "default_vault_list_concurrency")]
+    pub list_concurrency: usize,
+    /// How many directory levels below `secret_path` to descend. Unlimited
+    /// when unset; 0 lists only `secret_path` itself.
+    pub max_depth: Option<usize>,
+    /// Requests per second sent to Vault at most, to stay under its rate
+    /// limit quotas. Unlimited when unset.
+    pub max_requests_per_second: Option<u32>,
+    /// Times a request Vault answers with 429 (rate limited) or 503 (sealed
+    /// or standby) is retried, with backoff. At most 10.
+    #[serde(default = "default_vault_max_retries")]
+    pub max_retries: u32,
+    /// PEM bundle of CAs trusted for Vault, alongside the system roots,
+    /// e.g. for an internal CA.
+    pub ca_cert: Option<PathBuf>,
+    /// PEM client certificate for Vaults requiring mutual TLS. Holds the
+    /// key too unless `client_key` is set.
+    pub client_cert: Option<PathBuf>,
+    pub client_key: Option<PathBuf>,
+    /// Accepts any certificate from Vault. For testing only.
+    #[serde(default)]
+    pub skip_verify: bool,
+}
+
+/// How a Vault backend gets its token.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VaultAuthMethod {
+    /// The static `token`.
+    #[default]
+    Token,
+    /// Logs in with `role_id` and `secret_id` or `secret_id_file`.
+    AppRole,
+}
+
+fn default_vault_approle_mount() -> String {
+    "approle".to_string()
+}
+
+/// One of a Vault backend's `mounts`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct VaultMountConfig {
+    /// The mount's path, or a glob such as `kv-*` matched against the KV
+    /// mounts the token can see.
+    pub mount_path: String,
+    #[serde(default = "default_vault_secret_path")]
+    pub secret_path: String,
+    pub kv_version: Option<u8>,
+}
+
+pub(crate) fn default_vault_mount_path() -> String {
+    "secret".to_string()
+}
+
+pub(crate) fn default_vault_secret_path() -> String {
+    "/".to_string()
+}
+
+fn default_vault_max_retries() -> u32 {
+    3
+}
+
+fn default_vault_list_concurrency() -> usize {
+    8
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CredHubBackendConfig {
+    pub url: String,
+    /// UAA that issues access tokens. Discovered from CredHub's `/info`
+    /// when unset.
+    pub uaa_url: Option<String>,
+    /// Only list credentials under this path, e.g. `/concourse`. Everything
+    /// is listed when unset.
+    pub path: Option<String>,
+    /// UAA client whose token authenticates requests, unless CredHub is
+    /// authenticated to with `client_cert` alone.
+    pub client_id: Option<String>,
+    pub client_secret: Option<String>,
+    /// PEM client certificate presented to CredHub (mTLS). Holds the key
+    /// too unless `client_key` is set.
+    pub client_cert: Option<PathBuf>,
+    pub client_key: Option<PathBuf>,
+    /// PEM bundle of CAs trusted for CredHub, alongside the system roots.
+    pub ca_cert: Option<PathBuf>,
+    /// Accepts any certificate from CredHub and its UAA. For testing only.
+    #[serde(default)]
+    pub skip_verify: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OpsMgrBackendConfig {
+    pub url: String,
+    pub username: String,
+    pub password: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TlsClientBackendConfig {
+    pub targets: Vec<TlsTargetConfig>,
+}
+
+/// An endpoint whose certificate is read from the TLS handshake.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TlsTargetConfig {
+    pub host: String,
+    #[serde(default = "default_tls_port")]
+    pub port: u16,
+    /// SNI name, when it differs from `host`.
+    pub server_name: Option<String>,
+    pub starttls: Option<StartTls>,
+}
+
+fn default_tls_port() -> u16 {
+    443
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct ServerConfig {
     pub port: u16,
     pub tls: Option<TlsConfig>,
     pub auth: AuthConfig,
+    /// Start with the remaining backends when some are misconfigured instead of
+    /// refusing to start. Skipped backends are reported as degraded.
+    #[serde(default)]
+    pub skip_invalid_backends: bool,
+    /// Directory for state that must survive restarts (e.g. the ignore list).
+    /// State is kept in memory only when unset.
+    pub data_dir: Option<String>,
+    /// Seconds to wait for running tasks to finish on shutdown.
+    pub drain_timeout: Option<u64>,
+    /// Hours to keep finished scheduler tasks in the task history.
+    pub task_retention: Option<u64>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct TlsConfig {
     pub cert: String,
     pub key: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct AuthConfig {
     #[serde(rename = "type")]
     pub auth_type: String,
     pub properties: HashMap<String, serde_yaml::Value>,
+    /// Break-glass credentials used only while the primary provider is unreachable.
+    #[serde(default)]
+    pub fallback: Option<AuthFallbackConfig>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AuthFallbackConfig {
+    /// Static token accepted as `X-Doomsday-Token` during an outage.
+    pub emergency_token: Option<String>,
+    /// Local username -> password pairs that may log in during an outage.
+    #[serde(default)]
+    pub users: HashMap<String, String>,
+    pub session_timeout: Option<u64>, // minutes
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct NotificationConfig {
     pub doomsday_url: String,
-    pub backend: NotificationBackend,
+    /// Receives every notification. May be combined with `backends`.
+    #[serde(default)]
+    pub backend: Option<NotificationBackend>,
+    /// Named backends, each receiving the notifications its route matches.
+    #[serde(default)]
+    pub backends: Vec<RoutedNotificationBackend>,
     pub schedule: ScheduleConfig,
+    /// Only notify about certificates carrying all of these labels.
+    #[serde(default)]
+    pub labels: HashMap<String, String>,
+    /// Cache events sent as they happen, in addition to the scheduled
+    /// digest.
+    #[serde(default = "default_notification_events")]
+    pub events: Vec<String>,
+    /// When to repeat a scheduled alert about a certificate. Without rules,
+    /// each certificate is alerted once per threshold.
+    #[serde(default)]
+    pub reminders: Vec<ReminderRule>,
+    /// Days before expiry at which scheduled digests alert, each with its
+    /// own urgency and optionally its own backend.
+    #[serde(default)]
+    pub thresholds: Vec<AlertThreshold>,
+    /// What each `schedule` run sends.
+    #[serde(default)]
+    pub mode: NotificationMode,
+    #[serde(default)]
+    pub digest: DigestConfig,
+    /// Daily windows during which notifications are held, then summarized
+    /// once the window ends.
+    #[serde(default)]
+    pub quiet_hours: Vec<QuietHours>,
+    /// One-off windows during which notifications are held, e.g. planned
+    /// maintenance.
+    #[serde(default)]
+    pub maintenance: Vec<MaintenanceWindow>,
+    /// Retries for failed deliveries, made in the background. Deliveries
+    /// still failing after the last attempt go to the dead-letter log.
+    #[serde(default)]
+    pub retry: RetryPolicy,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NotificationMode {
+    /// Alerts for certificates newly at a threshold, or due a reminder.
+    #[default]
+    Alerts,
+    /// One summary of every certificate at a threshold, sent every run.
+    Digest,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct NotificationBackend {
-    #[serde(rename = "type")]
-    pub backend_type: String,
-    pub properties: HashMap<String, serde_yaml::Value>,
+#[serde(deny_unknown_fields)]
+pub struct DigestConfig {
+    /// Certificates listed per group, soonest expiry first.
+    #[serde(default = "default_digest_top")]
+    pub top: usize,
+    #[serde(default)]
+    pub group_by: Option<DigestGrouping>,
+}
+
+impl Default for DigestConfig {
+    fn default() -> Self {
+        DigestConfig {
+            top: default_digest_top(),
+            group_by: None,
+        }
+    }
+}
+
+fn default_digest_top() -> usize {
+    10
+}
+
+/// Written as `backend` or `label:<name>`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum DigestGrouping {
+    /// By the storage backends a certificate was found on.
+    Backend,
+    /// By the value of a label.
+    Label(String),
+}
+
+impl TryFrom<String> for DigestGrouping {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        match value.split_once(':') {
+            None if value == "backend" => Ok(DigestGrouping::Backend),
+            Some(("label", label)) if !label.is_empty() => {
+                Ok(DigestGrouping::Label(label.to_string()))
+            }
+            _ => Err(format!(
+                "invalid digest group_by '{}' (expected backend or label:<name>)",
+                value
+            )),
+        }
+    }
+}
+
+impl From<DigestGrouping> for String {
+    fn from(grouping: DigestGrouping) -> Self {
+        match grouping {
+            DigestGrouping::Backend => "backend".to_string(),
+            DigestGrouping::Label(label) => format!("label:{}", label),
+        }
+    }
+}
+
+/// Certificates expiring within `within` days are alerted at `urgency`. A
+/// certificate is alerted at the tightest threshold it falls under.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AlertThreshold {
+    /// Days until expiry; 0 alerts once the certificate has expired.
+    pub within: i64,
+    pub urgency: NotificationUrgency,
+    /// Sends this threshold's alerts here instead of the notification
+    /// backends.
+    #[serde(default)]
+    pub backend: Option<NotificationBackend>,
+}
+
+impl NotificationConfig {
+    pub fn accepts(&self, event_name: &str) -> bool {
+        self.events.iter().any(|e| e == event_name)
+    }
+}
+
+fn default_notification_events() -> Vec<String> {
+    vec![
+        "threshold_crossed".to_string(),
+        "certificate_rotated".to_string(),
+    ]
 }
 
+/// Each notification backend type with the properties it takes. Unknown
+/// properties are rejected when the config is loaded.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", content = "properties", rename_all = "lowercase")]
+pub enum NotificationBackend {
+    Slack(SlackNotificationConfig),
+    Shout(ShoutNotificationConfig),
+    Teams(TeamsNotificationConfig),
+    Webhook(WebhookNotificationConfig),
+    Telegram(TelegramNotificationConfig),
+}
+
+impl NotificationBackend {
+    pub fn backend_type(&self) -> &'static str {
+        match self {
+            NotificationBackend::Slack(_) => "slack",
+            NotificationBackend::Shout(_) => "shout",
+            NotificationBackend::Teams(_) => "teams",
+            NotificationBackend::Webhook(_) => "webhook",
+            NotificationBackend::Telegram(_) => "telegram",
+        }
+    }
+}
+
+/// Posts through an incoming webhook, or through the Web API with
+/// `bot_token`, which is needed to thread.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SlackNotificationConfig {
+    pub webhook_url: Option<String>,
+    pub bot_token: Option<String>,
+    pub channel: Option<String>,
+    pub username: Option<String>,
+    #[serde(default = "default_slack_api_url")]
+    pub api_url: String,
+    /// Reply in one thread per certificate rather than posting anew.
+    #[serde(default)]
+    pub thread: bool,
+}
+
+fn default_slack_api_url() -> String {
+    "https://slack.com/api".to_string()
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ShoutNotificationConfig {
+    pub url: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TeamsNotificationConfig {
+    pub webhook_url: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WebhookNotificationConfig {
+    pub url: String,
+    #[serde(default = "default_webhook_method")]
+    pub method: String,
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    /// Handlebars template for the request body.
+    pub body: Option<String>,
+}
+
+fn default_webhook_method() -> String {
+    "POST".to_string()
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TelegramNotificationConfig {
+    pub bot_token: String,
+    /// Numeric ids may be written unquoted.
+    #[serde(deserialize_with = "string_or_number")]
+    pub chat_id: String,
+    #[serde(default = "default_telegram_api_url")]
+    pub api_url: String,
+}
+
+fn default_telegram_api_url() -> String {
+    "https://api.telegram.org".to_string()
+}
+
+fn string_or_number<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum StringOrNumber {
+        String(String),
+        Number(serde_yaml::Number),
+    }
+
+    Ok(match StringOrNumber::deserialize(deserializer)? {
+        StringOrNumber::String(s) => s,
+        StringOrNumber::Number(n) => n.to_string(),
+    })
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "RawRoutedNotificationBackend")]
+pub struct RoutedNotificationBackend {
+    pub name: String,
+    #[serde(flatten)]
+    pub backend: NotificationBackend,
+    #[serde(default)]
+    pub route: NotificationRoute,
+}
+
+/// A routed notification backend as written, before unknown keys are
+/// rejected.
+#[derive(Deserialize)]
+struct RawRoutedNotificationBackend {
+    name: String,
+    #[serde(flatten)]
+    backend: NotificationBackend,
+    #[serde(default)]
+    route: NotificationRoute,
+    #[serde(flatten)]
+    unknown: UnknownFields,
+}
+
+impl TryFrom<RawRoutedNotificationBackend> for RoutedNotificationBackend {
+    type Error = String;
+
+    fn try_from(raw: RawRoutedNotificationBackend) -> Result<Self, Self::Error> {
+        deny_unknown_fields(&raw.unknown, &["name", "type", "properties", "route"])?;
+        Ok(RoutedNotificationBackend {
+            name: raw.name,
+            backend: raw.backend,
+            route: raw.route,
+        })
+    }
+}
+
+/// Which notifications a backend receives. Every set matcher must hold; an
+/// empty route receives everything. Certificates outside the route are left
+/// out of the backend's messages.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct NotificationRoute {
+    /// Urgencies to receive.
+    #[serde(default)]
+    pub urgency: Vec<NotificationUrgency>,
+    /// Storage backends the certificate was found on.
+    #[serde(default)]
+    pub backends: Vec<String>,
+    /// Regex matched against the certificate's paths.
+    pub path: Option<String>,
+    /// Labels the certificate must carry.
+    #[serde(default)]
+    pub labels: HashMap<String, String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct ScheduleConfig {
     #[serde(rename = "type")]
     pub schedule_type: String,
     pub properties: HashMap<String, serde_yaml::Value>,
 }
 
+impl ScheduleConfig {
+    /// `constant` schedules run every `interval` minutes; `cron` schedules run
+    /// on the times matched by `expression`.
+    pub fn schedule(&self) -> crate::Result<Schedule> {
+        match self.schedule_type.as_str() {
+            "constant" => {
+                let interval = self
+                    .properties
+                    .get("interval")
+                    .and_then(|v| v.as_u64())
+                    .ok_or_else(|| {
+                        crate::DoomsdayError::config(
+                            "Constant schedule requires an interval (minutes)",
+                        )
+                    })?;
+                Schedule::every_minutes(interval)
+            }
+            "cron" => {
+                let expression = self
+                    .properties
+                    .get("expression")
+                    .and_then(|v| v.as_str())
+                    .ok_or_else(|| {
+                        crate::DoomsdayError::config("Cron schedule requires an expression")
+                    })?;
+                Schedule::cron(expression)
+            }
+            other => Err(crate::DoomsdayError::config(format!(
+                "Unknown schedule type: {} (expected constant or cron)",
+                other
+            ))),
+        }
+    }
+}
+
 impl Config {
-    pub fn from_file<P: AsRef<Path>>(path: P) -> crate::Result<Self> {
+    /// Loads the config in `format`, or the format its extension implies.
+    pub fn from_file<P: AsRef<Path>>(path: P, format: Option<ConfigFormat>) -> crate::Result<Self> {
+        let path = path.as_ref();
         let content = fs::read_to_string(path)?;
-        let config: Config = serde_yaml::from_str(&content)?;
-        Ok(config)
+        let format = format.unwrap_or_else(|| ConfigFormat::from_path(path));
+
+        /// Just the includes, so a file that relies on them for required
+        /// sections is not rejected before they are merged, and whether
+        /// invalid backends are to be set aside rather than fail the file.
+        #[derive(Deserialize)]
+        struct Includes {
+            #[serde(default)]
+            include: Vec<String>,
+            #[serde(default)]
+            server: SkipInvalidBackends,
+        }
+
+        #[derive(Default, Deserialize)]
+        struct SkipInvalidBackends {
+            #[serde(default)]
+            skip_invalid_backends: bool,
+        }
+
+        let includes: Includes = format.deserialize(&content)?;
+        let overrides = env_overrides();
+        if includes.include.is_empty()
+            && overrides.is_empty()
+            && !includes.server.skip_invalid_backends
+        {
+            return format.deserialize(&content);
+        }
+        let mut layers = ConfigLayers::from_file(path, &content, format)?;
+        layers.apply_env(overrides)?;
+        layers.into_config()
+    }
+
+    /// The built-in defaults with any `DOOMSDAY__*` environment overrides,
+    /// for running without a config file.
+    pub fn from_env() -> crate::Result<Self> {
+        let mut layers = ConfigLayers::defaults()?;
+        layers.apply_env(env_overrides())?;
+        layers.into_config()
     }
 
     pub fn default() -> Self {
         Config {
+            include: vec![],
             backends: vec![],
             server: ServerConfig {
                 port: 8111,
@@ -76,32 +1348,162 @@ impl Config {
                 auth: AuthConfig {
                     auth_type: "none".to_string(),
                     properties: HashMap::new(),
+                    fallback: None,
                 },
+                skip_invalid_backends: false,
+                data_dir: None,
+                drain_timeout: None,
+                task_retention: None,
             },
             notifications: None,
+            pins: vec![],
+            webhooks: None,
+            events: None,
+            status: StatusThresholds::default(),
+            task_retry: RetryPolicy::default(),
+            task_timeouts: TaskTimeouts::default(),
+            scheduler: SchedulerConfig::default(),
+            settings: SettingsConfig::default(),
+            adaptive_refresh: None,
+            cache: CacheConfig::default(),
+            ignore: vec![],
+            labels: vec![],
+            hooks: vec![],
+            invalid_backends: vec![],
+        }
+    }
+
+    /// Where a persisted state file lives: `path` itself if absolute,
+    /// otherwise under `server.data_dir`, or nowhere without one.
+    pub fn state_path(&self, path: &str) -> Option<PathBuf> {
+        let path = Path::new(path);
+        if path.is_absolute() {
+            return Some(path.to_path_buf());
         }
+        self.server
+            .data_dir
+            .as_ref()
+            .map(|data_dir| Path::new(data_dir).join(path))
     }
 
     pub fn validate(&self) -> crate::Result<()> {
-        if self.backends.is_empty() {
+        if self.backends.is_empty() && self.invalid_backends.is_empty() {
             return Err(crate::DoomsdayError::config(
                 "At least one backend must be configured",
             ));
         }
 
-        for backend in &self.backends {
-            if backend.name.is_empty() {
-                return Err(crate::DoomsdayError::config("Backend name cannot be empty"));
+        if self.status.expiring_soon_days < 0 {
+            return Err(crate::DoomsdayError::config(
+                "status.expiring_soon_days must not be negative",
+            ));
+        }
+
+        if self
+            .status
+            .stale_after_hours
+            .is_some_and(|hours| hours <= 0)
+        {
+            return Err(crate::DoomsdayError::config(
+                "status.stale_after_hours must be greater than 0",
+            ));
+        }
+
+        if self.task_retry.max_attempts == 0 {
+            return Err(crate::DoomsdayError::config(
+                "task_retry.max_attempts must be at least 1",
+            ));
+        }
+
+        if self.scheduler.workers == 0 {
+            return Err(crate::DoomsdayError::config(
+                "scheduler.workers must be at least 1",
+            ));
+        }
+
+        if self.settings.max_concurrent_requests == 0 || self.settings.fetch_chunk_size == 0 {
+            return Err(crate::DoomsdayError::config(
+                "settings.max_concurrent_requests and settings.fetch_chunk_size must be at least 1",
+            ));
+        }
+
+        if self.settings.request_timeout == 0 {
+            return Err(crate::DoomsdayError::config(
+                "settings.request_timeout must be at least one second",
+            ));
+        }
+
+        if let Some(adaptive) = &self.adaptive_refresh {
+            if adaptive.within_days <= 0 || adaptive.interval == 0 {
+                return Err(crate::DoomsdayError::config(
+                    "adaptive_refresh.within_days and adaptive_refresh.interval must be positive",
+                ));
             }
+        }
 
-            match backend.backend_type.as_str() {
-                "vault" | "credhub" | "opsmgr" | "tlsclient" => {}
-                _ => {
-                    return Err(crate::DoomsdayError::config(format!(
-                        "Unknown backend type: {}",
-                        backend.backend_type
-                    )))
+        if self.task_timeouts.refresh_backend == 0
+            || self.task_timeouts.renew_auth_token == 0
+            || self.task_timeouts.run_hook == 0
+        {
+            return Err(crate::DoomsdayError::config(
+                "task_timeouts must be at least one second",
+            ));
+        }
+
+        if self.cache.persist {
+            if self.state_path(&self.settings.cache_path).is_none() {
+                return Err(crate::DoomsdayError::config(
+                    "cache.persist requires server.data_dir or an absolute settings.cache_path",
+                ));
+            }
+            if self.cache.snapshot_interval == 0 {
+                return Err(crate::DoomsdayError::config(
+                    "cache.snapshot_interval must be at least one second",
+                ));
+            }
+        }
+
+        if let Some(encryption) = &self.cache.encryption {
+            if !self.cache.persist {
+                return Err(crate::DoomsdayError::config(
+                    "cache.encryption requires cache.persist",
+                ));
+            }
+            encryption.validate()?;
+        }
+
+        if let Some(shared) = &self.cache.shared {
+            crate::redis::RedisUrl::parse(&shared.url)?;
+            if shared.sync_interval == 0 || shared.lease_ttl <= shared.sync_interval {
+                return Err(crate::DoomsdayError::config(
+                    "cache.shared.lease_ttl must be longer than a non-zero cache.shared.sync_interval",
+                ));
+            }
+        }
+
+        if self.task_retry.max_backoff < self.task_retry.initial_backoff {
+            return Err(crate::DoomsdayError::config(
+                "task_retry.max_backoff must not be less than task_retry.initial_backoff",
+            ));
+        }
+
+        let mut backend_names = HashSet::new();
+        for backend in &self.backends {
+            if !backend_names.insert(backend.name.as_str()) {
+                return Err(crate::DoomsdayError::config(format!(
+                    "Duplicate backend name: {}",
+                    backend.name
+                )));
+            }
+            if let Err(e) = backend.validate() {
+                if !self.server.skip_invalid_backends {
+                    return Err(e);
                 }
+                tracing::warn!(
+                    "Backend '{}' is invalid and will be skipped: {}",
+                    backend.name,
+                    e
+                );
             }
         }
 
@@ -115,8 +1517,251 @@ impl Config {
             }
         }
 
+        for pin in &self.pins {
+            if pin.fingerprints.is_empty() {
+                return Err(crate::DoomsdayError::config(format!(
+                    "Pin for {}:{} must list at least one fingerprint",
+                    pin.backend, pin.path
+                )));
+            }
+        }
+
+        for rule in &self.ignore {
+            rule.validate()?;
+        }
+
+        for rule in &self.labels {
+            rule.validate()?;
+        }
+
+        let mut hook_names = HashSet::new();
+        for hook in &self.hooks {
+            if !hook_names.insert(hook.name.as_str()) {
+                return Err(crate::DoomsdayError::config(format!(
+                    "Duplicate hook name: {}",
+                    hook.name
+                )));
+            }
+            hook.validate()?;
+        }
+
+        if let Some(notifications) = &self.notifications {
+            notifications.schedule.schedule()?;
+            validate_events(&notifications.events, "notifications")?;
+            if notifications.backend.is_none() && notifications.backends.is_empty() {
+                return Err(crate::DoomsdayError::config(
+                    "notifications requires a backend or backends",
+                ));
+            }
+            let mut names = HashSet::new();
+            for routed in &notifications.backends {
+                if !names.insert(routed.name.as_str()) {
+                    return Err(crate::DoomsdayError::config(format!(
+                        "Duplicate notification backend name: {}",
+                        routed.name
+                    )));
+                }
+                if let Some(path) = &routed.route.path {
+                    regex::Regex::new(path).map_err(|e| {
+                        crate::DoomsdayError::config(format!(
+                            "Invalid route path regex for notification backend {}: {}",
+                            routed.name, e
+                        ))
+                    })?;
+                }
+            }
+            for rule in &notifications.reminders {
+                rule.validate()?;
+            }
+            if notifications.retry.max_attempts == 0 {
+                return Err(crate::DoomsdayError::config(
+                    "notifications.retry.max_attempts must be at least 1",
+                ));
+            }
+            if notifications.retry.max_backoff < notifications.retry.initial_backoff {
+                return Err(crate::DoomsdayError::config(
+                    "notifications.retry.max_backoff must not be less than notifications.retry.initial_backoff",
+                ));
+            }
+            if notifications.digest.top == 0 {
+                return Err(crate::DoomsdayError::config(
+                    "notifications.digest.top must be at least 1",
+                ));
+            }
+            let mut within = HashSet::new();
+            for threshold in &notifications.thresholds {
+                if threshold.within < 0 {
+                    return Err(crate::DoomsdayError::config(
+                        "notifications.thresholds within must not be negative",
+                    ));
+                }
+                if !within.insert(threshold.within) {
+                    return Err(crate::DoomsdayError::config(format!(
+                        "notifications.thresholds lists {} days more than once",
+                        threshold.within
+                    )));
+                }
+            }
+
+            // Unnamed backends are known by their type
+            names.extend(notifications.backend.iter().map(|b| b.backend_type()));
+            names.extend(
+                notifications
+                    .thresholds
+                    .iter()
+                    .filter_map(|t| t.backend.as_ref())
+                    .map(|b| b.backend_type()),
+            );
+            let scopes = notifications
+                .quiet_hours
+                .iter()
+                .map(|quiet| quiet.validate().map(|_| &quiet.scope))
+                .chain(
+                    notifications
+                        .maintenance
+                        .iter()
+                        .map(|window| window.validate().map(|_| &window.scope)),
+                );
+            for scope in scopes {
+                if let Some(unknown) = scope?.backends.iter().find(|b| !names.contains(b.as_str()))
+                {
+                    return Err(crate::DoomsdayError::config(format!(
+                        "Quiet window refers to unknown notification backend: {}",
+                        unknown
+                    )));
+                }
+            }
+        }
+
+        if self.event_config().check_interval == 0 {
+            return Err(crate::DoomsdayError::config(
+                "events.check_interval must be greater than 0",
+            ));
+        }
+
+        if let Some(webhooks) = &self.webhooks {
+            for endpoint in &webhooks.endpoints {
+                url::Url::parse(&endpoint.url).map_err(|e| {
+                    crate::DoomsdayError::config(format!(
+                        "Invalid URL for webhook {}: {}",
+                        endpoint.name, e
+                    ))
+                })?;
+
+                validate_events(&endpoint.events, &format!("webhook {}", endpoint.name))?;
+            }
+        }
+
+        if let Some(fallback) = &self.server.auth.fallback {
+            if fallback.emergency_token.is_none() && fallback.users.is_empty() {
+                return Err(crate::DoomsdayError::config(
+                    "Auth fallback requires an emergency_token or at least one user",
+                ));
+            }
+        }
+
         Ok(())
     }
+
+    /// The `events` section, falling back to the thresholds configured under
+    /// `webhooks` before it existed.
+    /// The thresholds scheduled digests alert at. Without any configured,
+    /// expired certificates are critical and those within
+    /// `status.expiring_soon_days` are high.
+    pub fn alert_thresholds(&self) -> Vec<AlertThreshold> {
+        match &self.notifications {
+            Some(notifications) if !notifications.thresholds.is_empty() => {
+                notifications.thresholds.clone()
+            }
+            _ => vec![
+                AlertThreshold {
+                    within: 0,
+                    urgency: NotificationUrgency::Critical,
+                    backend: None,
+                },
+                AlertThreshold {
+                    within: self.status.expiring_soon_days,
+                    urgency: NotificationUrgency::High,
+                    backend: None,
+                },
+            ],
+        }
+    }
+
+    pub fn event_config(&self) -> EventConfig {
+        if let Some(events) = &self.events {
+            return events.clone();
+        }
+
+        let webhooks = self.webhooks.as_ref();
+        let defaults = EventConfig::default();
+        EventConfig {
+            thresholds: webhooks
+                .and_then(|w| w.thresholds.clone())
+                .unwrap_or(defaults.thresholds),
+            check_interval: webhooks
+                .and_then(|w| w.check_interval)
+                .unwrap_or(defaults.check_interval),
+        }
+    }
+}
+
+fn validate_events(events: &[String], owner: &str) -> crate::Result<()> {
+    for event in events {
+        if !CACHE_EVENTS.contains(&event.as_str()) {
+            return Err(crate::DoomsdayError::config(format!(
+                "Unknown event '{}' for {} (expected one of: {})",
+                event,
+                owner,
+                CACHE_EVENTS.join(", ")
+            )));
+        }
+    }
+    Ok(())
+}
+
+impl BackendConfig {
+    pub fn validate(&self) -> crate::Result<()> {
+        if self.name.is_empty() {
+            return Err(crate::DoomsdayError::config("Backend name cannot be empty"));
+        }
+
+        if self.max_concurrent_requests == Some(0) {
+            return Err(crate::DoomsdayError::config(format!(
+                "Backend {} max_concurrent_requests must be at least 1",
+                self.name
+            )));
+        }
+
+        self.refresh_schedule()?;
+        Ok(())
+    }
+
+    pub fn backend_type(&self) -> &'static str {
+        match self.kind {
+            BackendKind::Vault(_) => "vault",
+            BackendKind::CredHub(_) => "credhub",
+            BackendKind::OpsMgr(_) => "opsmgr",
+            BackendKind::TlsClient(_) => "tlsclient",
+        }
+    }
+
+    /// The periodic refresh schedule, if one is configured.
+    pub fn refresh_schedule(&self) -> crate::Result<Option<Schedule>> {
+        match (&self.refresh_cron, self.refresh_interval) {
+            (Some(_), Some(_)) => Err(crate::DoomsdayError::config(format!(
+                "Backend {} sets both refresh_interval and refresh_cron",
+                self.name
+            ))),
+            (Some(expression), None) => Schedule::cron(expression).map(Some),
+            (None, Some(minutes)) => Schedule::every_minutes(minutes).map(Some),
+            (None, None) => Ok(None),
+        }
+    }
+}
+
+fn unknown_target(name: &str) -> crate::DoomsdayError {
+    crate::DoomsdayError::invalid_input(format!("No target named '{}'", name))
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -132,6 +1777,34 @@ pub struct ClientTarget {
     pub skip_verify: bool,
     pub token: Option<String>,
     pub token_expires: Option<chrono::DateTime<chrono::Utc>>,
+    /// PEM bundle of CAs trusted for this server, alongside the system
+    /// roots, e.g. for an internal CA.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ca_cert: Option<PathBuf>,
+    /// PEM client certificate for servers behind mutual TLS. Holds the key
+    /// too unless `client_key` is set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_cert: Option<PathBuf>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_key: Option<PathBuf>,
+    /// Proxy URL for reaching this server. Without one, `HTTPS_PROXY`,
+    /// `HTTP_PROXY` and `NO_PROXY` are used.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proxy: Option<String>,
+    /// Seconds before a request to this server is abandoned.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<u64>,
+    /// Times a request is retried when the server can't be reached.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retries: Option<u32>,
+    /// Saved by `doomsday auth --remember`, to log in again once the token
+    /// expires.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub credentials: Option<AuthRequest>,
+    /// Where `token` and `credentials` are saved. Unset until the target
+    /// has any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub secret_store: Option<SecretStore>,
 }
 
 impl ClientConfig {
@@ -143,7 +1816,11 @@ impl ClientConfig {
 
         if config_path.exists() {
             let content = fs::read_to_string(&config_path)?;
-            let config: ClientConfig = serde_yaml::from_str(&content)?;
+            let mut config: ClientConfig = serde_yaml::from_str(&content)?;
+            config.check_permissions(&config_path)?;
+            for target in config.targets.values_mut() {
+                client_secrets::restore(target);
+            }
             Ok(config)
         } else {
             Ok(ClientConfig {
@@ -160,10 +1837,47 @@ impl ClientConfig {
         let doomsday_dir = config_dir.join("doomsday");
         fs::create_dir_all(&doomsday_dir)?;
 
+        // Tokens and credentials go to the OS keyring where there is one
+        let mut stored = self.clone();
+        for target in stored.targets.values_mut() {
+            client_secrets::stash(target);
+        }
+
         let config_path = doomsday_dir.join("config.yml");
-        let content = serde_yaml::to_string(self)?;
+        let content = serde_yaml::to_string(&stored)?;
         fs::write(&config_path, content)?;
 
+        // May hold tokens, and possibly credentials
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&config_path, fs::Permissions::from_mode(0o600))?;
+        }
+
+        Ok(())
+    }
+
+    /// Warns when the file holds tokens or credentials that other users can
+    /// read, e.g. after being copied or edited by hand.
+    fn check_permissions(&self, config_path: &Path) -> crate::Result<()> {
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(config_path)?.permissions().mode();
+            let holds_secrets = self
+                .targets
+                .values()
+                .any(|t| t.token.is_some() || t.credentials.is_some());
+            if holds_secrets && mode & 0o077 != 0 {
+                eprintln!(
+                    "⚠️  {} holds tokens but other users can read it; run 'chmod 600 {}'",
+                    config_path.display(),
+                    config_path.display()
+                );
+            }
+        }
+        #[cfg(not(unix))]
+        let _ = config_path;
         Ok(())
     }
 
@@ -172,4 +1886,173 @@ impl ClientConfig {
             .as_ref()
             .and_then(|name| self.targets.get(name))
     }
+
+    /// Makes an existing target the current one.
+    pub fn use_target(&mut self, name: &str) -> crate::Result<()> {
+        if !self.targets.contains_key(name) {
+            return Err(unknown_target(name));
+        }
+        self.current_target = Some(name.to_string());
+        Ok(())
+    }
+
+    /// Removes a target. The current target is only removed with `force`,
+    /// leaving no target selected.
+    pub fn delete_target(&mut self, name: &str, force: bool) -> crate::Result<()> {
+        if !self.targets.contains_key(name) {
+            return Err(unknown_target(name));
+        }
+        if self.current_target.as_deref() == Some(name) {
+            if !force {
+                return Err(crate::DoomsdayError::invalid_input(format!(
+                    "Target '{}' is the current target; use --force to delete it",
+                    name
+                )));
+            }
+            self.current_target = None;
+        }
+        if let Some(target) = self.targets.remove(name) {
+            client_secrets::forget(&target);
+        }
+        Ok(())
+    }
+
+    /// Renames a target, keeping its token and whether it is current.
+    pub fn rename_target(&mut self, name: &str, new_name: &str) -> crate::Result<()> {
+        if self.targets.contains_key(new_name) {
+            return Err(crate::DoomsdayError::invalid_input(format!(
+                "Target '{}' already exists",
+                new_name
+            )));
+        }
+        let mut target = self
+            .targets
+            .remove(name)
+            .ok_or_else(|| unknown_target(name))?;
+        // Saved to the keyring again under the new name
+        if target.secret_store == Some(SecretStore::Keyring) {
+            client_secrets::forget(&target);
+            target.secret_store = None;
+        }
+        target.name = new_name.to_string();
+        self.targets.insert(new_name.to_string(), target);
+        if self.current_target.as_deref() == Some(name) {
+            self.current_target = Some(new_name.to_string());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SERVER: &str = "server:\n  port: 8111\n  auth: {type: none, properties: {}}\n";
+
+    fn parse_error(content: &str) -> String {
+        ConfigFormat::Yaml
+            .deserialize::<Config>(content)
+            .unwrap_err()
+            .to_string()
+    }
+
+    #[test]
+    fn test_unknown_top_level_key_is_rejected() {
+        let error = parse_error(&format!("{}notifcations: {{}}\n", SERVER));
+        assert!(error.contains("unknown field `notifcations`"), "{}", error);
+        assert!(error.contains("line 4 column 1"), "{}", error);
+    }
+
+    #[test]
+    fn test_unknown_backend_key_is_rejected() {
+        let error = parse_error(&format!(
+            r#"backends:
+  - type: vault
+    name: production-vault
+    refresh_interal: 30
+    properties: {{url: "http://vault:8200", token: x}}
+{}"#,
+            SERVER
+        ));
+        assert!(
+            error.contains("unknown field `refresh_interal`"),
+            "{}",
+            error
+        );
+        // Located at the backend's entry, as the key is found after parsing
+        assert!(error.contains("backends: "), "{}", error);
+        assert!(error.contains("line 2 column 3"), "{}", error);
+    }
+
+    #[test]
+    fn test_unknown_routed_notification_key_is_rejected() {
+        let error = parse_error(&format!(
+            r#"backends: []
+{}notifications:
+  doomsday_url: https://doomsday.example.com
+  backends:
+    - name: oncall
+      type: webhook
+      properties: {{url: "https://events.example.com"}}
+      rout: {{urgency: [critical]}}
+  schedule: {{type: constant, properties: {{interval: 60}}}}
+"#,
+            SERVER
+        ));
+        assert!(error.contains("unknown field `rout`"), "{}", error);
+        assert!(error.contains("notifications.backends: "), "{}", error);
+        assert!(error.contains("line 8 column 5"), "{}", error);
+    }
+
+    #[test]
+    fn test_skip_invalid_backends_keeps_valid_ones() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("ddayconfig.yml");
+        fs::write(
+            &path,
+            r#"backends:
+  - type: tlsclient
+    name: web
+    properties:
+      targets: [{host: example.com, port: 443}]
+  - type: vault
+    name: broken
+    refresh_interal: 30
+    properties: {url: "http://vault:8200", token: x}
+server:
+  port: 8111
+  skip_invalid_backends: true
+  auth: {type: none, properties: {}}
+"#,
+        )
+        .unwrap();
+
+        let config = Config::from_file(&path, None).unwrap();
+        assert_eq!(config.backends.len(), 1);
+        assert_eq!(config.backends[0].name, "web");
+        assert_eq!(config.invalid_backends.len(), 1);
+        let invalid = &config.invalid_backends[0];
+        assert_eq!(invalid.name, "broken");
+        assert_eq!(invalid.backend_type, "vault");
+        assert!(
+            invalid.error.starts_with("backends[1]"),
+            "{}",
+            invalid.error
+        );
+        assert!(
+            invalid.error.contains("refresh_interal"),
+            "{}",
+            invalid.error
+        );
+        config.validate().unwrap();
+
+        // Without the flag the whole file is rejected
+        let strict = fs::read_to_string(&path).unwrap().replace(
+            "skip_invalid_backends: true",
+            "skip_invalid_backends: false",
+        );
+        fs::write(&path, strict).unwrap();
+        let error = Config::from_file(&path, None).unwrap_err().to_string();
+        assert!(error.contains("refresh_interal"), "{}", error);
+    }
 }
@@ -0,0 +1,469 @@
+use crate::config::{BackendConfig, Config, ConfigFormat, InvalidBackend};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Environment variables starting with this override config settings.
+pub const ENV_PREFIX: &str = "DOOMSDAY__";
+
+/// The file or environment variable each value came from, keyed like
+/// `server.port` or `backends[2]`.
+type Origins = HashMap<String, String>;
+
+/// The server config assembled from its layers before it is deserialized:
+/// the main file, the files it includes, then `DOOMSDAY__*` environment
+/// variables. Remembers where each value came from so errors can say.
+pub struct ConfigLayers {
+    values: Map<String, Value>,
+    origins: Origins,
+}
+
+impl ConfigLayers {
+    /// Starts from the built-in defaults, for running without a config file.
+    pub fn defaults() -> crate::Result<Self> {
+        let values = match serde_json::to_value(Config::default())? {
+            Value::Object(values) => values,
+            _ => Map::new(),
+        };
+        Ok(ConfigLayers {
+            values,
+            origins: Origins::new(),
+        })
+    }
+
+    /// Loads the main file and merges every file its `include` list names,
+    /// e.g. backend definitions kept by other teams.
+    ///
+    /// Includes are read in the order listed; the matches of a glob such as
+    /// `conf.d/*.yml` in sorted order. Lists are appended, mappings are
+    /// merged key by key, and a value set by two files is an error unless
+    /// both set it the same. Included files may not include others. Each
+    /// file's format follows its extension.
+    pub fn from_file(path: &Path, content: &str, format: ConfigFormat) -> crate::Result<Self> {
+        let main = parse(path, content, format)?;
+        let patterns: Vec<String> = match main.get("include") {
+            Some(include) => serde_json::from_value(include.clone()).map_err(|e| {
+                crate::DoomsdayError::config(format!("{}: include: {}", path.display(), e))
+            })?,
+            None => Vec::new(),
+        };
+
+        let mut layers = ConfigLayers {
+            values: Map::new(),
+            origins: Origins::new(),
+        };
+        layers.merge(main, path)?;
+
+        let base = path.parent().unwrap_or(Path::new(""));
+        for pattern in &patterns {
+            for file in expand(base, pattern)? {
+                let content = fs::read_to_string(&file).map_err(|e| {
+                    crate::DoomsdayError::config(format!(
+                        "Failed to read include {}: {}",
+                        file.display(),
+                        e
+                    ))
+                })?;
+                let included = parse(&file, &content, ConfigFormat::from_path(&file))?;
+                if included.contains_key("include") {
+                    return Err(crate::DoomsdayError::config(format!(
+                        "{}: included files cannot include others",
+                        file.display()
+                    )));
+                }
+                tracing::debug!("Merging config include {}", file.display());
+                layers.merge(included, &file)?;
+            }
+        }
+        Ok(layers)
+    }
+
+    /// Applies `DOOMSDAY__SECTION__KEY=value` overrides. Sections are
+    /// separated by double underscores and lowercased, so
+    /// `DOOMSDAY__SERVER__DATA_DIR` sets `server.data_dir`; numbers index
+    /// lists, as in `DOOMSDAY__BACKENDS__0__NAME`. Overrides replace
+    /// whatever the files set.
+    pub fn apply_env<I>(&mut self, vars: I) -> crate::Result<()>
+    where
+        I: IntoIterator<Item = (String, String)>,
+    {
+        for (name, raw) in vars {
+            let Some(key) = name.strip_prefix(ENV_PREFIX) else {
+                continue;
+            };
+            let segments: Vec<String> = key.split("__").map(str::to_lowercase).collect();
+            if segments.iter().any(String::is_empty) {
+                return Err(crate::DoomsdayError::config(format!(
+                    "{}: empty section in variable name",
+                    name
+                )));
+            }
+
+            let value = parse_env_value(&raw)
+                .map_err(|e| crate::DoomsdayError::config(format!("{}: {}", name, e)))?;
+            let mut target = self
+                .values
+                .entry(segments[0].clone())
+                .or_insert(Value::Null);
+            let mut path = segments[0].clone();
+            self.origins.entry(path.clone()).or_insert(name.clone());
+            for segment in &segments[1..] {
+                let (next, is_item) = child(target, segment, &path)
+                    .map_err(|e| crate::DoomsdayError::config(format!("{}: {}", name, e)))?;
+                target = next;
+                path = if is_item {
+                    format!("{}[{}]", path, segment)
+                } else {
+                    format!("{}.{}", path, segment)
+                };
+                self.origins.entry(path.clone()).or_insert(name.clone());
+            }
+            *target = value;
+            self.origins.insert(path, name.clone());
+        }
+        Ok(())
+    }
+
+    pub fn into_config(mut self) -> crate::Result<Config> {
+        let invalid_backends = self.take_invalid_backends();
+        let origins = self.origins;
+        let mut config: Config = serde_path_to_error::deserialize(Value::Object(self.values))
+            .map_err(|e| {
+                crate::DoomsdayError::config(match origin(&origins, e.path()) {
+                    Some(source) => format!("{} (from {}): {}", e.path(), source, e.inner()),
+                    None => format!("{}: {}", e.path(), e.inner()),
+                })
+            })?;
+        config.invalid_backends = invalid_backends;
+        Ok(config)
+    }
+
+    /// With `server.skip_invalid_backends` set, takes out the backends that
+    /// fail to parse, so the rest of the config still loads, and returns
+    /// them to be reported as failed.
+    fn take_invalid_backends(&mut self) -> Vec<InvalidBackend> {
+        let skip = self
+            .values
+            .get("server")
+            .and_then(|server| server.get("skip_invalid_backends"))
+            .and_then(Value::as_bool)
+            .unwrap_or(false);
+        let Some(Value::Array(backends)) = self.values.get_mut("backends").filter(|_| skip) else {
+            return Vec::new();
+        };
+
+        let mut invalid = Vec::new();
+        let mut index = 0;
+        backends.retain(|backend| {
+            let place = format!("backends[{}]", index);
+            index += 1;
+            let Err(e) = serde_path_to_error::deserialize::<_, BackendConfig>(backend.clone())
+            else {
+                return true;
+            };
+
+            let path = match e.path().to_string().as_str() {
+                "." => place.clone(),
+                path => format!("{}.{}", place, path),
+            };
+            let error = match self.origins.get(&place) {
+                Some(source) => format!("{} (from {}): {}", path, source, e.inner()),
+                None => format!("{}: {}", path, e.inner()),
+            };
+            let field = |key: &str| backend.get(key).and_then(Value::as_str).map(str::to_string);
+            invalid.push(InvalidBackend {
+                name: field("name").unwrap_or(place),
+                backend_type: field("type").unwrap_or_else(|| "unknown".to_string()),
+                error,
+            });
+            false
+        });
+        invalid
+    }
+
+    fn merge(&mut self, from: Map<String, Value>, source: &Path) -> crate::Result<()> {
+        let source = source.display().to_string();
+        merge(&mut self.values, from, "", &source, &mut self.origins)
+    }
+}
+
+/// Config overrides set in the environment, sorted by name with list
+/// indexes in numeric order, so `__10__` comes after `__9__`.
+pub fn env_overrides() -> Vec<(String, String)> {
+    let mut vars: Vec<_> = std::env::vars()
+        .filter(|(name, _)| name.starts_with(ENV_PREFIX))
+        .collect();
+    vars.sort_by_cached_key(|(name, _)| {
+        name.split("__")
+            .map(|segment| (segment.parse::<u64>().ok(), segment.to_string()))
+            .collect::<Vec<_>>()
+    });
+    vars
+}
+
+/// An override's value: numbers, booleans, `null` and `[...]` or `{...}`
+/// are parsed as YAML, and a quoted value is unquoted. Anything else is
+/// taken as is, so e.g. `0 9 * * *` or a password containing `#` survive.
+fn parse_env_value(raw: &str) -> Result<Value, serde_yaml::Error> {
+    let trimmed = raw.trim();
+    if trimmed.starts_with(['[', '{', '"', '\'']) {
+        return serde_yaml::from_str(trimmed);
+    }
+    match serde_yaml::from_str::<Value>(trimmed) {
+        Ok(value @ (Value::Number(_) | Value::Bool(_) | Value::Null)) => Ok(value),
+        _ => Ok(Value::String(raw.to_string())),
+    }
+}
+
+/// The entry `segment` names under `target`, found at `path`, created if
+/// missing: an item when `target` is a list, or unset and `segment` is a
+/// number, otherwise a key. Also says which. Fails if `target` holds a
+/// plain value, or for an index past the end of a list, which may only
+/// grow by one item at a time.
+fn child<'a>(
+    target: &'a mut Value,
+    segment: &str,
+    path: &str,
+) -> Result<(&'a mut Value, bool), String> {
+    let index = segment.parse::<usize>().ok();
+    if target.is_null() {
+        *target = match index {
+            Some(_) => Value::Array(Vec::new()),
+            None => Value::Object(Map::new()),
+        };
+    }
+    match target {
+        Value::Object(map) => Ok((map.entry(segment.to_string()).or_insert(Value::Null), false)),
+        Value::Array(items) => match index {
+            Some(index) if index < items.len() => Ok((&mut items[index], true)),
+            Some(index) if index == items.len() => {
+                items.push(Value::Null);
+                Ok((&mut items[index], true))
+            }
+            Some(index) => Err(format!(
+                "{}[{}] is past the end of {}, whose next item is {}[{}]",
+                path,
+                index,
+                path,
+                path,
+                items.len()
+            )),
+            None => Err(format!("{} is a list, not a section", path)),
+        },
+        _ => Err(format!("{} is not a section or list", path)),
+    }
+}
+
+/// One file's config as a mapping. An empty file is an empty mapping.
+fn parse(path: &Path, content: &str, format: ConfigFormat) -> crate::Result<Map<String, Value>> {
+    match format.deserialize(content)? {
+        Value::Object(map) => Ok(map),
+        Value::Null => Ok(Map::new()),
+        _ => Err(crate::DoomsdayError::config(format!(
+            "{}: config must be a mapping",
+            path.display()
+        ))),
+    }
+}
+
+/// The files an include names, relative to the main config's directory.
+/// A glob may match nothing; a plain path must exist.
+fn expand(base: &Path, pattern: &str) -> crate::Result<Vec<PathBuf>> {
+    let path = base.join(pattern);
+    if !pattern.contains(['*', '?', '[']) {
+        return Ok(vec![path]);
+    }
+
+    let glob = glob::glob(&path.to_string_lossy()).map_err(|e| {
+        crate::DoomsdayError::config(format!("Invalid include pattern {}: {}", pattern, e))
+    })?;
+    let mut files = glob
+        .filter_map(Result::ok)
+        .filter(|file| file.is_file())
+        .collect::<Vec<_>>();
+    files.sort();
+    Ok(files)
+}
+
+fn merge(
+    into: &mut Map<String, Value>,
+    from: Map<String, Value>,
+    prefix: &str,
+    source: &str,
+    origins: &mut Origins,
+) -> crate::Result<()> {
+    for (key, value) in from {
+        let path = if prefix.is_empty() {
+            key.clone()
+        } else {
+            format!("{}.{}", prefix, key)
+        };
+        match (into.get_mut(&key), value) {
+            (None, value) => {
+                record(&path, &value, source, origins);
+                into.insert(key, value);
+            }
+            (Some(Value::Object(existing)), Value::Object(value)) => {
+                merge(existing, value, &path, source, origins)?;
+            }
+            (Some(Value::Array(existing)), Value::Array(values)) => {
+                for value in values {
+                    origins.insert(format!("{}[{}]", path, existing.len()), source.to_string());
+                    existing.push(value);
+                }
+            }
+            (Some(existing), value) if *existing == value => {}
+            (Some(_), _) => {
+                let first = origins
+                    .get(&path)
+                    .cloned()
+                    .unwrap_or_else(|| "an earlier file".to_string());
+                return Err(crate::DoomsdayError::config(format!(
+                    "{} is set in both {} and {}",
+                    path, first, source
+                )));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Notes where a newly merged value and everything in it came from.
+fn record(path: &str, value: &Value, source: &str, origins: &mut Origins) {
+    origins.insert(path.to_string(), source.to_string());
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map {
+                record(&format!("{}.{}", path, key), value, source, origins);
+            }
+        }
+        Value::Array(values) => {
+            for index in 0..values.len() {
+                origins.insert(format!("{}[{}]", path, index), source.to_string());
+            }
+        }
+        _ => {}
+    }
+}
+
+/// The file or variable that set the innermost recorded value on an error's
+/// path.
+fn origin<'a>(origins: &'a Origins, path: &serde_path_to_error::Path) -> Option<&'a String> {
+    let mut key = String::new();
+    let mut found = None;
+    for segment in path.iter() {
+        match segment {
+            serde_path_to_error::Segment::Map { key: name } if key.is_empty() => key = name.clone(),
+            serde_path_to_error::Segment::Map { key: name } => key = format!("{}.{}", key, name),
+            serde_path_to_error::Segment::Seq { index } => key = format!("{}[{}]", key, index),
+            _ => continue,
+        }
+        found = origins.get(&key).or(found);
+    }
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_includes_merge_in_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let backend = |name: &str| {
+            format!(
+                "backends:\n  - type: vault\n    name: {}\n    properties: {{url: \"http://vault:8200\", token: x}}\n",
+                name
+            )
+        };
+        fs::create_dir(dir.path().join("conf.d")).unwrap();
+        fs::write(dir.path().join("conf.d/b.yml"), backend("payments")).unwrap();
+        fs::write(dir.path().join("conf.d/a.yml"), backend("web")).unwrap();
+        fs::write(
+            dir.path().join("extra.toml"),
+            "[server]\nport = 8111\nauth = { type = \"none\", properties = {} }\n",
+        )
+        .unwrap();
+        let main = dir.path().join("main.yml");
+        fs::write(
+            &main,
+            format!("include: [conf.d/*.yml, extra.toml]\n{}", backend("core")),
+        )
+        .unwrap();
+
+        let config = Config::from_file(&main, None).unwrap();
+        let names: Vec<&str> = config.backends.iter().map(|b| b.name.as_str()).collect();
+        assert_eq!(names, ["core", "web", "payments"]);
+        assert_eq!(config.server.port, 8111);
+
+        fs::write(dir.path().join("conf.d/c.yml"), "server:\n  port: 9000\n").unwrap();
+        let error = Config::from_file(&main, None).unwrap_err().to_string();
+        assert!(error.contains("server.port is set in both"), "{}", error);
+    }
+
+    #[test]
+    fn test_env_overrides() {
+        let vars = [
+            ("DOOMSDAY__SERVER__PORT", "9100"),
+            ("DOOMSDAY__SERVER__AUTH__TYPE", "none"),
+            ("DOOMSDAY__BACKENDS__0__TYPE", "tlsclient"),
+            ("DOOMSDAY__BACKENDS__0__NAME", "'443'"),
+            (
+                "DOOMSDAY__BACKENDS__0__PROPERTIES__TARGETS",
+                "[{host: example.com}]",
+            ),
+            ("PATH", "/usr/bin"),
+        ];
+        let mut layers = ConfigLayers::defaults().unwrap();
+        layers
+            .apply_env(vars.iter().map(|(k, v)| (k.to_string(), v.to_string())))
+            .unwrap();
+        let config = layers.into_config().unwrap();
+        assert_eq!(config.server.port, 9100);
+        assert_eq!(config.backends.len(), 1);
+        assert_eq!(config.backends[0].name, "443");
+
+        let mut layers = ConfigLayers::defaults().unwrap();
+        layers
+            .apply_env([("DOOMSDAY__SERVER__PROT".to_string(), "1".to_string())])
+            .unwrap();
+        let error = layers.into_config().unwrap_err().to_string();
+        assert!(error.contains("from DOOMSDAY__SERVER__PROT"), "{}", error);
+    }
+
+    #[test]
+    fn test_env_list_index_must_not_skip_items() {
+        let apply = |vars: &[(&str, &str)]| {
+            let mut layers = ConfigLayers::defaults().unwrap();
+            layers.apply_env(vars.iter().map(|(k, v)| (k.to_string(), v.to_string())))
+        };
+
+        // Would otherwise allocate billions of items
+        let error = apply(&[("DOOMSDAY__BACKENDS__4000000000__NAME", "web")])
+            .unwrap_err()
+            .to_string();
+        assert!(
+            error.contains("DOOMSDAY__BACKENDS__4000000000__NAME"),
+            "{}",
+            error
+        );
+        assert!(error.contains("next item is backends[0]"), "{}", error);
+
+        let error = apply(&[
+            ("DOOMSDAY__BACKENDS__0__NAME", "web"),
+            ("DOOMSDAY__BACKENDS__2__NAME", "vault"),
+        ])
+        .unwrap_err()
+        .to_string();
+        assert!(error.contains("DOOMSDAY__BACKENDS__2__NAME: "), "{}", error);
+        assert!(error.contains("next item is backends[1]"), "{}", error);
+
+        // Appending one item at a time is fine
+        apply(&[
+            ("DOOMSDAY__BACKENDS__0__NAME", "web"),
+            ("DOOMSDAY__BACKENDS__1__NAME", "vault"),
+        ])
+        .unwrap();
+    }
+}
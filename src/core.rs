@@ -1,86 +1,953 @@
+use crate::alerts::{AlertAcks, AlertLog};
 use crate::backends::create_accessor;
-use crate::cache::{Cache, CacheDiff};
-use crate::config::{BackendConfig, Config};
-use crate::scheduler::Scheduler;
+use crate::cache::{
+    Cache, CacheChanges, CacheDiff, CacheDiffPreview, CacheEvent, ChangeLog, CrossedThresholds,
+};
+use crate::cache_store::CacheStore;
+use crate::config::{BackendConfig, Config, HookAction, SettingsConfig};
+use crate::history::{RotationEvent, RotationHistory};
+use crate::hooks::{self, HookLog, HookPayload, HookRun, HookRunner};
+use crate::ignore::IgnoreList;
+use crate::notification_history::{
+    DeadLetter, DeadLetters, DeliveryStatus, NotificationDelivery, NotificationHistory,
+};
+use crate::notifications::NotificationService;
+use crate::pinning::{self, PinViolation};
+use crate::quiet::HeldNotifications;
+use crate::schedule::Schedule;
+use crate::scheduler::{Scheduler, TaskExecutor};
+use crate::shared_cache::SharedCache;
 use crate::storage::Accessor;
-use crate::types::{CacheObject, PathObject, PopulateStats, Task};
+use crate::task_store::TaskStore;
+use crate::types::{
+    BackendRefreshStats, BackendStatus, CacheItem, CacheObject, CertificateData, ListFailure,
+    NotificationSimulation, NotificationSimulationRequest, NotificationTestResult, PathObject,
+    PopulateStats, RefreshSummary, Task,
+};
+use crate::webhooks::{self, WebhookDispatcher};
+use async_trait::async_trait;
 use chrono::Utc;
-use sha1::{Digest, Sha1};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::time::Instant;
-use tokio::sync::RwLock;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::{RwLock, Semaphore};
+use tokio::task::JoinHandle;
+
+type AccessorMap = HashMap<String, Arc<dyn Accessor>>;
+
+/// Finished scheduler tasks are kept for a week unless configured otherwise.
+const DEFAULT_TASK_RETENTION_HOURS: u64 = 7 * 24;
+/// How often expired tasks are pruned from the history.
+const TASK_CLEANUP_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60 * 60);
+/// How long the event notifier collects cache events before sending, so a
+/// refresh produces one message rather than one per certificate.
+const EVENT_NOTIFICATION_BATCH_WINDOW: std::time::Duration = std::time::Duration::from_secs(5);
+/// How often a due notification digest checks whether refreshes have
+/// finished, so it does not report on a half-populated cache.
+const POPULATE_WAIT_INTERVAL: std::time::Duration = std::time::Duration::from_secs(15);
+
+/// How often held notifications are checked for a quiet window having ended.
+const HELD_RELEASE_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// Bounds on the certificate fetches of one scan: a semaphore per configured
+/// backend, and how many paths are queued at once.
+struct FetchLimits {
+    semaphores: HashMap<String, Arc<Semaphore>>,
+    default: usize,
+    chunk_size: usize,
+}
+
+impl FetchLimits {
+    fn new(config: &Config) -> Self {
+        let default = config.settings.max_concurrent_requests;
+        FetchLimits {
+            semaphores: config
+                .backends
+                .iter()
+                .map(|b| {
+                    let limit = b.max_concurrent_requests.unwrap_or(default);
+                    (b.name.clone(), Arc::new(Semaphore::new(limit)))
+                })
+                .collect(),
+            default,
+            chunk_size: config.settings.fetch_chunk_size,
+        }
+    }
+
+    fn for_backend(&self, backend_name: &str) -> Arc<Semaphore> {
+        self.semaphores
+            .get(backend_name)
+            .cloned()
+            .unwrap_or_else(|| Arc::new(Semaphore::new(self.default)))
+    }
+}
+
+/// Counts a refresh as in flight until dropped.
+struct RefreshGuard(Arc<AtomicUsize>);
+
+impl RefreshGuard {
+    fn new(counter: &Arc<AtomicUsize>) -> Self {
+        counter.fetch_add(1, Ordering::SeqCst);
+        RefreshGuard(counter.clone())
+    }
+}
+
+impl Drop for RefreshGuard {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Result of scanning one or more backends, before it is applied to the cache.
+struct BackendScan {
+    diff: CacheDiff,
+    num_certs: usize,
+    num_paths: usize,
+    backends: Vec<BackendRefreshStats>,
+}
+
+/// Adds a fetched certificate, and any chain delivered with it, to the scan
+/// results under `path`. Returns how many certificates were new to the scan.
+fn collect_certificate(
+    objects: &mut HashMap<String, CacheObject>,
+    mut cert_data: CertificateData,
+    path: PathObject,
+) -> usize {
+    let chain = std::mem::take(&mut cert_data.chain);
+    let mut added = 0;
+
+    for cert in std::iter::once(cert_data).chain(chain) {
+        match objects.get_mut(&cert.fingerprint_sha1) {
+            Some(existing) => {
+                if !existing.paths.contains(&path) {
+                    existing.paths.push(path.clone());
+                }
+            }
+            None => {
+                let sha1 = cert.fingerprint_sha1.clone();
+                objects.insert(sha1, CacheObject::from_certificate(cert, path.clone()));
+                added += 1;
+            }
+        }
+    }
+
+    added
+}
 
 #[derive(Clone)]
 pub struct Core {
     config: Arc<RwLock<Config>>,
     cache: Cache,
-    accessors: Arc<RwLock<HashMap<String, Arc<dyn Accessor>>>>,
+    accessors: Arc<RwLock<AccessorMap>>,
+    failed_backends: Arc<RwLock<HashMap<String, String>>>,
     scheduler: Scheduler,
+    notifier: Arc<RwLock<Option<Arc<NotificationService>>>>,
+    pin_violations: Arc<RwLock<HashSet<PinViolation>>>,
+    last_refresh: Arc<RwLock<Option<RefreshSummary>>>,
+    periodic_tasks: Arc<RwLock<Vec<JoinHandle<()>>>>,
+    webhook_dispatcher: Arc<RwLock<Option<JoinHandle<()>>>>,
+    cache_store: Option<Arc<CacheStore>>,
+    shared_cache: Option<Arc<SharedCache>>,
+    history: RotationHistory,
+    changes: Arc<RwLock<ChangeLog>>,
+    refreshes_in_flight: Arc<AtomicUsize>,
+    alert_log: AlertLog,
+    held_notifications: HeldNotifications,
+    notification_history: NotificationHistory,
+    dead_letters: DeadLetters,
+    hooks: HookRunner,
+}
+
+#[async_trait]
+impl TaskExecutor for Core {
+    async fn execute(&self, task: &Task) -> crate::Result<Option<PopulateStats>> {
+        match task {
+            Task::RefreshBackend { backend_name } => {
+                if self.shared_cache.as_ref().is_some_and(|s| !s.is_leader()) {
+                    tracing::debug!(
+                        "Skipping refresh of {}, another replica holds the refresh lease",
+                        backend_name
+                    );
+                    return Ok(None);
+                }
+                tracing::info!("Executing scheduled refresh for backend: {}", backend_name);
+                self.refresh_backend(backend_name).await.map(Some)
+            }
+            Task::RenewAuthToken { backend_name } => {
+                tracing::info!("Renewing auth token for backend: {}", backend_name);
+                self.renew_backend_auth(backend_name).await?;
+                Ok(None)
+            }
+            Task::RunHook { hook_name, sha1 } => {
+                self.run_hook_task(hook_name, sha1).await?;
+                Ok(None)
+            }
+        }
+    }
 }
 
 impl Core {
     pub async fn new(config: Config) -> crate::Result<Self> {
+        let core = Self::build(config).await?;
+
+        let task_store_path = {
+            let config = core.config.read().await;
+            config.state_path(&config.settings.task_store_path)
+        };
+        if let Some(path) = task_store_path {
+            core.scheduler.restore(TaskStore::new(path))?;
+        }
+
+        core.spawn_webhook_dispatcher().await;
+        core.spawn_event_notifier();
+
+        tracing::info!("Scheduling initial refresh tasks...");
+        core.schedule_refresh_tasks().await;
+        core.schedule_periodic_tasks().await;
+
+        tracing::info!("Core system initialization completed");
+        Ok(core)
+    }
+
+    /// Loads the persisted state and sets up backends and notifications,
+    /// without queueing or scheduling any work.
+    async fn build(config: Config) -> crate::Result<Self> {
         tracing::info!(
             "Initializing Core system with {} backends",
             config.backends.len()
         );
 
-        let cache = Cache::new();
+        let ignore_list = match &config.server.data_dir {
+            Some(data_dir) => IgnoreList::load(
+                Path::new(data_dir).join("ignored.json"),
+                Path::new(data_dir).join("ignore_rules.json"),
+            )?,
+            None => {
+                tracing::warn!("No data_dir configured, ignored certificates will not persist");
+                IgnoreList::new()
+            }
+        };
+
+        let history = match &config.server.data_dir {
+            Some(data_dir) => RotationHistory::load(Path::new(data_dir).join("history.json"))?,
+            None => {
+                tracing::warn!("No data_dir configured, rotation history will not persist");
+                RotationHistory::new()
+            }
+        };
+
+        let alert_log = match &config.server.data_dir {
+            Some(data_dir) => AlertLog::load(Path::new(data_dir).join("alerts.json"))?,
+            None => AlertLog::new(),
+        };
+        let acks = match &config.server.data_dir {
+            Some(data_dir) => AlertAcks::load(Path::new(data_dir).join("acks.json"))?,
+            None => AlertAcks::new(),
+        };
+        let held_notifications = match &config.server.data_dir {
+            Some(data_dir) => HeldNotifications::load(Path::new(data_dir).join("held.json"))?,
+            None => HeldNotifications::new(),
+        };
+        let notification_history = match &config.server.data_dir {
+            Some(data_dir) => {
+                NotificationHistory::load(Path::new(data_dir).join("notifications.json"))?
+            }
+            None => NotificationHistory::new(),
+        };
+        let dead_letters = match &config.server.data_dir {
+            Some(data_dir) => DeadLetters::load(Path::new(data_dir).join("dead_letters.json"))?,
+            None => DeadLetters::new(),
+        };
+
+        let crossed_thresholds = match &config.server.data_dir {
+            Some(data_dir) => CrossedThresholds::load(Path::new(data_dir).join("thresholds.json"))?,
+            None => CrossedThresholds::new(),
+        };
+
+        let hook_log = match &config.server.data_dir {
+            Some(data_dir) => HookLog::load(Path::new(data_dir).join("hooks.json"))?,
+            None => HookLog::new(),
+        };
+
+        ignore_list.set_config_rules(&config.ignore)?;
+
+        let cache = Cache::with_ignore_list(ignore_list)
+            .with_alert_acks(acks)
+            .with_crossed_thresholds(crossed_thresholds);
+        cache.label_rules().set_rules(&config.labels)?;
+        cache.set_status_thresholds(config.status);
+        cache.set_event_thresholds(&config.event_config().thresholds);
         tracing::debug!("Cache initialized");
 
-        let scheduler = Scheduler::default();
+        let cache_store = match config.state_path(&config.settings.cache_path) {
+            Some(path) if config.cache.persist => {
+                // A missing key must not fall back to writing plaintext
+                let store = match &config.cache.encryption {
+                    Some(encryption) => CacheStore::encrypted(path, &encryption.load_key()?),
+                    None => CacheStore::new(path),
+                };
+                // A bad snapshot only costs a cold start, so don't refuse to run
+                match store.load() {
+                    Ok(objects) => cache.restore(objects),
+                    Err(e) => tracing::warn!("Ignoring unreadable cache snapshot: {}", e),
+                }
+                Some(Arc::new(store))
+            }
+            _ => None,
+        };
+
+        let shared_cache = match &config.cache.shared {
+            Some(shared) => {
+                let shared = SharedCache::new(shared.clone())?;
+                // Settle leadership before the initial refreshes are scheduled
+                shared.sync(&cache).await;
+                Some(Arc::new(shared))
+            }
+            None => None,
+        };
+
+        let scheduler = Scheduler::new(config.scheduler.workers);
+        scheduler.set_retry_policy(config.task_retry);
+        scheduler.set_task_timeouts(config.task_timeouts);
         tracing::debug!("Scheduler initialized");
 
+        let (accessors, failed_backends) = Self::build_accessors(&config)?;
+        let notifier = Self::build_notifier(
+            &config,
+            &alert_log,
+            &held_notifications,
+            &notification_history,
+            &dead_letters,
+        );
+
+        let core = Core {
+            config: Arc::new(RwLock::new(config)),
+            cache,
+            accessors: Arc::new(RwLock::new(accessors)),
+            failed_backends: Arc::new(RwLock::new(failed_backends)),
+            scheduler,
+            notifier: Arc::new(RwLock::new(notifier)),
+            pin_violations: Arc::new(RwLock::new(HashSet::new())),
+            last_refresh: Arc::new(RwLock::new(None)),
+            periodic_tasks: Arc::new(RwLock::new(Vec::new())),
+            webhook_dispatcher: Arc::new(RwLock::new(None)),
+            cache_store,
+            shared_cache,
+            history,
+            changes: Arc::new(RwLock::new(ChangeLog::new())),
+            refreshes_in_flight: Arc::new(AtomicUsize::new(0)),
+            alert_log,
+            held_notifications,
+            notification_history,
+            dead_letters,
+            hooks: HookRunner::new(hook_log),
+        };
+
+        core.scheduler.set_executor(Arc::new(core.clone()));
+        Ok(core)
+    }
+
+    /// Sends the scheduled notifications once and returns how many
+    /// certificates were considered, for running notifications from cron as
+    /// the original doomsday did. Scans every backend unless `certificates`
+    /// were fetched from a running server. Alerts already sent are
+    /// remembered in `data_dir` as usual, but no hooks, webhooks or
+    /// refreshes are started.
+    pub async fn notify_once(
+        mut config: Config,
+        certificates: Option<Vec<CacheItem>>,
+    ) -> crate::Result<usize> {
+        if config.notifications.is_none() {
+            return Err(crate::DoomsdayError::config(
+                "No notifications are configured",
+            ));
+        }
+        config.hooks.clear();
+        config.cache.shared = None;
+
+        let core = Self::build(config).await?;
+        let Some(notifier) = core.notifier.read().await.clone() else {
+            return Err(crate::DoomsdayError::config(
+                "Notifications could not be set up",
+            ));
+        };
+
+        let certificates = match certificates {
+            Some(certificates) => certificates,
+            None => {
+                core.populate_cache().await?;
+                core.cache.list()
+            }
+        };
+
+        // Held by a quiet window during an earlier run
+        notifier.release_held().await?;
+        let sent = notifier.notify_scheduled(&certificates).await;
+        core.flush_state().await;
+        sent?;
+        Ok(certificates.len())
+    }
+
+    /// Creates an accessor for every configured backend. When the server is
+    /// configured to skip invalid backends, failures are collected (keyed by
+    /// backend name) instead of aborting.
+    fn build_accessors(config: &Config) -> crate::Result<(AccessorMap, HashMap<String, String>)> {
         let mut accessors = HashMap::new();
+        let mut failed_backends = HashMap::new();
 
         for backend_config in &config.backends {
+            if !backend_config.enabled {
+                tracing::info!("Skipping disabled backend: {}", backend_config.name);
+                continue;
+            }
+
             tracing::info!(
                 "Creating accessor for backend: {} (type: {})",
                 backend_config.name,
-                backend_config.backend_type
+                backend_config.backend_type()
             );
-            let accessor = create_accessor(backend_config)?;
-            accessors.insert(backend_config.name.clone(), accessor);
-            tracing::debug!("Accessor created for backend: {}", backend_config.name);
+
+            match Self::create_backend_accessor(backend_config, &config.settings) {
+                Ok(accessor) => {
+                    accessors.insert(backend_config.name.clone(), accessor);
+                    tracing::debug!("Accessor created for backend: {}", backend_config.name);
+                }
+                Err(e) if config.server.skip_invalid_backends => {
+                    tracing::error!(
+                        "⚠️ DEGRADED: skipping misconfigured backend '{}': {}",
+                        backend_config.name,
+                        e
+                    );
+                    failed_backends.insert(backend_config.name.clone(), e.to_string());
+                }
+                Err(e) => return Err(e),
+            }
         }
 
-        let core = Core {
-            config: Arc::new(RwLock::new(config)),
-            cache,
-            accessors: Arc::new(RwLock::new(accessors)),
-            scheduler,
+        // Only present when skipping invalid backends
+        for invalid in &config.invalid_backends {
+            tracing::error!(
+                "⚠️ DEGRADED: skipping misconfigured backend '{}': {}",
+                invalid.name,
+                invalid.error
+            );
+            failed_backends.insert(invalid.name.clone(), invalid.error.clone());
+        }
+
+        Ok((accessors, failed_backends))
+    }
+
+    fn build_notifier(
+        config: &Config,
+        alert_log: &AlertLog,
+        held: &HeldNotifications,
+        history: &NotificationHistory,
+        dead_letters: &DeadLetters,
+    ) -> Option<Arc<NotificationService>> {
+        let notification_config = config.notifications.as_ref()?;
+
+        match NotificationService::new(notification_config, &config.alert_thresholds()) {
+            Ok(service) => Some(Arc::new(
+                service
+                    .with_alert_log(alert_log.clone())
+                    .with_held_notifications(held.clone())
+                    .with_notification_history(history.clone())
+                    .with_dead_letters(dead_letters.clone()),
+            )),
+            Err(e) => {
+                tracing::error!("Notifications disabled, failed to configure backend: {}", e);
+                None
+            }
+        }
+    }
+
+    /// Every problem that would stop `config` from starting a server, or
+    /// leave a backend or notifications disabled, without connecting to
+    /// anything. Used by `doomsday-server validate`.
+    pub fn check_config(config: &Config) -> Vec<String> {
+        let mut problems = Vec::new();
+        let mut backend_errors = HashSet::new();
+
+        for invalid in &config.invalid_backends {
+            problems.push(format!("Backend '{}': {}", invalid.name, invalid.error));
+        }
+
+        let mut names = HashSet::new();
+        for backend_config in &config.backends {
+            if !names.insert(backend_config.name.as_str()) {
+                let e = crate::DoomsdayError::config(format!(
+                    "Duplicate backend name: {}",
+                    backend_config.name
+                ));
+                problems.push(e.to_string());
+                backend_errors.insert(e.to_string());
+            }
+            if !backend_config.enabled {
+                continue;
+            }
+            // Builds the accessor too, which checks its properties
+            if let Err(e) = Self::create_backend_accessor(backend_config, &config.settings) {
+                problems.push(format!("Backend '{}': {}", backend_config.name, e));
+                backend_errors.insert(e.to_string());
+            }
+        }
+
+        // Stops at the first problem, which may be a backend's already reported
+        if let Err(e) = config.validate() {
+            if !backend_errors.contains(&e.to_string()) {
+                problems.push(e.to_string());
+            }
+        }
+
+        if let Some(notification_config) = &config.notifications {
+            if let Err(e) =
+                NotificationService::new(notification_config, &config.alert_thresholds())
+            {
+                problems.push(format!("Notifications: {}", e));
+            }
+        }
+
+        problems
+    }
+
+    fn create_backend_accessor(
+        backend_config: &BackendConfig,
+        settings: &SettingsConfig,
+    ) -> crate::Result<Arc<dyn Accessor>> {
+        backend_config.validate()?;
+        create_accessor(backend_config, settings.request_timeout())
+    }
+
+    pub async fn is_degraded(&self) -> bool {
+        !self.failed_backends.read().await.is_empty()
+    }
+
+    pub async fn backend_statuses(&self) -> Vec<BackendStatus> {
+        let config = self.config.read().await;
+        let accessors = self.accessors.read().await;
+        let failed_backends = self.failed_backends.read().await;
+
+        config
+            .backends
+            .iter()
+            .filter(|backend_config| backend_config.enabled)
+            .map(|backend_config| {
+                let error = failed_backends.get(&backend_config.name).cloned();
+                BackendStatus {
+                    name: backend_config.name.clone(),
+                    backend_type: backend_config.backend_type().to_string(),
+                    healthy: error.is_none(),
+                    error,
+                    failing_since: self.cache.failing_since(&backend_config.name),
+                    stale: self.cache.is_backend_stale(&backend_config.name),
+                    auth: accessors
+                        .get(&backend_config.name)
+                        .and_then(|accessor| accessor.auth_status()),
+                }
+            })
+            .chain(config.invalid_backends.iter().map(|invalid| BackendStatus {
+                name: invalid.name.clone(),
+                backend_type: invalid.backend_type.clone(),
+                healthy: false,
+                error: Some(invalid.error.clone()),
+                failing_since: self.cache.failing_since(&invalid.name),
+                stale: self.cache.is_backend_stale(&invalid.name),
+                auth: None,
+            }))
+            .collect()
+    }
+
+    /// Starts delivering cache events to the configured webhooks, replacing
+    /// the dispatcher for a previous configuration.
+    async fn spawn_webhook_dispatcher(&self) {
+        let mut dispatcher = self.webhook_dispatcher.write().await;
+        if let Some(handle) = dispatcher.take() {
+            handle.abort();
+        }
+
+        if let Some(webhooks) = &self.config.read().await.webhooks {
+            if !webhooks.endpoints.is_empty() {
+                *dispatcher =
+                    Some(WebhookDispatcher::new(webhooks.clone(), self.cache.clone()).spawn());
+            }
+        }
+    }
+
+    /// Forwards cache events to the notification service, batching the events
+    /// a single refresh produces into one message per kind. Whichever service
+    /// is configured when a batch arrives receives it.
+    fn spawn_event_notifier(&self) {
+        let cache = self.cache.clone();
+        let notifier = self.notifier.clone();
+        let mut events = cache.subscribe();
+
+        tokio::spawn(async move {
+            loop {
+                let first = match events.recv().await {
+                    Ok(event) => event,
+                    Err(RecvError::Lagged(skipped)) => {
+                        tracing::warn!(
+                            "Event notifier lagged, {} cache events were dropped",
+                            skipped
+                        );
+                        continue;
+                    }
+                    Err(RecvError::Closed) => break,
+                };
+
+                tokio::time::sleep(EVENT_NOTIFICATION_BATCH_WINDOW).await;
+                let mut batch = vec![first];
+                while let Ok(event) = events.try_recv() {
+                    batch.push(event);
+                }
+
+                let Some(notifier) = notifier.read().await.clone() else {
+                    continue;
+                };
+                let batch: Vec<(CacheEvent, CacheItem)> = batch
+                    .into_iter()
+                    .map(|event| {
+                        let item = cache.item(event.certificate());
+                        (event, item)
+                    })
+                    .collect();
+                if let Err(e) = notifier.notify_events(&batch).await {
+                    tracing::error!("Failed to send event notifications: {}", e);
+                }
+            }
+        });
+    }
+
+    /// Runs a hypothetical certificate through the notification rules and
+    /// webhook thresholds, reporting what would fire without sending anything.
+    pub async fn simulate_notification(
+        &self,
+        request: NotificationSimulationRequest,
+    ) -> NotificationSimulation {
+        let not_after = Utc::now() + chrono::Duration::days(request.days_to_expiry);
+        let mut matches = Vec::new();
+
+        let config = self.config.read().await;
+        let thresholds = config.event_config().thresholds;
+        let threshold = webhooks::tightest_threshold(&thresholds, request.days_to_expiry);
+
+        let paths: Vec<PathObject> = match (&request.backend, &request.path) {
+            (Some(backend), Some(path)) => vec![PathObject::new(backend.as_str(), path.as_str())],
+            (Some(backend), None) => vec![PathObject::new(backend.as_str(), "")],
+            _ => Vec::new(),
         };
 
-        tracing::info!("Scheduling initial refresh tasks...");
-        core.schedule_refresh_tasks().await;
+        if let Some(notifier) = self.notifier.read().await.as_ref() {
+            matches.extend(notifier.simulate(request.days_to_expiry, &request.labels, &paths));
+            if let Some(threshold) = threshold {
+                matches.extend(notifier.simulate_threshold(threshold, &request.labels, &paths));
+            }
+        }
 
-        tracing::info!("Core system initialization completed");
-        Ok(core)
+        if let Some(webhooks) = &config.webhooks {
+            matches.extend(webhooks::simulate(
+                webhooks,
+                &thresholds,
+                request.days_to_expiry,
+                &request.labels,
+            ));
+        }
+        drop(config);
+
+        tracing::info!(
+            "Simulated notification routing for {} ({} days): {} matches",
+            request.subject,
+            request.days_to_expiry,
+            matches.len()
+        );
+
+        NotificationSimulation {
+            subject: request.subject,
+            not_after,
+            labels: request.labels,
+            matches,
+        }
+    }
+
+    /// Applies a scan to the cache, recording what it changed and any
+    /// certificates it rotated.
+    async fn apply_diff(&self, diff: CacheDiff) -> crate::Result<()> {
+        // Filling an empty cache says nothing about what changed
+        let baseline = self.cache.is_empty();
+        let preview = self.cache.preview_diff(&diff);
+        self.cache.update_from_diff(diff)?;
+        self.cache.publish_rotations(&preview.renewed);
+
+        if let Err(e) = self.history.record(preview.renewed.clone()) {
+            tracing::warn!("Failed to save rotation history: {}", e);
+        }
+        if !baseline {
+            let capacity = self.config.read().await.cache.change_history;
+            self.changes.write().await.record(preview, capacity);
+        }
+        self.run_hooks().await;
+        Ok(())
+    }
+
+    /// Starts the hooks due for the current cache. Scripts and webhooks run
+    /// in the background; `task` hooks are queued with the scheduler.
+    pub async fn run_hooks(&self) {
+        let hooks = self.config.read().await.hooks.clone();
+        if hooks.is_empty() {
+            return;
+        }
+
+        let certificates = self.cache.list();
+        let now = Utc::now();
+        for (hook, cert) in self.hooks.claim_due(&hooks, &certificates, now) {
+            if let HookAction::Task { .. } = hook.action {
+                let task = Task::RunHook {
+                    hook_name: hook.name.clone(),
+                    sha1: cert.sha1.clone(),
+                };
+                let result = self.scheduler.schedule_task(task).map(|_| ());
+                self.hooks.finish(hook, cert, &result);
+                continue;
+            }
+
+            tracing::info!("Running hook {} for {}", hook.name, cert.subject);
+            let runner = self.hooks.clone();
+            let (hook, cert) = (hook.clone(), cert.clone());
+            tokio::spawn(async move {
+                let result = runner
+                    .run(&hook, &HookPayload::new(&hook, &cert, now))
+                    .await;
+                runner.finish(&hook, &cert, &result);
+            });
+        }
+    }
+
+    /// Runs a `task` hook's command for a certificate still in the cache.
+    async fn run_hook_task(&self, hook_name: &str, sha1: &str) -> crate::Result<()> {
+        let hook = self
+            .config
+            .read()
+            .await
+            .hooks
+            .iter()
+            .find(|hook| hook.name == hook_name)
+            .cloned()
+            .ok_or_else(|| {
+                crate::DoomsdayError::config(format!("Hook {} is no longer configured", hook_name))
+            })?;
+        let HookAction::Task { command, args } = &hook.action else {
+            return Err(crate::DoomsdayError::config(format!(
+                "Hook {} is no longer a task hook",
+                hook_name
+            )));
+        };
+        let cert = self
+            .cache
+            .get(sha1)
+            .map(|object| self.cache.item(&object))
+            .ok_or_else(|| {
+                crate::DoomsdayError::cache(format!("Certificate {} is no longer cached", sha1))
+            })?;
+
+        tracing::info!("Running hook {} for {}", hook.name, cert.subject);
+        hooks::run_script(command, args, &HookPayload::new(&hook, &cert, Utc::now())).await
+    }
+
+    pub fn hook_runs(
+        &self,
+        hook: Option<&str>,
+        sha1: Option<&str>,
+        limit: Option<usize>,
+    ) -> Vec<HookRun> {
+        self.hooks.log().list(hook, sha1, limit)
+    }
+
+    pub async fn changes_since(&self, since: Option<chrono::DateTime<Utc>>) -> CacheChanges {
+        self.changes.read().await.since(since)
+    }
+
+    pub fn rotation_history(
+        &self,
+        backend: Option<&str>,
+        path: Option<&str>,
+        limit: Option<usize>,
+    ) -> Vec<RotationEvent> {
+        self.history.list(backend, path, limit)
+    }
+
+    pub fn notification_history(
+        &self,
+        backend: Option<&str>,
+        sha1: Option<&str>,
+        status: Option<DeliveryStatus>,
+        limit: Option<usize>,
+    ) -> Vec<NotificationDelivery> {
+        self.notification_history.list(backend, sha1, status, limit)
+    }
+
+    pub fn dead_letters(&self, backend: Option<&str>, limit: Option<usize>) -> Vec<DeadLetter> {
+        self.dead_letters.list(backend, limit)
+    }
+
+    pub async fn last_refresh(&self) -> Option<RefreshSummary> {
+        self.last_refresh.read().await.clone()
+    }
+
+    /// Whether a refresh is running or queued, leaving the cache partially
+    /// updated. Includes the initial refreshes after startup.
+    pub fn is_populating(&self) -> bool {
+        self.refreshes_in_flight.load(Ordering::SeqCst) > 0
+            || self.scheduler.has_unfinished_refreshes()
+    }
+
+    /// Sends a test notification via every configured backend, or only the
+    /// one named `backend`. `None` when notifications are not configured.
+    pub async fn test_notifications(
+        &self,
+        backend: Option<&str>,
+    ) -> Option<Vec<NotificationTestResult>> {
+        let notifier = self.notifier.read().await.clone()?;
+        Some(notifier.send_test(backend).await)
+    }
+
+    /// Sends the scheduled notifications for the current cache.
+    pub async fn send_scheduled_notifications(&self) -> crate::Result<()> {
+        let Some(notifier) = self.notifier.read().await.clone() else {
+            return Ok(());
+        };
+        notifier.notify_scheduled(&self.cache.list()).await
     }
 
     pub async fn populate_cache(&self) -> crate::Result<PopulateStats> {
+        let _refreshing = RefreshGuard::new(&self.refreshes_in_flight);
         tracing::info!("Starting cache population from all backends");
         let start_time = Instant::now();
-        let accessors = self.accessors.read().await;
+        let mut scan = self.scan_all_backends().await?;
+
+        // Update cache with new data
+        tracing::info!("Updating cache with {} certificates", scan.diff.added.len());
+        self.apply_diff(scan.diff).await?;
+        self.check_pins().await;
+
+        let duration_ms = start_time.elapsed().as_millis() as u64;
+
+        // Backends that never initialized are part of the picture too
+        for (name, error) in self.failed_backends.read().await.iter() {
+            let mut stats = BackendRefreshStats::new(name);
+            stats.error = Some(error.clone());
+            scan.backends.push(stats);
+        }
+        scan.backends.sort_by(|a, b| a.name.cmp(&b.name));
+        self.cache.record_backend_refresh(&scan.backends);
+
+        *self.last_refresh.write().await = Some(RefreshSummary {
+            finished_at: Utc::now(),
+            duration_ms,
+            num_certs: scan.num_certs,
+            num_paths: scan.num_paths,
+            backends: scan.backends,
+        });
+
+        tracing::info!(
+            "Cache population completed: {} certificates, {} paths, {}ms",
+            scan.num_certs,
+            scan.num_paths,
+            duration_ms
+        );
+
+        Ok(PopulateStats {
+            num_certs: scan.num_certs,
+            num_paths: scan.num_paths,
+            duration_ms,
+        })
+    }
+
+    /// Scans the requested backends (or all of them) and reports what a refresh
+    /// would change, without touching the cache.
+    pub async fn preview_refresh(
+        &self,
+        backends: Option<&[String]>,
+    ) -> crate::Result<CacheDiffPreview> {
+        tracing::info!("Starting dry-run refresh: backends={:?}", backends);
+
+        let diff = match backends {
+            Some(backend_names) => {
+                let mut diff = CacheDiff::new();
+                for backend_name in backend_names {
+                    let scan = self.scan_backend(backend_name).await?;
+                    diff.added.extend(scan.diff.added);
+                    diff.removed.extend(scan.diff.removed);
+                }
+                diff
+            }
+            None => self.scan_all_backends().await?.diff,
+        };
+
+        let preview = self.cache.preview_diff(&diff);
+        tracing::info!(
+            "Dry-run refresh completed: {} added, {} removed, {} renewed, {} unchanged",
+            preview.added.len(),
+            preview.removed.len(),
+            preview.renewed.len(),
+            preview.unchanged
+        );
+        Ok(preview)
+    }
+
+    async fn scan_all_backends(&self) -> crate::Result<BackendScan> {
+        // Read before taking the accessors lock; update_config locks config first
+        let limits = self.fetch_limits().await;
+        let current = self.accessors.read().await;
+        // Each scan gets its own handles, so an overlapping one (a dry run
+        // during a scheduled refresh) does not share their state
+        let accessors: AccessorMap = current
+            .iter()
+            .map(|(name, accessor)| {
+                let run = accessor.start_run().unwrap_or_else(|| accessor.clone());
+                (name.clone(), run)
+            })
+            .collect();
         let mut all_paths = Vec::new();
+        let mut backend_stats: HashMap<String, BackendRefreshStats> = HashMap::new();
+        // Only backends that listed successfully may have entries pruned
+        let mut scanned = HashSet::new();
+        let mut unreadable = HashSet::new();
+        // Backends are fetched concurrently, so each reports the wall-clock
+        // time since it started rather than the sum of its fetches
+        let mut started: HashMap<String, Instant> = HashMap::new();
 
         tracing::debug!("Found {} active backends", accessors.len());
 
         // Collect all paths from all backends
         for (backend_name, accessor) in accessors.iter() {
             tracing::info!("Listing paths from backend: {}", backend_name);
+            let stats = backend_stats
+                .entry(backend_name.clone())
+                .or_insert_with(|| BackendRefreshStats::new(backend_name));
+            let list_start = Instant::now();
+            started.insert(backend_name.clone(), list_start);
+
             match accessor.list().await {
                 Ok(paths) => {
                     tracing::info!("Backend {} returned {} paths", backend_name, paths.len());
+                    stats.num_paths = paths.len();
+                    stats.list_failures = accessor.list_failures();
+                    unreadable.extend(self.unlisted_paths(backend_name, &stats.list_failures));
+                    scanned.insert(backend_name.clone());
                     for path in paths {
                         all_paths.push((backend_name.clone(), path));
                     }
                 }
                 Err(e) => {
                     tracing::error!("Failed to list paths from backend {}: {}", backend_name, e);
+                    stats.error = Some(e.to_string());
                 }
             }
+
+            stats.duration_ms = list_start.elapsed().as_millis() as u64;
         }
 
         let num_paths = all_paths.len();
@@ -89,110 +956,153 @@ impl Core {
         let mut num_certs = 0;
         let mut new_cache_objects: HashMap<String, CacheObject> = HashMap::new();
 
-        // Process paths in chunks for better performance
-        let chunk_size = 100;
-        tracing::debug!("Processing paths in chunks of {}", chunk_size);
-
-        for (chunk_idx, chunk) in all_paths.chunks(chunk_size).enumerate() {
-            tracing::debug!("Processing chunk {} ({} paths)", chunk_idx + 1, chunk.len());
-            let mut tasks = Vec::new();
+        let mut all_paths = all_paths.into_iter().peekable();
+        while all_paths.peek().is_some() {
+            let mut tasks = Vec::with_capacity(limits.chunk_size);
 
-            for (backend_name, path) in chunk {
-                let accessor = accessors.get(backend_name).unwrap().clone();
-                let path = path.clone();
-                let backend_name = backend_name.clone();
+            for (backend_name, path) in all_paths.by_ref().take(limits.chunk_size) {
+                let accessor = accessors.get(&backend_name).unwrap().clone();
+                let limit = limits.for_backend(&backend_name);
 
-                let task = tokio::spawn(async move {
-                    accessor
-                        .get(&path)
-                        .await
-                        .map(|cert_data| (backend_name, path, cert_data))
-                });
-
-                tasks.push(task);
+                tasks.push(tokio::spawn(async move {
+                    let _permit = limit.acquire_owned().await;
+                    let result = accessor.get(&path).await;
+                    (backend_name, path, result)
+                }));
             }
 
-            // Wait for all tasks in this chunk to complete
             for task in tasks {
-                match task.await {
-                    Ok(Ok((backend_name, path, Some(cert_data)))) => {
-                        let sha1 = cert_data.fingerprint_sha1.clone();
-
-                        if let Some(existing) = new_cache_objects.get_mut(&sha1) {
-                            // Certificate already exists, add this path
-                            existing.paths.push(PathObject {
-                                backend: backend_name,
-                                path,
-                            });
-                        } else {
-                            // New certificate
-                            let cache_object = CacheObject {
-                                subject: cert_data.subject,
-                                not_after: cert_data.not_after,
-                                sha1: sha1.clone(),
-                                paths: vec![PathObject {
-                                    backend: backend_name,
-                                    path,
-                                }],
-                            };
-
-                            new_cache_objects.insert(sha1, cache_object);
-                            num_certs += 1;
-                        }
+                let (backend_name, path, result) = match task.await {
+                    Ok(output) => output,
+                    Err(e) => {
+                        tracing::error!("Task failed: {}", e);
+                        continue;
                     }
-                    Ok(Ok((_, _, None))) => {
-                        // No certificate data at this path
+                };
+
+                if let Some(stats) = backend_stats.get_mut(&backend_name) {
+                    stats.duration_ms = started[&backend_name].elapsed().as_millis() as u64;
+                    stats.finished_at = Utc::now();
+                    if matches!(result, Ok(Some(_))) {
+                        stats.num_certs += 1;
                     }
-                    Ok(Err(e)) => {
-                        tracing::error!("Failed to get certificate data: {}", e);
+                }
+
+                match result {
+                    Ok(Some(cert_data)) => {
+                        num_certs += collect_certificate(
+                            &mut new_cache_objects,
+                            cert_data,
+                            PathObject::new(backend_name, path),
+                        );
+                    }
+                    Ok(None) => {
+                        // No certificate data at this path
                     }
                     Err(e) => {
-                        tracing::error!("Task failed: {}", e);
+                        tracing::error!("Failed to get certificate data: {}", e);
+                        unreadable.insert(PathObject::new(backend_name, path));
                     }
                 }
             }
         }
 
-        // Update cache with new data
+        let diff = self
+            .cache
+            .diff_scan(new_cache_objects, &scanned, &unreadable);
         tracing::info!(
-            "Updating cache with {} certificates",
-            new_cache_objects.len()
+            "Full scan: {} certificates to add or update, {} to remove",
+            diff.added.len(),
+            diff.removed.len()
         );
-        let diff = CacheDiff {
-            added: new_cache_objects,
-            removed: Vec::new(), // TODO: Implement proper diffing to remove stale entries
+
+        Ok(BackendScan {
+            diff,
+            num_certs,
+            num_paths,
+            backends: backend_stats.into_values().collect(),
+        })
+    }
+
+    pub async fn refresh_backend(&self, backend_name: &str) -> crate::Result<PopulateStats> {
+        let _refreshing = RefreshGuard::new(&self.refreshes_in_flight);
+        tracing::info!("Starting refresh for backend: {}", backend_name);
+        let start_time = Instant::now();
+        let scan = match self.scan_backend(backend_name).await {
+            Ok(scan) => scan,
+            Err(e) => {
+                let mut stats = BackendRefreshStats::new(backend_name);
+                stats.duration_ms = start_time.elapsed().as_millis() as u64;
+                stats.error = Some(e.to_string());
+                self.record_backend_refresh(vec![stats]).await;
+                return Err(e);
+            }
         };
 
-        self.cache.update_from_diff(diff)?;
+        self.apply_diff(scan.diff).await?;
+        self.check_pins().await;
 
         let duration_ms = start_time.elapsed().as_millis() as u64;
+        self.record_backend_refresh(scan.backends).await;
 
         tracing::info!(
-            "Cache population completed: {} certificates, {} paths, {}ms",
-            num_certs,
-            num_paths,
+            "Backend {} refresh completed: {} certificates, {} paths, {}ms",
+            backend_name,
+            scan.num_certs,
+            scan.num_paths,
             duration_ms
         );
 
         Ok(PopulateStats {
-            num_certs,
-            num_paths,
+            num_certs: scan.num_certs,
+            num_paths: scan.num_paths,
             duration_ms,
         })
     }
 
-    pub async fn refresh_backend(&self, backend_name: &str) -> crate::Result<PopulateStats> {
-        tracing::info!("Starting refresh for backend: {}", backend_name);
-        let start_time = Instant::now();
+    /// Folds the result of a single-backend refresh into the last summary so
+    /// `/v1/info` reflects it without waiting for the next full populate.
+    async fn record_backend_refresh(&self, backends: Vec<BackendRefreshStats>) {
+        self.cache.record_backend_refresh(&backends);
+        let mut last_refresh = self.last_refresh.write().await;
+        let summary = last_refresh.get_or_insert_with(|| RefreshSummary {
+            finished_at: Utc::now(),
+            duration_ms: 0,
+            num_certs: 0,
+            num_paths: 0,
+            backends: Vec::new(),
+        });
+
+        for stats in backends {
+            summary.finished_at = stats.finished_at;
+            match summary.backends.iter_mut().find(|b| b.name == stats.name) {
+                Some(existing) => *existing = stats,
+                None => summary.backends.push(stats),
+            }
+        }
+        summary.backends.sort_by(|a, b| a.name.cmp(&b.name));
+    }
+
+    async fn fetch_limits(&self) -> FetchLimits {
+        FetchLimits::new(&*self.config.read().await)
+    }
+
+    async fn scan_backend(&self, backend_name: &str) -> crate::Result<BackendScan> {
+        // Read before taking the accessors lock; update_config locks config first
+        let limits = self.fetch_limits().await;
+        let limit = limits.for_backend(backend_name);
         let accessors = self.accessors.read().await;
 
         let accessor = accessors.get(backend_name).ok_or_else(|| {
             tracing::error!("Backend {} not found in accessor list", backend_name);
             crate::DoomsdayError::not_found(format!("Backend {} not found", backend_name))
         })?;
+        let accessor = accessor.start_run().unwrap_or_else(|| accessor.clone());
 
         tracing::debug!("Listing paths from backend: {}", backend_name);
+        let scan_start = Instant::now();
         let paths = accessor.list().await?;
+        let list_failures = accessor.list_failures();
         let num_paths = paths.len();
         tracing::info!(
             "Backend {} has {} paths to process",
@@ -202,59 +1112,43 @@ impl Core {
 
         let mut num_certs = 0;
         let mut backend_cache_objects: HashMap<String, CacheObject> = HashMap::new();
+        let mut unreadable = self.unlisted_paths(backend_name, &list_failures);
 
-        // Process paths in chunks
-        let chunk_size = 50;
-        tracing::debug!("Processing {} paths in chunks of {}", num_paths, chunk_size);
-
-        for (chunk_idx, chunk) in paths.chunks(chunk_size).enumerate() {
-            tracing::debug!(
-                "Processing chunk {} for backend {} ({} paths)",
-                chunk_idx + 1,
-                backend_name,
-                chunk.len()
-            );
-            let mut tasks = Vec::new();
+        tracing::debug!(
+            "Fetching {} paths with up to {} requests in flight",
+            num_paths,
+            limit.available_permits()
+        );
+        let mut paths = paths.into_iter().peekable();
+        while paths.peek().is_some() {
+            let mut tasks = Vec::with_capacity(limits.chunk_size);
 
-            for path in chunk {
+            for path in paths.by_ref().take(limits.chunk_size) {
                 let accessor = accessor.clone();
-                let path = path.clone();
-
-                let task = tokio::spawn(async move {
-                    accessor.get(&path).await.map(|cert_data| (path, cert_data))
-                });
+                let limit = limit.clone();
 
-                tasks.push(task);
+                tasks.push(tokio::spawn(async move {
+                    let _permit = limit.acquire_owned().await;
+                    match accessor.get(&path).await {
+                        Ok(cert_data) => Ok((path, cert_data)),
+                        Err(e) => Err((path, e)),
+                    }
+                }));
             }
 
             for task in tasks {
                 match task.await {
                     Ok(Ok((path, Some(cert_data)))) => {
-                        let sha1 = cert_data.fingerprint_sha1.clone();
-
-                        if let Some(existing) = backend_cache_objects.get_mut(&sha1) {
-                            existing.paths.push(PathObject {
-                                backend: backend_name.to_string(),
-                                path,
-                            });
-                        } else {
-                            let cache_object = CacheObject {
-                                subject: cert_data.subject,
-                                not_after: cert_data.not_after,
-                                sha1: sha1.clone(),
-                                paths: vec![PathObject {
-                                    backend: backend_name.to_string(),
-                                    path,
-                                }],
-                            };
-
-                            backend_cache_objects.insert(sha1, cache_object);
-                            num_certs += 1;
-                        }
+                        num_certs += collect_certificate(
+                            &mut backend_cache_objects,
+                            cert_data,
+                            PathObject::new(backend_name, path),
+                        );
                     }
                     Ok(Ok((_, None))) => {}
-                    Ok(Err(e)) => {
+                    Ok(Err((path, e))) => {
                         tracing::error!("Failed to get certificate from {}: {}", backend_name, e);
+                        unreadable.insert(PathObject::new(backend_name, path));
                     }
                     Err(e) => {
                         tracing::error!("Task failed: {}", e);
@@ -263,56 +1157,89 @@ impl Core {
             }
         }
 
-        // Remove old entries for this backend from cache
-        tracing::debug!(
-            "Checking for stale cache entries from backend: {}",
-            backend_name
-        );
-        let all_cache_items = self.cache.list();
-        let mut to_remove = Vec::new();
-
-        for item in all_cache_items {
-            if item.paths.iter().any(|p| p.backend == backend_name) {
-                // This certificate has paths from the backend we're refreshing
-                // We need to check if it still exists in our new data
-                let sha1 = Sha1::digest(item.subject.as_bytes());
-                let sha1_hex = hex::encode(sha1);
-
-                if !backend_cache_objects.contains_key(&sha1_hex) {
-                    to_remove.push(sha1_hex);
-                }
-            }
-        }
+        // Strip only this backend's stale paths, keyed by fingerprint like
+        // the cache itself; entries still served elsewhere are kept
+        let scanned = HashSet::from([backend_name.to_string()]);
+        let diff = self
+            .cache
+            .diff_scan(backend_cache_objects, &scanned, &unreadable);
 
         tracing::info!(
-            "Backend {} refresh: {} certificates to add, {} to remove",
+            "Backend {} refresh: {} certificates to add or update, {} to remove",
             backend_name,
-            backend_cache_objects.len(),
-            to_remove.len()
+            diff.added.len(),
+            diff.removed.len()
         );
 
-        let diff = CacheDiff {
-            added: backend_cache_objects,
-            removed: to_remove,
-        };
-
-        self.cache.update_from_diff(diff)?;
+        let mut stats = BackendRefreshStats::new(backend_name);
+        stats.duration_ms = scan_start.elapsed().as_millis() as u64;
+        stats.num_certs = num_certs;
+        stats.num_paths = num_paths;
+        stats.list_failures = list_failures;
 
-        let duration_ms = start_time.elapsed().as_millis() as u64;
-
-        tracing::info!(
-            "Backend {} refresh completed: {} certificates, {} paths, {}ms",
-            backend_name,
+        Ok(BackendScan {
+            diff,
             num_certs,
             num_paths,
-            duration_ms
+            backends: vec![stats],
+        })
+    }
+
+    /// Cached paths in the directories a backend's listing skipped, which
+    /// the scan must keep since it cannot tell whether they still exist.
+    fn unlisted_paths(&self, backend_name: &str, failures: &[ListFailure]) -> HashSet<PathObject> {
+        if failures.is_empty() {
+            return HashSet::new();
+        }
+        tracing::warn!(
+            "Backend {} listing skipped {} unreadable directories, keeping their cached certificates",
+            backend_name,
+            failures.len()
         );
+        let dirs: Vec<String> = failures.iter().map(|f| f.path.clone()).collect();
+        self.cache.paths_under(backend_name, &dirs)
+    }
 
-        Ok(PopulateStats {
-            num_certs,
-            num_paths,
-            duration_ms,
-        })
+    /// Checks pinned locations against the cache and alerts on mismatches that
+    /// were not already reported by a previous refresh.
+    async fn check_pins(&self) {
+        let pins = self.config.read().await.pins.clone();
+        if pins.is_empty() {
+            return;
+        }
+
+        let violations = pinning::check_pins(&pins, &self.cache);
+        let new_violations: Vec<PinViolation> = {
+            let mut known = self.pin_violations.write().await;
+            let new_violations = violations
+                .iter()
+                .filter(|v| !known.contains(*v))
+                .cloned()
+                .collect();
+            *known = violations.into_iter().collect();
+            new_violations
+        };
+
+        if new_violations.is_empty() {
+            return;
+        }
+
+        for violation in &new_violations {
+            tracing::error!(
+                "🚨 Pinned certificate mismatch at {}:{}: observed {}, expected one of {:?}",
+                violation.backend,
+                violation.path,
+                violation.observed,
+                violation.expected
+            );
+        }
+
+        let notifier = self.notifier.read().await.clone();
+        if let Some(notifier) = notifier {
+            if let Err(e) = notifier.notify_pin_violations(&new_violations).await {
+                tracing::error!("Failed to send pin mismatch alert: {}", e);
+            }
+        }
     }
 
     pub fn get_cache(&self) -> &Cache {
@@ -330,7 +1257,17 @@ impl Core {
             config.backends.len()
         );
 
-        for backend_config in &config.backends {
+        let failed_backends = self.failed_backends.read().await;
+
+        for backend_config in config.backends.iter().filter(|b| b.enabled) {
+            if failed_backends.contains_key(&backend_config.name) {
+                tracing::warn!(
+                    "Not scheduling refresh for degraded backend: {}",
+                    backend_config.name
+                );
+                continue;
+            }
+
             tracing::debug!(
                 "Scheduling refresh task for backend: {}",
                 backend_config.name
@@ -363,77 +1300,672 @@ impl Core {
             config.backends.len()
         );
 
-        for backend_config in &config.backends {
-            if let Some(refresh_interval) = backend_config.refresh_interval {
-                let backend_name = backend_config.name.clone();
-                let scheduler = self.scheduler.clone();
+        let failed_backends = self.failed_backends.read().await;
 
-                tracing::info!(
-                    "Setting up periodic refresh for backend {} every {} minutes",
-                    backend_name,
-                    refresh_interval
-                );
+        // Replace loops left over from a previous configuration
+        let mut periodic_tasks = self.periodic_tasks.write().await;
+        for handle in periodic_tasks.drain(..) {
+            handle.abort();
+        }
 
-                tokio::spawn(async move {
-                    let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(
-                        refresh_interval * 60,
-                    ));
+        for backend_config in config.backends.iter().filter(|b| b.enabled) {
+            if failed_backends.contains_key(&backend_config.name) {
+                continue;
+            }
 
-                    loop {
-                        interval.tick().await;
+            // Schedules were checked during config validation
+            let schedule = match backend_config.refresh_schedule() {
+                Ok(schedule) => schedule,
+                Err(e) => {
+                    tracing::error!(
+                        "Invalid refresh schedule for backend {}: {}",
+                        backend_config.name,
+                        e
+                    );
+                    continue;
+                }
+            };
 
-                        tracing::debug!("Periodic refresh triggered for backend: {}", backend_name);
+            if schedule.is_none() && config.adaptive_refresh.is_none() {
+                tracing::debug!(
+                    "No periodic refresh configured for backend: {}",
+                    backend_config.name
+                );
+                continue;
+            }
+
+            let backend_name = backend_config.name.clone();
+            let scheduler = self.scheduler.clone();
+            let cache = self.cache.clone();
+            let adaptive = config.adaptive_refresh;
 
-                        let task = Task::RefreshBackend {
-                            backend_name: backend_name.clone(),
-                        };
+            match &schedule {
+                Some(schedule) => tracing::info!(
+                    "Setting up periodic refresh for backend {} {}",
+                    backend_name,
+                    schedule
+                ),
+                None => tracing::info!(
+                    "Setting up adaptive-only refresh for backend {}",
+                    backend_name
+                ),
+            }
 
-                        if let Err(e) = scheduler.schedule_task(task) {
-                            tracing::error!(
-                                "Failed to schedule periodic refresh for {}: {}",
-                                backend_name,
-                                e
+            let handle = tokio::spawn(async move {
+                let next_regular = || {
+                    schedule
+                        .as_ref()
+                        .and_then(|s| s.until_next(chrono::Local::now()))
+                        .map(|delay| tokio::time::Instant::now() + delay)
+                };
+
+                // The initial refresh is scheduled separately, so wait for
+                // the next scheduled time before the first periodic one
+                let mut regular_at = next_regular();
+
+                loop {
+                    let adaptive_at = adaptive.map(|a| {
+                        tokio::time::Instant::now()
+                            + std::time::Duration::from_secs(a.interval * 60)
+                    });
+                    let wake_at = match (regular_at, adaptive_at) {
+                        (Some(r), Some(a)) => r.min(a),
+                        (Some(r), None) => r,
+                        (None, Some(a)) => a,
+                        (None, None) => {
+                            tracing::info!(
+                                "Refresh schedule for backend {} has no further runs",
+                                backend_name
                             );
+                            break;
                         }
+                    };
+                    tokio::time::sleep_until(wake_at).await;
+
+                    if !scheduler.is_accepting() {
+                        tracing::debug!("Stopping periodic refresh for backend: {}", backend_name);
+                        break;
                     }
-                });
-            } else {
-                tracing::debug!(
-                    "No periodic refresh configured for backend: {}",
-                    backend_config.name
+
+                    let regular_due = regular_at.is_some_and(|r| r <= tokio::time::Instant::now());
+                    if regular_due {
+                        regular_at = next_regular();
+                    } else {
+                        // Woken early for an adaptive check
+                        let within = chrono::Duration::days(adaptive.map_or(0, |a| a.within_days));
+                        if !cache.has_expiring(&backend_name, within) {
+                            continue;
+                        }
+                        tracing::info!(
+                            "Backend {} has certificates expiring within {} days, refreshing early",
+                            backend_name,
+                            within.num_days()
+                        );
+                    }
+
+                    if scheduler.is_paused() {
+                        tracing::info!(
+                            "Scheduler paused, skipping periodic refresh for backend: {}",
+                            backend_name
+                        );
+                        continue;
+                    }
+
+                    tracing::debug!("Periodic refresh triggered for backend: {}", backend_name);
+
+                    let task = Task::RefreshBackend {
+                        backend_name: backend_name.clone(),
+                    };
+
+                    if let Err(e) = scheduler.schedule_task(task) {
+                        tracing::error!(
+                            "Failed to schedule periodic refresh for {}: {}",
+                            backend_name,
+                            e
+                        );
+                    }
+                }
+            });
+            periodic_tasks.push(handle);
+        }
+
+        // Renew credentials now; each renewal schedules the next from the token's TTL
+        let accessors = self.accessors.read().await;
+        for (backend_name, accessor) in accessors.iter() {
+            if !accessor.renews_auth() {
+                continue;
+            }
+
+            let task = Task::RenewAuthToken {
+                backend_name: backend_name.clone(),
+            };
+            if let Err(e) = self.scheduler.schedule_task(task) {
+                tracing::error!(
+                    "Failed to schedule auth renewal for {}: {}",
+                    backend_name,
+                    e
                 );
             }
         }
 
+        let retention = std::time::Duration::from_secs(
+            config
+                .server
+                .task_retention
+                .unwrap_or(DEFAULT_TASK_RETENTION_HOURS)
+                * 60
+                * 60,
+        );
+        let scheduler = self.scheduler.clone();
+        periodic_tasks.push(tokio::spawn(async move {
+            let mut interval = tokio::time::interval(TASK_CLEANUP_INTERVAL);
+            loop {
+                interval.tick().await;
+                scheduler.cleanup_completed_tasks(retention);
+            }
+        }));
+
+        if let Some(store) = self.cache_store.clone() {
+            let cache = self.cache.clone();
+            let period = std::time::Duration::from_secs(config.cache.snapshot_interval);
+            periodic_tasks.push(tokio::spawn(async move {
+                let mut interval = tokio::time::interval(period);
+                // The first tick completes immediately
+                interval.tick().await;
+                loop {
+                    interval.tick().await;
+                    if let Err(e) = store.save(cache.snapshot()) {
+                        tracing::error!("Failed to save cache snapshot: {}", e);
+                    }
+                }
+            }));
+        }
+
+        // Refreshes check thresholds as they apply; this catches certificates
+        // crossing one while their backend is not refreshed
+        let core = self.clone();
+        let period = std::time::Duration::from_secs(config.event_config().check_interval * 60);
+        periodic_tasks.push(tokio::spawn(async move {
+            let mut interval = tokio::time::interval(period);
+            interval.tick().await;
+            loop {
+                interval.tick().await;
+                // Only the replica refreshing backends raises events
+                if core.shared_cache.as_ref().is_some_and(|s| !s.is_leader()) {
+                    continue;
+                }
+                let crossed = core.cache.check_thresholds();
+                if crossed > 0 {
+                    tracing::info!("{} certificates crossed an expiry threshold", crossed);
+                }
+                core.run_hooks().await;
+            }
+        }));
+
+        if let Some(notifications) = &config.notifications {
+            match notifications.schedule.schedule() {
+                Ok(schedule) => {
+                    tracing::info!("Sending notification digests {}", schedule);
+                    let core = self.clone();
+                    periodic_tasks.push(tokio::spawn(async move {
+                        core.run_notification_schedule(schedule).await
+                    }));
+                }
+                // Checked during config validation
+                Err(e) => tracing::error!("Invalid notification schedule: {}", e),
+            }
+
+            let notifier = self.notifier.clone();
+            let shared_cache = self.shared_cache.clone();
+            periodic_tasks.push(tokio::spawn(async move {
+                let mut interval = tokio::time::interval(HELD_RELEASE_INTERVAL);
+                loop {
+                    interval.tick().await;
+                    if shared_cache.as_ref().is_some_and(|s| !s.is_leader()) {
+                        continue;
+                    }
+                    let Some(notifier) = notifier.read().await.clone() else {
+                        continue;
+                    };
+                    if let Err(e) = notifier.release_held().await {
+                        tracing::error!("Failed to send held notifications: {}", e);
+                    }
+                }
+            }));
+        }
+
+        if let Some(shared) = self.shared_cache.clone() {
+            let cache = self.cache.clone();
+            periodic_tasks.push(tokio::spawn(async move {
+                let mut interval = tokio::time::interval(shared.sync_interval());
+                interval.tick().await;
+                loop {
+                    interval.tick().await;
+                    shared.sync(&cache).await;
+                }
+            }));
+        }
+
         tracing::info!("All periodic refresh tasks configured");
     }
 
+    /// Sends the notification digest each time `schedule` fires. A digest
+    /// that falls due while refreshes are running waits for them to finish.
+    async fn run_notification_schedule(&self, schedule: Schedule) {
+        loop {
+            let Some(delay) = schedule.until_next(chrono::Local::now()) else {
+                tracing::info!("Notification schedule has no further runs");
+                break;
+            };
+            tokio::time::sleep(delay).await;
+
+            if !self.scheduler.is_accepting() {
+                break;
+            }
+            // Only the replica refreshing backends notifies
+            if self.shared_cache.as_ref().is_some_and(|s| !s.is_leader()) {
+                continue;
+            }
+
+            while self.is_populating() {
+                tracing::debug!("Cache is being refreshed, delaying notification digest");
+                tokio::time::sleep(POPULATE_WAIT_INTERVAL).await;
+            }
+
+            tracing::info!("Sending scheduled notification digest");
+            if let Err(e) = self.send_scheduled_notifications().await {
+                tracing::error!("Failed to send scheduled notifications: {}", e);
+            }
+        }
+    }
+
+    /// Renews a backend's credentials and schedules the next renewal based on
+    /// the lifetime of the renewed token.
+    async fn renew_backend_auth(&self, backend_name: &str) -> crate::Result<()> {
+        let accessor = self
+            .accessors
+            .read()
+            .await
+            .get(backend_name)
+            .cloned()
+            .ok_or_else(|| {
+                crate::DoomsdayError::not_found(format!("Backend {} not found", backend_name))
+            })?;
+
+        let Some(delay) = accessor.renew_auth().await? else {
+            tracing::debug!(
+                "No further auth renewal needed for backend: {}",
+                backend_name
+            );
+            return Ok(());
+        };
+
+        tracing::info!(
+            "Next auth renewal for backend {} in {:?}",
+            backend_name,
+            delay
+        );
+
+        let scheduler = self.scheduler.clone();
+        let task = Task::RenewAuthToken {
+            backend_name: backend_name.to_string(),
+        };
+        let handle = tokio::spawn(async move {
+            tokio::time::sleep(delay).await;
+            if let Err(e) = scheduler.schedule_task(task) {
+                tracing::error!("Failed to schedule auth renewal: {}", e);
+            }
+        });
+
+        // Tracked with the periodic loops so a reconfigure cancels it
+        let mut periodic_tasks = self.periodic_tasks.write().await;
+        periodic_tasks.retain(|h| !h.is_finished());
+        periodic_tasks.push(handle);
+        Ok(())
+    }
+
     pub async fn get_config(&self) -> Config {
         self.config.read().await.clone()
     }
 
+    /// Stops the scheduler and waits up to `drain_timeout` for running tasks,
+    /// so in-flight refreshes are not cut off halfway through applying to the
+    /// cache. Persistent state is flushed once work has drained.
+    pub async fn shutdown(&self, drain_timeout: std::time::Duration) {
+        tracing::info!("Shutting down core, drain timeout {:?}", drain_timeout);
+
+        if !self.scheduler.shutdown(drain_timeout).await {
+            tracing::warn!("Shutting down with scheduler tasks still running");
+        }
+
+        self.flush_state().await;
+        if let Some(store) = &self.cache_store {
+            match store.save(self.cache.snapshot()) {
+                Ok(()) => {
+                    tracing::info!("Saved cache snapshot ({} certificates)", self.cache.len())
+                }
+                Err(e) => tracing::error!("Failed to save cache snapshot: {}", e),
+            }
+        }
+
+        if let Some(shared) = &self.shared_cache {
+            shared.release().await;
+        }
+
+        tracing::info!("Core shutdown complete");
+    }
+
+    /// Writes state saved in the background whose latest changes are not
+    /// on disk yet.
+    async fn flush_state(&self) {
+        let ignore_list = self.cache.ignore_list().clone();
+        let acks = self.cache.alert_acks().clone();
+        let alert_log = self.alert_log.clone();
+        let held_notifications = self.held_notifications.clone();
+        let hook_log = self.hooks.log().clone();
+        let history = self.history.clone();
+        let notification_history = self.notification_history.clone();
+        let dead_letters = self.dead_letters.clone();
+        let crossed_thresholds = self.cache.crossed_thresholds();
+        let flushed = tokio::task::spawn_blocking(move || {
+            if let Err(e) = ignore_list.flush() {
+                tracing::error!("Failed to save ignored certificates: {}", e);
+            }
+            if let Err(e) = acks.flush() {
+                tracing::error!("Failed to save alert acknowledgements: {}", e);
+            }
+            if let Err(e) = alert_log.flush() {
+                tracing::error!("Failed to save alert log: {}", e);
+            }
+            if let Err(e) = held_notifications.flush() {
+                tracing::error!("Failed to save held notifications: {}", e);
+            }
+            if let Err(e) = hook_log.flush() {
+                tracing::error!("Failed to save hook runs: {}", e);
+            }
+            if let Err(e) = history.flush() {
+                tracing::error!("Failed to save rotation history: {}", e);
+            }
+            if let Err(e) = notification_history.flush() {
+                tracing::error!("Failed to save notification history: {}", e);
+            }
+            if let Err(e) = dead_letters.flush() {
+                tracing::error!("Failed to save dead letters: {}", e);
+            }
+            if let Err(e) = crossed_thresholds.flush() {
+                tracing::error!("Failed to save crossed thresholds: {}", e);
+            }
+        })
+        .await;
+        if let Err(e) = flushed {
+            tracing::error!("Saving state failed: {}", e);
+        }
+    }
+
     pub async fn update_config(&self, new_config: Config) -> crate::Result<()> {
         new_config.validate()?;
 
         // Update accessors based on new config
-        let mut new_accessors = HashMap::new();
-        for backend_config in &new_config.backends {
-            let accessor = create_accessor(backend_config)?;
-            new_accessors.insert(backend_config.name.clone(), accessor);
-        }
+        let (new_accessors, new_failed_backends) = Self::build_accessors(&new_config)?;
+        let new_notifier = Self::build_notifier(
+            &new_config,
+            &self.alert_log,
+            &self.held_notifications,
+            &self.notification_history,
+            &self.dead_letters,
+        );
 
         {
             let mut config = self.config.write().await;
+            if config.scheduler.workers != new_config.scheduler.workers {
+                tracing::warn!(
+                    "scheduler.workers changed from {} to {}; restart to apply",
+                    config.scheduler.workers,
+                    new_config.scheduler.workers
+                );
+            }
+            if config.cache != new_config.cache {
+                tracing::warn!("cache settings changed; restart to apply");
+            }
+            if config.settings.cache_path != new_config.settings.cache_path
+                || config.settings.task_store_path != new_config.settings.task_store_path
+            {
+                tracing::warn!("settings paths changed; restart to apply");
+            }
             let mut accessors = self.accessors.write().await;
-
+            let mut failed_backends = self.failed_backends.write().await;
+            let mut notifier = self.notifier.write().await;
+
+            self.cache.set_status_thresholds(new_config.status);
+            self.cache
+                .set_event_thresholds(&new_config.event_config().thresholds);
+            self.cache
+                .ignore_list()
+                .set_config_rules(&new_config.ignore)?;
+            self.cache.label_rules().set_rules(&new_config.labels)?;
+            self.scheduler.set_retry_policy(new_config.task_retry);
+            self.scheduler.set_task_timeouts(new_config.task_timeouts);
             *config = new_config;
             *accessors = new_accessors;
+            *failed_backends = new_failed_backends;
+            *notifier = new_notifier;
         }
 
         // Reschedule tasks with new configuration
+        self.spawn_webhook_dispatcher().await;
         self.schedule_refresh_tasks().await;
         self.schedule_periodic_tasks().await;
 
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{ConfigFormat, WebhookConfig, WebhookEndpointConfig};
+    use crate::types::{PathList, TaskStatus};
+    use std::sync::Mutex;
+
+    fn config(skip_invalid_backends: bool) -> Config {
+        ConfigFormat::Yaml
+            .deserialize(&format!(
+                r#"backends:
+  - type: tlsclient
+    name: web
+    properties:
+      targets: [{{host: example.com, port: 443}}]
+  - type: tlsclient
+    name: broken
+    max_concurrent_requests: 0
+    properties:
+      targets: [{{host: example.org, port: 443}}]
+server:
+  port: 8111
+  skip_invalid_backends: {}
+  auth: {{type: none, properties: {{}}}}
+"#,
+                skip_invalid_backends
+            ))
+            .unwrap()
+    }
+
+    /// Serves certificates from memory, by path.
+    #[derive(Default)]
+    struct StaticAccessor {
+        certs: Mutex<HashMap<String, CertificateData>>,
+    }
+
+    impl StaticAccessor {
+        fn serve(&self, path: &str, cert: Option<CertificateData>) {
+            let mut certs = self.certs.lock().unwrap();
+            match cert {
+                Some(cert) => certs.insert(path.to_string(), cert),
+                None => certs.remove(path),
+            };
+        }
+    }
+
+    #[async_trait]
+    impl Accessor for StaticAccessor {
+        async fn list(&self) -> crate::Result<PathList> {
+            Ok(self.certs.lock().unwrap().keys().cloned().collect())
+        }
+
+        async fn get(&self, path: &str) -> crate::Result<Option<CertificateData>> {
+            Ok(self.certs.lock().unwrap().get(path).cloned())
+        }
+
+        fn name(&self) -> &str {
+            "static"
+        }
+    }
+
+    fn certificate(sha1: &str) -> CertificateData {
+        CertificateData {
+            subject: format!("CN={}", sha1),
+            not_before: Utc::now(),
+            not_after: Utc::now() + chrono::Duration::days(90),
+            serial_number: "01".to_string(),
+            issuer: "CN=Test CA".to_string(),
+            subject_alt_names: vec![],
+            key_usage: vec![],
+            ext_key_usage: vec![],
+            is_ca: false,
+            key_algorithm: "RSA".to_string(),
+            key_size: 2048,
+            subject_key_id: String::new(),
+            authority_key_id: String::new(),
+            fingerprint_sha1: sha1.to_string(),
+            fingerprint_sha256: String::new(),
+            pem_data: String::new(),
+            chain: vec![],
+        }
+    }
+
+    /// A core whose `web` backend serves from the returned accessor.
+    async fn static_core() -> (Core, Arc<StaticAccessor>) {
+        let core = Core::build(config(true)).await.unwrap();
+        let accessor = Arc::new(StaticAccessor::default());
+        core.accessors
+            .write()
+            .await
+            .insert("web".to_string(), accessor.clone());
+        (core, accessor)
+    }
+
+    #[tokio::test]
+    async fn test_degraded_start_skips_broken_backend() {
+        let core = Core::build(config(true)).await.unwrap();
+        assert!(core.accessors.read().await.contains_key("web"));
+        assert!(!core.accessors.read().await.contains_key("broken"));
+        assert!(core.is_degraded().await);
+
+        let statuses = core.backend_statuses().await;
+        let web = statuses.iter().find(|s| s.name == "web").unwrap();
+        assert!(web.healthy && web.error.is_none());
+        let broken = statuses.iter().find(|s| s.name == "broken").unwrap();
+        assert!(!broken.healthy);
+        let error = broken.error.as_deref().unwrap();
+        assert!(error.contains("max_concurrent_requests"), "{}", error);
+
+        // Without the setting, one broken backend stops the start
+        let error = Core::build(config(false)).await.err().unwrap().to_string();
+        assert!(error.contains("max_concurrent_requests"), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn test_webhook_dispatcher_follows_config() {
+        let core = Core::build(config(true)).await.unwrap();
+        core.spawn_webhook_dispatcher().await;
+        assert!(core.webhook_dispatcher.read().await.is_none());
+
+        core.config.write().await.webhooks = Some(WebhookConfig {
+            thresholds: None,
+            check_interval: None,
+            endpoints: vec![WebhookEndpointConfig {
+                name: "hook".to_string(),
+                url: "http://127.0.0.1:9/hook".to_string(),
+                secret: None,
+                events: vec![],
+                max_retries: None,
+                labels: HashMap::new(),
+            }],
+        });
+        core.spawn_webhook_dispatcher().await;
+        let first = core.webhook_dispatcher.read().await.as_ref().unwrap().id();
+
+        // A reload replaces the dispatcher rather than keeping the old one
+        core.spawn_webhook_dispatcher().await;
+        let second = core.webhook_dispatcher.read().await.as_ref().unwrap().id();
+        assert_ne!(first, second);
+
+        core.config.write().await.webhooks = None;
+        core.spawn_webhook_dispatcher().await;
+        assert!(core.webhook_dispatcher.read().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_scheduled_refresh_updates_cache_and_task_stats() {
+        let (core, web) = static_core().await;
+        web.serve("a.example.com:443", Some(certificate("aaa")));
+        web.serve("b.example.com:443", Some(certificate("bbb")));
+
+        let id = core
+            .scheduler
+            .schedule_task(Task::RefreshBackend {
+                backend_name: "web".to_string(),
+            })
+            .unwrap();
+        let task = tokio::time::timeout(std::time::Duration::from_secs(10), async {
+            loop {
+                let task = core.scheduler.get_task(&id).unwrap();
+                if task.completed_at.is_some() {
+                    return task;
+                }
+                tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+            }
+        })
+        .await
+        .unwrap();
+
+        assert!(matches!(task.status, TaskStatus::Completed), "{:?}", task);
+        let stats = task.stats.unwrap();
+        assert_eq!(stats.num_paths, 2);
+        assert_eq!(stats.num_certs, 2);
+
+        let mut cached: Vec<String> = core.cache.list().into_iter().map(|i| i.sha1).collect();
+        cached.sort();
+        assert_eq!(cached, ["aaa", "bbb"]);
+    }
+
+    #[tokio::test]
+    async fn test_refresh_prunes_only_its_own_stale_paths() {
+        let (core, web) = static_core().await;
+        let vault = Arc::new(StaticAccessor::default());
+        core.accessors
+            .write()
+            .await
+            .insert("vault".to_string(), vault.clone());
+
+        web.serve("shared.example.com:443", Some(certificate("shared")));
+        web.serve("gone.example.com:443", Some(certificate("gone")));
+        vault.serve("secret/shared", Some(certificate("shared")));
+        core.refresh_backend("web").await.unwrap();
+        core.refresh_backend("vault").await.unwrap();
+
+        web.serve("shared.example.com:443", None);
+        web.serve("gone.example.com:443", None);
+        core.refresh_backend("web").await.unwrap();
+
+        // The certificate only web served is gone; the one vault still
+        // serves keeps just vault's path
+        let cached = core.cache.list();
+        assert_eq!(cached.len(), 1);
+        assert_eq!(cached[0].sha1, "shared");
+        assert_eq!(
+            cached[0].paths,
+            vec![PathObject::new("vault", "secret/shared")]
+        );
+    }
+}
@@ -13,6 +13,9 @@ pub enum DoomsdayError {
     #[error("YAML error: {0}")]
     SerdeYaml(#[from] serde_yaml::Error),
 
+    #[error("TOML error: {0}")]
+    Toml(#[from] toml::de::Error),
+
     #[error("HTTP error: {0}")]
     Reqwest(#[from] reqwest::Error),
 
@@ -0,0 +1,260 @@
+use crate::types::CacheItem;
+use chrono::Utc;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Csv,
+    Json,
+    Prometheus,
+}
+
+impl ExportFormat {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "text/csv; charset=utf-8",
+            ExportFormat::Json => "application/json",
+            ExportFormat::Prometheus => "text/plain; version=0.0.4; charset=utf-8",
+        }
+    }
+
+    pub fn file_extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Json => "json",
+            ExportFormat::Prometheus => "prom",
+        }
+    }
+}
+
+impl FromStr for ExportFormat {
+    type Err = crate::DoomsdayError;
+
+    fn from_str(s: &str) -> crate::Result<Self> {
+        match s.to_lowercase().as_str() {
+            "csv" => Ok(ExportFormat::Csv),
+            "json" => Ok(ExportFormat::Json),
+            "prometheus" | "prom" => Ok(ExportFormat::Prometheus),
+            _ => Err(crate::DoomsdayError::invalid_input(format!(
+                "Unknown export format: {} (expected csv, json or prometheus)",
+                s
+            ))),
+        }
+    }
+}
+
+/// Chunks of an export, rendered one at a time as they are consumed.
+pub type ExportChunks = Box<dyn Iterator<Item = crate::Result<String>> + Send>;
+
+/// Renders the inventory as a sequence of chunks so callers can stream large
+/// exports instead of building a single buffer. Items are only pulled from
+/// `items` as the chunks are consumed.
+pub fn export_chunks<I>(items: I, format: ExportFormat) -> ExportChunks
+where
+    I: IntoIterator<Item = CacheItem>,
+    I::IntoIter: Send + 'static,
+{
+    let items = items.into_iter();
+    match format {
+        ExportFormat::Csv => Box::new(csv_chunks(items).map(Ok)),
+        ExportFormat::Json => Box::new(json_chunks(items)),
+        ExportFormat::Prometheus => Box::new(prometheus_chunks(items).map(Ok)),
+    }
+}
+
+fn csv_chunks(items: impl Iterator<Item = CacheItem>) -> impl Iterator<Item = String> {
+    let now = Utc::now();
+    let header = "subject,not_after,days_remaining,backends,paths,status,labels\n".to_string();
+
+    std::iter::once(header).chain(items.map(move |item| {
+        let mut backends: Vec<&str> = item.paths.iter().map(|p| &*p.backend).collect();
+        backends.sort_unstable();
+        backends.dedup();
+
+        let paths: Vec<String> = item
+            .paths
+            .iter()
+            .map(|p| format!("{}:{}", p.backend, p.path))
+            .collect();
+
+        let mut labels: Vec<String> = item
+            .labels
+            .iter()
+            .map(|(key, value)| format!("{}={}", key, value))
+            .collect();
+        labels.sort_unstable();
+
+        format!(
+            "{},{},{},{},{},{},{}\n",
+            csv_escape(&item.subject),
+            item.not_after.to_rfc3339(),
+            (item.not_after - now).num_days(),
+            csv_escape(&backends.join(";")),
+            csv_escape(&paths.join(";")),
+            item.status,
+            csv_escape(&labels.join(";"))
+        )
+    }))
+}
+
+/// Quotes a text cell where needed. Cells that a spreadsheet would read as
+/// a formula are prefixed with `'`, so opening an export cannot run one
+/// planted in a subject or label.
+fn csv_escape(value: &str) -> String {
+    let value = if value.starts_with(['=', '+', '-', '@', '\t', '\r']) {
+        format!("'{}", value)
+    } else {
+        value.to_string()
+    };
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value
+    }
+}
+
+/// A JSON array written one element at a time.
+fn json_chunks(
+    items: impl Iterator<Item = CacheItem>,
+) -> impl Iterator<Item = crate::Result<String>> {
+    let elements = items.enumerate().map(|(i, item)| {
+        let separator = if i == 0 { "" } else { "," };
+        Ok(format!("{}{}", separator, serde_json::to_string(&item)?))
+    });
+    std::iter::once(Ok("[".to_string()))
+        .chain(elements)
+        .chain(std::iter::once(Ok("]".to_string())))
+}
+
+fn prometheus_chunks(items: impl Iterator<Item = CacheItem>) -> impl Iterator<Item = String> {
+    let header =
+        "# HELP doomsday_certificate_expiry_timestamp_seconds Certificate notAfter as a Unix timestamp.\n\
+         # TYPE doomsday_certificate_expiry_timestamp_seconds gauge\n"
+            .to_string();
+
+    std::iter::once(header).chain(items.map(|item| {
+        let mut chunk = String::new();
+        for path in &item.paths {
+            chunk.push_str(&format!(
+                "doomsday_certificate_expiry_timestamp_seconds{{subject=\"{}\",backend=\"{}\",path=\"{}\"}} {}\n",
+                prometheus_escape(&item.subject),
+                prometheus_escape(&path.backend),
+                prometheus_escape(&path.path),
+                item.not_after.timestamp()
+            ));
+        }
+        chunk
+    }))
+}
+
+pub(crate) fn prometheus_escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{CertificateStatus, PathObject};
+    use chrono::{Duration, TimeZone};
+
+    fn create_test_item() -> CacheItem {
+        CacheItem {
+            sha1: "abc123".to_string(),
+            subject: "CN=example.com, O=\"Example\"".to_string(),
+            not_before: None,
+            not_after: Utc.with_ymd_and_hms(2030, 1, 1, 0, 0, 0).unwrap(),
+            paths: vec![
+                PathObject {
+                    backend: "vault".into(),
+                    path: "certs/example".into(),
+                },
+                PathObject {
+                    backend: "tls".into(),
+                    path: "example.com:443".into(),
+                },
+            ],
+            status: CertificateStatus::Ok,
+            stale: false,
+            labels: [("team", "web"), ("env", "prod")]
+                .into_iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+            acknowledged: None,
+            metadata: Default::default(),
+        }
+    }
+
+    #[test]
+    fn test_parse_format() {
+        assert_eq!("CSV".parse::<ExportFormat>().unwrap(), ExportFormat::Csv);
+        assert_eq!(
+            "prometheus".parse::<ExportFormat>().unwrap(),
+            ExportFormat::Prometheus
+        );
+        assert!("xml".parse::<ExportFormat>().is_err());
+    }
+
+    fn render(items: Vec<CacheItem>, format: ExportFormat) -> Vec<String> {
+        export_chunks(items, format)
+            .collect::<crate::Result<_>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_csv_export_escapes_fields() {
+        let chunks = render(vec![create_test_item()], ExportFormat::Csv);
+        assert_eq!(chunks.len(), 2);
+        assert!(chunks[1]
+            .starts_with("\"CN=example.com, O=\"\"Example\"\"\",2030-01-01T00:00:00+00:00,"));
+        assert!(chunks[1].ends_with(
+            ",tls;vault,vault:certs/example;tls:example.com:443,ok,env=prod;team=web\n"
+        ));
+    }
+
+    #[test]
+    fn test_prometheus_export_one_sample_per_path() {
+        let mut item = create_test_item();
+        item.not_after = Utc::now() + Duration::days(1);
+        let chunks = render(vec![item.clone()], ExportFormat::Prometheus);
+        let samples: Vec<&str> = chunks[1].lines().collect();
+
+        assert_eq!(samples.len(), 2);
+        assert_eq!(
+            samples[0],
+            format!(
+                "doomsday_certificate_expiry_timestamp_seconds{{subject=\"CN=example.com, O=\\\"Example\\\"\",backend=\"vault\",path=\"certs/example\"}} {}",
+                item.not_after.timestamp()
+            )
+        );
+    }
+
+    #[test]
+    fn test_csv_export_neutralizes_formulas() {
+        let mut item = create_test_item();
+        item.subject = "=HYPERLINK(\"http://evil\")".to_string();
+        item.labels = [("team".to_string(), "@ops".to_string())].into();
+        item.paths.truncate(1);
+        item.paths[0].path = "-certs".into();
+
+        let chunks = render(vec![item], ExportFormat::Csv);
+        assert!(chunks[1].starts_with("\"'=HYPERLINK(\"\"http://evil\"\")\","));
+        assert!(chunks[1].ends_with(",vault,vault:-certs,ok,team=@ops\n"));
+        assert_eq!(csv_escape("-1"), "'-1");
+        assert_eq!(csv_escape("+cmd"), "'+cmd");
+        assert_eq!(csv_escape("@sum"), "'@sum");
+    }
+
+    #[test]
+    fn test_json_export_is_one_array() {
+        let items = vec![create_test_item(), create_test_item()];
+        let json = render(items.clone(), ExportFormat::Json).concat();
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&json).unwrap(),
+            serde_json::to_value(&items).unwrap()
+        );
+        assert_eq!(render(vec![], ExportFormat::Json).concat(), "[]");
+    }
+}
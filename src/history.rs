@@ -0,0 +1,125 @@
+use crate::cache::CertificateRenewal;
+use crate::json_store::JsonStore;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// Oldest rotations are dropped beyond this many.
+const MAX_HISTORY_ENTRIES: usize = 10_000;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RotationEvent {
+    pub rotated_at: DateTime<Utc>,
+    #[serde(flatten)]
+    pub renewal: CertificateRenewal,
+}
+
+/// Certificates that were replaced at a path by a different certificate,
+/// kept across restarts when backed by a file.
+#[derive(Debug, Clone, Default)]
+pub struct RotationHistory {
+    entries: JsonStore<Vec<RotationEvent>>,
+}
+
+impl RotationHistory {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn load(path: PathBuf) -> crate::Result<Self> {
+        let entries: JsonStore<Vec<RotationEvent>> = JsonStore::load(path.clone())?;
+        tracing::info!(
+            "Loaded {} certificate rotations from {}",
+            entries.read().len(),
+            path.display()
+        );
+        Ok(RotationHistory { entries })
+    }
+
+    pub fn record(&self, renewals: Vec<CertificateRenewal>) -> crate::Result<()> {
+        if renewals.is_empty() {
+            return Ok(());
+        }
+
+        let rotated_at = Utc::now();
+        self.entries.update(|entries| {
+            for renewal in renewals {
+                tracing::info!(
+                    "Certificate rotated at {}:{}: {} -> {}",
+                    renewal.backend,
+                    renewal.path,
+                    renewal.old_sha1,
+                    renewal.new_sha1
+                );
+                entries.push(RotationEvent {
+                    rotated_at,
+                    renewal,
+                });
+            }
+            let excess = entries.len().saturating_sub(MAX_HISTORY_ENTRIES);
+            entries.drain(..excess);
+        })
+    }
+
+    /// Rotations matching the filters, newest first.
+    pub fn list(
+        &self,
+        backend: Option<&str>,
+        path: Option<&str>,
+        limit: Option<usize>,
+    ) -> Vec<RotationEvent> {
+        self.entries
+            .read()
+            .iter()
+            .rev()
+            .filter(|e| backend.is_none_or(|b| e.renewal.backend == b))
+            .filter(|e| path.is_none_or(|p| e.renewal.path == p))
+            .take(limit.unwrap_or(usize::MAX))
+            .cloned()
+            .collect()
+    }
+
+    /// Writes rotations not yet saved. Blocks on the disk.
+    pub fn flush(&self) -> crate::Result<()> {
+        self.entries.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn renewal(path: &str, old: &str, new: &str) -> CertificateRenewal {
+        CertificateRenewal {
+            backend: "vault".to_string(),
+            path: path.to_string(),
+            subject: "CN=example.com".to_string(),
+            old_sha1: old.to_string(),
+            old_not_after: Utc::now(),
+            new_sha1: new.to_string(),
+            new_not_after: Utc::now() + chrono::Duration::days(90),
+        }
+    }
+
+    #[test]
+    fn test_history_persists_newest_first() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("history.json");
+
+        let history = RotationHistory::load(path.clone()).unwrap();
+        history.record(vec![renewal("certs/a", "1", "2")]).unwrap();
+        history
+            .record(vec![
+                renewal("certs/a", "2", "3"),
+                renewal("certs/b", "x", "y"),
+            ])
+            .unwrap();
+
+        let reloaded = RotationHistory::load(path).unwrap();
+        let a = reloaded.list(None, Some("certs/a"), None);
+        assert_eq!(a.len(), 2);
+        assert_eq!(a[0].renewal.new_sha1, "3");
+        assert_eq!(reloaded.list(Some("vault"), None, Some(1)).len(), 1);
+        assert!(reloaded.list(Some("credhub"), None, None).is_empty());
+    }
+}
@@ -0,0 +1,360 @@
+use crate::config::{HookAction, HookConfig};
+use crate::json_store::JsonStore;
+use crate::labels::matches_selector;
+use crate::types::CacheItem;
+use crate::webhooks::sign;
+use chrono::{DateTime, Duration, Utc};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::process::Stdio;
+use std::sync::{Arc, Mutex};
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+/// Oldest runs are dropped beyond this many.
+const MAX_HOOK_RUNS: usize = 10_000;
+
+/// How long a hook webhook may take to respond.
+const HOOK_WEBHOOK_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// What a hook is given about the certificate that triggered it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HookPayload {
+    pub hook: String,
+    pub within: i64,
+    pub days_until_expiry: i64,
+    pub certificate: CacheItem,
+}
+
+impl HookPayload {
+    pub fn new(hook: &HookConfig, certificate: &CacheItem, now: DateTime<Utc>) -> Self {
+        HookPayload {
+            hook: hook.name.clone(),
+            within: hook.within,
+            days_until_expiry: (certificate.not_after - now).num_days(),
+            certificate: certificate.clone(),
+        }
+    }
+}
+
+/// One time a hook ran, or was queued as a task, for a certificate.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HookRun {
+    pub ran_at: DateTime<Utc>,
+    pub hook: String,
+    /// `script`, `webhook` or `task`.
+    pub action: String,
+    pub sha1: String,
+    pub subject: String,
+    pub success: bool,
+    pub error: Option<String>,
+}
+
+/// Every hook run, so a hook fires once per certificate across restarts.
+#[derive(Debug, Clone, Default)]
+pub struct HookLog {
+    entries: JsonStore<Vec<HookRun>>,
+}
+
+impl HookLog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn load(path: PathBuf) -> crate::Result<Self> {
+        let entries: JsonStore<Vec<HookRun>> = JsonStore::load(path.clone())?;
+        tracing::info!(
+            "Loaded {} hook runs from {}",
+            entries.read().len(),
+            path.display()
+        );
+        Ok(HookLog { entries })
+    }
+
+    pub fn record(&self, run: HookRun) -> crate::Result<()> {
+        self.entries.update(|entries| {
+            entries.push(run);
+            let excess = entries.len().saturating_sub(MAX_HOOK_RUNS);
+            entries.drain(..excess);
+        })
+    }
+
+    /// (hook, sha1) pairs that have run successfully.
+    pub fn succeeded(&self) -> HashSet<(String, String)> {
+        self.entries
+            .read()
+            .iter()
+            .filter(|run| run.success)
+            .map(|run| (run.hook.clone(), run.sha1.clone()))
+            .collect()
+    }
+
+    /// Runs matching the filters, newest first.
+    pub fn list(
+        &self,
+        hook: Option<&str>,
+        sha1: Option<&str>,
+        limit: Option<usize>,
+    ) -> Vec<HookRun> {
+        self.entries
+            .read()
+            .iter()
+            .rev()
+            .filter(|run| hook.is_none_or(|h| run.hook == h))
+            .filter(|run| sha1.is_none_or(|s| run.sha1 == s))
+            .take(limit.unwrap_or(usize::MAX))
+            .cloned()
+            .collect()
+    }
+
+    /// Writes runs not yet saved. Blocks on the disk.
+    pub fn flush(&self) -> crate::Result<()> {
+        self.entries.flush()
+    }
+}
+
+/// Decides which hooks are due and runs their script and webhook actions.
+/// `task` actions are queued by `Core`, which owns the scheduler.
+#[derive(Clone)]
+pub struct HookRunner {
+    log: HookLog,
+    /// (hook, sha1) pairs whose action has not finished yet.
+    in_flight: Arc<Mutex<HashSet<(String, String)>>>,
+    client: reqwest::Client,
+}
+
+impl HookRunner {
+    pub fn new(log: HookLog) -> Self {
+        HookRunner {
+            log,
+            in_flight: Arc::new(Mutex::new(HashSet::new())),
+            client: reqwest::Client::builder()
+                .timeout(HOOK_WEBHOOK_TIMEOUT)
+                .build()
+                .unwrap_or_default(),
+        }
+    }
+
+    pub fn log(&self) -> &HookLog {
+        &self.log
+    }
+
+    /// Hooks that have not yet run for a certificate they match, claiming
+    /// each so that a check running alongside does not pick it up too.
+    /// Acknowledged certificates are skipped, as their rotation is already
+    /// in hand.
+    pub fn claim_due<'a>(
+        &self,
+        hooks: &'a [HookConfig],
+        certificates: &'a [CacheItem],
+        now: DateTime<Utc>,
+    ) -> Vec<(&'a HookConfig, &'a CacheItem)> {
+        let succeeded = self.log.succeeded();
+        let mut in_flight = self.in_flight.lock().unwrap();
+        let mut due = Vec::new();
+        for hook in hooks {
+            // Checked during config validation
+            let path = hook.path.as_deref().and_then(|p| Regex::new(p).ok());
+            for cert in certificates {
+                if cert.not_after - now > Duration::days(hook.within)
+                    || cert.acknowledged.is_some()
+                    || !matches(hook, path.as_ref(), cert)
+                    || succeeded.contains(&(hook.name.clone(), cert.sha1.clone()))
+                    || !in_flight.insert((hook.name.clone(), cert.sha1.clone()))
+                {
+                    continue;
+                }
+                due.push((hook, cert));
+            }
+        }
+        due
+    }
+
+    /// Records the outcome of a claimed hook and releases the claim. A
+    /// failed hook is tried again by the next check.
+    pub fn finish(&self, hook: &HookConfig, cert: &CacheItem, result: &crate::Result<()>) {
+        let error = result.as_ref().err().map(|e| e.to_string());
+        match &error {
+            None => tracing::info!("Hook {} ran for {}", hook.name, cert.subject),
+            Some(e) => tracing::error!("Hook {} failed for {}: {}", hook.name, cert.subject, e),
+        }
+
+        let run = HookRun {
+            ran_at: Utc::now(),
+            hook: hook.name.clone(),
+            action: hook.action.kind().to_string(),
+            sha1: cert.sha1.clone(),
+            subject: cert.subject.clone(),
+            success: error.is_none(),
+            error,
+        };
+        if let Err(e) = self.log.record(run) {
+            tracing::warn!("Failed to save hook runs: {}", e);
+        }
+        self.in_flight
+            .lock()
+            .unwrap()
+            .remove(&(hook.name.clone(), cert.sha1.clone()));
+    }
+
+    /// Runs a script or webhook action. `task` actions are not run here.
+    pub async fn run(&self, hook: &HookConfig, payload: &HookPayload) -> crate::Result<()> {
+        match &hook.action {
+            HookAction::Script {
+                command,
+                args,
+                timeout,
+            } => {
+                let timeout = std::time::Duration::from_secs(*timeout);
+                tokio::time::timeout(timeout, run_script(command, args, payload))
+                    .await
+                    .map_err(|_| {
+                        crate::DoomsdayError::internal(format!(
+                            "{} timed out after {:?}",
+                            command, timeout
+                        ))
+                    })?
+            }
+            HookAction::Webhook { url, secret } => {
+                let body = serde_json::to_string(payload)?;
+                let mut request = self
+                    .client
+                    .post(url)
+                    .header("Content-Type", "application/json")
+                    .header("X-Doomsday-Event", "hook")
+                    .body(body.clone());
+                if let Some(secret) = secret {
+                    request = request.header(
+                        "X-Doomsday-Signature",
+                        format!("sha256={}", sign(secret, body.as_bytes())),
+                    );
+                }
+
+                let response = request.send().await?;
+                if !response.status().is_success() {
+                    return Err(crate::DoomsdayError::internal(format!(
+                        "Hook webhook returned {}",
+                        response.status()
+                    )));
+                }
+                Ok(())
+            }
+            HookAction::Task { .. } => Err(crate::DoomsdayError::internal(format!(
+                "Hook {} runs as a scheduler task",
+                hook.name
+            ))),
+        }
+    }
+}
+
+/// Whether the certificate carries the hook's labels and has a location
+/// matching both its backends and path.
+fn matches(hook: &HookConfig, path: Option<&Regex>, cert: &CacheItem) -> bool {
+    if !matches_selector(&hook.labels, &cert.labels) {
+        return false;
+    }
+    if hook.backends.is_empty() && path.is_none() {
+        return true;
+    }
+    cert.paths.iter().any(|p| {
+        (hook.backends.is_empty() || hook.backends.iter().any(|b| *b == *p.backend))
+            && path.is_none_or(|path| path.is_match(&p.path))
+    })
+}
+
+/// Runs `command` with the payload as JSON on stdin and the basics in
+/// `DOOMSDAY_*` environment variables. Fails on a non-zero exit, with the
+/// command's stderr. The command is killed if the future is dropped.
+pub async fn run_script(
+    command: &str,
+    args: &[String],
+    payload: &HookPayload,
+) -> crate::Result<()> {
+    let cert = &payload.certificate;
+    let mut child = Command::new(command)
+        .args(args)
+        .env("DOOMSDAY_HOOK", &payload.hook)
+        .env("DOOMSDAY_CERT_SHA1", &cert.sha1)
+        .env("DOOMSDAY_CERT_SUBJECT", &cert.subject)
+        .env("DOOMSDAY_CERT_NOT_AFTER", cert.not_after.to_rfc3339())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| crate::DoomsdayError::internal(format!("Failed to run {}: {}", command, e)))?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        // A command that exits without reading its input is not an error
+        let _ = stdin.write_all(&serde_json::to_vec(payload)?).await;
+    }
+
+    let output = child.wait_with_output().await?;
+    if !output.status.success() {
+        return Err(crate::DoomsdayError::internal(format!(
+            "{} exited with {}: {}",
+            command,
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{CertificateStatus, PathObject};
+    use std::collections::HashMap;
+
+    #[tokio::test]
+    async fn test_hooks_run_once_per_certificate() {
+        let hooks: Vec<HookConfig> = serde_yaml::from_str(
+            r#"
+- name: rotate
+  within: 14
+  backends: [vault]
+  path: "^secret/prod/"
+  action:
+    type: script
+    command: sh
+    args: ["-c", "grep -q '\"hook\":\"rotate\"' && test \"$DOOMSDAY_CERT_SHA1\" = abc"]
+"#,
+        )
+        .unwrap();
+        let now = Utc::now();
+        let cert = |sha1: &str, days: i64, path: &str| CacheItem {
+            sha1: sha1.to_string(),
+            subject: format!("CN={}", sha1),
+            not_before: None,
+            not_after: now + Duration::days(days),
+            paths: vec![PathObject::new("vault", path)],
+            status: CertificateStatus::ExpiringSoon,
+            stale: false,
+            labels: HashMap::new(),
+            acknowledged: None,
+            metadata: Default::default(),
+        };
+        let certs = vec![
+            cert("abc", 10, "secret/prod/api"),
+            cert("def", 30, "secret/prod/web"),
+            cert("ghi", 10, "secret/dev/api"),
+        ];
+
+        let runner = HookRunner::new(HookLog::new());
+        let due = runner.claim_due(&hooks, &certs, now);
+        assert_eq!(due.len(), 1);
+        let (hook, cert) = due[0];
+        assert_eq!(cert.sha1, "abc");
+        // Claimed, so not due again while it runs
+        assert!(runner.claim_due(&hooks, &certs, now).is_empty());
+
+        let result = runner.run(hook, &HookPayload::new(hook, cert, now)).await;
+        assert!(result.is_ok(), "{:?}", result);
+        runner.finish(hook, cert, &result);
+        assert!(runner.claim_due(&hooks, &certs, now).is_empty());
+        assert_eq!(runner.log().list(Some("rotate"), None, None).len(), 1);
+    }
+}
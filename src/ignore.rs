@@ -0,0 +1,439 @@
+use crate::json_store::JsonStore;
+use crate::pinning::normalize_fingerprint;
+use crate::types::CacheObject;
+use chrono::{DateTime, Utc};
+use regex::Regex;
+use serde::{Deserialize, Serialize, Serializer};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IgnoredCertificate {
+    pub sha1: String,
+    pub subject: String,
+    pub ignored_at: DateTime<Utc>,
+    pub reason: Option<String>,
+    /// The certificate shows up again after this time.
+    #[serde(default)]
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+impl IgnoredCertificate {
+    fn is_active(&self, now: DateTime<Utc>) -> bool {
+        self.expires_at.is_none_or(|expires_at| expires_at > now)
+    }
+}
+
+/// Hides every certificate matching all of the rule's set matchers.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IgnoreRule {
+    /// Assigned when the rule is created through the API or loaded from the
+    /// config.
+    #[serde(default)]
+    pub id: String,
+    /// Regex matched against each of the certificate's paths.
+    pub path: Option<String>,
+    /// Subject DN or common name, compared case-insensitively.
+    pub subject: Option<String>,
+    /// SHA1 or SHA256 fingerprint, hex encoded (colons and case are ignored).
+    pub fingerprint: Option<String>,
+    pub reason: Option<String>,
+    /// The rule stops applying after this time.
+    pub expires_at: Option<DateTime<Utc>>,
+    /// Rules from the config file can only be changed there.
+    #[serde(default, skip_deserializing)]
+    pub from_config: bool,
+}
+
+impl IgnoreRule {
+    pub fn validate(&self) -> crate::Result<()> {
+        if self.path.is_none() && self.subject.is_none() && self.fingerprint.is_none() {
+            return Err(crate::DoomsdayError::config(
+                "Ignore rule must set at least one of path, subject or fingerprint",
+            ));
+        }
+        if self
+            .fingerprint
+            .as_deref()
+            .is_some_and(|f| normalize_fingerprint(f).is_empty())
+        {
+            return Err(crate::DoomsdayError::config(
+                "Ignore rule fingerprint must be hex encoded",
+            ));
+        }
+        CompiledRule::new(self.clone()).map(|_| ())
+    }
+}
+
+/// Saved as the rule it was compiled from.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(try_from = "IgnoreRule")]
+struct CompiledRule {
+    rule: IgnoreRule,
+    path: Option<Regex>,
+    fingerprint: Option<String>,
+}
+
+impl TryFrom<IgnoreRule> for CompiledRule {
+    type Error = crate::DoomsdayError;
+
+    fn try_from(rule: IgnoreRule) -> crate::Result<Self> {
+        CompiledRule::new(rule)
+    }
+}
+
+impl Serialize for CompiledRule {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.rule.serialize(serializer)
+    }
+}
+
+/// Ignored certificates by SHA1, saved as a list.
+#[derive(Debug, Default, Deserialize)]
+#[serde(from = "Vec<IgnoredCertificate>")]
+struct IgnoredCertificates(HashMap<String, IgnoredCertificate>);
+
+impl From<Vec<IgnoredCertificate>> for IgnoredCertificates {
+    fn from(list: Vec<IgnoredCertificate>) -> Self {
+        IgnoredCertificates(list.into_iter().map(|e| (e.sha1.clone(), e)).collect())
+    }
+}
+
+impl Serialize for IgnoredCertificates {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        // Expired ignores are dropped here rather than kept forever
+        let now = Utc::now();
+        let mut list: Vec<&IgnoredCertificate> =
+            self.0.values().filter(|e| e.is_active(now)).collect();
+        list.sort_by_key(|e| e.ignored_at);
+        serializer.collect_seq(list)
+    }
+}
+
+impl CompiledRule {
+    fn new(rule: IgnoreRule) -> crate::Result<Self> {
+        let path = rule
+            .path
+            .as_deref()
+            .map(Regex::new)
+            .transpose()
+            .map_err(|e| {
+                crate::DoomsdayError::config(format!("Invalid ignore rule path regex: {}", e))
+            })?;
+        let fingerprint = rule.fingerprint.as_deref().map(normalize_fingerprint);
+        Ok(CompiledRule {
+            rule,
+            path,
+            fingerprint,
+        })
+    }
+
+    fn matches(&self, object: &CacheObject, now: DateTime<Utc>) -> bool {
+        if self
+            .rule
+            .expires_at
+            .is_some_and(|expires_at| expires_at <= now)
+        {
+            return false;
+        }
+        if let Some(path) = &self.path {
+            if !object.paths.iter().any(|p| path.is_match(&p.path)) {
+                return false;
+            }
+        }
+        if let Some(subject) = &self.rule.subject {
+            let cn = object
+                .subject
+                .split(',')
+                .find_map(|part| part.trim().strip_prefix("CN="));
+            if !object.subject.eq_ignore_ascii_case(subject)
+                && !cn.is_some_and(|cn| cn.eq_ignore_ascii_case(subject))
+            {
+                return false;
+            }
+        }
+        if let Some(fingerprint) = &self.fingerprint {
+            if normalize_fingerprint(&object.sha1) != *fingerprint
+                && normalize_fingerprint(&object.metadata.fingerprint_sha256) != *fingerprint
+            {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// Certificates hidden from listings, stats and notifications, either one by
+/// one or through rules, kept across restarts when backed by files.
+#[derive(Debug, Clone, Default)]
+pub struct IgnoreList {
+    entries: JsonStore<IgnoredCertificates>,
+    rules: JsonStore<Vec<CompiledRule>>,
+    config_rules: Arc<RwLock<Vec<CompiledRule>>>,
+}
+
+impl IgnoreList {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn load(path: PathBuf, rules_path: PathBuf) -> crate::Result<Self> {
+        let entries: JsonStore<IgnoredCertificates> = JsonStore::load(path.clone())?;
+        tracing::info!(
+            "Loaded {} ignored certificates from {}",
+            entries.read().0.len(),
+            path.display()
+        );
+
+        let rules: JsonStore<Vec<CompiledRule>> = JsonStore::load(rules_path.clone())?;
+        tracing::info!(
+            "Loaded {} ignore rules from {}",
+            rules.read().len(),
+            rules_path.display()
+        );
+
+        Ok(IgnoreList {
+            entries,
+            rules,
+            config_rules: Arc::default(),
+        })
+    }
+
+    /// Whether the certificate is ignored by fingerprint alone. Rules are only
+    /// checked by `matches`, which needs the whole certificate.
+    pub fn contains(&self, sha1: &str) -> bool {
+        self.entries
+            .read()
+            .0
+            .get(sha1)
+            .is_some_and(|e| e.is_active(Utc::now()))
+    }
+
+    /// Whether the certificate is ignored, individually or by any active rule.
+    pub fn matches(&self, object: &CacheObject) -> bool {
+        let now = Utc::now();
+        if self
+            .entries
+            .read()
+            .0
+            .get(&object.sha1)
+            .is_some_and(|e| e.is_active(now))
+        {
+            return true;
+        }
+
+        self.config_rules
+            .read()
+            .unwrap()
+            .iter()
+            .chain(self.rules.read().iter())
+            .any(|rule| rule.matches(object, now))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        let now = Utc::now();
+        !self.entries.read().0.values().any(|e| e.is_active(now))
+            && self.config_rules.read().unwrap().is_empty()
+            && self.rules.read().is_empty()
+    }
+
+    /// Ignored certificates, leaving out ignores that have expired.
+    pub fn list(&self) -> Vec<IgnoredCertificate> {
+        let now = Utc::now();
+        let mut list: Vec<IgnoredCertificate> = self
+            .entries
+            .read()
+            .0
+            .values()
+            .filter(|e| e.is_active(now))
+            .cloned()
+            .collect();
+        list.sort_by_key(|e| e.ignored_at);
+        list
+    }
+
+    /// Config rules first, then rules added through the API. Expired rules
+    /// are included so they can be cleaned up.
+    pub fn rules(&self) -> Vec<IgnoreRule> {
+        self.config_rules
+            .read()
+            .unwrap()
+            .iter()
+            .chain(self.rules.read().iter())
+            .map(|r| r.rule.clone())
+            .collect()
+    }
+
+    /// Replaces the rules defined in the config file.
+    pub fn set_config_rules(&self, rules: &[IgnoreRule]) -> crate::Result<()> {
+        let compiled = rules
+            .iter()
+            .enumerate()
+            .map(|(i, rule)| {
+                CompiledRule::new(IgnoreRule {
+                    id: format!("config-{}", i + 1),
+                    from_config: true,
+                    ..rule.clone()
+                })
+            })
+            .collect::<crate::Result<Vec<_>>>()?;
+        *self.config_rules.write().unwrap() = compiled;
+        Ok(())
+    }
+
+    pub fn add_rule(&self, mut rule: IgnoreRule) -> crate::Result<IgnoreRule> {
+        rule.validate()?;
+        rule.id = uuid::Uuid::new_v4().to_string();
+        rule.from_config = false;
+        tracing::info!("Adding ignore rule {}: {:?}", rule.id, rule);
+
+        let compiled = CompiledRule::new(rule.clone())?;
+        self.rules.update(|rules| rules.push(compiled))?;
+        Ok(rule)
+    }
+
+    pub fn remove_rule(&self, id: &str) -> crate::Result<Option<IgnoreRule>> {
+        if !self.rules.read().iter().any(|r| r.rule.id == id) {
+            return Ok(None);
+        }
+        let removed = self.rules.update(|rules| {
+            rules
+                .iter()
+                .position(|r| r.rule.id == id)
+                .map(|i| rules.remove(i).rule)
+        })?;
+        tracing::info!("Removed ignore rule {}", id);
+        Ok(removed)
+    }
+
+    pub fn ignore(&self, entry: IgnoredCertificate) -> crate::Result<()> {
+        tracing::info!("Ignoring certificate {} ({})", entry.subject, entry.sha1);
+        self.entries.update(|entries| {
+            entries.0.insert(entry.sha1.clone(), entry);
+        })
+    }
+
+    pub fn unignore(&self, sha1: &str) -> crate::Result<Option<IgnoredCertificate>> {
+        if !self.entries.read().0.contains_key(sha1) {
+            return Ok(None);
+        }
+        let removed = self.entries.update(|entries| entries.0.remove(sha1))?;
+        tracing::info!("Certificate {} is no longer ignored", sha1);
+        Ok(removed)
+    }
+
+    /// Writes ignores and rules not yet saved. Blocks on the disk.
+    pub fn flush(&self) -> crate::Result<()> {
+        self.entries.flush()?;
+        self.rules.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ignore_list_persists() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("ignored.json");
+
+        let rules_path = dir.path().join("ignore_rules.json");
+
+        let list = IgnoreList::load(path.clone(), rules_path.clone()).unwrap();
+        list.ignore(IgnoredCertificate {
+            sha1: "abc".to_string(),
+            subject: "CN=old.example.com".to_string(),
+            ignored_at: Utc::now(),
+            reason: Some("decommissioned".to_string()),
+            expires_at: None,
+        })
+        .unwrap();
+
+        let reloaded = IgnoreList::load(path.clone(), rules_path.clone()).unwrap();
+        assert!(reloaded.contains("abc"));
+
+        reloaded.unignore("abc").unwrap();
+        assert!(!IgnoreList::load(path, rules_path).unwrap().contains("abc"));
+    }
+
+    fn object(subject: &str, path: &str) -> CacheObject {
+        CacheObject {
+            subject: subject.to_string(),
+            not_before: None,
+            not_after: Utc::now(),
+            sha1: "AB:CD:EF".to_string(),
+            paths: vec![crate::types::PathObject {
+                backend: "vault".into(),
+                path: path.into(),
+            }],
+            metadata: Default::default(),
+        }
+    }
+
+    fn rule() -> IgnoreRule {
+        IgnoreRule {
+            id: String::new(),
+            path: None,
+            subject: None,
+            fingerprint: None,
+            reason: None,
+            expires_at: None,
+            from_config: false,
+        }
+    }
+
+    #[test]
+    fn test_ignore_rules_match() {
+        let dir = tempfile::tempdir().unwrap();
+        let rules_path = dir.path().join("ignore_rules.json");
+        let list = IgnoreList::load(dir.path().join("ignored.json"), rules_path.clone()).unwrap();
+
+        let test_cert = object("CN=test.example.com,O=Example", "secret/test/tls");
+        let prod_cert = object("CN=api.example.com", "secret/prod/tls");
+
+        assert!(list.add_rule(rule()).is_err());
+        let added = list
+            .add_rule(IgnoreRule {
+                path: Some("^secret/test/".to_string()),
+                ..rule()
+            })
+            .unwrap();
+        assert!(list.matches(&test_cert));
+        assert!(!list.matches(&prod_cert));
+
+        // Every set matcher must match
+        list.set_config_rules(&[IgnoreRule {
+            subject: Some("API.example.com".to_string()),
+            fingerprint: Some("abcdef".to_string()),
+            ..rule()
+        }])
+        .unwrap();
+        assert!(list.matches(&prod_cert));
+        list.set_config_rules(&[IgnoreRule {
+            subject: Some("api.example.com".to_string()),
+            fingerprint: Some("00".to_string()),
+            ..rule()
+        }])
+        .unwrap();
+        assert!(!list.matches(&prod_cert));
+
+        // Expired rules no longer apply
+        list.set_config_rules(&[IgnoreRule {
+            subject: Some("api.example.com".to_string()),
+            expires_at: Some(Utc::now() - chrono::Duration::hours(1)),
+            ..rule()
+        }])
+        .unwrap();
+        assert!(!list.matches(&prod_cert));
+        assert!(list.rules()[0].from_config);
+
+        // API rules persist, config rules do not
+        let reloaded = IgnoreList::load(dir.path().join("ignored.json"), rules_path).unwrap();
+        assert_eq!(reloaded.rules().len(), 1);
+        assert!(reloaded.matches(&test_cert));
+        assert!(reloaded.remove_rule(&added.id).unwrap().is_some());
+        assert!(!reloaded.matches(&test_cert));
+    }
+}
@@ -0,0 +1,177 @@
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, RwLock, RwLockReadGuard};
+use std::time::Duration;
+
+/// How long after a change the file is saved, so a burst of changes is
+/// written once.
+const SAVE_DELAY: Duration = Duration::from_secs(1);
+
+/// State kept as a single JSON document, such as the alert log or the
+/// notification history, shared by every clone. When backed by a file,
+/// changes are saved shortly after they happen on the blocking pool, and
+/// each save renames a complete copy over the file so a crash mid-write
+/// cannot truncate it.
+#[derive(Debug)]
+pub struct JsonStore<T> {
+    data: Arc<RwLock<T>>,
+    file: Option<Arc<StoreFile>>,
+}
+
+#[derive(Debug)]
+struct StoreFile {
+    path: PathBuf,
+    /// Changed since the data was last read for a save.
+    pending: AtomicBool,
+    /// A background save is waiting to run.
+    scheduled: AtomicBool,
+    // Serializes writers so an older copy never replaces a newer one
+    lock: Mutex<()>,
+}
+
+impl<T> Clone for JsonStore<T> {
+    fn clone(&self) -> Self {
+        JsonStore {
+            data: self.data.clone(),
+            file: self.file.clone(),
+        }
+    }
+}
+
+impl<T: Default> Default for JsonStore<T> {
+    fn default() -> Self {
+        JsonStore {
+            data: Arc::new(RwLock::new(T::default())),
+            file: None,
+        }
+    }
+}
+
+impl<T> JsonStore<T>
+where
+    T: Serialize + DeserializeOwned + Default + Send + Sync + 'static,
+{
+    /// An in-memory store, for running without `server.data_dir`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reads the store saved at `path`, or starts empty if there is none.
+    pub fn load(path: PathBuf) -> crate::Result<Self> {
+        let data = if path.exists() {
+            serde_json::from_str(&fs::read_to_string(&path)?)?
+        } else {
+            T::default()
+        };
+
+        Ok(JsonStore {
+            data: Arc::new(RwLock::new(data)),
+            file: Some(Arc::new(StoreFile {
+                path,
+                pending: AtomicBool::new(false),
+                scheduled: AtomicBool::new(false),
+                lock: Mutex::new(()),
+            })),
+        })
+    }
+
+    pub fn read(&self) -> RwLockReadGuard<'_, T> {
+        self.data.read().unwrap()
+    }
+
+    /// Changes the data and schedules a save. Outside a Tokio runtime, as
+    /// in tests, the save happens before returning.
+    pub fn update<R>(&self, change: impl FnOnce(&mut T) -> R) -> crate::Result<R> {
+        let result = change(&mut self.data.write().unwrap());
+
+        let Some(file) = &self.file else {
+            return Ok(result);
+        };
+        file.pending.store(true, Ordering::SeqCst);
+        match tokio::runtime::Handle::try_current() {
+            // Later changes are picked up by the save already waiting
+            Ok(_) if file.scheduled.swap(true, Ordering::SeqCst) => {}
+            Ok(runtime) => {
+                let (store, file) = (self.clone(), file.clone());
+                runtime.spawn(async move {
+                    tokio::time::sleep(SAVE_DELAY).await;
+                    file.scheduled.store(false, Ordering::SeqCst);
+                    let saving = store.clone();
+                    match tokio::task::spawn_blocking(move || saving.flush()).await {
+                        Ok(Ok(())) => {}
+                        Ok(Err(e)) => tracing::error!("Failed to save {}: {}", store.describe(), e),
+                        Err(e) => tracing::error!("Saving {} failed: {}", store.describe(), e),
+                    }
+                });
+            }
+            Err(_) => self.flush()?,
+        }
+        Ok(result)
+    }
+
+    /// Writes changes not yet saved. Blocks on the disk.
+    pub fn flush(&self) -> crate::Result<()> {
+        let Some(file) = &self.file else {
+            return Ok(());
+        };
+        let _guard = file.lock.lock().unwrap();
+        // Cleared before reading, so a change made meanwhile is saved again
+        if !file.pending.swap(false, Ordering::SeqCst) {
+            return Ok(());
+        }
+
+        let content = serde_json::to_string_pretty(&*self.read())?;
+        if let Some(parent) = file.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut tmp = file.path.clone().into_os_string();
+        tmp.push(".tmp");
+        fs::write(&tmp, content)?;
+        fs::rename(&tmp, &file.path)?;
+        Ok(())
+    }
+
+    fn describe(&self) -> String {
+        match &self.file {
+            Some(file) => file.path.display().to_string(),
+            None => "in-memory store".to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_changes_are_saved_in_the_background() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state.json");
+
+        let store: JsonStore<Vec<u32>> = JsonStore::load(path.clone()).unwrap();
+        for i in 0..3 {
+            store.update(|entries| entries.push(i)).unwrap();
+        }
+        // Nothing is written on the calling task
+        assert!(!path.exists());
+
+        tokio::time::sleep(SAVE_DELAY * 2).await;
+        let saved: Vec<u32> = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(saved, vec![0, 1, 2]);
+
+        // Flushing writes what is pending without waiting
+        store.update(|entries| entries.push(3)).unwrap();
+        store.flush().unwrap();
+        assert_eq!(
+            JsonStore::<Vec<u32>>::load(path.clone())
+                .unwrap()
+                .read()
+                .len(),
+            4
+        );
+        assert!(!dir.path().join("state.json.tmp").exists());
+    }
+}
@@ -0,0 +1,191 @@
+use crate::types::CacheObject;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+/// Attaches labels (e.g. team, environment, criticality) to every certificate
+/// matching all of the rule's set matchers.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LabelRule {
+    /// Backend the certificate was found on.
+    pub backend: Option<String>,
+    /// Regex matched against the certificate's paths.
+    pub path: Option<String>,
+    /// Regex matched against the certificate's subject DN.
+    pub subject: Option<String>,
+    pub labels: HashMap<String, String>,
+}
+
+impl LabelRule {
+    pub fn validate(&self) -> crate::Result<()> {
+        if self.labels.is_empty() {
+            return Err(crate::DoomsdayError::config(
+                "Label rule must set at least one label",
+            ));
+        }
+        CompiledRule::new(self.clone()).map(|_| ())
+    }
+}
+
+#[derive(Debug, Clone)]
+struct CompiledRule {
+    rule: LabelRule,
+    path: Option<Regex>,
+    subject: Option<Regex>,
+}
+
+impl CompiledRule {
+    fn new(rule: LabelRule) -> crate::Result<Self> {
+        let compile = |field: &str, pattern: Option<&str>| {
+            pattern.map(Regex::new).transpose().map_err(|e| {
+                crate::DoomsdayError::config(format!("Invalid label rule {} regex: {}", field, e))
+            })
+        };
+        let path = compile("path", rule.path.as_deref())?;
+        let subject = compile("subject", rule.subject.as_deref())?;
+        Ok(CompiledRule {
+            rule,
+            path,
+            subject,
+        })
+    }
+
+    fn matches(&self, object: &CacheObject) -> bool {
+        if let Some(subject) = &self.subject {
+            if !subject.is_match(&object.subject) {
+                return false;
+            }
+        }
+
+        // Backend and path must hold for the same location
+        if self.rule.backend.is_none() && self.path.is_none() {
+            return true;
+        }
+        object.paths.iter().any(|p| {
+            self.rule
+                .backend
+                .as_deref()
+                .is_none_or(|b| *p.backend == *b)
+                && self.path.as_ref().is_none_or(|path| path.is_match(&p.path))
+        })
+    }
+}
+
+/// Label rules from the config, shared by every clone of the cache.
+#[derive(Debug, Clone, Default)]
+pub struct LabelRules {
+    rules: Arc<RwLock<Arc<Vec<CompiledRule>>>>,
+}
+
+impl LabelRules {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set_rules(&self, rules: &[LabelRule]) -> crate::Result<()> {
+        let compiled = rules
+            .iter()
+            .cloned()
+            .map(CompiledRule::new)
+            .collect::<crate::Result<Vec<_>>>()?;
+        *self.rules.write().unwrap() = Arc::new(compiled);
+        Ok(())
+    }
+
+    /// Labels from every matching rule. Where rules set the same label, the
+    /// one listed last wins.
+    pub fn labels_for(&self, object: &CacheObject) -> HashMap<String, String> {
+        let rules = self.rules.read().unwrap().clone();
+        let mut labels = HashMap::new();
+        for rule in rules.iter().filter(|rule| rule.matches(object)) {
+            labels.extend(rule.rule.labels.clone());
+        }
+        labels
+    }
+}
+
+/// Whether `labels` carries every label in `selector`. An empty selector
+/// matches everything.
+pub fn matches_selector(
+    selector: &HashMap<String, String>,
+    labels: &HashMap<String, String>,
+) -> bool {
+    selector
+        .iter()
+        .all(|(key, value)| labels.get(key) == Some(value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::PathObject;
+    use chrono::Utc;
+
+    fn rule(labels: &[(&str, &str)]) -> LabelRule {
+        LabelRule {
+            backend: None,
+            path: None,
+            subject: None,
+            labels: labels
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_labels_from_matching_rules() {
+        let object = CacheObject {
+            subject: "CN=api.payments.example.com".to_string(),
+            not_before: None,
+            not_after: Utc::now(),
+            sha1: "abc".to_string(),
+            paths: vec![
+                PathObject::new("vault", "secret/prod/payments/tls"),
+                PathObject::new("credhub", "/staging/payments"),
+            ],
+            metadata: Default::default(),
+        };
+
+        let rules = LabelRules::new();
+        rules
+            .set_rules(&[
+                LabelRule {
+                    path: Some("^secret/prod/".to_string()),
+                    ..rule(&[("environment", "production"), ("criticality", "low")])
+                },
+                LabelRule {
+                    subject: Some(r"\.payments\.".to_string()),
+                    ..rule(&[("team", "payments"), ("criticality", "high")])
+                },
+                // The path exists, but not on this backend
+                LabelRule {
+                    backend: Some("credhub".to_string()),
+                    path: Some("^secret/prod/".to_string()),
+                    ..rule(&[("owner", "nobody")])
+                },
+            ])
+            .unwrap();
+
+        let labels = rules.labels_for(&object);
+        assert_eq!(labels.len(), 3);
+        assert_eq!(labels["environment"], "production");
+        assert_eq!(labels["team"], "payments");
+        assert_eq!(labels["criticality"], "high");
+
+        let selector = HashMap::from([("team".to_string(), "payments".to_string())]);
+        assert!(matches_selector(&selector, &labels));
+        assert!(matches_selector(&HashMap::new(), &labels));
+        assert!(!matches_selector(&selector, &HashMap::new()));
+
+        assert!(rule(&[]).validate().is_err());
+        assert!(LabelRule {
+            path: Some("(".to_string()),
+            ..rule(&[("a", "b")])
+        }
+        .validate()
+        .is_err());
+    }
+}
@@ -1,15 +1,34 @@
+pub mod alerts;
 pub mod auth;
 pub mod backends;
 pub mod cache;
+pub mod cache_store;
+pub mod client_secrets;
 pub mod config;
+pub mod config_layers;
 pub mod core;
 pub mod duration;
 pub mod error;
+pub mod export;
+pub mod history;
+pub mod hooks;
+pub mod ignore;
+pub mod json_store;
+pub mod labels;
+pub mod metrics;
+pub mod notification_history;
 pub mod notifications;
+pub mod pinning;
+pub mod quiet;
+pub mod redis;
+pub mod schedule;
 pub mod scheduler;
 pub mod server;
+pub mod shared_cache;
 pub mod storage;
+pub mod task_store;
 pub mod types;
 pub mod version;
+pub mod webhooks;
 
 pub use error::{DoomsdayError, Result};
@@ -0,0 +1,136 @@
+use crate::export::prometheus_escape;
+use crate::types::SchedulerInfo;
+use std::fmt::Write;
+
+/// Renders scheduler state and per-task-type statistics in the Prometheus
+/// text exposition format.
+pub fn render_scheduler_metrics(info: &SchedulerInfo) -> String {
+    let mut out = String::new();
+
+    let gauges = [
+        (
+            "doomsday_scheduler_workers",
+            "Scheduler worker count.",
+            info.workers as u64,
+        ),
+        (
+            "doomsday_scheduler_pending_tasks",
+            "Tasks waiting to run, including those waiting to be retried.",
+            info.pending_tasks as u64,
+        ),
+        (
+            "doomsday_scheduler_running_tasks",
+            "Tasks currently running.",
+            info.running_tasks as u64,
+        ),
+        (
+            "doomsday_scheduler_paused",
+            "1 while scheduled refreshes are paused.",
+            info.paused as u64,
+        ),
+    ];
+    for (name, help, value) in gauges {
+        let _ = writeln!(
+            out,
+            "# HELP {} {}\n# TYPE {} gauge\n{} {}",
+            name, help, name, name, value
+        );
+    }
+
+    out.push_str(
+        "# HELP doomsday_scheduler_task_executions_total Task executions by outcome; each retry attempt counts once.\n\
+         # TYPE doomsday_scheduler_task_executions_total counter\n",
+    );
+    for stats in &info.task_stats {
+        let labels = labels(&stats.task_type, &stats.backend);
+        let _ = writeln!(
+            out,
+            "doomsday_scheduler_task_executions_total{{{},result=\"success\"}} {}",
+            labels, stats.succeeded
+        );
+        let _ = writeln!(
+            out,
+            "doomsday_scheduler_task_executions_total{{{},result=\"failure\"}} {}",
+            labels, stats.failed
+        );
+    }
+
+    out.push_str(
+        "# HELP doomsday_scheduler_task_duration_milliseconds Task execution duration; quantiles cover recent executions.\n\
+         # TYPE doomsday_scheduler_task_duration_milliseconds summary\n",
+    );
+    for stats in &info.task_stats {
+        let labels = labels(&stats.task_type, &stats.backend);
+        for (quantile, value) in [
+            ("0.5", stats.p50_duration_ms),
+            ("0.95", stats.p95_duration_ms),
+            ("0.99", stats.p99_duration_ms),
+        ] {
+            let _ = writeln!(
+                out,
+                "doomsday_scheduler_task_duration_milliseconds{{{},quantile=\"{}\"}} {}",
+                labels, quantile, value
+            );
+        }
+        let _ = writeln!(
+            out,
+            "doomsday_scheduler_task_duration_milliseconds_sum{{{}}} {}",
+            labels, stats.total_duration_ms
+        );
+        let _ = writeln!(
+            out,
+            "doomsday_scheduler_task_duration_milliseconds_count{{{}}} {}",
+            labels,
+            stats.succeeded + stats.failed
+        );
+    }
+
+    out
+}
+
+fn labels(task_type: &str, backend: &str) -> String {
+    format!(
+        "task_type=\"{}\",backend=\"{}\"",
+        prometheus_escape(task_type),
+        prometheus_escape(backend)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::TaskTypeStats;
+
+    #[test]
+    fn test_render_scheduler_metrics() {
+        let info = SchedulerInfo {
+            workers: 4,
+            pending_tasks: 1,
+            running_tasks: 2,
+            paused: true,
+            task_stats: vec![TaskTypeStats {
+                task_type: "refresh_backend".to_string(),
+                backend: "vault".to_string(),
+                succeeded: 9,
+                failed: 1,
+                total_duration_ms: 5000,
+                avg_duration_ms: 500,
+                p50_duration_ms: 400,
+                p95_duration_ms: 900,
+                p99_duration_ms: 1200,
+            }],
+        };
+
+        let metrics = render_scheduler_metrics(&info);
+        assert!(metrics.contains("doomsday_scheduler_paused 1\n"));
+        assert!(metrics.contains(
+            "doomsday_scheduler_task_executions_total{task_type=\"refresh_backend\",backend=\"vault\",result=\"failure\"} 1\n"
+        ));
+        assert!(metrics.contains(
+            "doomsday_scheduler_task_duration_milliseconds{task_type=\"refresh_backend\",backend=\"vault\",quantile=\"0.95\"} 900\n"
+        ));
+        assert!(metrics.contains(
+            "doomsday_scheduler_task_duration_milliseconds_count{task_type=\"refresh_backend\",backend=\"vault\"} 10\n"
+        ));
+    }
+}
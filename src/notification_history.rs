@@ -0,0 +1,228 @@
+use crate::json_store::JsonStore;
+use crate::notifications::NotificationUrgency;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// Oldest deliveries are dropped beyond this many.
+const MAX_HISTORY_ENTRIES: usize = 10_000;
+
+/// Oldest dead letters are dropped beyond this many.
+const MAX_DEAD_LETTERS: usize = 1_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DeliveryStatus {
+    Sent,
+    Failed,
+    /// Held during a quiet window, to be summarized once it ends.
+    Held,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeliveredCertificate {
+    pub sha1: String,
+    pub subject: String,
+}
+
+/// One attempt to send a notification via one backend.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NotificationDelivery {
+    pub attempted_at: DateTime<Utc>,
+    /// The notification backend's name, or its type when unnamed.
+    pub backend: String,
+    pub title: String,
+    pub urgency: NotificationUrgency,
+    pub certificates: Vec<DeliveredCertificate>,
+    /// 1 for the first attempt, counting up through retries.
+    #[serde(default = "default_attempt")]
+    pub attempt: u32,
+    pub status: DeliveryStatus,
+    pub error: Option<String>,
+}
+
+fn default_attempt() -> u32 {
+    1
+}
+
+/// A notification that still failed after its last retry.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeadLetter {
+    pub failed_at: DateTime<Utc>,
+    pub backend: String,
+    pub title: String,
+    pub body: String,
+    pub urgency: NotificationUrgency,
+    pub certificates: Vec<DeliveredCertificate>,
+    pub attempts: u32,
+    pub error: String,
+}
+
+/// Every notification the server tried to send, so operators can check
+/// whether a certificate that expired unnoticed was ever alerted.
+#[derive(Debug, Clone, Default)]
+pub struct NotificationHistory {
+    entries: JsonStore<Vec<NotificationDelivery>>,
+}
+
+impl NotificationHistory {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn load(path: PathBuf) -> crate::Result<Self> {
+        let entries: JsonStore<Vec<NotificationDelivery>> = JsonStore::load(path.clone())?;
+        tracing::info!(
+            "Loaded {} notification deliveries from {}",
+            entries.read().len(),
+            path.display()
+        );
+        Ok(NotificationHistory { entries })
+    }
+
+    pub fn record(&self, delivery: NotificationDelivery) -> crate::Result<()> {
+        self.entries.update(|entries| {
+            entries.push(delivery);
+            let excess = entries.len().saturating_sub(MAX_HISTORY_ENTRIES);
+            entries.drain(..excess);
+        })
+    }
+
+    /// Deliveries matching the filters, newest first. `sha1` matches
+    /// deliveries that included the certificate.
+    pub fn list(
+        &self,
+        backend: Option<&str>,
+        sha1: Option<&str>,
+        status: Option<DeliveryStatus>,
+        limit: Option<usize>,
+    ) -> Vec<NotificationDelivery> {
+        self.entries
+            .read()
+            .iter()
+            .rev()
+            .filter(|d| backend.is_none_or(|b| d.backend == b))
+            .filter(|d| sha1.is_none_or(|s| d.certificates.iter().any(|c| c.sha1 == s)))
+            .filter(|d| status.is_none_or(|s| d.status == s))
+            .take(limit.unwrap_or(usize::MAX))
+            .cloned()
+            .collect()
+    }
+
+    /// Writes deliveries not yet saved. Blocks on the disk.
+    pub fn flush(&self) -> crate::Result<()> {
+        self.entries.flush()
+    }
+}
+
+/// Notifications given up on, kept so they can be found and resent by
+/// hand.
+#[derive(Debug, Clone, Default)]
+pub struct DeadLetters {
+    entries: JsonStore<Vec<DeadLetter>>,
+}
+
+impl DeadLetters {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn load(path: PathBuf) -> crate::Result<Self> {
+        let entries: JsonStore<Vec<DeadLetter>> = JsonStore::load(path.clone())?;
+        tracing::info!(
+            "Loaded {} undeliverable notifications from {}",
+            entries.read().len(),
+            path.display()
+        );
+        Ok(DeadLetters { entries })
+    }
+
+    pub fn record(&self, letter: DeadLetter) -> crate::Result<()> {
+        tracing::error!(
+            "Giving up on \"{}\" via {} after {} attempt(s): {}",
+            letter.title,
+            letter.backend,
+            letter.attempts,
+            letter.error
+        );
+        self.entries.update(|entries| {
+            entries.push(letter);
+            let excess = entries.len().saturating_sub(MAX_DEAD_LETTERS);
+            entries.drain(..excess);
+        })
+    }
+
+    /// Dead letters, newest first.
+    pub fn list(&self, backend: Option<&str>, limit: Option<usize>) -> Vec<DeadLetter> {
+        self.entries
+            .read()
+            .iter()
+            .rev()
+            .filter(|l| backend.is_none_or(|b| l.backend == b))
+            .take(limit.unwrap_or(usize::MAX))
+            .cloned()
+            .collect()
+    }
+
+    /// Writes dead letters not yet saved. Blocks on the disk.
+    pub fn flush(&self) -> crate::Result<()> {
+        self.entries.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn delivery(backend: &str, sha1s: &[&str], status: DeliveryStatus) -> NotificationDelivery {
+        NotificationDelivery {
+            attempted_at: Utc::now(),
+            backend: backend.to_string(),
+            title: "⚠️ Expired Certificates".to_string(),
+            urgency: NotificationUrgency::Critical,
+            certificates: sha1s
+                .iter()
+                .map(|sha1| DeliveredCertificate {
+                    sha1: sha1.to_string(),
+                    subject: "CN=example.com".to_string(),
+                })
+                .collect(),
+            attempt: 1,
+            status,
+            error: (status == DeliveryStatus::Failed).then(|| "HTTP 500".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_deliveries_filtered_newest_first() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("notifications.json");
+
+        let history = NotificationHistory::load(path.clone()).unwrap();
+        history
+            .record(delivery("slack", &["abc"], DeliveryStatus::Failed))
+            .unwrap();
+        history
+            .record(delivery("slack", &["abc", "def"], DeliveryStatus::Sent))
+            .unwrap();
+        history
+            .record(delivery("oncall", &["def"], DeliveryStatus::Sent))
+            .unwrap();
+
+        let reloaded = NotificationHistory::load(path).unwrap();
+        let abc = reloaded.list(None, Some("abc"), None, None);
+        assert_eq!(abc.len(), 2);
+        assert_eq!(abc[0].status, DeliveryStatus::Sent);
+        assert_eq!(abc[1].error.as_deref(), Some("HTTP 500"));
+        assert_eq!(
+            reloaded
+                .list(Some("slack"), None, Some(DeliveryStatus::Sent), None)
+                .len(),
+            1
+        );
+        assert_eq!(
+            reloaded.list(None, None, None, Some(1))[0].backend,
+            "oncall"
+        );
+    }
+}
@@ -1,13 +1,37 @@
-use crate::config::NotificationConfig;
-use crate::types::CacheItem;
+use crate::alerts::{reminder_interval, AlertLog, ReminderRule};
+use crate::cache::CacheEvent;
+use crate::config::{
+    AlertThreshold, DigestConfig, DigestGrouping, NotificationBackend as NotificationBackendConfig,
+    NotificationConfig, NotificationMode, RetryPolicy, RoutedNotificationBackend,
+    ShoutNotificationConfig, SlackNotificationConfig, TeamsNotificationConfig,
+    TelegramNotificationConfig, WebhookNotificationConfig,
+};
+use crate::labels::matches_selector;
+use crate::notification_history::{
+    DeadLetter, DeadLetters, DeliveredCertificate, DeliveryStatus, NotificationDelivery,
+    NotificationHistory,
+};
+use crate::pinning::PinViolation;
+use crate::quiet::{
+    hold_reason, HeldNotification, HeldNotifications, MaintenanceWindow, QuietHours,
+};
+use crate::scheduler::jitter;
+use crate::types::{
+    CacheItem, CertificateStatus, NotificationTestResult, PathObject, SimulatedNotification,
+};
 use async_trait::async_trait;
-use chrono::{Duration, Utc};
+use chrono::{DateTime, Duration, Utc};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
 use serde_json::json;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
 
 #[async_trait]
 pub trait NotificationBackend: Send + Sync {
     async fn send_notification(&self, message: &NotificationMessage) -> crate::Result<()>;
+    /// Human-readable description of where notifications are delivered.
+    fn recipients(&self) -> Vec<String>;
 }
 
 #[derive(Debug, Clone)]
@@ -16,9 +40,28 @@ pub struct NotificationMessage {
     pub body: String,
     pub urgency: NotificationUrgency,
     pub certificates: Vec<CacheItem>,
+    /// Where the dashboard is served, for backends that link to it.
+    pub dashboard_url: String,
 }
 
-#[derive(Debug, Clone)]
+impl NotificationMessage {
+    /// Expired and expiring-soon certificates among those attached.
+    pub fn status_counts(&self) -> (usize, usize) {
+        let count = |status| {
+            self.certificates
+                .iter()
+                .filter(|cert| cert.status == status)
+                .count()
+        };
+        (
+            count(CertificateStatus::Expired),
+            count(CertificateStatus::ExpiringSoon),
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum NotificationUrgency {
     Low,
     Normal,
@@ -26,205 +69,1583 @@ pub enum NotificationUrgency {
     Critical,
 }
 
+impl NotificationUrgency {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NotificationUrgency::Low => "low",
+            NotificationUrgency::Normal => "normal",
+            NotificationUrgency::High => "high",
+            NotificationUrgency::Critical => "critical",
+        }
+    }
+}
+
+/// An alert threshold with its backend built, shared by `check_and_notify`
+/// and the routing simulation so the two cannot disagree.
+struct AlertStage {
+    within: i64,
+    urgency: NotificationUrgency,
+    /// Replaces the service's backends for this threshold.
+    target: Option<NotificationTarget>,
+}
+
+impl AlertStage {
+    /// Identifies the stage in the alert log and simulations.
+    fn name(&self) -> String {
+        if self.within == 0 {
+            "expired".to_string()
+        } else {
+            format!("within_{}d", self.within)
+        }
+    }
+
+    fn label(&self) -> String {
+        if self.within == 0 {
+            "Expired".to_string()
+        } else {
+            format!("Within {} day(s)", self.within)
+        }
+    }
+
+    fn title(&self) -> String {
+        if self.within == 0 {
+            "⚠️ Expired Certificates".to_string()
+        } else {
+            format!("⏰ Certificates Expiring Within {} Day(s)", self.within)
+        }
+    }
+}
+
+/// Urgency of an alert for a certificate that has crossed `threshold_days`.
+fn threshold_urgency(threshold_days: i64) -> NotificationUrgency {
+    match threshold_days {
+        ..=1 => NotificationUrgency::Critical,
+        2..=7 => NotificationUrgency::High,
+        _ => NotificationUrgency::Normal,
+    }
+}
+
 pub fn create_notification_backend(
-    backend_type: &str,
-    properties: &HashMap<String, serde_yaml::Value>,
+    config: &NotificationBackendConfig,
 ) -> crate::Result<Box<dyn NotificationBackend>> {
-    match backend_type {
-        "slack" => {
-            let backend = SlackNotificationBackend::from_config(properties)?;
-            Ok(Box::new(backend))
+    Ok(match config {
+        NotificationBackendConfig::Slack(properties) => {
+            Box::new(SlackNotificationBackend::from_config(properties)?)
+        }
+        NotificationBackendConfig::Shout(properties) => {
+            Box::new(ShoutNotificationBackend::from_config(properties))
+        }
+        NotificationBackendConfig::Teams(properties) => {
+            Box::new(TeamsNotificationBackend::from_config(properties))
+        }
+        NotificationBackendConfig::Webhook(properties) => {
+            Box::new(WebhookNotificationBackend::from_config(properties)?)
+        }
+        NotificationBackendConfig::Telegram(properties) => {
+            Box::new(TelegramNotificationBackend::from_config(properties))
+        }
+    })
+}
+
+/// A backend the service sends to, with the route selecting what it
+/// receives.
+struct NotificationTarget {
+    /// The configured name, or the backend type when unnamed.
+    name: String,
+    backend: Arc<dyn NotificationBackend>,
+    urgency: Vec<NotificationUrgency>,
+    backends: Vec<String>,
+    path: Option<Regex>,
+    labels: HashMap<String, String>,
+}
+
+impl NotificationTarget {
+    /// A target receiving everything.
+    fn unrouted(config: &NotificationBackendConfig) -> crate::Result<Self> {
+        Ok(NotificationTarget {
+            name: config.backend_type().to_string(),
+            backend: create_notification_backend(config)?.into(),
+            urgency: Vec::new(),
+            backends: Vec::new(),
+            path: None,
+            labels: HashMap::new(),
+        })
+    }
+
+    fn routed(config: &RoutedNotificationBackend) -> crate::Result<Self> {
+        let route = &config.route;
+        let path = route
+            .path
+            .as_deref()
+            .map(Regex::new)
+            .transpose()
+            .map_err(|e| {
+                crate::DoomsdayError::config(format!(
+                    "Invalid route path regex for notification backend {}: {}",
+                    config.name, e
+                ))
+            })?;
+
+        Ok(NotificationTarget {
+            name: config.name.clone(),
+            urgency: route.urgency.clone(),
+            backends: route.backends.clone(),
+            path,
+            labels: route.labels.clone(),
+            ..Self::unrouted(&config.backend)?
+        })
+    }
+
+    fn accepts_urgency(&self, urgency: NotificationUrgency) -> bool {
+        self.urgency.is_empty() || self.urgency.contains(&urgency)
+    }
+
+    fn accepts_certificate(&self, labels: &HashMap<String, String>, paths: &[PathObject]) -> bool {
+        if !matches_selector(&self.labels, labels) {
+            return false;
         }
-        "shout" => {
-            let backend = ShoutNotificationBackend::from_config(properties)?;
-            Ok(Box::new(backend))
+
+        // Backend and path must hold for the same location
+        if self.backends.is_empty() && self.path.is_none() {
+            return true;
+        }
+        paths.iter().any(|p| {
+            (self.backends.is_empty() || self.backends.iter().any(|b| *b == *p.backend))
+                && self.path.as_ref().is_none_or(|path| path.is_match(&p.path))
+        })
+    }
+
+    fn simulated(&self, rule: String, urgency: NotificationUrgency) -> SimulatedNotification {
+        SimulatedNotification {
+            rule,
+            backend: self.name.clone(),
+            urgency: Some(urgency.as_str().to_string()),
+            recipients: self.backend.recipients(),
         }
-        _ => Err(crate::DoomsdayError::config(format!(
-            "Unknown notification backend: {}",
-            backend_type
-        ))),
     }
 }
 
 pub struct NotificationService {
-    backend: Box<dyn NotificationBackend>,
+    targets: Vec<NotificationTarget>,
+    /// Tightest first.
+    stages: Vec<AlertStage>,
     doomsday_url: String,
+    labels: HashMap<String, String>,
+    events: Vec<String>,
+    reminders: Vec<ReminderRule>,
+    alerts: AlertLog,
+    mode: NotificationMode,
+    digest: DigestConfig,
+    quiet_hours: Vec<QuietHours>,
+    maintenance: Vec<MaintenanceWindow>,
+    held: HeldNotifications,
+    history: NotificationHistory,
+    retry: RetryPolicy,
+    dead_letters: DeadLetters,
 }
 
+/// Certificates listed in an event notification before the rest are only
+/// counted.
+const MAX_LISTED_CERTIFICATES: usize = 20;
+
 impl NotificationService {
-    pub fn new(config: &NotificationConfig) -> crate::Result<Self> {
-        let backend =
-            create_notification_backend(&config.backend.backend_type, &config.backend.properties)?;
+    /// A service alerting at `thresholds`, usually `Config::alert_thresholds`.
+    pub fn new(config: &NotificationConfig, thresholds: &[AlertThreshold]) -> crate::Result<Self> {
+        let mut targets = Vec::new();
+        if let Some(backend) = &config.backend {
+            targets.push(NotificationTarget::unrouted(backend)?);
+        }
+        for routed in &config.backends {
+            targets.push(NotificationTarget::routed(routed)?);
+        }
+
+        let mut stages = thresholds
+            .iter()
+            .map(|threshold| {
+                Ok(AlertStage {
+                    within: threshold.within,
+                    urgency: threshold.urgency,
+                    target: threshold
+                        .backend
+                        .as_ref()
+                        .map(NotificationTarget::unrouted)
+                        .transpose()?,
+                })
+            })
+            .collect::<crate::Result<Vec<_>>>()?;
+        stages.sort_by_key(|stage| stage.within);
 
         Ok(NotificationService {
-            backend,
+            targets,
+            stages,
             doomsday_url: config.doomsday_url.clone(),
+            labels: config.labels.clone(),
+            events: config.events.clone(),
+            reminders: config.reminders.clone(),
+            alerts: AlertLog::new(),
+            mode: config.mode,
+            digest: config.digest.clone(),
+            quiet_hours: config.quiet_hours.clone(),
+            maintenance: config.maintenance.clone(),
+            held: HeldNotifications::new(),
+            history: NotificationHistory::new(),
+            retry: config.retry,
+            dead_letters: DeadLetters::new(),
         })
     }
 
+    /// Tracks scheduled alerts in `alerts`, which outlives reconfiguration.
+    pub fn with_alert_log(mut self, alerts: AlertLog) -> Self {
+        self.alerts = alerts;
+        self
+    }
+
+    /// Keeps notifications held during quiet windows in `held`, which
+    /// outlives reconfiguration.
+    pub fn with_held_notifications(mut self, held: HeldNotifications) -> Self {
+        self.held = held;
+        self
+    }
+
+    /// Records delivery attempts in `history`, which outlives
+    /// reconfiguration.
+    pub fn with_notification_history(mut self, history: NotificationHistory) -> Self {
+        self.history = history;
+        self
+    }
+
+    /// Files notifications that fail their last retry in `dead_letters`,
+    /// which outlives reconfiguration.
+    pub fn with_dead_letters(mut self, dead_letters: DeadLetters) -> Self {
+        self.dead_letters = dead_letters;
+        self
+    }
+
+    fn accepts(&self, event_name: &str) -> bool {
+        self.events.iter().any(|e| e == event_name)
+    }
+
+    fn stage_targets<'a>(&'a self, stage: &'a AlertStage) -> &'a [NotificationTarget] {
+        match &stage.target {
+            Some(target) => std::slice::from_ref(target),
+            None => &self.targets,
+        }
+    }
+
+    /// Sends each target the message built from its share of `entries`,
+    /// skipping targets that receive none of them or not at the message's
+    /// urgency. A failing target does not stop the others.
+    async fn dispatch<T: Clone>(
+        &self,
+        targets: &[NotificationTarget],
+        entries: &[T],
+        certificate: impl Fn(&T) -> &CacheItem,
+        build: impl Fn(Vec<T>) -> NotificationMessage,
+    ) -> crate::Result<()> {
+        let mut result = Ok(());
+        for target in targets {
+            let routed: Vec<T> = entries
+                .iter()
+                .filter(|entry| {
+                    let cert = certificate(entry);
+                    target.accepts_certificate(&cert.labels, &cert.paths)
+                })
+                .cloned()
+                .collect();
+            if routed.is_empty() {
+                continue;
+            }
+
+            let message = build(routed);
+            if !target.accepts_urgency(message.urgency) {
+                continue;
+            }
+
+            result = result.and(self.deliver(target, &message).await);
+        }
+        result
+    }
+
+    /// Sends `message` via `target`, or holds it while a quiet window
+    /// covers the target.
+    async fn deliver(
+        &self,
+        target: &NotificationTarget,
+        message: &NotificationMessage,
+    ) -> crate::Result<()> {
+        let now = Utc::now();
+        if let Some(reason) = hold_reason(
+            &self.quiet_hours,
+            &self.maintenance,
+            &target.name,
+            message.urgency,
+            now,
+        ) {
+            tracing::info!(
+                "Holding \"{}\" for {} certificate(s) via {} during {}",
+                message.title,
+                message.certificates.len(),
+                target.name,
+                reason
+            );
+            let held = HeldNotification {
+                title: message.title.clone(),
+                urgency: message.urgency,
+                reason,
+                held_at: now,
+                certificates: message.certificates.clone(),
+            };
+            if let Err(e) = self.held.hold(&target.name, held) {
+                tracing::warn!("Failed to save held notifications: {}", e);
+            }
+            record_delivery(
+                &self.history,
+                &target.name,
+                message,
+                1,
+                DeliveryStatus::Held,
+                None,
+            );
+            return Ok(());
+        }
+
+        tracing::info!(
+            "Sending \"{}\" for {} certificate(s) via {}",
+            message.title,
+            message.certificates.len(),
+            target.name
+        );
+        let error = match target.backend.send_notification(message).await {
+            Ok(()) => {
+                let sent = DeliveryStatus::Sent;
+                record_delivery(&self.history, &target.name, message, 1, sent, None);
+                return Ok(());
+            }
+            Err(e) => e,
+        };
+
+        tracing::error!("Notification via {} failed: {}", target.name, error);
+        let failed = DeliveryStatus::Failed;
+        let reason = Some(error.to_string());
+        record_delivery(&self.history, &target.name, message, 1, failed, reason);
+        if self.retry.max_attempts <= 1 {
+            file_dead_letter(&self.dead_letters, &target.name, message, 1, &error);
+            return Err(error);
+        }
+
+        // The alert counts as sent; retries carry on in the background so
+        // an unreachable backend does not hold up the others
+        let retry = RetryDelivery {
+            backend: target.backend.clone(),
+            name: target.name.clone(),
+            message: message.clone(),
+            policy: self.retry,
+            history: self.history.clone(),
+            dead_letters: self.dead_letters.clone(),
+        };
+        tokio::spawn(retry.run(error));
+        Ok(())
+    }
+
+    /// Sends each target whose quiet windows have ended one summary of the
+    /// notifications held for it.
+    pub async fn release_held(&self) -> crate::Result<()> {
+        let now = Utc::now();
+        let mut result = Ok(());
+        for target in self.all_targets() {
+            let held = self.held.held(&target.name);
+            let still_held = held.iter().any(|h| {
+                hold_reason(
+                    &self.quiet_hours,
+                    &self.maintenance,
+                    &target.name,
+                    h.urgency,
+                    now,
+                )
+                .is_some()
+            });
+            if held.is_empty() || still_held {
+                continue;
+            }
+
+            let message = self.held_summary(&held);
+            tracing::info!(
+                "Sending summary of {} held notification(s) via {}",
+                held.len(),
+                target.name
+            );
+            match target.backend.send_notification(&message).await {
+                Ok(()) => {
+                    let sent = DeliveryStatus::Sent;
+                    record_delivery(&self.history, &target.name, &message, 1, sent, None);
+                    if let Err(e) = self.held.release(&target.name, held.len()) {
+                        tracing::warn!("Failed to save held notifications: {}", e);
+                    }
+                }
+                Err(e) => {
+                    tracing::error!("Notification via {} failed: {}", target.name, e);
+                    let (failed, error) = (DeliveryStatus::Failed, Some(e.to_string()));
+                    record_delivery(&self.history, &target.name, &message, 1, failed, error);
+                    result = result.and(Err(e));
+                }
+            }
+        }
+        result
+    }
+
+    /// Every target, including per-threshold ones, once each by name.
+    fn all_targets(&self) -> Vec<&NotificationTarget> {
+        let mut seen = HashSet::new();
+        self.targets
+            .iter()
+            .chain(self.stages.iter().filter_map(|stage| stage.target.as_ref()))
+            .filter(|target| seen.insert(target.name.as_str()))
+            .collect()
+    }
+
+    /// Sends a test message via every target, or only the one named
+    /// `backend`, ignoring routes, quiet windows and retries so a
+    /// misconfigured backend shows up straight away.
+    pub async fn send_test(&self, backend: Option<&str>) -> Vec<NotificationTestResult> {
+        let message = NotificationMessage {
+            title: "🧪 Doomsday Test Notification".to_string(),
+            body: format!(
+                "This is a test notification from Doomsday. Certificate alerts will be delivered here; see {} for the current status.",
+                self.doomsday_url
+            ),
+            urgency: NotificationUrgency::Low,
+            certificates: Vec::new(),
+            dashboard_url: self.doomsday_url.clone(),
+        };
+
+        let mut results = Vec::new();
+        for target in self.all_targets() {
+            if backend.is_some_and(|name| name != target.name) {
+                continue;
+            }
+
+            tracing::info!("Sending test notification via {}", target.name);
+            let error = match target.backend.send_notification(&message).await {
+                Ok(()) => None,
+                Err(e) => {
+                    tracing::error!("Test notification via {} failed: {}", target.name, e);
+                    Some(e.to_string())
+                }
+            };
+            let status = match error {
+                None => DeliveryStatus::Sent,
+                Some(_) => DeliveryStatus::Failed,
+            };
+            record_delivery(
+                &self.history,
+                &target.name,
+                &message,
+                1,
+                status,
+                error.clone(),
+            );
+
+            results.push(NotificationTestResult {
+                backend: target.name.clone(),
+                recipients: target.backend.recipients(),
+                success: error.is_none(),
+                error,
+            });
+        }
+        results
+    }
+
+    fn held_summary(&self, held: &[HeldNotification]) -> NotificationMessage {
+        let mut reasons: Vec<&str> = held.iter().map(|h| h.reason.as_str()).collect();
+        reasons.sort();
+        reasons.dedup();
+
+        // The most recent copy of each certificate
+        let mut certificates: HashMap<&str, &CacheItem> = HashMap::new();
+        for cert in held.iter().flat_map(|h| &h.certificates) {
+            certificates.insert(&cert.sha1, cert);
+        }
+        let mut certificates: Vec<CacheItem> = certificates.into_values().cloned().collect();
+        certificates.sort_by_key(|cert| cert.not_after);
+
+        let lines: Vec<String> = held
+            .iter()
+            .map(|h| {
+                format!(
+                    "• {} {} ({} certificate(s))",
+                    h.held_at
+                        .with_timezone(&chrono::Local)
+                        .format("%Y-%m-%d %H:%M"),
+                    h.title,
+                    h.certificates.len()
+                )
+            })
+            .collect();
+
+        NotificationMessage {
+            title: "🔔 Held Notifications".to_string(),
+            body: format!(
+                "{} notification(s) were held during {}. Please check {} for the current status.\n{}",
+                held.len(),
+                reasons.join(", "),
+                self.doomsday_url,
+                lines.join("\n")
+            ),
+            urgency: held
+                .iter()
+                .map(|h| h.urgency)
+                .max()
+                .unwrap_or(NotificationUrgency::Low),
+            certificates,
+            dashboard_url: self.doomsday_url.clone(),
+        }
+    }
+
+    /// Reports what `check_and_notify` would send for a certificate expiring
+    /// in `days_to_expiry` days, without sending anything.
+    pub fn simulate(
+        &self,
+        days_to_expiry: i64,
+        labels: &HashMap<String, String>,
+        paths: &[PathObject],
+    ) -> Vec<SimulatedNotification> {
+        if !matches_selector(&self.labels, labels) {
+            return Vec::new();
+        }
+        let Some(stage) = self
+            .stages
+            .iter()
+            .find(|stage| days_to_expiry <= stage.within)
+        else {
+            return Vec::new();
+        };
+
+        self.stage_targets(stage)
+            .iter()
+            .filter(|target| {
+                target.accepts_urgency(stage.urgency) && target.accepts_certificate(labels, paths)
+            })
+            .map(|target| target.simulated(stage.name(), stage.urgency))
+            .collect()
+    }
+
+    /// Reports whether a `threshold_crossed` event for `threshold_days` would
+    /// be sent for a certificate carrying `labels`.
+    pub fn simulate_threshold(
+        &self,
+        threshold_days: i64,
+        labels: &HashMap<String, String>,
+        paths: &[PathObject],
+    ) -> Vec<SimulatedNotification> {
+        if !self.accepts("threshold_crossed") || !matches_selector(&self.labels, labels) {
+            return Vec::new();
+        }
+
+        let urgency = threshold_urgency(threshold_days);
+        self.targets
+            .iter()
+            .filter(|target| {
+                target.accepts_urgency(urgency) && target.accepts_certificate(labels, paths)
+            })
+            .map(|target| {
+                target.simulated(
+                    format!("threshold_crossed ({} days)", threshold_days),
+                    urgency,
+                )
+            })
+            .collect()
+    }
+
+    /// Sends one message per kind of event for a batch of cache events, each
+    /// paired with the certificate as the API reports it. Events the service
+    /// is not subscribed to, for certificates outside its label selector, or
+    /// for acknowledged certificates are dropped. A rotation notification
+    /// stands in for the addition and removal it consists of.
+    pub async fn notify_events(&self, events: &[(CacheEvent, CacheItem)]) -> crate::Result<()> {
+        let mut rotated = HashSet::new();
+        if self.accepts("certificate_rotated") {
+            for (event, _) in events {
+                if let CacheEvent::CertificateRotated {
+                    certificate,
+                    old_sha1,
+                    ..
+                } = event
+                {
+                    rotated.insert(certificate.sha1.as_str());
+                    rotated.insert(old_sha1.as_str());
+                }
+            }
+        }
+
+        let mut by_name: Vec<(&'static str, Vec<&(CacheEvent, CacheItem)>)> = Vec::new();
+        for entry in events {
+            let (event, item) = entry;
+            if !self.accepts(event.name())
+                || !matches_selector(&self.labels, &item.labels)
+                || item.acknowledged.is_some()
+            {
+                continue;
+            }
+            let part_of_rotation = matches!(
+                event,
+                CacheEvent::CertificateAdded { .. } | CacheEvent::CertificateRemoved { .. }
+            ) && rotated.contains(item.sha1.as_str());
+            if part_of_rotation {
+                continue;
+            }
+            match by_name.iter_mut().find(|(name, _)| *name == event.name()) {
+                Some((_, entries)) => entries.push(entry),
+                None => by_name.push((event.name(), vec![entry])),
+            }
+        }
+
+        let mut result = Ok(());
+        for (name, entries) in by_name {
+            let sent = self
+                .dispatch(
+                    &self.targets,
+                    &entries,
+                    |(_, item)| item,
+                    |entries| self.event_message(name, &entries),
+                )
+                .await;
+            result = result.and(sent);
+        }
+        result
+    }
+
+    fn event_message(
+        &self,
+        name: &str,
+        entries: &[&(CacheEvent, CacheItem)],
+    ) -> NotificationMessage {
+        let (title, summary, urgency) = match name {
+            "threshold_crossed" => {
+                let tightest = entries
+                    .iter()
+                    .filter_map(|(event, _)| match event {
+                        CacheEvent::ThresholdCrossed { threshold_days, .. } => {
+                            Some(*threshold_days)
+                        }
+                        _ => None,
+                    })
+                    .min()
+                    .unwrap_or(0);
+                (
+                    "⏰ Certificates Crossed an Expiry Threshold",
+                    "crossed an expiry threshold",
+                    threshold_urgency(tightest),
+                )
+            }
+            "certificate_removed" => (
+                "🗑️ Certificates Removed",
+                "disappeared from their backends",
+                NotificationUrgency::Normal,
+            ),
+            "certificate_rotated" => (
+                "✅ Certificates Rotated",
+                "were replaced by certificates expiring later",
+                NotificationUrgency::Low,
+            ),
+            _ => (
+                "🆕 Certificates Discovered",
+                "were discovered",
+                NotificationUrgency::Low,
+            ),
+        };
+
+        let mut lines: Vec<String> = entries
+            .iter()
+            .take(MAX_LISTED_CERTIFICATES)
+            .map(|(event, item)| {
+                let days = (item.not_after - Utc::now()).num_days();
+                match event {
+                    CacheEvent::ThresholdCrossed { threshold_days, .. } => format!(
+                        "{} expires in {} days ({}-day threshold)",
+                        item.subject, days, threshold_days
+                    ),
+                    CacheEvent::CertificateRotated {
+                        backend,
+                        path,
+                        old_not_after,
+                        ..
+                    } => format!(
+                        "{} at {}:{} now expires {} (in {} days, was {})",
+                        item.subject,
+                        backend,
+                        path,
+                        item.not_after.format("%Y-%m-%d"),
+                        days,
+                        old_not_after.format("%Y-%m-%d")
+                    ),
+                    _ => format!("{} (expires in {} days)", item.subject, days),
+                }
+            })
+            .collect();
+        if entries.len() > MAX_LISTED_CERTIFICATES {
+            lines.push(format!(
+                "... and {} more",
+                entries.len() - MAX_LISTED_CERTIFICATES
+            ));
+        }
+
+        NotificationMessage {
+            title: title.to_string(),
+            body: format!(
+                "{} certificate(s) {}. Please check {} for details.\n{}",
+                entries.len(),
+                summary,
+                self.doomsday_url,
+                lines.join("\n")
+            ),
+            urgency,
+            certificates: entries.iter().map(|(_, item)| item.clone()).collect(),
+            dashboard_url: self.doomsday_url.clone(),
+        }
+    }
+
+    /// The tightest threshold `cert` falls under.
+    fn stage_index(&self, cert: &CacheItem, now: DateTime<Utc>) -> Option<usize> {
+        let remaining = cert.not_after - now;
+        self.stages
+            .iter()
+            .position(|stage| remaining <= Duration::days(stage.within))
+    }
+
+    /// Sends what a `schedule` run sends in the configured mode.
+    pub async fn notify_scheduled(&self, certificates: &[CacheItem]) -> crate::Result<()> {
+        match self.mode {
+            NotificationMode::Alerts => self.check_and_notify(certificates).await,
+            NotificationMode::Digest => self.send_digest(certificates).await,
+        }
+    }
+
+    /// Sends one summary of every certificate at a threshold, or an all-clear
+    /// when there are none.
+    pub async fn send_digest(&self, certificates: &[CacheItem]) -> crate::Result<()> {
+        let now = Utc::now();
+        let mut reported: Vec<CacheItem> = certificates
+            .iter()
+            .filter(|cert| matches_selector(&self.labels, &cert.labels))
+            .filter(|cert| self.stage_index(cert, now).is_some())
+            .cloned()
+            .collect();
+        reported.sort_by_key(|cert| cert.not_after);
+
+        if reported.is_empty() {
+            let message = NotificationMessage {
+                title: "📋 Certificate Digest".to_string(),
+                body: format!(
+                    "No certificates need attention. Please check {} for the full inventory.",
+                    self.doomsday_url
+                ),
+                urgency: NotificationUrgency::Low,
+                certificates: vec![],
+                dashboard_url: self.doomsday_url.clone(),
+            };
+            return self.broadcast(&message).await;
+        }
+
+        self.dispatch(
+            &self.targets,
+            &reported,
+            |cert| cert,
+            |certificates| self.digest_summary(certificates, now),
+        )
+        .await
+    }
+
+    /// Counts per threshold, then the soonest expiring certificates of each
+    /// group. `certificates` are sorted by expiry.
+    fn digest_summary(
+        &self,
+        certificates: Vec<CacheItem>,
+        now: DateTime<Utc>,
+    ) -> NotificationMessage {
+        let mut counts = vec![0; self.stages.len()];
+        for cert in &certificates {
+            if let Some(index) = self.stage_index(cert, now) {
+                counts[index] += 1;
+            }
+        }
+        let urgency = self
+            .stages
+            .iter()
+            .zip(&counts)
+            .filter(|(_, count)| **count > 0)
+            .map(|(stage, _)| stage.urgency)
+            .max()
+            .unwrap_or(NotificationUrgency::Low);
+        let summary: Vec<String> = self
+            .stages
+            .iter()
+            .zip(&counts)
+            .filter(|(_, count)| **count > 0)
+            .map(|(stage, count)| format!("{}: {}", stage.label(), count))
+            .collect();
+
+        let mut groups: Vec<(String, Vec<&CacheItem>)> = Vec::new();
+        for cert in &certificates {
+            let keys: Vec<String> = match &self.digest.group_by {
+                None => vec![String::new()],
+                Some(DigestGrouping::Backend) => {
+                    let mut backends: Vec<String> =
+                        cert.paths.iter().map(|p| p.backend.to_string()).collect();
+                    backends.sort();
+                    backends.dedup();
+                    backends
+                }
+                Some(DigestGrouping::Label(label)) => vec![cert
+                    .labels
+                    .get(label)
+                    .cloned()
+                    .unwrap_or_else(|| format!("no {}", label))],
+            };
+            for key in keys {
+                match groups.iter_mut().find(|(name, _)| *name == key) {
+                    Some((_, members)) => members.push(cert),
+                    None => groups.push((key, vec![cert])),
+                }
+            }
+        }
+        groups.sort_by(|a, b| a.0.cmp(&b.0));
+
+        let mut lines = vec![summary.join(" · ")];
+        for (name, members) in &groups {
+            lines.push(String::new());
+            if !name.is_empty() {
+                lines.push(format!("{} ({})", name, members.len()));
+            }
+            for cert in members.iter().take(self.digest.top) {
+                let days = (cert.not_after - now).num_days();
+                let expiry = if cert.not_after <= now {
+                    format!("expired {} day(s) ago", -days)
+                } else {
+                    format!("expires in {} day(s)", days)
+                };
+                match &cert.acknowledged {
+                    Some(ack) => lines.push(format!(
+                        "• {} ({}, acknowledged until {})",
+                        cert.subject,
+                        expiry,
+                        ack.until.format("%Y-%m-%d")
+                    )),
+                    None => lines.push(format!("• {} ({})", cert.subject, expiry)),
+                }
+            }
+            if members.len() > self.digest.top {
+                lines.push(format!("… and {} more", members.len() - self.digest.top));
+            }
+        }
+
+        NotificationMessage {
+            title: "📋 Certificate Digest".to_string(),
+            body: format!(
+                "{} certificate(s) need attention. Please check {} for details.\n{}",
+                certificates.len(),
+                self.doomsday_url,
+                lines.join("\n")
+            ),
+            urgency,
+            certificates,
+            dashboard_url: self.doomsday_url.clone(),
+        }
+    }
+
+    /// Sends a message about no particular certificate to every target
+    /// accepting its urgency.
+    async fn broadcast(&self, message: &NotificationMessage) -> crate::Result<()> {
+        let mut result = Ok(());
+        for target in self
+            .targets
+            .iter()
+            .filter(|t| t.accepts_urgency(message.urgency))
+        {
+            result = result.and(self.deliver(target, message).await);
+        }
+        result
+    }
+
+    /// Alerts every target that pinned locations now present an
+    /// unexpected certificate.
+    pub async fn notify_pin_violations(&self, violations: &[PinViolation]) -> crate::Result<()> {
+        if violations.is_empty() {
+            return Ok(());
+        }
+
+        let details: Vec<String> = violations
+            .iter()
+            .map(|v| {
+                format!(
+                    "{}:{} ({}) presented {} (expected one of: {})",
+                    v.backend,
+                    v.path,
+                    v.subject,
+                    v.observed,
+                    v.expected.join(", ")
+                )
+            })
+            .collect();
+
+        let message = NotificationMessage {
+            title: "🚨 Certificate Pin Mismatch".to_string(),
+            body: format!(
+                "{} pinned certificate location(s) now present an unexpected certificate. \
+                 This may indicate an unplanned or malicious certificate swap. Please check {} for details.\n{}",
+                violations.len(),
+                self.doomsday_url,
+                details.join("\n")
+            ),
+            urgency: NotificationUrgency::High,
+            certificates: vec![],
+            dashboard_url: self.doomsday_url.clone(),
+        };
+
+        // Pin violations are not certificates in the cache, so only urgency
+        // routes apply
+        self.broadcast(&message).await
+    }
+
+    /// Sends the scheduled digest: one message per threshold listing the
+    /// certificates not yet alerted at that threshold, or due a reminder.
+    /// Each certificate is alerted at the tightest threshold it falls under.
     pub async fn check_and_notify(&self, certificates: &[CacheItem]) -> crate::Result<()> {
         let now = Utc::now();
+        let mut alerting = HashSet::new();
+        let mut due: Vec<Vec<CacheItem>> = vec![Vec::new(); self.stages.len()];
+
+        for cert in certificates
+            .iter()
+            .filter(|cert| matches_selector(&self.labels, &cert.labels))
+        {
+            let Some(index) = self.stage_index(cert, now) else {
+                continue;
+            };
+            alerting.insert(cert.sha1.clone());
+            if cert.acknowledged.is_some() {
+                continue;
+            }
+
+            let days_left = (cert.not_after - now).num_days();
+            let reminder = reminder_interval(&self.reminders, days_left);
+            let stage = &self.stages[index];
+            if self.alerts.is_due(&cert.sha1, &stage.name(), reminder, now) {
+                due[index].push(cert.clone());
+            }
+        }
+
+        // Most urgent first
+        for (stage, certificates) in self.stages.iter().zip(due) {
+            if certificates.is_empty() {
+                continue;
+            }
+
+            self.dispatch(
+                self.stage_targets(stage),
+                &certificates,
+                |cert| cert,
+                |certificates| self.digest_message(stage, certificates),
+            )
+            .await?;
+
+            let sent = certificates.iter().map(|cert| cert.sha1.as_str());
+            if let Err(e) = self.alerts.record(sent, &stage.name(), now) {
+                tracing::warn!("Failed to save alert log: {}", e);
+            }
+        }
+
+        if let Err(e) = self.alerts.retain(&alerting) {
+            tracing::warn!("Failed to save alert log: {}", e);
+        }
+        Ok(())
+    }
+
+    fn digest_message(
+        &self,
+        stage: &AlertStage,
+        certificates: Vec<CacheItem>,
+    ) -> NotificationMessage {
+        let body = if stage.within == 0 {
+            format!(
+                "{} certificate(s) have expired. Please check {} for details.",
+                certificates.len(),
+                self.doomsday_url
+            )
+        } else {
+            format!(
+                "{} certificate(s) will expire within {} day(s). Please check {} for details.",
+                certificates.len(),
+                stage.within,
+                self.doomsday_url
+            )
+        };
+
+        NotificationMessage {
+            title: stage.title(),
+            body,
+            urgency: stage.urgency,
+            certificates,
+            dashboard_url: self.doomsday_url.clone(),
+        }
+    }
+}
+
+fn delivered_certificates(message: &NotificationMessage) -> Vec<DeliveredCertificate> {
+    message
+        .certificates
+        .iter()
+        .map(|cert| DeliveredCertificate {
+            sha1: cert.sha1.clone(),
+            subject: cert.subject.clone(),
+        })
+        .collect()
+}
+
+fn record_delivery(
+    history: &NotificationHistory,
+    target: &str,
+    message: &NotificationMessage,
+    attempt: u32,
+    status: DeliveryStatus,
+    error: Option<String>,
+) {
+    let delivery = NotificationDelivery {
+        attempted_at: Utc::now(),
+        backend: target.to_string(),
+        title: message.title.clone(),
+        urgency: message.urgency,
+        certificates: delivered_certificates(message),
+        attempt,
+        status,
+        error,
+    };
+    if let Err(e) = history.record(delivery) {
+        tracing::warn!("Failed to save notification history: {}", e);
+    }
+}
+
+fn file_dead_letter(
+    dead_letters: &DeadLetters,
+    target: &str,
+    message: &NotificationMessage,
+    attempts: u32,
+    error: &crate::DoomsdayError,
+) {
+    let letter = DeadLetter {
+        failed_at: Utc::now(),
+        backend: target.to_string(),
+        title: message.title.clone(),
+        body: message.body.clone(),
+        urgency: message.urgency,
+        certificates: delivered_certificates(message),
+        attempts,
+        error: error.to_string(),
+    };
+    if let Err(e) = dead_letters.record(letter) {
+        tracing::warn!("Failed to save dead letters: {}", e);
+    }
+}
+
+/// A failed delivery being retried in the background.
+struct RetryDelivery {
+    backend: Arc<dyn NotificationBackend>,
+    name: String,
+    message: NotificationMessage,
+    policy: RetryPolicy,
+    history: NotificationHistory,
+    dead_letters: DeadLetters,
+}
+
+impl RetryDelivery {
+    /// Retries with jittered exponential backoff until the message is sent
+    /// or the policy's attempts run out, then files it as a dead letter.
+    async fn run(self, mut error: crate::DoomsdayError) {
+        for attempt in 2..=self.policy.max_attempts {
+            let delay = jitter(self.policy.backoff(attempt - 1));
+            tracing::warn!(
+                "Retrying \"{}\" via {} in {:?} (attempt {}/{})",
+                self.message.title,
+                self.name,
+                delay,
+                attempt,
+                self.policy.max_attempts
+            );
+            tokio::time::sleep(delay).await;
+
+            let (status, reason) = match self.backend.send_notification(&self.message).await {
+                Ok(()) => (DeliveryStatus::Sent, None),
+                Err(e) => {
+                    tracing::error!("Notification via {} failed: {}", self.name, e);
+                    let reason = Some(e.to_string());
+                    error = e;
+                    (DeliveryStatus::Failed, reason)
+                }
+            };
+            record_delivery(
+                &self.history,
+                &self.name,
+                &self.message,
+                attempt,
+                status,
+                reason,
+            );
+            if status == DeliveryStatus::Sent {
+                tracing::info!(
+                    "Sent \"{}\" via {} on attempt {}",
+                    self.message.title,
+                    self.name,
+                    attempt
+                );
+                return;
+            }
+        }
+
+        file_dead_letter(
+            &self.dead_letters,
+            &self.name,
+            &self.message,
+            self.policy.max_attempts,
+            &error,
+        );
+    }
+}
+
+/// Certificates listed per Slack message. Slack allows 50 blocks per
+/// message; the rest of the budget goes to the header, body and counts.
+const SLACK_CERTIFICATES_PER_MESSAGE: usize = 40;
+/// Slack rejects section text longer than this many characters.
+const SLACK_MAX_SECTION_CHARS: usize = 3000;
+/// Slack rejects header text longer than this many characters.
+const SLACK_MAX_HEADER_CHARS: usize = 150;
+
+/// Posts to Slack through an incoming webhook, or through the Web API with a
+/// bot token. Only the Web API reports where a message was posted, so
+/// threading follow-up alerts requires a bot token.
+pub struct SlackNotificationBackend {
+    webhook_url: Option<String>,
+    bot_token: Option<String>,
+    api_url: String,
+    channel: Option<String>,
+    username: Option<String>,
+    /// Reply to the message that first alerted about a certificate.
+    thread: bool,
+    /// `ts` of the message that first alerted about each certificate, by
+    /// SHA1.
+    threads: std::sync::Mutex<HashMap<String, String>>,
+    client: reqwest::Client,
+}
+
+impl SlackNotificationBackend {
+    pub fn new(webhook_url: String, channel: Option<String>, username: Option<String>) -> Self {
+        SlackNotificationBackend {
+            webhook_url: Some(webhook_url),
+            bot_token: None,
+            api_url: "https://slack.com/api".to_string(),
+            channel,
+            username,
+            thread: false,
+            threads: std::sync::Mutex::new(HashMap::new()),
+            client: reqwest::Client::new(),
+        }
+    }
+
+    pub fn from_config(config: &SlackNotificationConfig) -> crate::Result<Self> {
+        if config.webhook_url.is_none() && config.bot_token.is_none() {
+            return Err(crate::DoomsdayError::config(
+                "Slack webhook_url or bot_token is required",
+            ));
+        }
+        if config.bot_token.is_some() && config.channel.is_none() {
+            return Err(crate::DoomsdayError::config(
+                "Slack channel is required with bot_token",
+            ));
+        }
+        if config.thread && config.bot_token.is_none() {
+            return Err(crate::DoomsdayError::config(
+                "Slack thread requires bot_token (incoming webhooks cannot thread)",
+            ));
+        }
+
+        Ok(SlackNotificationBackend {
+            webhook_url: config.webhook_url.clone(),
+            bot_token: config.bot_token.clone(),
+            api_url: config.api_url.trim_end_matches('/').to_string(),
+            thread: config.thread,
+            ..Self::new(
+                String::new(),
+                config.channel.clone(),
+                config.username.clone(),
+            )
+        })
+    }
+
+    /// The message as Block Kit payloads, one per
+    /// `SLACK_CERTIFICATES_PER_MESSAGE` certificates, soonest expiry first.
+    fn payloads(message: &NotificationMessage, now: DateTime<Utc>) -> Vec<serde_json::Value> {
+        let color = match message.urgency {
+            NotificationUrgency::Low => "#36a64f",      // Green
+            NotificationUrgency::Normal => "#2196F3",   // Blue
+            NotificationUrgency::High => "#ff9800",     // Orange
+            NotificationUrgency::Critical => "#f44336", // Red
+        };
+
+        let mut certificates: Vec<&CacheItem> = message.certificates.iter().collect();
+        certificates.sort_by_key(|cert| cert.not_after);
+        let chunks: Vec<&[&CacheItem]> = if certificates.is_empty() {
+            vec![&[]]
+        } else {
+            certificates
+                .chunks(SLACK_CERTIFICATES_PER_MESSAGE)
+                .collect()
+        };
+
+        let (expired_count, expiring_soon_count) = message.status_counts();
+        let mut counts = vec![];
+        if expired_count > 0 {
+            counts.push(format!("*Expired:* {}", expired_count));
+        }
+        if expiring_soon_count > 0 {
+            counts.push(format!("*Expiring soon:* {}", expiring_soon_count));
+        }
+        counts.push(format!("<{}|Open dashboard>", message.dashboard_url));
+
+        chunks
+            .iter()
+            .enumerate()
+            .map(|(index, chunk)| {
+                let title = if chunks.len() > 1 {
+                    format!("{} ({}/{})", message.title, index + 1, chunks.len())
+                } else {
+                    message.title.clone()
+                };
+
+                let mut blocks = vec![json!({
+                    "type": "header",
+                    "text": {
+                        "type": "plain_text",
+                        "text": truncate(&title, SLACK_MAX_HEADER_CHARS),
+                        "emoji": true
+                    }
+                })];
+                if index == 0 {
+                    blocks.push(json!({
+                        "type": "section",
+                        "text": {
+                            "type": "mrkdwn",
+                            "text": truncate(&slack_escape(&message.body), SLACK_MAX_SECTION_CHARS)
+                        }
+                    }));
+                    blocks.push(json!({
+                        "type": "context",
+                        "elements": [{"type": "mrkdwn", "text": counts.join("  ·  ")}]
+                    }));
+                }
+                if !chunk.is_empty() {
+                    blocks.push(json!({"type": "divider"}));
+                }
+                for cert in chunk.iter() {
+                    blocks.push(json!({
+                        "type": "section",
+                        "text": {
+                            "type": "mrkdwn",
+                            "text": truncate(&Self::certificate_text(cert, now), SLACK_MAX_SECTION_CHARS)
+                        }
+                    }));
+                }
+
+                json!({
+                    "text": title,
+                    "attachments": [{
+                        "color": color,
+                        "blocks": blocks,
+                        "footer": "Doomsday Certificate Monitor",
+                        "ts": now.timestamp()
+                    }]
+                })
+            })
+            .collect()
+    }
+
+    fn certificate_text(cert: &CacheItem, now: DateTime<Utc>) -> String {
+        let days = (cert.not_after - now).num_days();
+        let expiry = if cert.not_after <= now {
+            format!("expired {} day(s) ago", -days)
+        } else {
+            format!("{} day(s) left", days)
+        };
+        let location = match cert.paths.as_slice() {
+            [] => String::new(),
+            [path] => format!("\n`{}:{}`", path.backend, path.path),
+            [path, rest @ ..] => format!(
+                "\n`{}:{}` and {} more location(s)",
+                path.backend,
+                path.path,
+                rest.len()
+            ),
+        };
+        format!(
+            "*{}*\n{} · expires {}{}",
+            slack_escape(&cert.subject),
+            expiry,
+            cert.not_after.format("%Y-%m-%d"),
+            location
+        )
+    }
+
+    /// Where to reply: the thread of the first certificate already alerted.
+    fn thread_for(&self, message: &NotificationMessage) -> Option<String> {
+        if !self.thread {
+            return None;
+        }
+        let threads = self.threads.lock().unwrap();
+        message
+            .certificates
+            .iter()
+            .find_map(|cert| threads.get(&cert.sha1).cloned())
+    }
+
+    async fn post(
+        &self,
+        mut payload: serde_json::Value,
+        thread_ts: Option<&str>,
+    ) -> crate::Result<Option<String>> {
+        if let Some(channel) = &self.channel {
+            payload["channel"] = json!(channel);
+        }
+        if let Some(username) = &self.username {
+            payload["username"] = json!(username);
+        }
+
+        let Some(token) = &self.bot_token else {
+            let webhook_url = self.webhook_url.as_deref().unwrap_or_default();
+            let response = self.client.post(webhook_url).json(&payload).send().await?;
+            if !response.status().is_success() {
+                return Err(crate::DoomsdayError::internal(format!(
+                    "Slack notification failed: {}",
+                    response.status()
+                )));
+            }
+            return Ok(None);
+        };
+
+        if let Some(ts) = thread_ts {
+            payload["thread_ts"] = json!(ts);
+        }
+        let response: serde_json::Value = self
+            .client
+            .post(format!("{}/chat.postMessage", self.api_url))
+            .bearer_auth(token)
+            .json(&payload)
+            .send()
+            .await?
+            .json()
+            .await?;
+        // The Web API answers 200 with ok: false on failure
+        if response["ok"].as_bool() != Some(true) {
+            return Err(crate::DoomsdayError::internal(format!(
+                "Slack notification failed: {}",
+                response["error"].as_str().unwrap_or("unknown error")
+            )));
+        }
+        Ok(response["ts"].as_str().map(|ts| ts.to_string()))
+    }
+}
+
+/// Escapes the characters Slack treats as control sequences in text.
+fn slack_escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// `value` cut to at most `max` characters, marked with an ellipsis.
+fn truncate(value: &str, max: usize) -> String {
+    if value.chars().count() <= max {
+        return value.to_string();
+    }
+    let mut cut: String = value.chars().take(max - 1).collect();
+    cut.push('…');
+    cut
+}
+
+#[async_trait]
+impl NotificationBackend for SlackNotificationBackend {
+    fn recipients(&self) -> Vec<String> {
+        vec![self
+            .channel
+            .clone()
+            .unwrap_or_else(|| "default channel of the Slack webhook".to_string())]
+    }
+
+    async fn send_notification(&self, message: &NotificationMessage) -> crate::Result<()> {
+        let mut thread_ts = self.thread_for(message);
+        let starts_thread = thread_ts.is_none();
+
+        for payload in Self::payloads(message, Utc::now()) {
+            let ts = self.post(payload, thread_ts.as_deref()).await?;
+            // Later parts of a split message reply to the first
+            if self.thread && thread_ts.is_none() {
+                thread_ts = ts;
+            }
+        }
+
+        if self.thread && starts_thread {
+            if let Some(ts) = thread_ts {
+                let mut threads = self.threads.lock().unwrap();
+                for cert in &message.certificates {
+                    threads
+                        .entry(cert.sha1.clone())
+                        .or_insert_with(|| ts.clone());
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+pub struct ShoutNotificationBackend {
+    url: String,
+    client: reqwest::Client,
+}
 
-        let expired: Vec<CacheItem> = certificates
-            .iter()
-            .filter(|cert| cert.not_after < now)
-            .cloned()
-            .collect();
+impl ShoutNotificationBackend {
+    pub fn new(url: String) -> Self {
+        ShoutNotificationBackend {
+            url,
+            client: reqwest::Client::new(),
+        }
+    }
 
-        let expiring_soon: Vec<CacheItem> = certificates
-            .iter()
-            .filter(|cert| {
-                let days_until_expiry = (cert.not_after - now).num_days();
-                days_until_expiry > 0 && days_until_expiry <= 30
-            })
-            .cloned()
-            .collect();
+    pub fn from_config(config: &ShoutNotificationConfig) -> Self {
+        ShoutNotificationBackend::new(config.url.clone())
+    }
+}
 
-        if !expired.is_empty() {
-            let message = NotificationMessage {
-                title: "⚠️ Expired Certificates".to_string(),
-                body: format!(
-                    "{} certificate(s) have expired. Please check {} for details.",
-                    expired.len(),
-                    self.doomsday_url
-                ),
-                urgency: NotificationUrgency::Critical,
-                certificates: expired,
-            };
+#[async_trait]
+impl NotificationBackend for ShoutNotificationBackend {
+    fn recipients(&self) -> Vec<String> {
+        vec![self.url.clone()]
+    }
 
-            self.backend.send_notification(&message).await?;
-        }
+    async fn send_notification(&self, message: &NotificationMessage) -> crate::Result<()> {
+        let payload = json!({
+            "title": message.title,
+            "body": message.body,
+            "urgency": message.urgency.as_str(),
+            "certificates": message.certificates.len(),
+            "timestamp": Utc::now().to_rfc3339(),
+        });
 
-        if !expiring_soon.is_empty() {
-            let message = NotificationMessage {
-                title: "⏰ Certificates Expiring Soon".to_string(),
-                body: format!(
-                    "{} certificate(s) will expire within 30 days. Please check {} for details.",
-                    expiring_soon.len(),
-                    self.doomsday_url
-                ),
-                urgency: NotificationUrgency::High,
-                certificates: expiring_soon,
-            };
+        let response = self.client.post(&self.url).json(&payload).send().await?;
 
-            self.backend.send_notification(&message).await?;
+        if !response.status().is_success() {
+            return Err(crate::DoomsdayError::internal(format!(
+                "Shout notification failed: {}",
+                response.status()
+            )));
         }
 
         Ok(())
     }
 }
 
-pub struct SlackNotificationBackend {
+/// Posts Adaptive Cards to a Microsoft Teams incoming webhook (or a Workflows
+/// webhook accepting the same payload).
+pub struct TeamsNotificationBackend {
     webhook_url: String,
-    channel: Option<String>,
-    username: Option<String>,
     client: reqwest::Client,
 }
 
-impl SlackNotificationBackend {
-    pub fn new(webhook_url: String, channel: Option<String>, username: Option<String>) -> Self {
-        SlackNotificationBackend {
+impl TeamsNotificationBackend {
+    pub fn new(webhook_url: String) -> Self {
+        TeamsNotificationBackend {
             webhook_url,
-            channel,
-            username,
             client: reqwest::Client::new(),
         }
     }
 
-    pub fn from_config(properties: &HashMap<String, serde_yaml::Value>) -> crate::Result<Self> {
-        let webhook_url = properties
-            .get("webhook_url")
-            .and_then(|v| v.as_str())
-            .ok_or_else(|| crate::DoomsdayError::config("Slack webhook_url is required"))?;
-
-        let channel = properties
-            .get("channel")
-            .and_then(|v| v.as_str())
-            .map(|s| s.to_string());
-
-        let username = properties
-            .get("username")
-            .and_then(|v| v.as_str())
-            .map(|s| s.to_string());
-
-        Ok(SlackNotificationBackend::new(
-            webhook_url.to_string(),
-            channel,
-            username,
-        ))
+    pub fn from_config(config: &TeamsNotificationConfig) -> Self {
+        TeamsNotificationBackend::new(config.webhook_url.clone())
     }
-}
 
-#[async_trait]
-impl NotificationBackend for SlackNotificationBackend {
-    async fn send_notification(&self, message: &NotificationMessage) -> crate::Result<()> {
+    fn card(message: &NotificationMessage) -> serde_json::Value {
         let color = match message.urgency {
-            NotificationUrgency::Low => "#36a64f",      // Green
-            NotificationUrgency::Normal => "#2196F3",   // Blue
-            NotificationUrgency::High => "#ff9800",     // Orange
-            NotificationUrgency::Critical => "#f44336", // Red
+            NotificationUrgency::Low => "Good",
+            NotificationUrgency::Normal => "Accent",
+            NotificationUrgency::High => "Warning",
+            NotificationUrgency::Critical => "Attention",
         };
 
-        let mut fields = vec![];
-
-        // Group certificates by expiry status
-        let now = Utc::now();
-        let mut expired_count = 0;
-        let mut expiring_soon_count = 0;
-
-        for cert in &message.certificates {
-            if cert.not_after < now {
-                expired_count += 1;
-            } else {
-                expiring_soon_count += 1;
-            }
-        }
-
+        let (expired_count, expiring_soon_count) = message.status_counts();
+        let mut facts = vec![json!({"title": "Urgency", "value": message.urgency.as_str()})];
         if expired_count > 0 {
-            fields.push(json!({
+            facts.push(json!({
                 "title": "Expired",
-                "value": format!("{} certificates", expired_count),
-                "short": true
+                "value": format!("{} certificates", expired_count)
             }));
         }
-
         if expiring_soon_count > 0 {
-            fields.push(json!({
+            facts.push(json!({
                 "title": "Expiring Soon",
-                "value": format!("{} certificates", expiring_soon_count),
-                "short": true
+                "value": format!("{} certificates", expiring_soon_count)
             }));
         }
 
-        let mut payload = json!({
-            "text": message.title,
+        json!({
+            "type": "message",
             "attachments": [{
-                "color": color,
-                "text": message.body,
-                "fields": fields,
-                "footer": "Doomsday Certificate Monitor",
-                "ts": Utc::now().timestamp()
+                "contentType": "application/vnd.microsoft.card.adaptive",
+                "content": {
+                    "$schema": "http://adaptivecards.io/schemas/adaptive-card.json",
+                    "type": "AdaptiveCard",
+                    "version": "1.4",
+                    "body": [
+                        {
+                            "type": "TextBlock",
+                            "text": message.title,
+                            "size": "Large",
+                            "weight": "Bolder",
+                            "color": color,
+                            "wrap": true
+                        },
+                        {"type": "TextBlock", "text": message.body, "wrap": true},
+                        {"type": "FactSet", "facts": facts},
+                        {
+                            "type": "TextBlock",
+                            "text": "Doomsday Certificate Monitor",
+                            "size": "Small",
+                            "isSubtle": true
+                        }
+                    ],
+                    "actions": [{
+                        "type": "Action.OpenUrl",
+                        "title": "Open dashboard",
+                        "url": message.dashboard_url
+                    }]
+                }
             }]
-        });
-
-        if let Some(channel) = &self.channel {
-            payload["channel"] = json!(channel);
-        }
+        })
+    }
+}
 
-        if let Some(username) = &self.username {
-            payload["username"] = json!(username);
-        }
+#[async_trait]
+impl NotificationBackend for TeamsNotificationBackend {
+    fn recipients(&self) -> Vec<String> {
+        vec!["channel of the Teams webhook".to_string()]
+    }
 
+    async fn send_notification(&self, message: &NotificationMessage) -> crate::Result<()> {
         let response = self
             .client
             .post(&self.webhook_url)
-            .json(&payload)
+            .json(&Self::card(message))
             .send()
             .await?;
 
         if !response.status().is_success() {
             return Err(crate::DoomsdayError::internal(format!(
-                "Slack notification failed: {}",
+                "Teams notification failed: {}",
                 response.status()
             )));
         }
@@ -233,50 +1654,221 @@ impl NotificationBackend for SlackNotificationBackend {
     }
 }
 
-pub struct ShoutNotificationBackend {
+handlebars::handlebars_helper!(json_helper: |value: Json| serde_json::to_string(value).unwrap_or_default());
+
+/// Sends each notification to an arbitrary HTTP endpoint, with the body
+/// rendered from a Handlebars template. Templates see `title`, `body`,
+/// `urgency`, `dashboard_url`, `timestamp`, `expired`, `expiring_soon`,
+/// `count` and `certificates` (as returned by `/v1/cache`); `{{json value}}` renders a
+/// value as JSON. Output is not HTML-escaped.
+pub struct WebhookNotificationBackend {
     url: String,
+    method: reqwest::Method,
+    headers: Vec<(String, String)>,
+    templates: handlebars::Handlebars<'static>,
     client: reqwest::Client,
 }
 
-impl ShoutNotificationBackend {
-    pub fn new(url: String) -> Self {
-        ShoutNotificationBackend {
-            url,
+const BODY_TEMPLATE: &str = "body";
+
+/// Used when no `body` template is configured.
+const DEFAULT_BODY_TEMPLATE: &str = r#"{"title": {{json title}}, "body": {{json body}}, "urgency": {{json urgency}}, "certificates": {{json certificates}}, "dashboard_url": {{json dashboard_url}}, "timestamp": {{json timestamp}}}"#;
+
+impl WebhookNotificationBackend {
+    pub fn from_config(config: &WebhookNotificationConfig) -> crate::Result<Self> {
+        let method =
+            reqwest::Method::from_bytes(config.method.to_uppercase().as_bytes()).map_err(|_| {
+                crate::DoomsdayError::config(format!("Invalid webhook method: {}", config.method))
+            })?;
+
+        let mut templates = handlebars::Handlebars::new();
+        templates.register_escape_fn(handlebars::no_escape);
+        templates.register_helper("json", Box::new(json_helper));
+        templates
+            .register_template_string(
+                BODY_TEMPLATE,
+                config.body.as_deref().unwrap_or(DEFAULT_BODY_TEMPLATE),
+            )
+            .map_err(|e| {
+                crate::DoomsdayError::config(format!("Invalid webhook body template: {}", e))
+            })?;
+
+        Ok(WebhookNotificationBackend {
+            url: config.url.clone(),
+            method,
+            headers: config
+                .headers
+                .iter()
+                .map(|(name, value)| (name.clone(), value.clone()))
+                .collect(),
+            templates,
+            client: reqwest::Client::new(),
+        })
+    }
+
+    fn render(&self, message: &NotificationMessage) -> crate::Result<String> {
+        let (expired, expiring_soon) = message.status_counts();
+        let context = json!({
+            "title": message.title,
+            "body": message.body,
+            "urgency": message.urgency.as_str(),
+            "dashboard_url": message.dashboard_url,
+            "timestamp": Utc::now().to_rfc3339(),
+            "expired": expired,
+            "expiring_soon": expiring_soon,
+            "count": message.certificates.len(),
+            "certificates": message.certificates,
+        });
+
+        self.templates
+            .render(BODY_TEMPLATE, &context)
+            .map_err(|e| crate::DoomsdayError::internal(format!("Webhook template error: {}", e)))
+    }
+}
+
+#[async_trait]
+impl NotificationBackend for WebhookNotificationBackend {
+    fn recipients(&self) -> Vec<String> {
+        vec![format!("{} {}", self.method, self.url)]
+    }
+
+    async fn send_notification(&self, message: &NotificationMessage) -> crate::Result<()> {
+        let mut request = self
+            .client
+            .request(self.method.clone(), &self.url)
+            .header("Content-Type", "application/json");
+        // Configured headers come last so they can override the content type
+        for (name, value) in &self.headers {
+            request = request.header(name, value);
+        }
+
+        let response = request.body(self.render(message)?).send().await?;
+
+        if !response.status().is_success() {
+            return Err(crate::DoomsdayError::internal(format!(
+                "Webhook notification failed: {}",
+                response.status()
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+/// Certificates listed in a Telegram message, soonest expiry first.
+const TELEGRAM_LISTED_CERTIFICATES: usize = 5;
+/// Telegram rejects messages longer than this many characters.
+const TELEGRAM_MAX_MESSAGE_CHARS: usize = 4096;
+
+/// Sends messages to a Telegram chat through a bot.
+pub struct TelegramNotificationBackend {
+    api_url: String,
+    bot_token: String,
+    chat_id: String,
+    client: reqwest::Client,
+}
+
+impl TelegramNotificationBackend {
+    pub fn from_config(config: &TelegramNotificationConfig) -> Self {
+        TelegramNotificationBackend {
+            api_url: config.api_url.trim_end_matches('/').to_string(),
+            bot_token: config.bot_token.clone(),
+            chat_id: config.chat_id.clone(),
             client: reqwest::Client::new(),
         }
     }
 
-    pub fn from_config(properties: &HashMap<String, serde_yaml::Value>) -> crate::Result<Self> {
-        let url = properties
-            .get("url")
-            .and_then(|v| v.as_str())
-            .ok_or_else(|| crate::DoomsdayError::config("Shout URL is required"))?;
+    /// The message as Telegram HTML.
+    fn text(message: &NotificationMessage) -> String {
+        let emoji = match message.urgency {
+            NotificationUrgency::Low => "🟢",
+            NotificationUrgency::Normal => "🔵",
+            NotificationUrgency::High => "🟠",
+            NotificationUrgency::Critical => "🔴",
+        };
+
+        let mut text = format!(
+            "{} <b>{}</b>\n{}",
+            emoji,
+            html_escape(&message.title),
+            html_escape(&message.body)
+        );
+
+        let (expired, expiring_soon) = message.status_counts();
+        if expired > 0 || expiring_soon > 0 {
+            text.push_str(&format!(
+                "\n\nExpired: {} · Expiring soon: {}",
+                expired, expiring_soon
+            ));
+        }
+
+        let mut certificates: Vec<&CacheItem> = message.certificates.iter().collect();
+        certificates.sort_by_key(|cert| cert.not_after);
+        if !certificates.is_empty() {
+            text.push('\n');
+        }
+        for cert in certificates.iter().take(TELEGRAM_LISTED_CERTIFICATES) {
+            text.push_str(&format!(
+                "\n• <code>{}</code> {}",
+                html_escape(&cert.subject),
+                cert.not_after.format("%Y-%m-%d")
+            ));
+        }
+        if certificates.len() > TELEGRAM_LISTED_CERTIFICATES {
+            text.push_str(&format!(
+                "\n… and {} more",
+                certificates.len() - TELEGRAM_LISTED_CERTIFICATES
+            ));
+        }
+
+        if !message.dashboard_url.is_empty() {
+            text.push_str(&format!(
+                "\n\n<a href=\"{}\">Open dashboard</a>",
+                html_escape(&message.dashboard_url)
+            ));
+        }
 
-        Ok(ShoutNotificationBackend::new(url.to_string()))
+        if text.chars().count() > TELEGRAM_MAX_MESSAGE_CHARS {
+            // Cutting may split a tag, so fall back to the plain summary
+            return format!(
+                "{} <b>{}</b>\n{} certificate(s), see the dashboard for details.",
+                emoji,
+                html_escape(&message.title),
+                message.certificates.len()
+            );
+        }
+        text
     }
 }
 
+fn html_escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
 #[async_trait]
-impl NotificationBackend for ShoutNotificationBackend {
+impl NotificationBackend for TelegramNotificationBackend {
+    fn recipients(&self) -> Vec<String> {
+        vec![format!("Telegram chat {}", self.chat_id)]
+    }
+
     async fn send_notification(&self, message: &NotificationMessage) -> crate::Result<()> {
         let payload = json!({
-            "title": message.title,
-            "body": message.body,
-            "urgency": match message.urgency {
-                NotificationUrgency::Low => "low",
-                NotificationUrgency::Normal => "normal",
-                NotificationUrgency::High => "high",
-                NotificationUrgency::Critical => "critical",
-            },
-            "certificates": message.certificates.len(),
-            "timestamp": Utc::now().to_rfc3339(),
+            "chat_id": self.chat_id,
+            "text": Self::text(message),
+            "parse_mode": "HTML",
+            "disable_web_page_preview": true,
         });
 
-        let response = self.client.post(&self.url).json(&payload).send().await?;
+        let url = format!("{}/bot{}/sendMessage", self.api_url, self.bot_token);
+        let response = self.client.post(&url).json(&payload).send().await?;
 
         if !response.status().is_success() {
             return Err(crate::DoomsdayError::internal(format!(
-                "Shout notification failed: {}",
+                "Telegram notification failed: {}",
                 response.status()
             )));
         }
@@ -284,3 +1876,602 @@ impl NotificationBackend for ShoutNotificationBackend {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alerts::AlertAck;
+    use crate::types::{CacheObject, PathObject};
+
+    fn item(status: CertificateStatus) -> CacheItem {
+        let object = CacheObject {
+            subject: "CN=example.com".to_string(),
+            not_before: None,
+            not_after: Utc::now(),
+            sha1: "abc".to_string(),
+            paths: vec![PathObject::new("vault", "secret/example")],
+            metadata: Default::default(),
+        };
+        CacheItem::new(&object, status)
+    }
+
+    #[test]
+    fn test_teams_card() {
+        let message = NotificationMessage {
+            title: "⚠️ Expired Certificates".to_string(),
+            body: "2 certificate(s) have expired.".to_string(),
+            urgency: NotificationUrgency::Critical,
+            certificates: vec![
+                item(CertificateStatus::Expired),
+                item(CertificateStatus::Expired),
+                item(CertificateStatus::ExpiringSoon),
+            ],
+            dashboard_url: "https://doomsday.example.com".to_string(),
+        };
+
+        let card = &TeamsNotificationBackend::card(&message)["attachments"][0]["content"];
+        assert_eq!(card["type"], "AdaptiveCard");
+        assert_eq!(card["body"][0]["color"], "Attention");
+        let facts = card["body"][2]["facts"].as_array().unwrap();
+        assert_eq!(facts[1]["value"], "2 certificates");
+        assert_eq!(facts[2]["value"], "1 certificates");
+        assert_eq!(card["actions"][0]["url"], "https://doomsday.example.com");
+    }
+
+    #[test]
+    fn test_webhook_body_template() {
+        let properties: WebhookNotificationConfig = serde_yaml::from_str(
+            r#"
+url: https://alerts.example.com/hook
+method: put
+headers:
+  Authorization: Bearer token
+body: '{"summary": {{json title}}, "severity": "{{urgency}}", "count": {{count}}, "first": "{{certificates.0.subject}}"}'
+"#,
+        )
+        .unwrap();
+        let backend = WebhookNotificationBackend::from_config(&properties).unwrap();
+        assert_eq!(backend.method, reqwest::Method::PUT);
+
+        let message = NotificationMessage {
+            title: "Quote \"this\" & that".to_string(),
+            body: String::new(),
+            urgency: NotificationUrgency::High,
+            certificates: vec![item(CertificateStatus::ExpiringSoon)],
+            dashboard_url: String::new(),
+        };
+        let rendered: serde_json::Value =
+            serde_json::from_str(&backend.render(&message).unwrap()).unwrap();
+        assert_eq!(rendered["summary"], "Quote \"this\" & that");
+        assert_eq!(rendered["severity"], "high");
+        assert_eq!(rendered["count"], 1);
+        assert_eq!(rendered["first"], "CN=example.com");
+
+        // The default body is valid JSON as well
+        let mut defaults = properties.clone();
+        defaults.body = None;
+        let backend = WebhookNotificationBackend::from_config(&defaults).unwrap();
+        let rendered: serde_json::Value =
+            serde_json::from_str(&backend.render(&message).unwrap()).unwrap();
+        assert_eq!(rendered["urgency"], "high");
+
+        let mut invalid = properties;
+        invalid.body = Some("{{#if}}".to_string());
+        assert!(WebhookNotificationBackend::from_config(&invalid).is_err());
+    }
+
+    #[test]
+    fn test_slack_blocks_split() {
+        let now = Utc::now();
+        let mut certificates: Vec<CacheItem> = (0..SLACK_CERTIFICATES_PER_MESSAGE + 5)
+            .map(|i| {
+                let mut cert = item(CertificateStatus::ExpiringSoon);
+                cert.not_after = now + Duration::days(10 + i as i64);
+                cert
+            })
+            .collect();
+        certificates[7].subject = "CN=a&b".to_string();
+        certificates[7].not_after = now - Duration::days(2);
+
+        let payloads = SlackNotificationBackend::payloads(
+            &NotificationMessage {
+                title: "⏰ Certificates Expiring Within 30 Day(s)".to_string(),
+                body: "Please check".to_string(),
+                urgency: NotificationUrgency::High,
+                certificates,
+                dashboard_url: "https://doomsday.example.com".to_string(),
+            },
+            now,
+        );
+
+        assert_eq!(payloads.len(), 2);
+        assert_eq!(
+            payloads[1]["text"],
+            "⏰ Certificates Expiring Within 30 Day(s) (2/2)"
+        );
+        let blocks = |payload: &serde_json::Value| payload["attachments"][0]["blocks"].clone();
+        let first = blocks(&payloads[0]);
+        assert_eq!(
+            first.as_array().unwrap().len(),
+            4 + SLACK_CERTIFICATES_PER_MESSAGE
+        );
+        assert_eq!(
+            first[2]["elements"][0]["text"],
+            "*Expiring soon:* 45  ·  <https://doomsday.example.com|Open dashboard>"
+        );
+        // Soonest expiry first, escaped, with where it was found
+        let soonest = first[4]["text"]["text"].as_str().unwrap();
+        assert!(soonest.starts_with("*CN=a&amp;b*\nexpired 2 day(s) ago"));
+        assert!(soonest.ends_with("`vault:secret/example`"));
+        assert_eq!(blocks(&payloads[1]).as_array().unwrap().len(), 1 + 1 + 5);
+
+        let properties: SlackNotificationConfig =
+            serde_yaml::from_str("webhook_url: https://hooks.slack.com/x\nthread: true").unwrap();
+        assert!(SlackNotificationBackend::from_config(&properties).is_err());
+    }
+
+    #[test]
+    fn test_telegram_text() {
+        let mut certificates: Vec<CacheItem> = (0..7)
+            .map(|_| item(CertificateStatus::ExpiringSoon))
+            .collect();
+        certificates[0].subject = "CN=<script>".to_string();
+        certificates[0].status = CertificateStatus::Expired;
+        certificates[0].not_after = Utc::now() - Duration::days(1);
+
+        let text = TelegramNotificationBackend::text(&NotificationMessage {
+            title: "⚠️ Expired Certificates".to_string(),
+            body: "Please check".to_string(),
+            urgency: NotificationUrgency::Critical,
+            certificates,
+            dashboard_url: "https://doomsday.example.com".to_string(),
+        });
+
+        assert!(text.starts_with("🔴 <b>⚠️ Expired Certificates</b>"));
+        assert!(text.contains("Expired: 1 · Expiring soon: 6"));
+        // Soonest first, escaped
+        assert!(text.contains("\n• <code>CN=&lt;script&gt;</code>"));
+        assert_eq!(text.matches("\n• ").count(), TELEGRAM_LISTED_CERTIFICATES);
+        assert!(text.contains("… and 2 more"));
+        assert!(text.ends_with("<a href=\"https://doomsday.example.com\">Open dashboard</a>"));
+
+        let properties: TelegramNotificationConfig =
+            serde_yaml::from_str("bot_token: '123:abc'\nchat_id: -1001234").unwrap();
+        let backend = TelegramNotificationBackend::from_config(&properties);
+        assert_eq!(backend.chat_id, "-1001234");
+    }
+
+    #[test]
+    fn test_alert_threshold_routing() {
+        let config: NotificationConfig = serde_yaml::from_str(
+            r#"
+doomsday_url: https://doomsday.example.com
+backend:
+  type: slack
+  properties:
+    webhook_url: https://hooks.slack.com/services/T/B/X
+schedule:
+  type: constant
+  properties:
+    interval: 60
+thresholds:
+  - within: 30
+    urgency: normal
+  - within: 7
+    urgency: high
+  - within: 1
+    urgency: critical
+    backend:
+      type: webhook
+      properties:
+        url: https://events.pagerduty.example.com/v2/enqueue
+"#,
+        )
+        .unwrap();
+        let service = NotificationService::new(&config, &config.thresholds).unwrap();
+        let route = |days| {
+            service
+                .simulate(days, &HashMap::new(), &[])
+                .pop()
+                .map(|m| (m.rule, m.backend, m.urgency.unwrap()))
+        };
+
+        assert_eq!(route(45), None);
+        assert_eq!(
+            route(20),
+            Some(("within_30d".into(), "slack".into(), "normal".into()))
+        );
+        assert_eq!(
+            route(7),
+            Some(("within_7d".into(), "slack".into(), "high".into()))
+        );
+        // Expired certificates fall under the tightest threshold
+        assert_eq!(
+            route(-3),
+            Some(("within_1d".into(), "webhook".into(), "critical".into()))
+        );
+    }
+
+    #[test]
+    fn test_backend_routing() {
+        let config: NotificationConfig = serde_yaml::from_str(
+            r#"
+doomsday_url: https://doomsday.example.com
+backends:
+  - name: oncall
+    type: webhook
+    properties:
+      url: https://events.pagerduty.example.com/v2/enqueue
+    route:
+      urgency: [critical]
+      backends: [vault]
+      path: "^secret/prod/"
+  - name: dev-slack
+    type: slack
+    properties:
+      webhook_url: https://hooks.slack.com/services/T/B/X
+    route:
+      labels:
+        environment: dev
+schedule:
+  type: constant
+  properties:
+    interval: 60
+"#,
+        )
+        .unwrap();
+        let thresholds = [
+            AlertThreshold {
+                within: 0,
+                urgency: NotificationUrgency::Critical,
+                backend: None,
+            },
+            AlertThreshold {
+                within: 30,
+                urgency: NotificationUrgency::High,
+                backend: None,
+            },
+        ];
+        let service = NotificationService::new(&config, &thresholds).unwrap();
+        let dev = HashMap::from([("environment".to_string(), "dev".to_string())]);
+        let prod = [PathObject::new("vault", "secret/prod/api")];
+        let routed = |days, labels: &HashMap<String, String>, paths: &[PathObject]| {
+            service
+                .simulate(days, labels, paths)
+                .into_iter()
+                .map(|m| m.backend)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(routed(-1, &HashMap::new(), &prod), ["oncall"]);
+        // Expiring soon is not critical enough to page
+        assert!(routed(10, &HashMap::new(), &prod).is_empty());
+        assert!(routed(
+            -1,
+            &HashMap::new(),
+            &[PathObject::new("credhub", "secret/prod/api")]
+        )
+        .is_empty());
+        assert_eq!(routed(10, &dev, &[]), ["dev-slack"]);
+    }
+
+    #[test]
+    fn test_digest_grouped_by_label() {
+        let config: NotificationConfig = serde_yaml::from_str(
+            r#"
+doomsday_url: https://doomsday.example.com
+backend:
+  type: slack
+  properties:
+    webhook_url: https://hooks.slack.com/services/T/B/X
+schedule:
+  type: cron
+  properties:
+    expression: "0 9 * * Mon-Fri"
+mode: digest
+digest:
+  top: 1
+  group_by: label:team
+"#,
+        )
+        .unwrap();
+        let thresholds = [
+            AlertThreshold {
+                within: 0,
+                urgency: NotificationUrgency::Critical,
+                backend: None,
+            },
+            AlertThreshold {
+                within: 30,
+                urgency: NotificationUrgency::High,
+                backend: None,
+            },
+        ];
+        let service = NotificationService::new(&config, &thresholds).unwrap();
+
+        let now = Utc::now();
+        let cert = |subject: &str, days: i64, team: Option<&str>| {
+            let mut cert = item(CertificateStatus::ExpiringSoon);
+            cert.subject = subject.to_string();
+            cert.not_after = now + Duration::days(days) + Duration::hours(1);
+            if let Some(team) = team {
+                cert.labels.insert("team".to_string(), team.to_string());
+            }
+            cert
+        };
+        let message = service.digest_summary(
+            vec![
+                cert("CN=old", -3, Some("payments")),
+                cert("CN=soon", 5, Some("payments")),
+                cert("CN=orphan", 20, None),
+            ],
+            now,
+        );
+
+        assert_eq!(message.urgency, NotificationUrgency::Critical);
+        assert!(message.body.contains("Expired: 1 · Within 30 day(s): 2"));
+        // Groups are listed by name, each with its soonest expiring first
+        let lines: Vec<&str> = message.body.lines().skip(2).collect();
+        assert_eq!(
+            lines,
+            [
+                "",
+                "no team (1)",
+                "• CN=orphan (expires in 20 day(s))",
+                "",
+                "payments (2)",
+                "• CN=old (expired 2 day(s) ago)",
+                "… and 1 more",
+            ]
+        );
+    }
+
+    /// Fails its first `failures` sends.
+    struct FlakyBackend {
+        failures: std::sync::atomic::AtomicU32,
+    }
+
+    #[async_trait]
+    impl NotificationBackend for FlakyBackend {
+        async fn send_notification(&self, _message: &NotificationMessage) -> crate::Result<()> {
+            use std::sync::atomic::Ordering;
+            let remaining = self.failures.load(Ordering::SeqCst);
+            if remaining == 0 {
+                return Ok(());
+            }
+            self.failures.store(remaining - 1, Ordering::SeqCst);
+            Err(crate::DoomsdayError::internal("HTTP 503"))
+        }
+
+        fn recipients(&self) -> Vec<String> {
+            vec![]
+        }
+    }
+
+    #[tokio::test]
+    async fn test_retry_then_dead_letter() {
+        let policy = RetryPolicy {
+            max_attempts: 3,
+            initial_backoff: 0,
+            max_backoff: 0,
+        };
+        let retry = |failures: u32| RetryDelivery {
+            backend: Arc::new(FlakyBackend {
+                failures: failures.into(),
+            }),
+            name: "slack".to_string(),
+            message: NotificationMessage {
+                title: "⚠️ Expired Certificates".to_string(),
+                body: String::new(),
+                urgency: NotificationUrgency::Critical,
+                certificates: vec![item(CertificateStatus::Expired)],
+                dashboard_url: String::new(),
+            },
+            policy,
+            history: NotificationHistory::new(),
+            dead_letters: DeadLetters::new(),
+        };
+
+        // The first attempt already failed; the next one succeeds
+        let recovers = retry(0);
+        let (history, dead_letters) = (recovers.history.clone(), recovers.dead_letters.clone());
+        recovers
+            .run(crate::DoomsdayError::internal("HTTP 503"))
+            .await;
+        let sent = history.list(None, Some("abc"), Some(DeliveryStatus::Sent), None);
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].attempt, 2);
+        assert!(dead_letters.list(None, None).is_empty());
+
+        let outage = retry(5);
+        let (history, dead_letters) = (outage.history.clone(), outage.dead_letters.clone());
+        outage.run(crate::DoomsdayError::internal("HTTP 503")).await;
+        assert_eq!(history.list(None, None, None, None).len(), 2);
+        let letters = dead_letters.list(Some("slack"), None);
+        assert_eq!(letters.len(), 1);
+        assert_eq!(letters[0].attempts, 3);
+        assert!(letters[0].error.contains("HTTP 503"));
+    }
+
+    #[tokio::test]
+    async fn test_send_test_reports_each_backend() {
+        let config: NotificationConfig = serde_yaml::from_str(
+            r#"
+doomsday_url: https://doomsday.example.com
+schedule:
+  type: constant
+  properties:
+    interval: 60
+quiet_hours:
+  - start: "00:00"
+    end: "23:59"
+"#,
+        )
+        .unwrap();
+        let mut service = NotificationService::new(&config, &[]).unwrap();
+        let target = |name: &str, failures: u32| NotificationTarget {
+            name: name.to_string(),
+            backend: Arc::new(FlakyBackend {
+                failures: failures.into(),
+            }),
+            urgency: vec![NotificationUrgency::Critical],
+            backends: Vec::new(),
+            path: None,
+            labels: HashMap::new(),
+        };
+        service.targets = vec![target("slack", 0), target("oncall", 1)];
+
+        // Sent despite quiet hours and routes that only take critical alerts
+        let results = service.send_test(None).await;
+        assert_eq!(results.len(), 2);
+        assert!(results[0].success);
+        assert!(!results[1].success);
+        assert!(results[1].error.as_deref().unwrap().contains("HTTP 503"));
+        assert_eq!(
+            service
+                .history
+                .list(Some("oncall"), None, Some(DeliveryStatus::Failed), None)
+                .len(),
+            1
+        );
+
+        let results = service.send_test(Some("oncall")).await;
+        assert_eq!(results.len(), 1);
+        assert!(results[0].success);
+    }
+
+    /// Keeps every message it is asked to send.
+    #[derive(Default)]
+    struct RecordingBackend {
+        sent: std::sync::Mutex<Vec<NotificationMessage>>,
+    }
+
+    #[async_trait]
+    impl NotificationBackend for RecordingBackend {
+        async fn send_notification(&self, message: &NotificationMessage) -> crate::Result<()> {
+            self.sent.lock().unwrap().push(message.clone());
+            Ok(())
+        }
+
+        fn recipients(&self) -> Vec<String> {
+            vec![]
+        }
+    }
+
+    #[tokio::test]
+    async fn test_event_batch_grouping_and_label_filter() {
+        let config: NotificationConfig = serde_yaml::from_str(
+            r#"
+doomsday_url: https://doomsday.example.com
+schedule:
+  type: constant
+  properties:
+    interval: 60
+labels:
+  team: web
+events: [threshold_crossed, certificate_added, certificate_removed, certificate_rotated]
+"#,
+        )
+        .unwrap();
+        let mut service = NotificationService::new(&config, &[]).unwrap();
+        let backend = Arc::new(RecordingBackend::default());
+        service.targets = vec![NotificationTarget {
+            name: "slack".to_string(),
+            backend: backend.clone(),
+            urgency: Vec::new(),
+            backends: Vec::new(),
+            path: None,
+            labels: HashMap::new(),
+        }];
+
+        let cert = |sha1: &str, team: &str, days: i64| {
+            let object = CacheObject {
+                subject: format!("CN={}", sha1),
+                not_before: None,
+                not_after: Utc::now() + chrono::Duration::days(days),
+                sha1: sha1.to_string(),
+                paths: vec![PathObject::new("vault", "secret/example")],
+                metadata: Default::default(),
+            };
+            let mut item = CacheItem::new(&object, CertificateStatus::Ok);
+            item.labels = [("team".to_string(), team.to_string())].into();
+            (object, item)
+        };
+        let crossed = |sha1: &str, team: &str, threshold_days: i64| {
+            let (certificate, item) = cert(sha1, team, threshold_days - 1);
+            let event = CacheEvent::ThresholdCrossed {
+                certificate,
+                threshold_days,
+            };
+            (event, item)
+        };
+
+        let (new, new_item) = cert("new", "web", 90);
+        let (old, old_item) = cert("old", "web", 3);
+        let (found, found_item) = cert("found", "web", 60);
+        let mut acked = crossed("acked", "web", 7);
+        acked.1.acknowledged = Some(AlertAck {
+            sha1: "acked".to_string(),
+            subject: "CN=acked".to_string(),
+            acknowledged_at: Utc::now(),
+            until: Utc::now() + chrono::Duration::days(3),
+            comment: None,
+        });
+
+        let batch = vec![
+            crossed("soon", "web", 7),
+            crossed("other-team", "payments", 7),
+            (
+                CacheEvent::CertificateAdded {
+                    certificate: new.clone(),
+                },
+                new_item.clone(),
+            ),
+            (
+                CacheEvent::CertificateRemoved { certificate: old },
+                old_item,
+            ),
+            crossed("later", "web", 30),
+            (
+                CacheEvent::CertificateRotated {
+                    certificate: new,
+                    backend: "vault".to_string(),
+                    path: "secret/example".to_string(),
+                    old_sha1: "old".to_string(),
+                    old_not_after: Utc::now(),
+                },
+                new_item,
+            ),
+            (
+                CacheEvent::CertificateAdded { certificate: found },
+                found_item,
+            ),
+            acked,
+        ];
+        service.notify_events(&batch).await.unwrap();
+
+        // One message per kind, in the order each kind first appeared; the
+        // rotation stands in for its addition and removal
+        let sent = backend.sent.lock().unwrap();
+        let summary: Vec<(&str, Vec<&str>)> = sent
+            .iter()
+            .map(|message| {
+                let subjects = message.certificates.iter().map(|c| c.subject.as_str());
+                (message.title.as_str(), subjects.collect())
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (
+                    "⏰ Certificates Crossed an Expiry Threshold",
+                    vec!["CN=soon", "CN=later"]
+                ),
+                ("✅ Certificates Rotated", vec!["CN=new"]),
+                ("🆕 Certificates Discovered", vec!["CN=found"]),
+            ]
+        );
+        assert!(sent[0].body.contains("(7-day threshold)"));
+        assert!(sent[0].body.contains("(30-day threshold)"));
+    }
+}
@@ -0,0 +1,128 @@
+use crate::cache::Cache;
+use crate::config::PinConfig;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct PinViolation {
+    pub backend: String,
+    pub path: String,
+    pub subject: String,
+    pub expected: Vec<String>,
+    pub observed: String,
+}
+
+pub fn normalize_fingerprint(fingerprint: &str) -> String {
+    fingerprint
+        .chars()
+        .filter(|c| c.is_ascii_hexdigit())
+        .collect::<String>()
+        .to_lowercase()
+}
+
+/// Compares every pinned location against what the cache currently holds.
+/// Locations with no certificate at all are not reported, since that usually
+/// means the backend could not be reached rather than that the cert changed.
+pub fn check_pins(pins: &[PinConfig], cache: &Cache) -> Vec<PinViolation> {
+    let mut violations = Vec::new();
+
+    for pin in pins {
+        let expected: Vec<String> = pin
+            .fingerprints
+            .iter()
+            .map(|fp| normalize_fingerprint(fp))
+            .collect();
+
+        let at_path = cache.find_by_path(&pin.backend, &pin.path);
+        // Issuers delivered alongside the pinned certificate share its path
+        let issuers: HashSet<&str> = at_path
+            .iter()
+            .filter(|c| !c.metadata.issuer.is_empty())
+            .filter(|c| c.metadata.issuer != c.subject)
+            .map(|c| c.metadata.issuer.as_str())
+            .collect();
+        let pinned: Vec<_> = at_path
+            .iter()
+            .filter(|c| !issuers.contains(c.subject.as_str()))
+            .cloned()
+            .collect();
+
+        for observed in pinned {
+            let observed_sha1 = normalize_fingerprint(&observed.sha1);
+            if !expected.contains(&observed_sha1) {
+                violations.push(PinViolation {
+                    backend: pin.backend.clone(),
+                    path: pin.path.clone(),
+                    subject: observed.subject,
+                    expected: expected.clone(),
+                    observed: observed_sha1,
+                });
+            }
+        }
+    }
+
+    violations
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{CacheObject, PathObject};
+    use chrono::Utc;
+
+    fn insert_cert(cache: &Cache, sha1: &str, backend: &str, path: &str) {
+        cache.insert(
+            sha1.to_string(),
+            CacheObject {
+                subject: format!("CN={}", path),
+                not_before: None,
+                not_after: Utc::now(),
+                sha1: sha1.to_string(),
+                paths: vec![PathObject {
+                    backend: backend.into(),
+                    path: path.into(),
+                }],
+                metadata: Default::default(),
+            },
+        );
+    }
+
+    #[test]
+    fn test_check_pins() {
+        let cache = Cache::new();
+        insert_cert(&cache, "aabbcc", "tls", "pinned.example.com:443");
+        insert_cert(&cache, "ddeeff", "tls", "swapped.example.com:443");
+
+        // The CA sent in the same handshake is not checked against the pin
+        insert_cert(&cache, "ca0001", "tls", "pinned.example.com:443");
+        let mut ca = cache.get("ca0001").unwrap();
+        ca.subject = "CN=Issuing CA".to_string();
+        cache.insert(ca.sha1.clone(), ca);
+        let mut leaf = cache.get("aabbcc").unwrap();
+        leaf.metadata.issuer = "CN=Issuing CA".to_string();
+        cache.insert(leaf.sha1.clone(), leaf);
+
+        let pins = vec![
+            PinConfig {
+                backend: "tls".to_string(),
+                path: "pinned.example.com:443".to_string(),
+                fingerprints: vec!["AA:BB:CC".to_string()],
+            },
+            PinConfig {
+                backend: "tls".to_string(),
+                path: "swapped.example.com:443".to_string(),
+                fingerprints: vec!["112233".to_string()],
+            },
+            PinConfig {
+                backend: "tls".to_string(),
+                path: "missing.example.com:443".to_string(),
+                fingerprints: vec!["445566".to_string()],
+            },
+        ];
+
+        let violations = check_pins(&pins, &cache);
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].path, "swapped.example.com:443");
+        assert_eq!(violations[0].observed, "ddeeff");
+    }
+}
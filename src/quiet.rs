@@ -0,0 +1,315 @@
+use crate::config::{deny_unknown_fields, UnknownFields};
+use crate::json_store::JsonStore;
+use crate::notifications::NotificationUrgency;
+use crate::types::CacheItem;
+use chrono::{DateTime, Datelike, Local, NaiveTime, Utc, Weekday};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+/// Which notifications a quiet window holds. Empty lists hold everything.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct HoldScope {
+    /// Notification backend names (or types, for unnamed backends).
+    #[serde(default)]
+    pub backends: Vec<String>,
+    #[serde(default)]
+    pub urgency: Vec<NotificationUrgency>,
+}
+
+impl HoldScope {
+    fn applies(&self, target: &str, urgency: NotificationUrgency) -> bool {
+        (self.backends.is_empty() || self.backends.iter().any(|b| b == target))
+            && (self.urgency.is_empty() || self.urgency.contains(&urgency))
+    }
+}
+
+/// A daily window, in the server's local time zone, during which
+/// notifications are held. A window ending before it starts runs past
+/// midnight.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "RawQuietHours")]
+pub struct QuietHours {
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+    /// Days the window starts on. Empty means every day.
+    #[serde(default)]
+    pub days: Vec<Weekday>,
+    #[serde(flatten)]
+    pub scope: HoldScope,
+}
+
+#[derive(Deserialize)]
+struct RawQuietHours {
+    start: NaiveTime,
+    end: NaiveTime,
+    #[serde(default)]
+    days: Vec<Weekday>,
+    #[serde(flatten)]
+    scope: HoldScope,
+    #[serde(flatten)]
+    unknown: UnknownFields,
+}
+
+impl TryFrom<RawQuietHours> for QuietHours {
+    type Error = String;
+
+    fn try_from(raw: RawQuietHours) -> Result<Self, Self::Error> {
+        deny_unknown_fields(
+            &raw.unknown,
+            &["start", "end", "days", "backends", "urgency"],
+        )?;
+        Ok(QuietHours {
+            start: raw.start,
+            end: raw.end,
+            days: raw.days,
+            scope: raw.scope,
+        })
+    }
+}
+
+impl QuietHours {
+    pub fn validate(&self) -> crate::Result<()> {
+        if self.start == self.end {
+            return Err(crate::DoomsdayError::config(
+                "Quiet hours must start and end at different times",
+            ));
+        }
+        Ok(())
+    }
+
+    fn covers(&self, now: DateTime<Local>) -> bool {
+        let time = now.time();
+        let starts_on = |day: Weekday| self.days.is_empty() || self.days.contains(&day);
+        if self.start < self.end {
+            starts_on(now.weekday()) && self.start <= time && time < self.end
+        } else if time >= self.start {
+            starts_on(now.weekday())
+        } else {
+            time < self.end && starts_on(now.weekday().pred())
+        }
+    }
+}
+
+/// A one-off window, e.g. planned maintenance, during which notifications
+/// are held.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "RawMaintenanceWindow")]
+pub struct MaintenanceWindow {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub reason: Option<String>,
+    #[serde(flatten)]
+    pub scope: HoldScope,
+}
+
+#[derive(Deserialize)]
+struct RawMaintenanceWindow {
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    reason: Option<String>,
+    #[serde(flatten)]
+    scope: HoldScope,
+    #[serde(flatten)]
+    unknown: UnknownFields,
+}
+
+impl TryFrom<RawMaintenanceWindow> for MaintenanceWindow {
+    type Error = String;
+
+    fn try_from(raw: RawMaintenanceWindow) -> Result<Self, Self::Error> {
+        deny_unknown_fields(
+            &raw.unknown,
+            &["start", "end", "reason", "backends", "urgency"],
+        )?;
+        Ok(MaintenanceWindow {
+            start: raw.start,
+            end: raw.end,
+            reason: raw.reason,
+            scope: raw.scope,
+        })
+    }
+}
+
+impl MaintenanceWindow {
+    pub fn validate(&self) -> crate::Result<()> {
+        if self.end <= self.start {
+            return Err(crate::DoomsdayError::config(format!(
+                "Maintenance window starting {} must end after it starts",
+                self.start
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Why a notification to `target` at `urgency` is held at `now`, or `None`
+/// if it can be sent.
+pub fn hold_reason(
+    quiet_hours: &[QuietHours],
+    maintenance: &[MaintenanceWindow],
+    target: &str,
+    urgency: NotificationUrgency,
+    now: DateTime<Utc>,
+) -> Option<String> {
+    let window = maintenance.iter().find(|window| {
+        window.start <= now && now < window.end && window.scope.applies(target, urgency)
+    });
+    if let Some(window) = window {
+        return Some(match &window.reason {
+            Some(reason) => format!("maintenance ({})", reason),
+            None => "maintenance".to_string(),
+        });
+    }
+
+    let local = now.with_timezone(&Local);
+    quiet_hours
+        .iter()
+        .any(|quiet| quiet.covers(local) && quiet.scope.applies(target, urgency))
+        .then(|| "quiet hours".to_string())
+}
+
+/// A notification that fell in a quiet window, kept for the summary sent
+/// once the window ends.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HeldNotification {
+    pub title: String,
+    pub urgency: NotificationUrgency,
+    pub reason: String,
+    pub held_at: DateTime<Utc>,
+    pub certificates: Vec<CacheItem>,
+}
+
+/// Held notifications by the backend they were bound for, kept across
+/// restarts when backed by a file so a restart during a window does not
+/// lose them.
+#[derive(Debug, Clone, Default)]
+pub struct HeldNotifications {
+    entries: JsonStore<HashMap<String, Vec<HeldNotification>>>,
+}
+
+impl HeldNotifications {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn load(path: PathBuf) -> crate::Result<Self> {
+        let entries: JsonStore<HashMap<String, Vec<HeldNotification>>> =
+            JsonStore::load(path.clone())?;
+        tracing::info!(
+            "Loaded {} held notifications from {}",
+            entries.read().values().map(Vec::len).sum::<usize>(),
+            path.display()
+        );
+        Ok(HeldNotifications { entries })
+    }
+
+    pub fn hold(&self, target: &str, notification: HeldNotification) -> crate::Result<()> {
+        self.entries.update(|entries| {
+            entries
+                .entry(target.to_string())
+                .or_default()
+                .push(notification);
+        })
+    }
+
+    /// Notifications held for `target`, oldest first.
+    pub fn held(&self, target: &str) -> Vec<HeldNotification> {
+        self.entries.read().get(target).cloned().unwrap_or_default()
+    }
+
+    /// Drops the oldest `count` notifications held for `target`, once they
+    /// have been summarized.
+    pub fn release(&self, target: &str, count: usize) -> crate::Result<()> {
+        if count == 0 || !self.entries.read().contains_key(target) {
+            return Ok(());
+        }
+        self.entries.update(|entries| {
+            if let Some(held) = entries.get_mut(target) {
+                held.drain(..count.min(held.len()));
+                if held.is_empty() {
+                    entries.remove(target);
+                }
+            }
+        })
+    }
+
+    /// Writes held notifications not yet saved. Blocks on the disk.
+    pub fn flush(&self) -> crate::Result<()> {
+        self.entries.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_quiet_windows() {
+        let quiet: Vec<QuietHours> = serde_yaml::from_str(
+            "- start: \"22:00\"\n  end: \"07:00\"\n  days: [fri]\n  urgency: [low, normal]\n",
+        )
+        .unwrap();
+        let at = |y, m, d, h| {
+            Local
+                .with_ymd_and_hms(y, m, d, h, 0, 0)
+                .unwrap()
+                .with_timezone(&Utc)
+        };
+        let normal = NotificationUrgency::Normal;
+
+        // Friday 2024-06-07 23:00 and the following early morning
+        assert!(hold_reason(&quiet, &[], "slack", normal, at(2024, 6, 7, 23)).is_some());
+        assert!(hold_reason(&quiet, &[], "slack", normal, at(2024, 6, 8, 6)).is_some());
+        assert!(hold_reason(&quiet, &[], "slack", normal, at(2024, 6, 8, 7)).is_none());
+        // Thursday night is not covered, nor are critical alerts
+        assert!(hold_reason(&quiet, &[], "slack", normal, at(2024, 6, 6, 23)).is_none());
+        let critical = NotificationUrgency::Critical;
+        assert!(hold_reason(&quiet, &[], "slack", critical, at(2024, 6, 7, 23)).is_none());
+
+        let maintenance = vec![MaintenanceWindow {
+            start: at(2024, 6, 10, 1),
+            end: at(2024, 6, 10, 5),
+            reason: Some("vault upgrade".to_string()),
+            scope: HoldScope {
+                backends: vec!["pager".to_string()],
+                urgency: vec![],
+            },
+        }];
+        assert_eq!(
+            hold_reason(&[], &maintenance, "pager", critical, at(2024, 6, 10, 2)).as_deref(),
+            Some("maintenance (vault upgrade)")
+        );
+        assert!(hold_reason(&[], &maintenance, "slack", critical, at(2024, 6, 10, 2)).is_none());
+        assert!(hold_reason(&[], &maintenance, "pager", critical, at(2024, 6, 10, 5)).is_none());
+
+        let held = HeldNotifications::new();
+        for title in ["first", "second"] {
+            held.hold(
+                "pager",
+                HeldNotification {
+                    title: title.to_string(),
+                    urgency: normal,
+                    reason: "quiet hours".to_string(),
+                    held_at: Utc::now(),
+                    certificates: vec![],
+                },
+            )
+            .unwrap();
+        }
+        held.release("pager", 1).unwrap();
+        let remaining = held.held("pager");
+        assert_eq!(remaining.len(), 1);
+        assert_eq!(remaining[0].title, "second");
+        assert!(held.held("slack").is_empty());
+    }
+
+    #[test]
+    fn test_unknown_keys_rejected() {
+        let result: Result<Vec<QuietHours>, _> =
+            serde_yaml::from_str("- start: \"22:00\"\n  end: \"07:00\"\n  urgncy: [low]\n");
+        let error = result.unwrap_err().to_string();
+        assert!(error.contains("unknown field `urgncy`"), "{}", error);
+    }
+}
@@ -0,0 +1,259 @@
+//! A minimal Redis client covering the few commands the shared cache needs,
+//! speaking RESP2 over plain TCP.
+
+use std::time::Duration;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+const DEFAULT_PORT: u16 = 6379;
+
+/// Connection details parsed from a `redis://[user:password@]host[:port][/db]` URL.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RedisUrl {
+    pub host: String,
+    pub port: u16,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub db: u32,
+}
+
+impl RedisUrl {
+    pub fn parse(url: &str) -> crate::Result<Self> {
+        let parsed = url::Url::parse(url)
+            .map_err(|e| crate::DoomsdayError::config(format!("Invalid Redis URL: {}", e)))?;
+
+        if parsed.scheme() != "redis" {
+            return Err(crate::DoomsdayError::config(format!(
+                "Unsupported Redis URL scheme '{}', expected redis://",
+                parsed.scheme()
+            )));
+        }
+
+        let host = parsed
+            .host_str()
+            .filter(|h| !h.is_empty())
+            .ok_or_else(|| crate::DoomsdayError::config("Redis URL has no host"))?
+            .to_string();
+
+        let decode = |value: &str| {
+            urlencoding::decode(value)
+                .map(|v| v.into_owned())
+                .map_err(|e| crate::DoomsdayError::config(format!("Invalid Redis URL: {}", e)))
+        };
+        let username = match parsed.username() {
+            "" => None,
+            username => Some(decode(username)?),
+        };
+        let password = parsed.password().map(decode).transpose()?;
+
+        let db = match parsed.path().trim_start_matches('/') {
+            "" => 0,
+            db => db.parse().map_err(|_| {
+                crate::DoomsdayError::config(format!("Invalid Redis database '{}'", db))
+            })?,
+        };
+
+        Ok(RedisUrl {
+            host,
+            port: parsed.port().unwrap_or(DEFAULT_PORT),
+            username,
+            password,
+            db,
+        })
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Reply {
+    Status(String),
+    Integer(i64),
+    Bulk(Option<Vec<u8>>),
+}
+
+pub struct RedisClient {
+    stream: BufReader<TcpStream>,
+}
+
+impl RedisClient {
+    pub async fn connect(url: &RedisUrl) -> crate::Result<Self> {
+        tracing::debug!("Connecting to Redis at {}:{}", url.host, url.port);
+        let stream = tokio::time::timeout(
+            CONNECT_TIMEOUT,
+            TcpStream::connect((url.host.as_str(), url.port)),
+        )
+        .await
+        .map_err(|_| {
+            crate::DoomsdayError::cache(format!(
+                "Timed out connecting to Redis at {}:{}",
+                url.host, url.port
+            ))
+        })??;
+
+        let mut client = RedisClient {
+            stream: BufReader::new(stream),
+        };
+
+        if let Some(password) = &url.password {
+            match &url.username {
+                Some(username) => {
+                    client
+                        .command(&[b"AUTH", username.as_bytes(), password.as_bytes()])
+                        .await?
+                }
+                None => client.command(&[b"AUTH", password.as_bytes()]).await?,
+            };
+        }
+        if url.db != 0 {
+            client
+                .command(&[b"SELECT", url.db.to_string().as_bytes()])
+                .await?;
+        }
+
+        Ok(client)
+    }
+
+    pub async fn command(&mut self, args: &[&[u8]]) -> crate::Result<Reply> {
+        self.stream.get_mut().write_all(&encode(args)).await?;
+        read_reply(&mut self.stream).await
+    }
+
+    pub async fn get(&mut self, key: &str) -> crate::Result<Option<Vec<u8>>> {
+        match self.command(&[b"GET", key.as_bytes()]).await? {
+            Reply::Bulk(value) => Ok(value),
+            reply => Err(unexpected(reply)),
+        }
+    }
+
+    pub async fn set(&mut self, key: &str, value: &[u8]) -> crate::Result<()> {
+        self.command(&[b"SET", key.as_bytes(), value]).await?;
+        Ok(())
+    }
+
+    /// Sets `key` only if it does not exist, expiring after `ttl`. Returns
+    /// whether the key was set.
+    pub async fn set_nx(&mut self, key: &str, value: &str, ttl: Duration) -> crate::Result<bool> {
+        let ttl_ms = ttl.as_millis().to_string();
+        let reply = self
+            .command(&[
+                b"SET",
+                key.as_bytes(),
+                value.as_bytes(),
+                b"NX",
+                b"PX",
+                ttl_ms.as_bytes(),
+            ])
+            .await?;
+        // A nil reply means the key already existed
+        Ok(matches!(reply, Reply::Status(_)))
+    }
+
+    /// Runs a Lua script that returns an integer.
+    pub async fn eval(&mut self, script: &str, key: &str, args: &[&str]) -> crate::Result<i64> {
+        let mut command: Vec<&[u8]> = vec![b"EVAL", script.as_bytes(), b"1", key.as_bytes()];
+        command.extend(args.iter().map(|a| a.as_bytes()));
+        match self.command(&command).await? {
+            Reply::Integer(value) => Ok(value),
+            reply => Err(unexpected(reply)),
+        }
+    }
+}
+
+fn unexpected(reply: Reply) -> crate::DoomsdayError {
+    crate::DoomsdayError::cache(format!("Unexpected Redis reply: {:?}", reply))
+}
+
+fn encode(args: &[&[u8]]) -> Vec<u8> {
+    let mut buf = format!("*{}\r\n", args.len()).into_bytes();
+    for arg in args {
+        buf.extend_from_slice(format!("${}\r\n", arg.len()).as_bytes());
+        buf.extend_from_slice(arg);
+        buf.extend_from_slice(b"\r\n");
+    }
+    buf
+}
+
+async fn read_reply<R: AsyncBufRead + Unpin>(reader: &mut R) -> crate::Result<Reply> {
+    let mut line = Vec::new();
+    reader.read_until(b'\n', &mut line).await?;
+    if line.is_empty() {
+        return Err(crate::DoomsdayError::cache("Redis closed the connection"));
+    }
+    if line.len() < 3 || !line.ends_with(b"\r\n") {
+        return Err(crate::DoomsdayError::cache("Malformed Redis reply"));
+    }
+
+    let body = String::from_utf8_lossy(&line[1..line.len() - 2]).into_owned();
+    let malformed = || crate::DoomsdayError::cache(format!("Malformed Redis reply: {}", body));
+
+    match line[0] {
+        b'+' => Ok(Reply::Status(body)),
+        b'-' => Err(crate::DoomsdayError::cache(format!(
+            "Redis error: {}",
+            body
+        ))),
+        b':' => body.parse().map(Reply::Integer).map_err(|_| malformed()),
+        b'$' => {
+            let len: i64 = body.parse().map_err(|_| malformed())?;
+            if len < 0 {
+                return Ok(Reply::Bulk(None));
+            }
+            let mut value = vec![0; len as usize + 2];
+            reader.read_exact(&mut value).await?;
+            value.truncate(len as usize);
+            Ok(Reply::Bulk(Some(value)))
+        }
+        kind => Err(crate::DoomsdayError::cache(format!(
+            "Unsupported Redis reply type '{}'",
+            kind as char
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_url() {
+        let url = RedisUrl::parse("redis://:s%40cret@cache.internal/2").unwrap();
+        assert_eq!(url.host, "cache.internal");
+        assert_eq!(url.port, 6379);
+        assert_eq!(url.username, None);
+        assert_eq!(url.password.as_deref(), Some("s@cret"));
+        assert_eq!(url.db, 2);
+
+        let url = RedisUrl::parse("redis://doomsday:pw@localhost:6380").unwrap();
+        assert_eq!(url.port, 6380);
+        assert_eq!(url.username.as_deref(), Some("doomsday"));
+        assert_eq!(url.db, 0);
+
+        assert!(RedisUrl::parse("rediss://localhost").is_err());
+        assert!(RedisUrl::parse("redis://localhost/cache").is_err());
+    }
+
+    #[test]
+    fn test_encode() {
+        assert_eq!(
+            encode(&[b"GET", b"key"]),
+            b"*2\r\n$3\r\nGET\r\n$3\r\nkey\r\n".to_vec()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_read_reply() {
+        let mut input: &[u8] = b"+OK\r\n:1\r\n$5\r\nhe\r\no\r\n$-1\r\n-ERR wrong type\r\n";
+        assert_eq!(
+            read_reply(&mut input).await.unwrap(),
+            Reply::Status("OK".to_string())
+        );
+        assert_eq!(read_reply(&mut input).await.unwrap(), Reply::Integer(1));
+        assert_eq!(
+            read_reply(&mut input).await.unwrap(),
+            Reply::Bulk(Some(b"he\r\no".to_vec()))
+        );
+        assert_eq!(read_reply(&mut input).await.unwrap(), Reply::Bulk(None));
+        assert!(read_reply(&mut input).await.is_err());
+        assert!(read_reply(&mut input).await.is_err());
+    }
+}
@@ -0,0 +1,182 @@
+use chrono::{DateTime, Local};
+use std::str::FromStr;
+use std::time::Duration;
+
+/// When a recurring job (backend refresh, notification digest) should run.
+#[derive(Debug, Clone)]
+pub enum Schedule {
+    /// Run every fixed period.
+    Interval(Duration),
+    /// Run at the times matched by a cron expression, evaluated in the
+    /// server's local time zone.
+    Cron(Box<cron::Schedule>),
+}
+
+impl Schedule {
+    pub fn every_minutes(minutes: u64) -> crate::Result<Self> {
+        if minutes == 0 {
+            return Err(crate::DoomsdayError::config(
+                "Schedule interval must be at least one minute",
+            ));
+        }
+        Ok(Schedule::Interval(Duration::from_secs(minutes * 60)))
+    }
+
+    /// Parses a cron expression. Standard five-field expressions
+    /// (`minute hour day month weekday`) are accepted, as are the six and seven
+    /// field forms with leading seconds and trailing year. Numeric weekdays
+    /// count from 0 or 7 for Sunday, as in standard cron.
+    pub fn cron(expression: &str) -> crate::Result<Self> {
+        let mut fields: Vec<String> = expression
+            .split_whitespace()
+            .map(|field| field.to_string())
+            .collect();
+        if fields.len() == 5 {
+            fields.insert(0, "0".to_string());
+        }
+        // The cron crate numbers weekdays from 1 for Sunday
+        if let Some(weekdays) = fields.get_mut(5) {
+            *weekdays = weekdays
+                .split(',')
+                .map(weekday_names)
+                .collect::<Vec<_>>()
+                .join(",");
+        }
+
+        cron::Schedule::from_str(&fields.join(" "))
+            .map(|schedule| Schedule::Cron(Box::new(schedule)))
+            .map_err(|e| {
+                crate::DoomsdayError::config(format!(
+                    "Invalid cron expression '{}': {}",
+                    expression, e
+                ))
+            })
+    }
+
+    /// Time from `now` until the next run, or `None` if the schedule never
+    /// fires again.
+    pub fn until_next(&self, now: DateTime<Local>) -> Option<Duration> {
+        match self {
+            Schedule::Interval(period) => Some(*period),
+            Schedule::Cron(schedule) => {
+                let next = schedule.after(&now).next()?;
+                (next - now).to_std().ok()
+            }
+        }
+    }
+}
+
+const WEEKDAYS: [&str; 7] = ["Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat"];
+
+/// Spells out one item of a weekday field, such as `1-5` or `0/2`, as day
+/// names, which the cron crate reads the same way standard cron does. Items
+/// without numbers, or that do not parse, are left for the cron crate.
+fn weekday_names(item: &str) -> String {
+    let (range, step) = match item.split_once('/') {
+        Some((range, step)) => (range, Some(step)),
+        None => (item, None),
+    };
+    if range == "*" || range == "?" || !range.bytes().any(|b| b.is_ascii_digit()) {
+        return item.to_string();
+    }
+
+    let weekday = |value: &str| -> Option<usize> {
+        match value.parse::<usize>() {
+            Ok(day) => (day <= 7).then_some(day),
+            Err(_) => WEEKDAYS
+                .iter()
+                .position(|name| name.eq_ignore_ascii_case(value)),
+        }
+    };
+    let bounds = match range.split_once('-') {
+        Some((start, end)) => weekday(start).zip(weekday(end)),
+        // `n/step` runs to the end of the week
+        None if step.is_some() => weekday(range).map(|start| (start, 6)),
+        None => weekday(range).map(|day| (day, day)),
+    };
+    let step = match step {
+        Some(step) => step.parse::<usize>().ok().filter(|step| *step > 0),
+        None => Some(1),
+    };
+    let (Some((start, end)), Some(step)) = (bounds, step) else {
+        return item.to_string();
+    };
+    if start > end {
+        return item.to_string();
+    }
+
+    let mut days: Vec<&str> = Vec::new();
+    for day in (start..=end).step_by(step) {
+        let name = WEEKDAYS[day % 7];
+        if !days.contains(&name) {
+            days.push(name);
+        }
+    }
+    days.join(",")
+}
+
+impl std::fmt::Display for Schedule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Schedule::Interval(period) => write!(f, "every {} minutes", period.as_secs() / 60),
+            Schedule::Cron(schedule) => write!(f, "cron '{}'", schedule),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_five_field_cron() {
+        // 09:00 on weekdays
+        let schedule = Schedule::cron("0 9 * * Mon-Fri").unwrap();
+
+        // Saturday 2024-06-01 12:00 -> Monday 2024-06-03 09:00
+        let now = Local.with_ymd_and_hms(2024, 6, 1, 12, 0, 0).unwrap();
+        assert_eq!(
+            schedule.until_next(now),
+            Some(Duration::from_secs(45 * 60 * 60))
+        );
+    }
+
+    #[test]
+    fn test_numeric_weekdays_are_standard() {
+        use chrono::{Datelike, Weekday};
+
+        let weekdays = |expression: &str| -> Vec<Weekday> {
+            let Schedule::Cron(schedule) = Schedule::cron(expression).unwrap() else {
+                unreachable!()
+            };
+            // Saturday 2024-06-01
+            let now = Local.with_ymd_and_hms(2024, 6, 1, 12, 0, 0).unwrap();
+            let mut days: Vec<Weekday> = schedule
+                .after(&now)
+                .take(7)
+                .map(|time| time.weekday())
+                .collect();
+            days.sort_by_key(|day| day.num_days_from_sunday());
+            days.dedup();
+            days
+        };
+
+        use Weekday::*;
+        assert_eq!(weekdays("0 9 * * 1-5"), vec![Mon, Tue, Wed, Thu, Fri]);
+        assert_eq!(weekdays("0 9 * * 0"), vec![Sun]);
+        assert_eq!(weekdays("0 9 * * 7"), vec![Sun]);
+        assert_eq!(weekdays("0 9 * * 5-7"), vec![Sun, Fri, Sat]);
+        assert_eq!(weekdays("0 9 * * 0,3"), vec![Sun, Wed]);
+        assert_eq!(weekdays("0 9 * * 1-5/2"), vec![Mon, Wed, Fri]);
+        assert_eq!(weekdays("0 9 * * */3"), vec![Sun, Wed, Sat]);
+        assert_eq!(weekdays("0 0 9 * * 6"), vec![Sat]);
+        assert!(Schedule::cron("0 9 * * 8").is_err());
+    }
+
+    #[test]
+    fn test_invalid_schedules() {
+        assert!(Schedule::cron("not a cron").is_err());
+        assert!(Schedule::every_minutes(0).is_err());
+    }
+}
@@ -1,16 +1,178 @@
-use crate::types::{SchedulerInfo, Task, TaskInfo, TaskStatus};
+use crate::config::{RetryPolicy, TaskTimeouts};
+use crate::task_store::TaskStore;
+use crate::types::{PopulateStats, SchedulerInfo, Task, TaskInfo, TaskStatus, TaskTypeStats};
+use async_trait::async_trait;
 use chrono::{DateTime, Utc};
+use dashmap::mapref::entry::Entry;
 use dashmap::DashMap;
-use std::sync::Arc;
-use tokio::sync::{mpsc, Semaphore};
-use tokio::time::{sleep, Duration};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, OnceLock, RwLock};
+use tokio::sync::{mpsc, Notify, Semaphore};
+use tokio::time::Duration;
 use uuid::Uuid;
 
+/// Runs scheduled tasks. Implemented by `Core`, which registers itself once it
+/// has been constructed.
+#[async_trait]
+pub trait TaskExecutor: Send + Sync {
+    /// Executes a task, returning refresh statistics for tasks that scan a backend.
+    async fn execute(&self, task: &Task) -> crate::Result<Option<PopulateStats>>;
+}
+
+type ExecutorSlot = Arc<OnceLock<Arc<dyn TaskExecutor>>>;
+
+/// IDs of the pending or running task for each distinct task, used to
+/// coalesce duplicate requests (e.g. a periodic and a manual refresh of the
+/// same backend) into a single run.
+type ActiveTasks = Arc<DashMap<Task, String>>;
+
+type StoreSlot = Arc<OnceLock<TaskStore>>;
+
+/// Signals that stop a running task, by task ID.
+type CancelSignals = Arc<DashMap<String, Arc<Notify>>>;
+
+/// Number of recent executions kept per task type and backend for percentiles.
+const DURATION_SAMPLES: usize = 100;
+
+/// Running execution statistics for one task type against one backend.
+#[derive(Debug, Default)]
+struct ExecutionStats {
+    succeeded: u64,
+    failed: u64,
+    total_duration_ms: u64,
+    recent_durations_ms: VecDeque<u64>,
+}
+
+impl ExecutionStats {
+    fn record(&mut self, succeeded: bool, duration_ms: u64) {
+        if succeeded {
+            self.succeeded += 1;
+        } else {
+            self.failed += 1;
+        }
+        self.total_duration_ms += duration_ms;
+
+        if self.recent_durations_ms.len() == DURATION_SAMPLES {
+            self.recent_durations_ms.pop_front();
+        }
+        self.recent_durations_ms.push_back(duration_ms);
+    }
+
+    fn summary(&self, task_type: &str, backend: &str) -> TaskTypeStats {
+        let mut sorted: Vec<u64> = self.recent_durations_ms.iter().copied().collect();
+        sorted.sort_unstable();
+
+        // Nearest-rank percentile
+        let percentile = |p: usize| -> u64 {
+            if sorted.is_empty() {
+                return 0;
+            }
+            let rank = (p * sorted.len()).div_ceil(100).max(1);
+            sorted[rank - 1]
+        };
+
+        let executions = self.succeeded + self.failed;
+        TaskTypeStats {
+            task_type: task_type.to_string(),
+            backend: backend.to_string(),
+            succeeded: self.succeeded,
+            failed: self.failed,
+            total_duration_ms: self.total_duration_ms,
+            avg_duration_ms: self.total_duration_ms.checked_div(executions).unwrap_or(0),
+            p50_duration_ms: percentile(50),
+            p95_duration_ms: percentile(95),
+            p99_duration_ms: percentile(99),
+        }
+    }
+}
+
+type ExecutionStatsMap = Arc<DashMap<(&'static str, String), ExecutionStats>>;
+
+/// How long task history changes are collected before they are written.
+const HISTORY_FLUSH_DELAY: Duration = Duration::from_secs(1);
+
+/// Queues a task's new state for the store, if one is attached.
+fn persist(store: &StoreSlot, task_info: &TaskInfo) {
+    if let Some(store) = store.get() {
+        store.changed(task_info);
+    }
+}
+
+/// Writes queued task history changes to the store. Blocks on the disk.
+fn flush_history(store: &StoreSlot, tasks: &DashMap<String, TaskInfo>) {
+    let Some(store) = store.get() else {
+        return;
+    };
+
+    if let Err(e) = store.flush(|| tasks.iter().map(|entry| entry.clone()).collect()) {
+        tracing::error!("Failed to persist scheduler task history: {}", e);
+    }
+}
+
+/// Flushes task history changes shortly after they happen, on the blocking
+/// pool, so workers never wait on the disk.
+fn spawn_history_flusher(store: StoreSlot, tasks: Arc<DashMap<String, TaskInfo>>) {
+    tokio::spawn(async move {
+        let Some(task_store) = store.get() else {
+            return;
+        };
+        loop {
+            task_store.wait_for_changes().await;
+            tokio::time::sleep(HISTORY_FLUSH_DELAY).await;
+            let (store, tasks) = (store.clone(), tasks.clone());
+            if let Err(e) = tokio::task::spawn_blocking(move || flush_history(&store, &tasks)).await
+            {
+                tracing::error!("Scheduler task history flush failed: {}", e);
+            }
+        }
+    });
+}
+
+/// State shared between the scheduler handle and its worker loop.
+#[derive(Clone)]
+struct WorkerContext {
+    tasks: Arc<DashMap<String, TaskInfo>>,
+    active: ActiveTasks,
+    semaphore: Arc<Semaphore>,
+    task_sender: mpsc::UnboundedSender<TaskInfo>,
+    accepting: Arc<AtomicBool>,
+    paused: Arc<AtomicBool>,
+    executor: ExecutorSlot,
+    retry_policy: Arc<RwLock<RetryPolicy>>,
+    timeouts: Arc<RwLock<TaskTimeouts>>,
+    stats: ExecutionStatsMap,
+    store: StoreSlot,
+    cancels: CancelSignals,
+}
+
+impl WorkerContext {
+    /// Records a task's final state and lets new requests for the same task
+    /// be scheduled again.
+    fn finish(&self, task_info: TaskInfo) {
+        self.cancels.remove(&task_info.id);
+        self.active
+            .remove_if(&task_info.task, |_, id| *id == task_info.id);
+        self.tasks.insert(task_info.id.clone(), task_info.clone());
+        persist(&self.store, &task_info);
+    }
+}
+
 #[derive(Clone)]
 pub struct Scheduler {
     tasks: Arc<DashMap<String, TaskInfo>>,
+    active: ActiveTasks,
     semaphore: Arc<Semaphore>,
     task_sender: mpsc::UnboundedSender<TaskInfo>,
+    max_workers: usize,
+    accepting: Arc<AtomicBool>,
+    paused: Arc<AtomicBool>,
+    executor: ExecutorSlot,
+    retry_policy: Arc<RwLock<RetryPolicy>>,
+    timeouts: Arc<RwLock<TaskTimeouts>>,
+    stats: ExecutionStatsMap,
+    store: StoreSlot,
+    cancels: CancelSignals,
 }
 
 impl Scheduler {
@@ -19,25 +181,177 @@ impl Scheduler {
 
         let (task_sender, task_receiver) = mpsc::unbounded_channel::<TaskInfo>();
         let tasks = Arc::new(DashMap::new());
+        let active: ActiveTasks = Arc::new(DashMap::new());
         let semaphore = Arc::new(Semaphore::new(max_workers));
+        let accepting = Arc::new(AtomicBool::new(true));
+        let paused = Arc::new(AtomicBool::new(false));
+        let executor: ExecutorSlot = Arc::new(OnceLock::new());
+        let retry_policy = Arc::new(RwLock::new(RetryPolicy::default()));
+        let timeouts = Arc::new(RwLock::new(TaskTimeouts::default()));
+        let stats: ExecutionStatsMap = Arc::new(DashMap::new());
+        let store: StoreSlot = Arc::new(OnceLock::new());
+        let cancels: CancelSignals = Arc::new(DashMap::new());
 
         let scheduler = Scheduler {
             tasks: tasks.clone(),
+            active: active.clone(),
             semaphore: semaphore.clone(),
-            task_sender,
+            task_sender: task_sender.clone(),
+            max_workers,
+            accepting: accepting.clone(),
+            paused: paused.clone(),
+            executor: executor.clone(),
+            retry_policy: retry_policy.clone(),
+            timeouts: timeouts.clone(),
+            stats: stats.clone(),
+            store: store.clone(),
+            cancels: cancels.clone(),
         };
 
         tracing::debug!("Starting scheduler worker loop");
         // Start the worker loop
-        tokio::spawn(Self::worker_loop(task_receiver, tasks, semaphore));
+        tokio::spawn(Self::worker_loop(
+            task_receiver,
+            WorkerContext {
+                tasks,
+                active,
+                semaphore,
+                task_sender,
+                accepting,
+                paused,
+                executor,
+                retry_policy,
+                timeouts,
+                stats,
+                store,
+                cancels,
+            },
+        ));
 
         tracing::info!("Scheduler initialized successfully");
         scheduler
     }
 
+    pub fn set_executor(&self, executor: Arc<dyn TaskExecutor>) {
+        if self.executor.set(executor).is_err() {
+            tracing::warn!("Scheduler task executor already set, ignoring");
+        }
+    }
+
+    pub fn set_retry_policy(&self, policy: RetryPolicy) {
+        *self.retry_policy.write().unwrap() = policy;
+    }
+
+    pub fn retry_policy(&self) -> RetryPolicy {
+        *self.retry_policy.read().unwrap()
+    }
+
+    pub fn set_task_timeouts(&self, timeouts: TaskTimeouts) {
+        *self.timeouts.write().unwrap() = timeouts;
+    }
+
+    pub fn task_timeouts(&self) -> TaskTimeouts {
+        *self.timeouts.read().unwrap()
+    }
+
+    /// Attaches a persistent store. Task history saved by a previous run is
+    /// restored, and tasks that had not finished are queued again. Call after
+    /// `set_executor` so requeued tasks can run.
+    pub fn restore(&self, store: TaskStore) -> crate::Result<()> {
+        let saved = store.load()?;
+        if self.store.set(store).is_err() {
+            return Err(crate::DoomsdayError::scheduler(
+                "Scheduler task store already attached",
+            ));
+        }
+
+        let mut requeued = 0;
+        for mut task_info in saved {
+            if !matches!(task_info.status, TaskStatus::Pending | TaskStatus::Running) {
+                self.tasks.insert(task_info.id.clone(), task_info);
+                continue;
+            }
+
+            // Interrupted by the restart; coalescing keeps duplicates out
+            if self.active.contains_key(&task_info.task) {
+                task_info.status = TaskStatus::Cancelled;
+                task_info.completed_at = Some(Utc::now());
+                self.tasks.insert(task_info.id.clone(), task_info);
+                continue;
+            }
+
+            task_info.status = TaskStatus::Pending;
+            task_info.started_at = None;
+            task_info.next_retry_at = None;
+            self.active
+                .insert(task_info.task.clone(), task_info.id.clone());
+            self.tasks.insert(task_info.id.clone(), task_info.clone());
+            self.task_sender.send(task_info).map_err(|e| {
+                crate::DoomsdayError::scheduler(format!("Failed to requeue task: {}", e))
+            })?;
+            requeued += 1;
+        }
+
+        tracing::info!(
+            "Restored {} scheduler tasks, {} requeued",
+            self.tasks.len(),
+            requeued
+        );
+        if let Some(store) = self.store.get() {
+            store.rewrite(self.list_tasks())?;
+        }
+        spawn_history_flusher(self.store.clone(), self.tasks.clone());
+        Ok(())
+    }
+
+    /// Pauses scheduled refreshes, e.g. during backend maintenance. Periodic
+    /// refreshes are skipped and pending refresh retries are dropped until
+    /// `resume` is called; manual refreshes and auth renewals still run.
+    pub fn pause(&self) {
+        if !self.paused.swap(true, Ordering::SeqCst) {
+            tracing::info!("Scheduled refreshes paused");
+        }
+    }
+
+    pub fn resume(&self) {
+        if self.paused.swap(false, Ordering::SeqCst) {
+            tracing::info!("Scheduled refreshes resumed");
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::SeqCst)
+    }
+
+    pub fn is_accepting(&self) -> bool {
+        self.accepting.load(Ordering::SeqCst)
+    }
+
+    /// Queues a task and returns its ID. If an identical task is already
+    /// pending or running, that task's ID is returned instead.
     pub fn schedule_task(&self, task: Task) -> crate::Result<String> {
+        if !self.is_accepting() {
+            return Err(crate::DoomsdayError::scheduler(
+                "Scheduler is shutting down and no longer accepts tasks",
+            ));
+        }
+
         let task_id = Uuid::new_v4().to_string();
 
+        match self.active.entry(task.clone()) {
+            Entry::Occupied(existing) => {
+                tracing::info!(
+                    "Task {:?} already pending or running, coalescing into {}",
+                    task,
+                    existing.get()
+                );
+                return Ok(existing.get().clone());
+            }
+            Entry::Vacant(slot) => {
+                slot.insert(task_id.clone());
+            }
+        }
+
         tracing::debug!("Scheduling task: {:?} (ID: {})", task, task_id);
 
         let task_info = TaskInfo {
@@ -48,12 +362,17 @@ impl Scheduler {
             completed_at: None,
             status: TaskStatus::Pending,
             error: None,
+            stats: None,
+            attempts: 0,
+            next_retry_at: None,
         };
 
         self.tasks.insert(task_id.clone(), task_info.clone());
+        persist(&self.store, &task_info);
 
         self.task_sender.send(task_info).map_err(|e| {
             tracing::error!("Failed to send task to scheduler queue: {}", e);
+            self.active.remove(&task);
             crate::DoomsdayError::scheduler(format!("Failed to schedule task: {}", e))
         })?;
 
@@ -65,10 +384,56 @@ impl Scheduler {
         self.tasks.get(task_id).map(|entry| entry.clone())
     }
 
+    /// Cancels a pending or running task, returning it or `None` if there
+    /// is no such task. A pending task, including one waiting to retry, is
+    /// cancelled at once; a running one is stopped by its worker shortly
+    /// after, as if it had timed out.
+    pub fn cancel_task(&self, task_id: &str) -> crate::Result<Option<TaskInfo>> {
+        let Some(mut entry) = self.tasks.get_mut(task_id) else {
+            return Ok(None);
+        };
+
+        match entry.status {
+            TaskStatus::Pending => {
+                // The worker loop skips it when it comes up in the queue
+                entry.status = TaskStatus::Cancelled;
+                entry.completed_at = Some(Utc::now());
+                entry.next_retry_at = None;
+                self.active.remove_if(&entry.task, |_, id| id == task_id);
+            }
+            TaskStatus::Running => {
+                self.cancels
+                    .entry(task_id.to_string())
+                    .or_default()
+                    .notify_one();
+            }
+            _ => {
+                return Err(crate::DoomsdayError::scheduler(format!(
+                    "Task {} has already finished",
+                    task_id
+                )))
+            }
+        }
+
+        let task_info = entry.clone();
+        drop(entry);
+        tracing::info!("Task cancelled: {:?} (ID: {})", task_info.task, task_id);
+        persist(&self.store, &task_info);
+        Ok(Some(task_info))
+    }
+
     pub fn list_tasks(&self) -> Vec<TaskInfo> {
         self.tasks.iter().map(|entry| entry.clone()).collect()
     }
 
+    /// Whether a backend refresh is queued, retrying or running.
+    pub fn has_unfinished_refreshes(&self) -> bool {
+        self.tasks.iter().any(|entry| {
+            matches!(entry.task, Task::RefreshBackend { .. })
+                && matches!(entry.status, TaskStatus::Pending | TaskStatus::Running)
+        })
+    }
+
     pub fn get_info(&self) -> SchedulerInfo {
         let tasks: Vec<TaskInfo> = self.list_tasks();
         let pending_tasks = tasks
@@ -86,14 +451,62 @@ impl Scheduler {
             workers: total_workers,
             pending_tasks,
             running_tasks,
+            paused: self.is_paused(),
+            task_stats: self.task_stats(),
         }
     }
 
-    async fn worker_loop(
-        mut task_receiver: mpsc::UnboundedReceiver<TaskInfo>,
-        tasks: Arc<DashMap<String, TaskInfo>>,
-        semaphore: Arc<Semaphore>,
-    ) {
+    /// Execution statistics per task type and backend, sorted by both.
+    pub fn task_stats(&self) -> Vec<TaskTypeStats> {
+        let mut stats: Vec<TaskTypeStats> = self
+            .stats
+            .iter()
+            .map(|entry| {
+                let (task_type, backend) = entry.key();
+                entry.value().summary(task_type, backend)
+            })
+            .collect();
+        stats.sort_by(|a, b| (&a.task_type, &a.backend).cmp(&(&b.task_type, &b.backend)));
+        stats
+    }
+
+    /// Stops accepting tasks, cancels the ones still queued and waits up to
+    /// `timeout` for running tasks to finish. Returns false if the timeout
+    /// elapsed with tasks still running.
+    pub async fn shutdown(&self, timeout: Duration) -> bool {
+        tracing::info!("Scheduler shutting down, draining running tasks");
+        self.accepting.store(false, Ordering::SeqCst);
+
+        // Every permit being free means no task is running
+        let drained = tokio::time::timeout(
+            timeout,
+            self.semaphore.acquire_many(self.max_workers as u32),
+        )
+        .await;
+
+        // Write out what the flusher has not yet
+        let (store, tasks) = (self.store.clone(), self.tasks.clone());
+        if let Err(e) = tokio::task::spawn_blocking(move || flush_history(&store, &tasks)).await {
+            tracing::error!("Scheduler task history flush failed: {}", e);
+        }
+
+        match drained {
+            Ok(_) => {
+                tracing::info!("All scheduler tasks drained");
+                true
+            }
+            Err(_) => {
+                tracing::warn!(
+                    "Drain timeout of {:?} elapsed with {} tasks still running",
+                    timeout,
+                    self.get_info().running_tasks
+                );
+                false
+            }
+        }
+    }
+
+    async fn worker_loop(mut task_receiver: mpsc::UnboundedReceiver<TaskInfo>, ctx: WorkerContext) {
         tracing::info!("Scheduler worker loop started");
 
         while let Some(mut task_info) = task_receiver.recv().await {
@@ -103,57 +516,171 @@ impl Scheduler {
                 task_info.id
             );
 
-            let permit = semaphore.clone().acquire_owned().await.unwrap();
-            let tasks_clone = tasks.clone();
+            let permit = match ctx.semaphore.clone().acquire_owned().await {
+                Ok(permit) if ctx.accepting.load(Ordering::SeqCst) => permit,
+                _ => {
+                    tracing::info!("Cancelling queued task during shutdown: {}", task_info.id);
+                    task_info.status = TaskStatus::Cancelled;
+                    task_info.completed_at = Some(Utc::now());
+                    ctx.finish(task_info);
+                    continue;
+                }
+            };
 
-            tokio::spawn(async move {
-                let _permit = permit; // Keep permit until task completes
+            // Marked running under the entry lock, so `cancel_task` sees
+            // either a pending task it can cancel or a running one
+            match ctx.tasks.get_mut(&task_info.id) {
+                Some(mut entry) if !matches!(entry.status, TaskStatus::Cancelled) => {
+                    task_info.status = TaskStatus::Running;
+                    task_info.started_at = Some(Utc::now());
+                    task_info.attempts += 1;
+                    task_info.next_retry_at = None;
+                    *entry = task_info.clone();
+                }
+                _ => {
+                    tracing::debug!("Skipping cancelled task: {}", task_info.id);
+                    continue;
+                }
+            }
+            let ctx = ctx.clone();
 
-                // Update task status to running
+            tokio::spawn(async move {
                 tracing::debug!("Starting execution of task: {}", task_info.id);
-                task_info.status = TaskStatus::Running;
-                task_info.started_at = Some(Utc::now());
-                tasks_clone.insert(task_info.id.clone(), task_info.clone());
+                let cancel = ctx.cancels.entry(task_info.id.clone()).or_default().clone();
+
+                // Execute the task, cancelling it if it hangs or is cancelled
+                let execution_start = std::time::Instant::now();
+                let timeout = ctx.timeouts.read().unwrap().for_task(&task_info.task);
+                let result = tokio::select! {
+                    result = tokio::time::timeout(
+                        timeout,
+                        Self::execute_task(&ctx.executor, &task_info.task),
+                    ) => match result {
+                        Ok(result) => result,
+                        Err(_) => Err(crate::DoomsdayError::scheduler(format!(
+                            "Task timed out after {:?}",
+                            timeout
+                        ))),
+                    },
+                    _ = cancel.notified() => {
+                        drop(permit);
+                        tracing::info!("Task stopped after cancellation: {}", task_info.id);
+                        task_info.status = TaskStatus::Cancelled;
+                        task_info.completed_at = Some(Utc::now());
+                        task_info.error = Some("Cancelled while running".to_string());
+                        ctx.finish(task_info);
+                        return;
+                    }
+                };
 
-                // Execute the task
-                let result = Self::execute_task(&task_info.task).await;
+                // Release the worker before waiting out any retry backoff
+                drop(permit);
+
+                ctx.stats
+                    .entry((
+                        task_info.task.kind(),
+                        task_info.task.backend_name().to_string(),
+                    ))
+                    .or_default()
+                    .record(result.is_ok(), execution_start.elapsed().as_millis() as u64);
 
                 // Update task status based on result
-                task_info.completed_at = Some(Utc::now());
                 match result {
-                    Ok(()) => {
+                    Ok(stats) => {
                         tracing::info!("Task completed successfully: {}", task_info.id);
                         task_info.status = TaskStatus::Completed;
+                        task_info.completed_at = Some(Utc::now());
+                        task_info.error = None;
+                        task_info.stats = stats;
                     }
                     Err(e) => {
-                        tracing::error!("Task failed: {} - Error: {}", task_info.id, e);
-                        task_info.status = TaskStatus::Failed;
                         task_info.error = Some(e.to_string());
+                        let policy = *ctx.retry_policy.read().unwrap();
+
+                        if task_info.task.is_retryable()
+                            && task_info.attempts < policy.max_attempts
+                            && ctx.accepting.load(Ordering::SeqCst)
+                        {
+                            Self::retry_later(ctx, task_info, &policy);
+                            return;
+                        }
+
+                        tracing::error!(
+                            "Task failed after {} attempt(s): {} - Error: {}",
+                            task_info.attempts,
+                            task_info.id,
+                            e
+                        );
+                        task_info.status = TaskStatus::Failed;
+                        task_info.completed_at = Some(Utc::now());
                     }
                 }
 
-                tasks_clone.insert(task_info.id.clone(), task_info);
+                ctx.finish(task_info);
             });
         }
 
         tracing::warn!("Scheduler worker loop ended - this should not happen in normal operation");
     }
 
-    async fn execute_task(task: &Task) -> crate::Result<()> {
-        match task {
-            Task::RefreshBackend { backend_name } => {
-                tracing::info!("Refreshing backend: {}", backend_name);
-                // TODO: Implement backend refresh logic
-                sleep(Duration::from_millis(100)).await; // Placeholder
-                Ok(())
+    /// Puts a failed task back into the pending state and requeues it once
+    /// its jittered backoff has elapsed.
+    fn retry_later(ctx: WorkerContext, mut task_info: TaskInfo, policy: &RetryPolicy) {
+        let delay = jitter(policy.backoff(task_info.attempts));
+        tracing::warn!(
+            "Task {} failed (attempt {}/{}), retrying in {:?}: {}",
+            task_info.id,
+            task_info.attempts,
+            policy.max_attempts,
+            delay,
+            task_info.error.as_deref().unwrap_or_default()
+        );
+
+        task_info.status = TaskStatus::Pending;
+        task_info.next_retry_at =
+            Some(Utc::now() + chrono::Duration::from_std(delay).unwrap_or_default());
+        ctx.tasks.insert(task_info.id.clone(), task_info.clone());
+        persist(&ctx.store, &task_info);
+
+        tokio::spawn(async move {
+            tokio::time::sleep(delay).await;
+
+            if !ctx.accepting.load(Ordering::SeqCst) {
+                tracing::info!("Cancelling retry during shutdown: {}", task_info.id);
+                task_info.status = TaskStatus::Cancelled;
+                task_info.completed_at = Some(Utc::now());
+                ctx.finish(task_info);
+                return;
             }
-            Task::RenewAuthToken { backend_name } => {
-                tracing::info!("Renewing auth token for backend: {}", backend_name);
-                // TODO: Implement auth token renewal logic
-                sleep(Duration::from_millis(50)).await; // Placeholder
-                Ok(())
+
+            if ctx.paused.load(Ordering::SeqCst)
+                && matches!(task_info.task, Task::RefreshBackend { .. })
+            {
+                tracing::info!("Dropping refresh retry while paused: {}", task_info.id);
+                task_info.status = TaskStatus::Cancelled;
+                task_info.completed_at = Some(Utc::now());
+                ctx.finish(task_info);
+                return;
             }
-        }
+
+            if let Err(e) = ctx.task_sender.send(task_info) {
+                tracing::error!("Failed to requeue task for retry: {}", e);
+                let mut task_info = e.0;
+                task_info.status = TaskStatus::Failed;
+                task_info.completed_at = Some(Utc::now());
+                ctx.finish(task_info);
+            }
+        });
+    }
+
+    async fn execute_task(
+        executor: &ExecutorSlot,
+        task: &Task,
+    ) -> crate::Result<Option<PopulateStats>> {
+        let executor = executor.get().ok_or_else(|| {
+            crate::DoomsdayError::scheduler("No task executor registered with the scheduler")
+        })?;
+        executor.execute(task).await
     }
 
     pub fn cleanup_completed_tasks(&self, max_age: Duration) {
@@ -169,10 +696,12 @@ impl Scheduler {
             .iter()
             .filter(|entry| {
                 let task = entry.value();
-                matches!(task.status, TaskStatus::Completed | TaskStatus::Failed)
-                    && task
-                        .completed_at
-                        .map_or(false, |completed| completed < cutoff)
+                matches!(
+                    task.status,
+                    TaskStatus::Completed | TaskStatus::Failed | TaskStatus::Cancelled
+                ) && task
+                    .completed_at
+                    .is_some_and(|completed| completed < cutoff)
             })
             .map(|entry| entry.key().clone())
             .collect();
@@ -186,6 +715,9 @@ impl Scheduler {
                         task_id,
                         task.status
                     );
+                    if let Some(store) = self.store.get() {
+                        store.removed(&task_id);
+                    }
                 }
             }
         } else {
@@ -194,8 +726,305 @@ impl Scheduler {
     }
 }
 
+/// Spreads retries over the upper half of the backoff so work that failed
+/// together (e.g. every refresh against one outage) does not retry in
+/// lockstep.
+pub(crate) fn jitter(backoff: Duration) -> Duration {
+    let half = backoff / 2;
+    let spread = half.as_millis() as u64;
+    if spread == 0 {
+        return backoff;
+    }
+    let random = (Uuid::new_v4().as_u128() % u128::from(spread + 1)) as u64;
+    half + Duration::from_millis(random)
+}
+
 impl Default for Scheduler {
     fn default() -> Self {
         Self::new(4) // Default to 4 workers
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::time::sleep;
+
+    struct SleepExecutor;
+
+    #[async_trait]
+    impl TaskExecutor for SleepExecutor {
+        async fn execute(&self, _task: &Task) -> crate::Result<Option<PopulateStats>> {
+            sleep(Duration::from_millis(100)).await;
+            Ok(None)
+        }
+    }
+
+    /// Fails the first `failures` executions, then succeeds.
+    struct FlakyExecutor {
+        failures: u32,
+        calls: std::sync::atomic::AtomicU32,
+    }
+
+    #[async_trait]
+    impl TaskExecutor for FlakyExecutor {
+        async fn execute(&self, _task: &Task) -> crate::Result<Option<PopulateStats>> {
+            if self.calls.fetch_add(1, Ordering::SeqCst) < self.failures {
+                return Err(crate::DoomsdayError::backend("backend down"));
+            }
+            Ok(None)
+        }
+    }
+
+    fn flaky_scheduler(failures: u32, max_attempts: u32) -> Scheduler {
+        let scheduler = Scheduler::new(2);
+        scheduler.set_executor(Arc::new(FlakyExecutor {
+            failures,
+            calls: Default::default(),
+        }));
+        scheduler.set_retry_policy(RetryPolicy {
+            max_attempts,
+            initial_backoff: 0,
+            max_backoff: 0,
+        });
+        scheduler
+    }
+
+    fn refresh_task() -> Task {
+        Task::RefreshBackend {
+            backend_name: "test".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_execution_stats_summary() {
+        let mut stats = ExecutionStats::default();
+        for duration_ms in 1..=200 {
+            stats.record(duration_ms % 10 != 0, duration_ms);
+        }
+
+        let summary = stats.summary("refresh_backend", "vault");
+        assert_eq!(summary.succeeded, 180);
+        assert_eq!(summary.failed, 20);
+        assert_eq!(summary.avg_duration_ms, 100);
+        // Percentiles cover only the most recent 100 executions (101..=200)
+        assert_eq!(summary.p50_duration_ms, 150);
+        assert_eq!(summary.p95_duration_ms, 195);
+        assert_eq!(summary.p99_duration_ms, 199);
+    }
+
+    #[test]
+    fn test_retry_backoff() {
+        let policy = RetryPolicy {
+            max_attempts: 10,
+            initial_backoff: 30,
+            max_backoff: 300,
+        };
+        assert_eq!(policy.backoff(1), Duration::from_secs(30));
+        assert_eq!(policy.backoff(2), Duration::from_secs(60));
+        assert_eq!(policy.backoff(4), Duration::from_secs(240));
+        assert_eq!(policy.backoff(5), Duration::from_secs(300));
+        assert_eq!(policy.backoff(40), Duration::from_secs(300));
+
+        for _ in 0..100 {
+            let delay = jitter(Duration::from_secs(60));
+            assert!(delay >= Duration::from_secs(30) && delay <= Duration::from_secs(60));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_failed_refresh_is_retried() {
+        let scheduler = flaky_scheduler(2, 5);
+        let task_id = scheduler.schedule_task(refresh_task()).unwrap();
+        sleep(Duration::from_millis(100)).await;
+
+        let task = scheduler.get_task(&task_id).unwrap();
+        assert!(matches!(task.status, TaskStatus::Completed));
+        assert_eq!(task.attempts, 3);
+        assert!(task.error.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_retries_stop_at_max_attempts() {
+        let scheduler = flaky_scheduler(u32::MAX, 3);
+        let task_id = scheduler.schedule_task(refresh_task()).unwrap();
+        sleep(Duration::from_millis(100)).await;
+
+        let task = scheduler.get_task(&task_id).unwrap();
+        assert!(matches!(task.status, TaskStatus::Failed));
+        assert_eq!(task.attempts, 3);
+        assert!(task.error.unwrap().contains("backend down"));
+
+        let stats = scheduler.task_stats();
+        assert_eq!(stats.len(), 1);
+        assert_eq!(stats[0].task_type, "refresh_backend");
+        assert_eq!(stats[0].failed, 3);
+    }
+
+    #[tokio::test]
+    async fn test_task_history_survives_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tasks.json");
+
+        let scheduler = Scheduler::new(2);
+        scheduler.set_executor(Arc::new(SleepExecutor));
+        scheduler.restore(TaskStore::new(path.clone())).unwrap();
+        let finished = scheduler.schedule_task(refresh_task()).unwrap();
+        sleep(Duration::from_millis(150)).await;
+
+        // A refresh that was still running when the process stopped
+        let mut interrupted = scheduler.get_task(&finished).unwrap();
+        interrupted.id = "interrupted".to_string();
+        interrupted.task = Task::RefreshBackend {
+            backend_name: "other".to_string(),
+        };
+        interrupted.status = TaskStatus::Running;
+        scheduler
+            .tasks
+            .insert(interrupted.id.clone(), interrupted.clone());
+        persist(&scheduler.store, &interrupted);
+        flush_history(&scheduler.store, &scheduler.tasks);
+
+        let restarted = Scheduler::new(2);
+        restarted.set_executor(Arc::new(SleepExecutor));
+        restarted.restore(TaskStore::new(path)).unwrap();
+
+        assert!(matches!(
+            restarted.get_task(&finished).unwrap().status,
+            TaskStatus::Completed
+        ));
+        assert_eq!(
+            restarted.schedule_task(interrupted.task).unwrap(),
+            "interrupted"
+        );
+        sleep(Duration::from_millis(150)).await;
+        assert!(matches!(
+            restarted.get_task("interrupted").unwrap().status,
+            TaskStatus::Completed
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_paused_scheduler_drops_refresh_retries() {
+        let scheduler = flaky_scheduler(u32::MAX, 5);
+        scheduler.pause();
+        assert!(scheduler.get_info().paused);
+
+        let task_id = scheduler.schedule_task(refresh_task()).unwrap();
+        sleep(Duration::from_millis(100)).await;
+
+        let task = scheduler.get_task(&task_id).unwrap();
+        assert!(matches!(task.status, TaskStatus::Cancelled));
+        assert_eq!(task.attempts, 1);
+
+        scheduler.resume();
+        assert!(!scheduler.get_info().paused);
+    }
+
+    #[tokio::test]
+    async fn test_duplicate_tasks_are_coalesced() {
+        let scheduler = Scheduler::new(2);
+        scheduler.set_executor(Arc::new(SleepExecutor));
+
+        let first = scheduler.schedule_task(refresh_task()).unwrap();
+        let second = scheduler.schedule_task(refresh_task()).unwrap();
+        assert_eq!(first, second);
+        assert!(scheduler.has_unfinished_refreshes());
+
+        // Let the worker loop pick the task up; it is still coalesced while running
+        sleep(Duration::from_millis(20)).await;
+        assert_eq!(scheduler.schedule_task(refresh_task()).unwrap(), first);
+
+        let other = scheduler
+            .schedule_task(Task::RefreshBackend {
+                backend_name: "other".to_string(),
+            })
+            .unwrap();
+        assert_ne!(other, first);
+        assert_eq!(scheduler.list_tasks().len(), 2);
+
+        // Once finished, the same task can be scheduled again
+        sleep(Duration::from_millis(150)).await;
+        assert!(!scheduler.has_unfinished_refreshes());
+        assert_ne!(scheduler.schedule_task(refresh_task()).unwrap(), first);
+    }
+
+    #[tokio::test]
+    async fn test_hung_task_times_out() {
+        let scheduler = Scheduler::new(1);
+        scheduler.set_executor(Arc::new(SleepExecutor));
+        scheduler.set_retry_policy(RetryPolicy {
+            max_attempts: 1,
+            initial_backoff: 0,
+            max_backoff: 0,
+        });
+        scheduler.set_task_timeouts(TaskTimeouts {
+            refresh_backend: 0,
+            renew_auth_token: 0,
+            run_hook: 0,
+        });
+
+        let task_id = scheduler.schedule_task(refresh_task()).unwrap();
+        sleep(Duration::from_millis(50)).await;
+
+        let task = scheduler.get_task(&task_id).unwrap();
+        assert!(matches!(task.status, TaskStatus::Failed));
+        assert!(task.error.unwrap().contains("timed out"));
+        // The worker permit was released
+        assert_eq!(scheduler.semaphore.available_permits(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_cancel_pending_and_running_tasks() {
+        let scheduler = Scheduler::new(1);
+        scheduler.set_executor(Arc::new(SleepExecutor));
+        let running = scheduler.schedule_task(refresh_task()).unwrap();
+        let pending = scheduler
+            .schedule_task(Task::RefreshBackend {
+                backend_name: "other".to_string(),
+            })
+            .unwrap();
+        sleep(Duration::from_millis(20)).await;
+
+        let task = scheduler.cancel_task(&pending).unwrap().unwrap();
+        assert!(matches!(task.status, TaskStatus::Cancelled));
+        assert!(scheduler.cancel_task(&pending).is_err());
+        assert!(scheduler.cancel_task("missing").unwrap().is_none());
+
+        scheduler.cancel_task(&running).unwrap();
+        sleep(Duration::from_millis(20)).await;
+        let task = scheduler.get_task(&running).unwrap();
+        assert!(matches!(task.status, TaskStatus::Cancelled));
+        assert!(!scheduler.has_unfinished_refreshes());
+
+        // The worker was freed and the cancelled task never ran
+        sleep(Duration::from_millis(150)).await;
+        assert_eq!(scheduler.semaphore.available_permits(), 1);
+        assert_eq!(scheduler.get_task(&pending).unwrap().attempts, 0);
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_drains_and_rejects_new_tasks() {
+        let scheduler = Scheduler::new(2);
+        scheduler.set_executor(Arc::new(SleepExecutor));
+        let task_id = scheduler
+            .schedule_task(Task::RefreshBackend {
+                backend_name: "test".to_string(),
+            })
+            .unwrap();
+
+        // Let the worker loop pick the task up
+        sleep(Duration::from_millis(20)).await;
+
+        assert!(scheduler.shutdown(Duration::from_secs(5)).await);
+        assert!(matches!(
+            scheduler.get_task(&task_id).unwrap().status,
+            TaskStatus::Completed
+        ));
+        assert!(scheduler
+            .schedule_task(Task::RefreshBackend {
+                backend_name: "test".to_string(),
+            })
+            .is_err());
+    }
+}
@@ -1,17 +1,33 @@
+use crate::alerts::AlertAck;
 use crate::auth::{create_auth_provider, AuthProvider};
+use crate::cache::{CaImpact, CacheChanges, CacheStats, CertificateChain, DuplicateReport};
 use crate::config::Config;
 use crate::core::Core;
 use crate::duration::DurationParser;
-use crate::types::{AuthRequest, InfoResponse, RefreshRequest};
+use crate::export::{export_chunks, ExportFormat};
+use crate::history::RotationEvent;
+use crate::hooks::HookRun;
+use crate::ignore::{IgnoreRule, IgnoredCertificate};
+use crate::labels::matches_selector;
+use crate::metrics::render_scheduler_metrics;
+use crate::notification_history::{DeadLetter, DeliveryStatus, NotificationDelivery};
+use crate::pinning::normalize_fingerprint;
+use crate::types::{
+    AuthRequest, BackendStatus, CertificateStatus, InfoResponse, NotificationSimulation,
+    NotificationSimulationRequest, NotificationTestResult, RefreshRequest, TaskInfo,
+};
 use crate::version;
-use axum::extract::{Query, Request, State};
-use axum::http::{HeaderMap, StatusCode};
+use axum::body::Body;
+use axum::extract::{Path, Query, Request, State};
+use axum::http::{header, HeaderMap, StatusCode};
 use axum::middleware::Next;
-use axum::response::{Json, Response};
-use axum::routing::{get, post};
+use axum::response::{IntoResponse, Json, Response};
+use axum::routing::{delete, get, post};
 use axum::Router;
 use axum_extra::extract::cookie::{Cookie, CookieJar};
+use axum_extra::extract::Query as ExtraQuery;
 use chrono::{Duration, Utc};
+use rust_embed::RustEmbed;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::net::SocketAddr;
@@ -22,6 +38,8 @@ use tower::ServiceBuilder;
 use tower_http::cors::CorsLayer;
 use tower_http::trace::TraceLayer;
 
+const DEFAULT_DRAIN_TIMEOUT_SECS: u64 = 30;
+
 #[derive(Clone)]
 pub struct AppState {
     pub core: Core,
@@ -58,9 +76,54 @@ impl DoomsdayServer {
         Router::new()
             .route("/v1/info", get(info_handler))
             .route("/v1/auth", post(auth_handler))
+            .route("/v1/backends", get(backends_handler))
             .route("/v1/cache", get(cache_handler))
+            .route("/v1/cache/stats", get(cache_stats_handler))
+            .route("/v1/cache/search", get(search_handler))
+            .route("/v1/cache/duplicates", get(duplicates_handler))
+            .route("/v1/cache/ca-impact", get(ca_impact_handler))
+            .route("/v1/cache/changes", get(changes_handler))
+            .route("/v1/cache/export", get(export_handler))
             .route("/v1/cache/refresh", post(refresh_handler))
+            .route("/v1/cache/ignored", get(ignored_handler))
+            .route(
+                "/v1/cache/ignored/rules",
+                get(ignore_rules_handler).post(add_ignore_rule_handler),
+            )
+            .route(
+                "/v1/cache/ignored/rules/:id",
+                delete(remove_ignore_rule_handler),
+            )
+            .route("/v1/cache/ignored/:sha1", delete(unignore_handler))
+            .route(
+                "/v1/cache/:sha1",
+                get(certificate_handler).delete(purge_handler),
+            )
+            .route("/v1/cache/:sha1/chain", get(chain_handler))
+            .route("/v1/history", get(history_handler))
             .route("/v1/scheduler", get(scheduler_handler))
+            .route("/v1/metrics", get(metrics_handler))
+            .route("/v1/scheduler/pause", post(pause_scheduler_handler))
+            .route("/v1/scheduler/resume", post(resume_scheduler_handler))
+            .route("/v1/scheduler/tasks", get(tasks_handler))
+            .route("/v1/scheduler/tasks/:id", get(task_handler))
+            .route("/v1/scheduler/tasks/:id/cancel", post(cancel_task_handler))
+            .route(
+                "/v1/notifications/simulate",
+                post(simulate_notification_handler),
+            )
+            .route("/v1/notifications/test", post(test_notification_handler))
+            .route(
+                "/v1/notifications/history",
+                get(notification_history_handler),
+            )
+            .route("/v1/notifications/dead-letters", get(dead_letters_handler))
+            .route("/v1/hooks/history", get(hook_history_handler))
+            .route("/v1/alerts/acks", get(acks_handler))
+            .route(
+                "/v1/alerts/:sha1/ack",
+                post(ack_handler).delete(unack_handler),
+            )
             .nest("/", static_routes())
             .layer(
                 ServiceBuilder::new()
@@ -99,9 +162,51 @@ impl DoomsdayServer {
         tracing::info!("📊 API endpoints:");
         tracing::info!("   GET  /v1/info - Server information");
         tracing::info!("   POST /v1/auth - Authentication");
+        tracing::info!("   GET  /v1/backends - Backend status");
         tracing::info!("   GET  /v1/cache - Certificate cache");
-        tracing::info!("   POST /v1/cache/refresh - Refresh cache");
+        tracing::info!("   GET  /v1/cache/stats - Certificate cache statistics");
+        tracing::info!("   GET  /v1/cache/search - Search by subject, SAN or issuer (?q=)");
+        tracing::info!("   GET  /v1/cache/duplicates - Copied certificates and name collisions");
+        tracing::info!(
+            "   GET  /v1/cache/ca-impact - Expiring CAs and the certificates under them (?within=)"
+        );
+        tracing::info!("   GET  /v1/cache/changes - Changes made by refreshes (?since=)");
+        tracing::info!("   GET  /v1/cache/export - Export inventory (csv, json, prometheus)");
+        tracing::info!("   POST /v1/cache/refresh - Refresh cache (?dry_run=true to preview)");
+        tracing::info!(
+            "   DELETE /v1/cache/:sha1 - Purge a certificate (?ignore=true&expires= to hide it)"
+        );
+        tracing::info!("   GET  /v1/cache/:sha1 - Certificate details");
+        tracing::info!("   GET  /v1/cache/:sha1/chain - Issuer chain and issued certificates");
+        tracing::info!("   GET  /v1/cache/ignored - Ignored certificates");
+        tracing::info!("   DELETE /v1/cache/ignored/:sha1 - Stop ignoring a certificate");
+        tracing::info!("   GET  /v1/cache/ignored/rules - Ignore rules");
+        tracing::info!("   POST /v1/cache/ignored/rules - Add an ignore rule");
+        tracing::info!("   DELETE /v1/cache/ignored/rules/:id - Remove an ignore rule");
+        tracing::info!("   GET  /v1/history - Certificate rotations (?backend=&path=&limit=)");
         tracing::info!("   GET  /v1/scheduler - Scheduler status");
+        tracing::info!("   GET  /v1/metrics - Scheduler metrics (Prometheus)");
+        tracing::info!("   POST /v1/scheduler/pause - Pause scheduled refreshes");
+        tracing::info!("   POST /v1/scheduler/resume - Resume scheduled refreshes");
+        tracing::info!("   GET  /v1/scheduler/tasks - Task history (?status=&limit=)");
+        tracing::info!("   GET  /v1/scheduler/tasks/:id - Task details");
+        tracing::info!("   POST /v1/scheduler/tasks/:id/cancel - Cancel a pending or running task");
+        tracing::info!("   POST /v1/notifications/simulate - Dry-run notification routing");
+        tracing::info!(
+            "   POST /v1/notifications/test - Send a test notification via each backend (?backend=)"
+        );
+        tracing::info!(
+            "   GET  /v1/notifications/history - Notification deliveries (?backend=&sha1=&status=&limit=)"
+        );
+        tracing::info!(
+            "   GET  /v1/notifications/dead-letters - Notifications that failed every retry (?backend=&limit=)"
+        );
+        tracing::info!("   GET  /v1/hooks/history - Renewal hook runs (?hook=&sha1=&limit=)");
+        tracing::info!("   GET  /v1/alerts/acks - Acknowledged certificates");
+        tracing::info!(
+            "   POST /v1/alerts/:sha1/ack - Silence a certificate's notifications (?snooze=&comment=)"
+        );
+        tracing::info!("   DELETE /v1/alerts/:sha1/ack - Resume a certificate's notifications");
 
         let server = axum::serve(listener, router).with_graceful_shutdown(shutdown_signal());
 
@@ -112,6 +217,18 @@ impl DoomsdayServer {
             crate::DoomsdayError::internal(format!("Server error: {}", e))
         })?;
 
+        // HTTP requests have finished, now let background work drain
+        let drain_timeout = self
+            .config
+            .server
+            .drain_timeout
+            .unwrap_or(DEFAULT_DRAIN_TIMEOUT_SECS);
+        tracing::info!("⏳ Draining in-flight work (up to {}s)...", drain_timeout);
+        self.app_state
+            .core
+            .shutdown(std::time::Duration::from_secs(drain_timeout))
+            .await;
+
         tracing::info!("🛑 Server shutdown complete");
         Ok(())
     }
@@ -195,15 +312,31 @@ async fn info_handler(State(state): State<AppState>) -> Json<InfoResponse> {
     let response = InfoResponse {
         version: version::version(),
         auth_required: state.auth.requires_auth(),
+        degraded: state.core.is_degraded().await,
+        last_refresh: state.core.last_refresh().await,
     };
     tracing::debug!(
-        "Info response: version={}, auth_required={}",
+        "Info response: version={}, auth_required={}, degraded={}",
         response.version,
-        response.auth_required
+        response.auth_required,
+        response.degraded
     );
     Json(response)
 }
 
+async fn backends_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    cookies: CookieJar,
+) -> Result<Json<Vec<BackendStatus>>, StatusCode> {
+    tracing::debug!("Backend status request received");
+    authorize(&state, &headers, &cookies).await?;
+
+    let statuses = state.core.backend_statuses().await;
+    tracing::debug!("Returning status for {} backends", statuses.len());
+    Ok(Json(statuses))
+}
+
 async fn auth_handler(
     State(state): State<AppState>,
     Json(request): Json<AuthRequest>,
@@ -229,20 +362,51 @@ async fn auth_handler(
 struct CacheQuery {
     beyond: Option<String>,
     within: Option<String>,
+    /// Only return certificates discovered by these backends (repeatable).
+    #[serde(default)]
+    backend: Vec<String>,
+    /// Only return certificates carrying these `key=value` labels (repeatable).
+    #[serde(default)]
+    label: Vec<String>,
+    /// Only return certificates with one of these statuses (repeatable).
+    #[serde(default)]
+    status: Vec<String>,
 }
 
 async fn cache_handler(
     State(state): State<AppState>,
     headers: HeaderMap,
     cookies: CookieJar,
-    Query(query): Query<CacheQuery>,
+    ExtraQuery(query): ExtraQuery<CacheQuery>,
 ) -> Result<Json<Vec<crate::types::CacheItem>>, StatusCode> {
     tracing::debug!(
-        "Cache request received with filters: beyond={:?}, within={:?}",
+        "Cache request received with filters: beyond={:?}, within={:?}, backend={:?}, label={:?}, status={:?}",
         query.beyond,
-        query.within
+        query.within,
+        query.backend,
+        query.label,
+        query.status
     );
 
+    let mut labels = HashMap::new();
+    for label in &query.label {
+        let Some((key, value)) = label.split_once('=') else {
+            tracing::warn!("Invalid label filter '{}', expected key=value", label);
+            return Err(StatusCode::BAD_REQUEST);
+        };
+        labels.insert(key.to_string(), value.to_string());
+    }
+
+    let statuses = query
+        .status
+        .iter()
+        .map(|status| status.parse::<CertificateStatus>())
+        .collect::<crate::Result<Vec<_>>>()
+        .map_err(|e| {
+            tracing::warn!("Invalid status filter: {}", e);
+            StatusCode::BAD_REQUEST
+        })?;
+
     // Check authentication
     if state.auth.requires_auth() {
         tracing::debug!("Authentication required, validating token");
@@ -258,60 +422,366 @@ async fn cache_handler(
         tracing::debug!("Authentication successful");
     }
 
-    let cache = state.core.get_cache();
-    let items = cache.list();
-    tracing::info!("Retrieved {} certificates from cache", items.len());
-
-    // Apply filters
-    let filtered_items = if query.beyond.is_some() || query.within.is_some() {
-        let now = Utc::now();
-
-        let filtered: Vec<_> = items
-            .into_iter()
-            .filter(|item| {
-                let time_until_expiry = item.not_after - now;
-
-                // Check "beyond" filter (certificates expiring beyond the specified duration)
-                if let Some(beyond_str) = &query.beyond {
-                    if let Ok(beyond_duration) = DurationParser::parse(beyond_str) {
-                        if time_until_expiry <= beyond_duration {
-                            return false;
-                        }
-                    }
-                }
+    let beyond = query
+        .beyond
+        .as_deref()
+        .and_then(|s| DurationParser::parse(s).ok());
+    let within = query
+        .within
+        .as_deref()
+        .and_then(|s| DurationParser::parse(s).ok());
+    let now = Utc::now();
+
+    let items = state.core.get_cache().list_filtered(|item| {
+        let time_until_expiry = item.not_after - now;
 
-                // Check "within" filter (certificates expiring within the specified duration)
-                if let Some(within_str) = &query.within {
-                    if let Ok(within_duration) = DurationParser::parse(within_str) {
-                        if time_until_expiry > within_duration {
-                            return false;
-                        }
-                    }
+        // Certificates expiring beyond the specified duration
+        if beyond.is_some_and(|beyond| time_until_expiry <= beyond) {
+            return false;
+        }
+
+        // Certificates expiring within the specified duration
+        if within.is_some_and(|within| time_until_expiry > within) {
+            return false;
+        }
+
+        if !matches_selector(&labels, &item.labels) {
+            return false;
+        }
+
+        if !statuses.is_empty() && !statuses.contains(&item.status) {
+            return false;
+        }
+
+        query.backend.is_empty()
+            || item
+                .paths
+                .iter()
+                .any(|p| query.backend.iter().any(|b| *b == *p.backend))
+    });
+
+    tracing::info!("Returning {} certificates from cache", items.len());
+    Ok(Json(items))
+}
+
+#[derive(Deserialize)]
+struct SearchQuery {
+    q: String,
+}
+
+async fn search_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    cookies: CookieJar,
+    Query(query): Query<SearchQuery>,
+) -> Result<Json<Vec<crate::types::CacheItem>>, StatusCode> {
+    tracing::debug!("Cache search request received: q={}", query.q);
+    authorize(&state, &headers, &cookies).await?;
+
+    if query.q.trim().is_empty() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    Ok(Json(state.core.get_cache().search(&query.q)))
+}
+
+#[derive(Deserialize)]
+struct DuplicatesQuery {
+    /// Report certificates found at this many paths or more (default 2).
+    min_paths: Option<usize>,
+}
+
+async fn duplicates_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    cookies: CookieJar,
+    Query(query): Query<DuplicatesQuery>,
+) -> Result<Json<DuplicateReport>, StatusCode> {
+    tracing::debug!(
+        "Duplicate report request received: min_paths={:?}",
+        query.min_paths
+    );
+    authorize(&state, &headers, &cookies).await?;
+
+    let report = state
+        .core
+        .get_cache()
+        .duplicates(query.min_paths.unwrap_or(2));
+    Ok(Json(report))
+}
+
+#[derive(Deserialize)]
+struct CaImpactQuery {
+    /// Report CAs expiring within this duration (default 90d).
+    within: Option<String>,
+}
+
+async fn ca_impact_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    cookies: CookieJar,
+    Query(query): Query<CaImpactQuery>,
+) -> Result<Json<Vec<CaImpact>>, StatusCode> {
+    tracing::debug!("CA impact request received: within={:?}", query.within);
+    authorize(&state, &headers, &cookies).await?;
+
+    let within = match DurationParser::parse(query.within.as_deref().unwrap_or("90d")) {
+        Ok(within) => within,
+        Err(e) => {
+            tracing::warn!("Invalid within parameter: {}", e);
+            return Err(StatusCode::BAD_REQUEST);
+        }
+    };
+
+    Ok(Json(state.core.get_cache().ca_impact(within)))
+}
+
+#[derive(Deserialize)]
+struct ChangesQuery {
+    /// An RFC 3339 timestamp, or a duration ago such as `24h` or `7d`.
+    since: Option<String>,
+}
+
+async fn changes_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    cookies: CookieJar,
+    Query(query): Query<ChangesQuery>,
+) -> Result<Json<CacheChanges>, StatusCode> {
+    tracing::debug!("Cache changes request received: since={:?}", query.since);
+    authorize(&state, &headers, &cookies).await?;
+
+    let since = match query.since.as_deref() {
+        Some(since) => match chrono::DateTime::parse_from_rfc3339(since) {
+            Ok(time) => Some(time.with_timezone(&Utc)),
+            Err(_) => match DurationParser::parse(since) {
+                Ok(ago) => Some(Utc::now() - ago),
+                Err(e) => {
+                    tracing::warn!("Invalid since parameter '{}': {}", since, e);
+                    return Err(StatusCode::BAD_REQUEST);
                 }
+            },
+        },
+        None => None,
+    };
 
-                true
-            })
-            .collect();
+    Ok(Json(state.core.changes_since(since).await))
+}
 
-        tracing::info!("Applied filters, returning {} certificates", filtered.len());
-        filtered
-    } else {
-        tracing::debug!("No filters applied, returning all certificates");
-        items
+#[derive(Deserialize)]
+struct HistoryQuery {
+    backend: Option<String>,
+    path: Option<String>,
+    limit: Option<usize>,
+}
+
+async fn history_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    cookies: CookieJar,
+    Query(query): Query<HistoryQuery>,
+) -> Result<Json<Vec<RotationEvent>>, StatusCode> {
+    tracing::debug!(
+        "Rotation history request received: backend={:?}, path={:?}, limit={:?}",
+        query.backend,
+        query.path,
+        query.limit
+    );
+    authorize(&state, &headers, &cookies).await?;
+
+    let events =
+        state
+            .core
+            .rotation_history(query.backend.as_deref(), query.path.as_deref(), query.limit);
+    Ok(Json(events))
+}
+
+#[derive(Deserialize)]
+struct NotificationTestQuery {
+    backend: Option<String>,
+}
+
+async fn test_notification_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    cookies: CookieJar,
+    Query(query): Query<NotificationTestQuery>,
+) -> Result<Json<Vec<NotificationTestResult>>, StatusCode> {
+    tracing::debug!(
+        "Test notification request received: backend={:?}",
+        query.backend
+    );
+    authorize(&state, &headers, &cookies).await?;
+
+    // Not found when notifications are off or no backend has that name
+    let results = state
+        .core
+        .test_notifications(query.backend.as_deref())
+        .await
+        .filter(|results| !results.is_empty())
+        .ok_or(StatusCode::NOT_FOUND)?;
+    Ok(Json(results))
+}
+
+#[derive(Deserialize)]
+struct NotificationHistoryQuery {
+    backend: Option<String>,
+    sha1: Option<String>,
+    status: Option<DeliveryStatus>,
+    limit: Option<usize>,
+}
+
+async fn notification_history_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    cookies: CookieJar,
+    Query(query): Query<NotificationHistoryQuery>,
+) -> Result<Json<Vec<NotificationDelivery>>, StatusCode> {
+    tracing::debug!(
+        "Notification history request received: backend={:?}, sha1={:?}, status={:?}, limit={:?}",
+        query.backend,
+        query.sha1,
+        query.status,
+        query.limit
+    );
+    authorize(&state, &headers, &cookies).await?;
+
+    let deliveries = state.core.notification_history(
+        query.backend.as_deref(),
+        query.sha1.as_deref(),
+        query.status,
+        query.limit,
+    );
+    Ok(Json(deliveries))
+}
+
+#[derive(Deserialize)]
+struct DeadLettersQuery {
+    backend: Option<String>,
+    limit: Option<usize>,
+}
+
+async fn dead_letters_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    cookies: CookieJar,
+    Query(query): Query<DeadLettersQuery>,
+) -> Result<Json<Vec<DeadLetter>>, StatusCode> {
+    tracing::debug!(
+        "Dead letters request received: backend={:?}, limit={:?}",
+        query.backend,
+        query.limit
+    );
+    authorize(&state, &headers, &cookies).await?;
+
+    Ok(Json(
+        state
+            .core
+            .dead_letters(query.backend.as_deref(), query.limit),
+    ))
+}
+
+#[derive(Deserialize)]
+struct HookHistoryQuery {
+    hook: Option<String>,
+    sha1: Option<String>,
+    limit: Option<usize>,
+}
+
+async fn hook_history_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    cookies: CookieJar,
+    Query(query): Query<HookHistoryQuery>,
+) -> Result<Json<Vec<HookRun>>, StatusCode> {
+    tracing::debug!(
+        "Hook history request received: hook={:?}, sha1={:?}, limit={:?}",
+        query.hook,
+        query.sha1,
+        query.limit
+    );
+    authorize(&state, &headers, &cookies).await?;
+
+    Ok(Json(state.core.hook_runs(
+        query.hook.as_deref(),
+        query.sha1.as_deref(),
+        query.limit,
+    )))
+}
+
+#[derive(Deserialize)]
+struct RefreshQuery {
+    #[serde(default)]
+    dry_run: bool,
+}
+
+async fn cache_stats_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    cookies: CookieJar,
+) -> Result<Json<CacheStats>, StatusCode> {
+    tracing::debug!("Cache stats request received");
+    authorize(&state, &headers, &cookies).await?;
+
+    Ok(Json(state.core.get_cache().get_stats()))
+}
+
+#[derive(Deserialize)]
+struct ExportQuery {
+    format: Option<String>,
+}
+
+async fn export_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    cookies: CookieJar,
+    Query(query): Query<ExportQuery>,
+) -> Result<Response, StatusCode> {
+    tracing::debug!("Cache export request received: format={:?}", query.format);
+    authorize(&state, &headers, &cookies).await?;
+
+    let format = match query.format.as_deref() {
+        Some(format) => format
+            .parse::<ExportFormat>()
+            .map_err(|_| StatusCode::BAD_REQUEST)?,
+        None => ExportFormat::Json,
     };
 
-    Ok(Json(filtered_items))
+    let cache = state.core.get_cache();
+    tracing::info!("Exporting {} certificates as {:?}", cache.len(), format);
+    // Items are rendered as the body is sent; a failure cuts the body short
+    let chunks = export_chunks(cache.items(), format).inspect(|chunk| {
+        if let Err(e) = chunk {
+            tracing::error!("Failed to export cache: {}", e);
+        }
+    });
+    let stream = futures::stream::iter(chunks);
+    let disposition = format!(
+        "attachment; filename=\"doomsday-export.{}\"",
+        format.file_extension()
+    );
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, format.content_type().to_string()),
+            (header::CONTENT_DISPOSITION, disposition),
+        ],
+        Body::from_stream(stream),
+    )
+        .into_response())
 }
 
 async fn refresh_handler(
     State(state): State<AppState>,
     headers: HeaderMap,
     cookies: CookieJar,
+    Query(query): Query<RefreshQuery>,
     Json(request): Json<RefreshRequest>,
-) -> Result<Json<crate::types::PopulateStats>, StatusCode> {
+) -> Result<Response, StatusCode> {
     tracing::info!(
-        "Cache refresh request received: backends={:?}",
-        request.backends
+        "Cache refresh request received: backends={:?}, dry_run={}",
+        request.backends,
+        query.dry_run
     );
 
     // Check authentication
@@ -329,6 +799,20 @@ async fn refresh_handler(
         tracing::debug!("Authentication successful for refresh");
     }
 
+    if query.dry_run {
+        return match state
+            .core
+            .preview_refresh(request.backends.as_deref())
+            .await
+        {
+            Ok(preview) => Ok(Json(preview).into_response()),
+            Err(e) => {
+                tracing::error!("Dry-run refresh failed: {}", e);
+                Err(StatusCode::INTERNAL_SERVER_ERROR)
+            }
+        };
+    }
+
     let stats = if let Some(backends) = request.backends {
         tracing::info!("Refreshing specific backends: {:?}", backends);
         // Refresh specific backends
@@ -385,7 +869,294 @@ async fn refresh_handler(
         }
     };
 
-    Ok(Json(stats))
+    Ok(Json(stats).into_response())
+}
+
+#[derive(Deserialize)]
+struct PurgeQuery {
+    #[serde(default)]
+    ignore: bool,
+    reason: Option<String>,
+    /// How long to ignore the certificate for (e.g. `30d`), forever if unset.
+    expires: Option<String>,
+}
+
+#[derive(Serialize)]
+struct PurgeResponse {
+    sha1: String,
+    subject: String,
+    ignored: bool,
+}
+
+async fn certificate_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    cookies: CookieJar,
+    Path(sha1): Path<String>,
+) -> Result<Json<crate::types::CacheItem>, StatusCode> {
+    tracing::debug!("Certificate request received: sha1={}", sha1);
+    authorize(&state, &headers, &cookies).await?;
+
+    // Accept fingerprints as printed by other tools, e.g. colon separated
+    let cache = state.core.get_cache();
+    cache
+        .get(&normalize_fingerprint(&sha1))
+        .map(|object| Json(cache.item(&object)))
+        .ok_or(StatusCode::NOT_FOUND)
+}
+
+async fn purge_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    cookies: CookieJar,
+    Path(sha1): Path<String>,
+    Query(query): Query<PurgeQuery>,
+) -> Result<Json<PurgeResponse>, StatusCode> {
+    tracing::info!(
+        "Cache purge request received: sha1={}, ignore={}",
+        sha1,
+        query.ignore
+    );
+    authorize_admin(&state, &headers, &cookies).await?;
+
+    let expires_at = match query.expires.as_deref().map(DurationParser::parse) {
+        Some(Ok(duration)) => Some(Utc::now() + duration),
+        Some(Err(e)) => {
+            tracing::warn!("Invalid ignore expiry: {}", e);
+            return Err(StatusCode::BAD_REQUEST);
+        }
+        None => None,
+    };
+
+    let cache = state.core.get_cache();
+    let subject = match cache.get(&sha1) {
+        Some(object) => object.subject,
+        None => {
+            tracing::warn!("Certificate {} not found in cache", sha1);
+            return Err(StatusCode::NOT_FOUND);
+        }
+    };
+
+    // Ignore first so the removal does not reach notification subscribers
+    if query.ignore {
+        cache
+            .ignore_list()
+            .ignore(IgnoredCertificate {
+                sha1: sha1.clone(),
+                subject: subject.clone(),
+                ignored_at: Utc::now(),
+                reason: query.reason,
+                expires_at,
+            })
+            .map_err(|e| {
+                tracing::error!("Failed to persist ignore list: {}", e);
+                StatusCode::INTERNAL_SERVER_ERROR
+            })?;
+    } else {
+        cache.purge(&sha1);
+    }
+
+    Ok(Json(PurgeResponse {
+        sha1,
+        subject,
+        ignored: query.ignore,
+    }))
+}
+
+#[derive(Deserialize)]
+struct AckQuery {
+    /// How long to silence the certificate for (e.g. `3d`). Never past its
+    /// expiry, which is also the default.
+    snooze: Option<String>,
+    comment: Option<String>,
+}
+
+async fn ack_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    cookies: CookieJar,
+    Path(sha1): Path<String>,
+    Query(query): Query<AckQuery>,
+) -> Result<Json<AlertAck>, StatusCode> {
+    tracing::info!(
+        "Alert acknowledgement received: sha1={}, snooze={:?}",
+        sha1,
+        query.snooze
+    );
+    authorize(&state, &headers, &cookies).await?;
+
+    let snooze = match query.snooze.as_deref().map(DurationParser::parse) {
+        Some(Ok(duration)) => Some(duration),
+        Some(Err(e)) => {
+            tracing::warn!("Invalid snooze duration: {}", e);
+            return Err(StatusCode::BAD_REQUEST);
+        }
+        None => None,
+    };
+
+    let cache = state.core.get_cache();
+    let Some(object) = cache.get(&sha1) else {
+        tracing::warn!("Certificate {} not found in cache", sha1);
+        return Err(StatusCode::NOT_FOUND);
+    };
+
+    // Expiry alerts still go out if the rotation does not happen in time
+    let now = Utc::now();
+    let until = snooze.map_or(object.not_after, |snooze| {
+        (now + snooze).min(object.not_after)
+    });
+    if until <= now {
+        tracing::warn!("Certificate {} has already expired", sha1);
+        return Err(StatusCode::CONFLICT);
+    }
+
+    let ack = AlertAck {
+        sha1,
+        subject: object.subject,
+        acknowledged_at: now,
+        until,
+        comment: query.comment,
+    };
+    cache.alert_acks().acknowledge(ack.clone()).map_err(|e| {
+        tracing::error!("Failed to persist alert acknowledgements: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    Ok(Json(ack))
+}
+
+async fn unack_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    cookies: CookieJar,
+    Path(sha1): Path<String>,
+) -> Result<Json<AlertAck>, StatusCode> {
+    tracing::info!("Alert acknowledgement removal received: sha1={}", sha1);
+    authorize(&state, &headers, &cookies).await?;
+
+    match state.core.get_cache().alert_acks().remove(&sha1) {
+        Ok(Some(ack)) => Ok(Json(ack)),
+        Ok(None) => Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            tracing::error!("Failed to persist alert acknowledgements: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+async fn acks_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    cookies: CookieJar,
+) -> Result<Json<Vec<AlertAck>>, StatusCode> {
+    tracing::debug!("Alert acknowledgements request received");
+    authorize(&state, &headers, &cookies).await?;
+
+    Ok(Json(state.core.get_cache().alert_acks().list()))
+}
+
+async fn chain_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    cookies: CookieJar,
+    Path(sha1): Path<String>,
+) -> Result<Json<CertificateChain>, StatusCode> {
+    tracing::debug!("Certificate chain request received: sha1={}", sha1);
+    authorize(&state, &headers, &cookies).await?;
+
+    state
+        .core
+        .get_cache()
+        .chain(&sha1)
+        .map(Json)
+        .ok_or(StatusCode::NOT_FOUND)
+}
+
+async fn ignored_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    cookies: CookieJar,
+) -> Result<Json<Vec<IgnoredCertificate>>, StatusCode> {
+    tracing::debug!("Ignored certificates request received");
+    authorize(&state, &headers, &cookies).await?;
+
+    Ok(Json(state.core.get_cache().ignore_list().list()))
+}
+
+async fn unignore_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    cookies: CookieJar,
+    Path(sha1): Path<String>,
+) -> Result<Json<IgnoredCertificate>, StatusCode> {
+    tracing::info!("Unignore request received: sha1={}", sha1);
+    authorize_admin(&state, &headers, &cookies).await?;
+
+    match state.core.get_cache().ignore_list().unignore(&sha1) {
+        Ok(Some(entry)) => Ok(Json(entry)),
+        Ok(None) => Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            tracing::error!("Failed to persist ignore list: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+async fn ignore_rules_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    cookies: CookieJar,
+) -> Result<Json<Vec<IgnoreRule>>, StatusCode> {
+    tracing::debug!("Ignore rules request received");
+    authorize(&state, &headers, &cookies).await?;
+
+    Ok(Json(state.core.get_cache().ignore_list().rules()))
+}
+
+async fn add_ignore_rule_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    cookies: CookieJar,
+    Json(rule): Json<IgnoreRule>,
+) -> Result<Json<IgnoreRule>, StatusCode> {
+    tracing::info!("Add ignore rule request received");
+    authorize_admin(&state, &headers, &cookies).await?;
+
+    if let Err(e) = rule.validate() {
+        tracing::warn!("Rejected ignore rule: {}", e);
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    state
+        .core
+        .get_cache()
+        .ignore_list()
+        .add_rule(rule)
+        .map(Json)
+        .map_err(|e| {
+            tracing::error!("Failed to persist ignore rules: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })
+}
+
+async fn remove_ignore_rule_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    cookies: CookieJar,
+    Path(id): Path<String>,
+) -> Result<Json<IgnoreRule>, StatusCode> {
+    tracing::info!("Remove ignore rule request received: id={}", id);
+    authorize_admin(&state, &headers, &cookies).await?;
+
+    match state.core.get_cache().ignore_list().remove_rule(&id) {
+        Ok(Some(rule)) => Ok(Json(rule)),
+        // Rules from the config file are not removable here
+        Ok(None) => Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            tracing::error!("Failed to persist ignore rules: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
 }
 
 async fn scheduler_handler(
@@ -419,6 +1190,187 @@ async fn scheduler_handler(
     Ok(Json(info))
 }
 
+async fn metrics_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    cookies: CookieJar,
+) -> Result<Response, StatusCode> {
+    tracing::debug!("Metrics request received");
+    authorize(&state, &headers, &cookies).await?;
+
+    let info = state.core.get_scheduler().get_info();
+    Ok((
+        [(
+            header::CONTENT_TYPE,
+            ExportFormat::Prometheus.content_type(),
+        )],
+        render_scheduler_metrics(&info),
+    )
+        .into_response())
+}
+
+async fn pause_scheduler_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    cookies: CookieJar,
+) -> Result<Json<crate::types::SchedulerInfo>, StatusCode> {
+    tracing::info!("Scheduler pause request received");
+    authorize_admin(&state, &headers, &cookies).await?;
+
+    let scheduler = state.core.get_scheduler();
+    scheduler.pause();
+    Ok(Json(scheduler.get_info()))
+}
+
+async fn resume_scheduler_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    cookies: CookieJar,
+) -> Result<Json<crate::types::SchedulerInfo>, StatusCode> {
+    tracing::info!("Scheduler resume request received");
+    authorize_admin(&state, &headers, &cookies).await?;
+
+    let scheduler = state.core.get_scheduler();
+    scheduler.resume();
+    Ok(Json(scheduler.get_info()))
+}
+
+#[derive(Deserialize)]
+struct TasksQuery {
+    /// Only return tasks in this state, e.g. `failed` (case-insensitive).
+    status: Option<String>,
+    limit: Option<usize>,
+}
+
+async fn tasks_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    cookies: CookieJar,
+    Query(query): Query<TasksQuery>,
+) -> Result<Json<Vec<TaskInfo>>, StatusCode> {
+    tracing::debug!(
+        "Task history request received: status={:?}, limit={:?}",
+        query.status,
+        query.limit
+    );
+    authorize(&state, &headers, &cookies).await?;
+
+    let mut tasks: Vec<TaskInfo> = state
+        .core
+        .get_scheduler()
+        .list_tasks()
+        .into_iter()
+        .filter(|t| {
+            query
+                .status
+                .as_ref()
+                .is_none_or(|s| format!("{:?}", t.status).eq_ignore_ascii_case(s))
+        })
+        .collect();
+
+    // Newest first
+    tasks.sort_by_key(|t| std::cmp::Reverse(t.created_at));
+    if let Some(limit) = query.limit {
+        tasks.truncate(limit);
+    }
+
+    Ok(Json(tasks))
+}
+
+async fn task_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    cookies: CookieJar,
+    Path(id): Path<String>,
+) -> Result<Json<TaskInfo>, StatusCode> {
+    tracing::debug!("Task request received: id={}", id);
+    authorize(&state, &headers, &cookies).await?;
+
+    state
+        .core
+        .get_scheduler()
+        .get_task(&id)
+        .map(Json)
+        .ok_or(StatusCode::NOT_FOUND)
+}
+
+async fn cancel_task_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    cookies: CookieJar,
+    Path(id): Path<String>,
+) -> Result<Json<TaskInfo>, StatusCode> {
+    tracing::info!("Task cancel request received: id={}", id);
+    authorize_admin(&state, &headers, &cookies).await?;
+
+    match state.core.get_scheduler().cancel_task(&id) {
+        Ok(Some(task)) => Ok(Json(task)),
+        Ok(None) => Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            tracing::warn!("Failed to cancel task {}: {}", id, e);
+            Err(StatusCode::CONFLICT)
+        }
+    }
+}
+
+async fn simulate_notification_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    cookies: CookieJar,
+    Json(request): Json<NotificationSimulationRequest>,
+) -> Result<Json<NotificationSimulation>, StatusCode> {
+    tracing::debug!(
+        "Notification simulation request received: subject={}, days_to_expiry={}",
+        request.subject,
+        request.days_to_expiry
+    );
+    authorize(&state, &headers, &cookies).await?;
+
+    Ok(Json(state.core.simulate_notification(request).await))
+}
+
+async fn authorize(
+    state: &AppState,
+    headers: &HeaderMap,
+    cookies: &CookieJar,
+) -> Result<(), StatusCode> {
+    if !state.auth.requires_auth() {
+        return Ok(());
+    }
+
+    let token = extract_token(headers, cookies).ok_or_else(|| {
+        tracing::warn!("No authentication token provided");
+        StatusCode::UNAUTHORIZED
+    })?;
+
+    if !state.auth.validate_token(&token).await.unwrap_or(false) {
+        tracing::warn!("Invalid authentication token provided");
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+
+    Ok(())
+}
+
+async fn authorize_admin(
+    state: &AppState,
+    headers: &HeaderMap,
+    cookies: &CookieJar,
+) -> Result<(), StatusCode> {
+    authorize(state, headers, cookies).await?;
+
+    if !state.auth.requires_auth() {
+        return Ok(());
+    }
+
+    let token = extract_token(headers, cookies).ok_or(StatusCode::UNAUTHORIZED)?;
+    if !state.auth.is_admin(&token).await.unwrap_or(false) {
+        tracing::warn!("Administrative action attempted without admin rights");
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    Ok(())
+}
+
 fn extract_token(headers: &HeaderMap, cookies: &CookieJar) -> Option<String> {
     // Try to get token from header first
     if let Some(auth_header) = headers.get("X-Doomsday-Token") {
@@ -445,49 +1397,29 @@ fn static_routes() -> Router<AppState> {
         .route("/static/*file", get(static_file_handler))
 }
 
-async fn dashboard_handler() -> &'static str {
+#[derive(RustEmbed)]
+#[folder = "static/"]
+struct StaticAssets;
+
+async fn dashboard_handler() -> Response {
     tracing::debug!("Serving dashboard page");
-    // TODO: Serve the actual dashboard HTML
-    "<!DOCTYPE html>
-<html>
-<head>
-    <title>Doomsday Certificate Monitor</title>
-    <style>
-        body { font-family: Arial, sans-serif; margin: 20px; }
-        .header { background: #2196F3; color: white; padding: 20px; margin: -20px -20px 20px -20px; }
-        .status { padding: 10px; margin: 10px 0; border-radius: 4px; }
-        .expired { background: #ffebee; border-left: 4px solid #f44336; }
-        .expiring { background: #fff3e0; border-left: 4px solid #ff9800; }
-        .ok { background: #e8f5e8; border-left: 4px solid #4caf50; }
-    </style>
-</head>
-<body>
-    <div class='header'>
-        <h1>🔒 Doomsday Certificate Monitor</h1>
-        <p>Certificate expiration tracking dashboard</p>
-    </div>
-    <div class='status expired'>
-        <h3>⚠️ Expired Certificates</h3>
-        <p>Please refresh the page or check the API for current data.</p>
-    </div>
-    <div class='status expiring'>
-        <h3>⏰ Expiring Soon</h3>
-        <p>Certificates expiring within 30 days.</p>
-    </div>
-    <div class='status ok'>
-        <h3>✅ OK Certificates</h3>
-        <p>Certificates in good standing.</p>
-    </div>
-    <script>
-        // TODO: Add JavaScript to fetch and display real certificate data
-        console.log('Doomsday Dashboard loaded');
-    </script>
-</body>
-</html>"
-}
-
-async fn static_file_handler() -> &'static str {
-    tracing::warn!("Static file serving not yet implemented");
-    // TODO: Serve static files
-    "Static file serving not implemented yet"
+    serve_static_asset("index.html")
+}
+
+async fn static_file_handler(Path(file): Path<String>) -> Response {
+    tracing::debug!("Serving static file: {}", file);
+    serve_static_asset(&file)
+}
+
+fn serve_static_asset(path: &str) -> Response {
+    match StaticAssets::get(path) {
+        Some(asset) => {
+            let content_type = asset.metadata.mimetype().to_string();
+            ([(header::CONTENT_TYPE, content_type)], asset.data).into_response()
+        }
+        None => {
+            tracing::debug!("Static asset not found: {}", path);
+            StatusCode::NOT_FOUND.into_response()
+        }
+    }
 }
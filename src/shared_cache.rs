@@ -0,0 +1,151 @@
+use crate::cache::Cache;
+use crate::config::SharedCacheConfig;
+use crate::redis::{RedisClient, RedisUrl};
+use crate::types::CacheObject;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use tokio::sync::Mutex;
+
+/// Longest a single sync may take before the store is treated as unreachable.
+const SYNC_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Extends the refresh lease, but only if this replica still holds it.
+const RENEW_LEASE_SCRIPT: &str = "if redis.call('get', KEYS[1]) == ARGV[1] then \
+     return redis.call('pexpire', KEYS[1], ARGV[2]) else return 0 end";
+
+/// Gives up the refresh lease, but only if this replica still holds it.
+const RELEASE_LEASE_SCRIPT: &str = "if redis.call('get', KEYS[1]) == ARGV[1] then \
+     return redis.call('del', KEYS[1]) else return 0 end";
+
+/// Shares the certificate cache between replicas through Redis. The replica
+/// holding the refresh lease scans the backends and publishes its cache;
+/// the others skip refreshes and serve the published copy.
+pub struct SharedCache {
+    config: SharedCacheConfig,
+    url: RedisUrl,
+    instance_id: String,
+    client: Mutex<Option<RedisClient>>,
+    leader: AtomicBool,
+}
+
+impl SharedCache {
+    pub fn new(config: SharedCacheConfig) -> crate::Result<Self> {
+        let url = RedisUrl::parse(&config.url)?;
+        Ok(SharedCache {
+            config,
+            url,
+            instance_id: uuid::Uuid::new_v4().to_string(),
+            client: Mutex::new(None),
+            leader: AtomicBool::new(false),
+        })
+    }
+
+    /// Whether this replica should refresh backends.
+    pub fn is_leader(&self) -> bool {
+        self.leader.load(Ordering::SeqCst)
+    }
+
+    pub fn sync_interval(&self) -> Duration {
+        Duration::from_secs(self.config.sync_interval)
+    }
+
+    fn lease_key(&self) -> String {
+        format!("{}:leader", self.config.key_prefix)
+    }
+
+    fn cache_key(&self) -> String {
+        format!("{}:cache", self.config.key_prefix)
+    }
+
+    /// Takes or keeps the refresh lease, then publishes the local cache if
+    /// this replica holds it or loads the published one otherwise. While the
+    /// store is unreachable every replica refreshes for itself.
+    pub async fn sync(&self, cache: &Cache) {
+        let error = match tokio::time::timeout(SYNC_TIMEOUT, self.try_sync(cache)).await {
+            Ok(Ok(())) => return,
+            Ok(Err(e)) => e.to_string(),
+            Err(_) => "timed out".to_string(),
+        };
+
+        // Reconnect on the next sync
+        *self.client.lock().await = None;
+        if !self.leader.swap(true, Ordering::SeqCst) {
+            tracing::warn!(
+                "Shared cache unreachable ({}), refreshing backends locally",
+                error
+            );
+        } else {
+            tracing::debug!("Shared cache sync failed: {}", error);
+        }
+    }
+
+    async fn try_sync(&self, cache: &Cache) -> crate::Result<()> {
+        let mut guard = self.client.lock().await;
+        if guard.is_none() {
+            *guard = Some(RedisClient::connect(&self.url).await?);
+        }
+        let client = guard.as_mut().unwrap();
+
+        let lease_key = self.lease_key();
+        let ttl = Duration::from_secs(self.config.lease_ttl);
+        let ttl_ms = ttl.as_millis().to_string();
+        let held = client
+            .eval(
+                RENEW_LEASE_SCRIPT,
+                &lease_key,
+                &[&self.instance_id, &ttl_ms],
+            )
+            .await?
+            == 1
+            || client.set_nx(&lease_key, &self.instance_id, ttl).await?;
+
+        if self.leader.swap(held, Ordering::SeqCst) != held {
+            if held {
+                tracing::info!("Acquired the shared cache refresh lease");
+            } else {
+                tracing::info!("Another replica holds the refresh lease, following its cache");
+            }
+        }
+
+        if held {
+            let objects = cache.snapshot();
+            // Don't wipe the published copy before the first refresh lands
+            if !objects.is_empty() {
+                client
+                    .set(&self.cache_key(), &serde_json::to_vec(&objects)?)
+                    .await?;
+                tracing::debug!("Published {} certificates to shared cache", objects.len());
+            }
+        } else if let Some(data) = client.get(&self.cache_key()).await? {
+            let objects: Vec<CacheObject> = serde_json::from_slice(&data)?;
+            tracing::debug!("Loaded {} certificates from shared cache", objects.len());
+            cache.replace_all(objects);
+        }
+
+        Ok(())
+    }
+
+    /// Hands the refresh lease to another replica on shutdown.
+    pub async fn release(&self) {
+        if !self.is_leader() {
+            return;
+        }
+
+        let mut guard = self.client.lock().await;
+        let Some(client) = guard.as_mut() else {
+            return;
+        };
+        match client
+            .eval(
+                RELEASE_LEASE_SCRIPT,
+                &self.lease_key(),
+                &[&self.instance_id],
+            )
+            .await
+        {
+            Ok(_) => tracing::info!("Released the shared cache refresh lease"),
+            Err(e) => tracing::warn!("Failed to release the shared cache refresh lease: {}", e),
+        }
+        self.leader.store(false, Ordering::SeqCst);
+    }
+}
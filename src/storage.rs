@@ -1,14 +1,272 @@
-use crate::types::{CertificateData, PathList};
+use crate::types::{AuthStatus, CertificateData, ListFailure, PathList};
 use async_trait::async_trait;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::RwLock;
 
 #[async_trait]
 pub trait Accessor: Send + Sync {
     async fn list(&self) -> crate::Result<PathList>;
     async fn get(&self, path: &str) -> crate::Result<Option<CertificateData>>;
     fn name(&self) -> &str;
+
+    /// Whether the backend holds credentials that expire and must be renewed.
+    fn renews_auth(&self) -> bool {
+        false
+    }
+
+    /// Renews the backend's credentials, returning how long to wait before
+    /// renewing again, or `None` if they do not expire.
+    async fn renew_auth(&self) -> crate::Result<Option<Duration>> {
+        Ok(None)
+    }
+
+    /// The state of the backend's token, reported by `/v1/backends`.
+    fn auth_status(&self) -> Option<AuthStatus> {
+        None
+    }
+
+    /// A handle for one refresh run, for backends that share work between the
+    /// `list` and `get` calls of a run. `None` runs on the backend itself.
+    fn start_run(&self) -> Option<Arc<dyn Accessor>> {
+        None
+    }
+
+    /// Directories the last `list` skipped because they could not be read,
+    /// for backends whose listing can partially fail.
+    fn list_failures(&self) -> Vec<ListFailure> {
+        Vec::new()
+    }
+}
+
+/// When to renew credentials valid for `ttl`: after two thirds of their
+/// lifetime, leaving room for a few retries before they expire.
+pub fn renewal_delay(ttl: Duration) -> Duration {
+    (ttl * 2 / 3).max(Duration::from_secs(1))
+}
+
+#[derive(Debug)]
+struct CachedToken {
+    value: String,
+    refresh_token: Option<String>,
+    renew_at: Instant,
+}
+
+/// An access token shared by every clone of an accessor, so requests
+/// reuse it until it is due for renewal instead of logging in each time.
+#[derive(Debug, Clone, Default)]
+pub struct TokenCache {
+    inner: Arc<RwLock<Option<CachedToken>>>,
+}
+
+impl TokenCache {
+    /// The cached token, unless it is missing or due for renewal.
+    pub async fn get(&self) -> Option<String> {
+        self.inner
+            .read()
+            .await
+            .as_ref()
+            .filter(|token| Instant::now() < token.renew_at)
+            .map(|token| token.value.clone())
+    }
+
+    pub async fn refresh_token(&self) -> Option<String> {
+        self.inner
+            .read()
+            .await
+            .as_ref()
+            .and_then(|token| token.refresh_token.clone())
+    }
+
+    /// Stores a freshly issued token and returns when it should be renewed.
+    pub async fn set(
+        &self,
+        value: String,
+        refresh_token: Option<String>,
+        expires_in: Duration,
+    ) -> Duration {
+        let delay = renewal_delay(expires_in);
+        *self.inner.write().await = Some(CachedToken {
+            value,
+            refresh_token,
+            renew_at: Instant::now() + delay,
+        });
+        delay
+    }
+}
+
+/// Spaces out requests to a backend so they stay under a rate, shared by
+/// every clone of an accessor.
+#[derive(Debug, Clone)]
+pub struct RateLimiter {
+    interval: Duration,
+    next: Arc<std::sync::Mutex<tokio::time::Instant>>,
+}
+
+impl RateLimiter {
+    pub fn new(per_second: u32) -> Self {
+        RateLimiter {
+            interval: Duration::from_secs(1) / per_second.max(1),
+            next: Arc::new(std::sync::Mutex::new(tokio::time::Instant::now())),
+        }
+    }
+
+    /// Waits for the next free slot.
+    pub async fn acquire(&self) {
+        let slot = {
+            let mut next = self.next.lock().unwrap();
+            let slot = (*next).max(tokio::time::Instant::now());
+            *next = slot + self.interval;
+            slot
+        };
+        tokio::time::sleep_until(slot).await;
+    }
+}
+
+/// An HTTP client builder that trusts the CAs in `ca_cert` beside the
+/// system roots and presents the certificate in `client_cert`, with its key
+/// in the same file or in `client_key`. `kind` names the backend in errors.
+pub fn tls_client_builder(
+    kind: &str,
+    ca_cert: Option<&Path>,
+    client_cert: Option<&Path>,
+    client_key: Option<&Path>,
+) -> crate::Result<reqwest::ClientBuilder> {
+    let mut builder = reqwest::Client::builder();
+
+    if let Some(path) = ca_cert {
+        let pem = read_pem(kind, "ca_cert", path)?;
+        let certs = reqwest::Certificate::from_pem_bundle(&pem).map_err(|e| {
+            crate::DoomsdayError::config(format!(
+                "Invalid {} ca_cert {}: {}",
+                kind,
+                path.display(),
+                e
+            ))
+        })?;
+        if certs.is_empty() {
+            return Err(crate::DoomsdayError::config(format!(
+                "{} ca_cert {} contains no PEM certificates",
+                kind,
+                path.display()
+            )));
+        }
+        for cert in certs {
+            builder = builder.add_root_certificate(cert);
+        }
+    }
+
+    match (client_cert, client_key) {
+        (Some(cert), key) => {
+            let mut pem = read_pem(kind, "client_cert", cert)?;
+            if let Some(key) = key {
+                pem.push(b'\n');
+                pem.extend(read_pem(kind, "client_key", key)?);
+            }
+            let identity = reqwest::Identity::from_pem(&pem).map_err(|e| {
+                crate::DoomsdayError::config(format!(
+                    "Invalid {} client certificate {}: {}",
+                    kind,
+                    cert.display(),
+                    e
+                ))
+            })?;
+            // PEM identities are only supported by the rustls backend
+            builder = builder.use_rustls_tls().identity(identity);
+        }
+        (None, Some(_)) => {
+            return Err(crate::DoomsdayError::config(format!(
+                "{} client_key requires client_cert",
+                kind
+            )))
+        }
+        (None, None) => {}
+    }
+
+    Ok(builder)
+}
+
+fn read_pem(kind: &str, property: &str, path: &Path) -> crate::Result<Vec<u8>> {
+    std::fs::read(path).map_err(|e| {
+        crate::DoomsdayError::config(format!(
+            "Failed to read {} {} {}: {}",
+            kind,
+            property,
+            path.display(),
+            e
+        ))
+    })
 }
 
 pub mod credhub;
 pub mod opsmgr;
 pub mod tlsclient;
 pub mod vault;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_renewal_delay() {
+        assert_eq!(
+            renewal_delay(Duration::from_secs(3600)),
+            Duration::from_secs(2400)
+        );
+        assert_eq!(renewal_delay(Duration::ZERO), Duration::from_secs(1));
+    }
+
+    #[tokio::test]
+    async fn test_token_cache_expires_before_token() {
+        let cache = TokenCache::default();
+        assert!(cache.get().await.is_none());
+
+        cache
+            .set("abc".to_string(), None, Duration::from_secs(3600))
+            .await;
+        assert_eq!(cache.get().await.as_deref(), Some("abc"));
+
+        // Due for renewal once two thirds of the lifetime have passed
+        cache
+            .set("short".to_string(), Some("r".to_string()), Duration::ZERO)
+            .await;
+        tokio::time::sleep(Duration::from_millis(1100)).await;
+        assert!(cache.get().await.is_none());
+        assert_eq!(cache.refresh_token().await.as_deref(), Some("r"));
+    }
+
+    #[test]
+    fn test_tls_client_builder_rejects_unusable_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let not_pem = dir.path().join("ca.txt");
+        std::fs::write(&not_pem, "not a certificate").unwrap();
+        let error = |ca: Option<&Path>, cert: Option<&Path>, key: Option<&Path>| {
+            tls_client_builder("CredHub", ca, cert, key)
+                .unwrap_err()
+                .to_string()
+        };
+
+        assert!(error(Some(&not_pem), None, None).contains("contains no PEM certificates"));
+        assert!(error(Some(&dir.path().join("missing.pem")), None, None)
+            .contains("Failed to read CredHub ca_cert"));
+        assert!(error(None, None, Some(&not_pem)).contains("client_key requires client_cert"));
+        assert!(tls_client_builder("CredHub", None, None, None).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_rate_limiter_spaces_requests() {
+        let limiter = RateLimiter::new(20);
+        let start = Instant::now();
+        let clone = limiter.clone();
+        let waits = (0..5).map(|i| {
+            let limiter = if i % 2 == 0 { &limiter } else { &clone };
+            limiter.acquire()
+        });
+        futures::future::join_all(waits).await;
+
+        // The first request goes at once, the other four 50ms apart
+        assert!(start.elapsed() >= Duration::from_millis(200));
+        assert!(start.elapsed() < Duration::from_millis(400));
+    }
+}
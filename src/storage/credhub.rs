@@ -1,20 +1,45 @@
-use crate::storage::Accessor;
+use crate::config::CredHubBackendConfig;
+use crate::storage::{tls_client_builder, Accessor, TokenCache};
 use crate::types::{CertificateData, PathList};
 use async_trait::async_trait;
-use reqwest::Client;
+use reqwest::header::{HeaderMap, LINK};
+use reqwest::{Client, RequestBuilder, Response, StatusCode};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{Mutex, OnceCell};
 use url::Url;
-use x509_parser::prelude::*;
+
+/// Pages of a listing read at most, against a server that never ends one.
+const MAX_LIST_PAGES: usize = 10_000;
 
 #[derive(Debug, Clone)]
 pub struct CredHubAccessor {
     name: String,
     client: Client,
     base_url: Url,
-    client_id: String,
-    client_secret: String,
-    access_token: Option<String>,
+    auth: CredHubAuth,
+    /// Subtree to list, instead of every credential.
+    path: Option<String>,
+    /// Configured, or discovered on first use and shared by every clone.
+    uaa_url: Arc<OnceCell<Url>>,
+    access_token: TokenCache,
+    /// Held while requesting a token, so requests turned away at once
+    /// share one new token.
+    login_lock: Arc<Mutex<()>>,
+}
+
+/// How the accessor authenticates to CredHub.
+#[derive(Debug, Clone)]
+pub enum CredHubAuth {
+    /// A UAA client, whose access token is sent with every request.
+    ClientCredentials {
+        client_id: String,
+        client_secret: String,
+    },
+    /// The client certificate presented in the TLS handshake alone.
+    Certificate,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -25,8 +50,9 @@ struct CredHubCredentialsResponse {
 #[derive(Debug, Serialize, Deserialize)]
 struct CredHubCredential {
     name: String,
-    #[serde(rename = "type")]
-    credential_type: String,
+    /// Absent from the results of a search by path.
+    #[serde(rename = "type", default)]
+    credential_type: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -36,6 +62,17 @@ struct CredHubValueResponse {
     value: serde_json::Value,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+struct CredHubInfoResponse {
+    #[serde(rename = "auth-server")]
+    auth_server: CredHubAuthServer,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CredHubAuthServer {
+    url: String,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 struct CredHubTokenRequest {
     grant_type: String,
@@ -54,150 +91,309 @@ impl CredHubAccessor {
     pub fn new(
         name: String,
         base_url: Url,
-        client_id: String,
-        client_secret: String,
-    ) -> crate::Result<Self> {
-        let client = Client::builder()
-            .danger_accept_invalid_certs(false)
-            .build()?;
-
-        Ok(CredHubAccessor {
+        auth: CredHubAuth,
+        uaa_url: Option<Url>,
+        client: Client,
+    ) -> Self {
+        CredHubAccessor {
             name,
             client,
             base_url,
-            client_id,
-            client_secret,
-            access_token: None,
-        })
+            auth,
+            path: None,
+            uaa_url: Arc::new(OnceCell::new_with(uaa_url)),
+            access_token: TokenCache::default(),
+            login_lock: Arc::new(Mutex::new(())),
+        }
     }
 
     pub fn from_config(
         name: String,
-        properties: &HashMap<String, serde_yaml::Value>,
+        config: &CredHubBackendConfig,
+        request_timeout: Duration,
     ) -> crate::Result<Self> {
-        let url = properties
-            .get("url")
-            .and_then(|v| v.as_str())
-            .ok_or_else(|| crate::DoomsdayError::config("CredHub URL is required"))?;
-
-        let client_id = properties
-            .get("client_id")
-            .and_then(|v| v.as_str())
-            .ok_or_else(|| crate::DoomsdayError::config("CredHub client_id is required"))?;
-
-        let client_secret = properties
-            .get("client_secret")
-            .and_then(|v| v.as_str())
-            .ok_or_else(|| crate::DoomsdayError::config("CredHub client_secret is required"))?;
-
-        let base_url = Url::parse(url)
+        let base_url = Url::parse(&config.url)
             .map_err(|e| crate::DoomsdayError::config(format!("Invalid CredHub URL: {}", e)))?;
+        let uaa_url = config
+            .uaa_url
+            .as_deref()
+            .map(Url::parse)
+            .transpose()
+            .map_err(|e| crate::DoomsdayError::config(format!("Invalid UAA URL: {}", e)))?;
+
+        let auth = match (
+            &config.client_id,
+            &config.client_secret,
+            &config.client_cert,
+        ) {
+            (Some(client_id), Some(client_secret), _) => CredHubAuth::ClientCredentials {
+                client_id: client_id.clone(),
+                client_secret: client_secret.clone(),
+            },
+            (None, None, Some(_)) => CredHubAuth::Certificate,
+            (None, None, None) => {
+                return Err(crate::DoomsdayError::config(
+                    "CredHub requires client_id and client_secret, or client_cert",
+                ))
+            }
+            _ => {
+                return Err(crate::DoomsdayError::config(
+                    "CredHub client_id and client_secret must be given together",
+                ))
+            }
+        };
 
-        Self::new(
-            name,
-            base_url,
-            client_id.to_string(),
-            client_secret.to_string(),
-        )
+        let mut builder = tls_client_builder(
+            "CredHub",
+            config.ca_cert.as_deref(),
+            config.client_cert.as_deref(),
+            config.client_key.as_deref(),
+        )?
+        .timeout(request_timeout);
+
+        if config.skip_verify {
+            tracing::warn!(
+                "CredHub accessor '{}': TLS certificate verification is disabled",
+                name
+            );
+            builder = builder.danger_accept_invalid_certs(true);
+        }
+        let client = builder.build()?;
+
+        Ok(Self::new(name, base_url, auth, uaa_url, client).with_path(config.path.clone()))
     }
 
-    async fn ensure_authenticated(&mut self) -> crate::Result<()> {
-        if self.access_token.is_some() {
-            return Ok(());
-        }
+    /// Limits listing to the credentials under `path`.
+    pub fn with_path(mut self, path: Option<String>) -> Self {
+        self.path = path.map(|path| format!("/{}", path.trim_matches('/')));
+        self
+    }
+
+    /// Requests a new access token and returns when it should be renewed.
+    async fn authenticate(&self) -> crate::Result<Duration> {
+        let CredHubAuth::ClientCredentials {
+            client_id,
+            client_secret,
+        } = &self.auth
+        else {
+            return Err(crate::DoomsdayError::internal(
+                "CredHub client certificate authentication has no access token",
+            ));
+        };
+        tracing::debug!("CredHub accessor '{}': requesting access token", self.name);
 
         let token_url = format!(
             "{}/oauth/token",
-            self.base_url.as_str().trim_end_matches('/')
+            self.uaa_url().await?.as_str().trim_end_matches('/')
         );
 
         let token_request = CredHubTokenRequest {
             grant_type: "client_credentials".to_string(),
-            client_id: self.client_id.clone(),
-            client_secret: self.client_secret.clone(),
+            client_id: client_id.clone(),
+            client_secret: client_secret.clone(),
         };
 
         let response = self
             .client
             .post(&token_url)
-            .json(&token_request)
+            .form(&token_request)
             .send()
             .await?;
 
         if !response.status().is_success() {
-            return Err(crate::DoomsdayError::auth(
-                "Failed to authenticate with CredHub",
-            ));
+            return Err(crate::DoomsdayError::auth(format!(
+                "Failed to authenticate with CredHub's UAA at {}: {}",
+                token_url,
+                response.status()
+            )));
         }
 
         let token_response: CredHubTokenResponse = response.json().await?;
-        self.access_token = Some(token_response.access_token);
+        Ok(self
+            .access_token
+            .set(
+                token_response.access_token,
+                None,
+                Duration::from_secs(token_response.expires_in),
+            )
+            .await)
+    }
+
+    /// The UAA issuing tokens. When it cannot be discovered, logging in
+    /// fails rather than sending the client's secret anywhere else, and
+    /// discovery is tried again on the next login.
+    async fn uaa_url(&self) -> crate::Result<Url> {
+        self.uaa_url
+            .get_or_try_init(|| self.discover_uaa_url())
+            .await
+            .cloned()
+            .map_err(|e| {
+                crate::DoomsdayError::auth(format!(
+                    "Could not discover CredHub's UAA, set uaa_url: {}",
+                    e
+                ))
+            })
+    }
+
+    /// Reads the UAA URL from CredHub's `/info`, which needs no token.
+    async fn discover_uaa_url(&self) -> crate::Result<Url> {
+        let url = format!("{}/info", self.base_url.as_str().trim_end_matches('/'));
+        let response = self.client.get(&url).send().await?;
+        if !response.status().is_success() {
+            return Err(crate::DoomsdayError::backend(format!(
+                "CredHub info request failed with status: {}",
+                response.status()
+            )));
+        }
 
-        Ok(())
+        let info: CredHubInfoResponse = response.json().await?;
+        let uaa_url = Url::parse(&info.auth_server.url).map_err(|e| {
+            crate::DoomsdayError::backend(format!("CredHub reported an invalid UAA URL: {}", e))
+        })?;
+        tracing::info!("CredHub accessor '{}': using UAA at {}", self.name, uaa_url);
+        Ok(uaa_url)
     }
 
-    async fn get_auth_header(&mut self) -> crate::Result<String> {
-        self.ensure_authenticated().await?;
-        Ok(format!("Bearer {}", self.access_token.as_ref().unwrap()))
+    /// The cached access token, requesting a new one when it is due.
+    async fn access_token(&self) -> crate::Result<String> {
+        let _login = self.login_lock.lock().await;
+        if let Some(token) = self.access_token.get().await {
+            return Ok(token);
+        }
+        self.authenticate().await?;
+        self.access_token
+            .get()
+            .await
+            .ok_or_else(|| crate::DoomsdayError::auth("CredHub issued an already expired token"))
+    }
+
+    /// Sends a request with the access token, unless the client certificate
+    /// authenticates it. When CredHub answers 401, as it does once a token
+    /// has expired or been revoked early, a new token is requested and the
+    /// request sent once more.
+    async fn send(&self, request: RequestBuilder) -> crate::Result<Response> {
+        if let CredHubAuth::Certificate = self.auth {
+            return Ok(request.send().await?);
+        }
+
+        let retry = request.try_clone();
+        let token = self.access_token().await?;
+        let response = request.bearer_auth(&token).send().await?;
+        if response.status() != StatusCode::UNAUTHORIZED {
+            return Ok(response);
+        }
+        let Some(retry) = retry else {
+            return Ok(response);
+        };
+
+        {
+            let _login = self.login_lock.lock().await;
+            // Unless a concurrent request has already replaced it
+            if self
+                .access_token
+                .get()
+                .await
+                .is_none_or(|current| current == token)
+            {
+                tracing::warn!(
+                    "CredHub accessor '{}': access token rejected, requesting a new one",
+                    self.name
+                );
+                self.authenticate().await?;
+            }
+        }
+        Ok(retry.bearer_auth(self.access_token().await?).send().await?)
     }
 }
 
 #[async_trait]
 impl Accessor for CredHubAccessor {
     async fn list(&self) -> crate::Result<PathList> {
-        let mut accessor = self.clone();
-        let auth_header = accessor.get_auth_header().await?;
-
-        let url = format!(
+        let mut url = Url::parse(&format!(
             "{}/api/v1/credentials",
             self.base_url.as_str().trim_end_matches('/')
-        );
-
-        let response = self
-            .client
-            .get(&url)
-            .header("Authorization", auth_header)
-            .send()
-            .await?;
-
-        if !response.status().is_success() {
-            return Err(crate::DoomsdayError::backend(
-                "Failed to list credentials from CredHub",
-            ));
+        ))
+        .map_err(|e| crate::DoomsdayError::internal(format!("Invalid CredHub URL: {}", e)))?;
+        if let Some(path) = &self.path {
+            url.query_pairs_mut().append_pair("path", path);
         }
 
-        let credentials_response: CredHubCredentialsResponse = response.json().await?;
+        let mut certificate_paths = Vec::new();
+        let mut fetched = HashSet::new();
+        loop {
+            let response = self.send(self.client.get(url.clone())).await?;
+
+            if !response.status().is_success() {
+                return Err(crate::DoomsdayError::backend(format!(
+                    "Failed to list credentials from CredHub: {}",
+                    response.status()
+                )));
+            }
+
+            let next = next_page(&url, response.headers());
+            fetched.insert(url);
+
+            let credentials_response: CredHubCredentialsResponse = response.json().await?;
+            certificate_paths.extend(
+                credentials_response
+                    .credentials
+                    .into_iter()
+                    // Credentials of unknown type are sorted out by `get`
+                    .filter(|cred| {
+                        cred.credential_type
+                            .as_deref()
+                            .is_none_or(|t| t == "certificate")
+                    })
+                    .map(|cred| cred.name),
+            );
+
+            match next {
+                Some(next) if fetched.contains(&next) => {
+                    tracing::warn!(
+                        "CredHub accessor '{}': listing links back to {}, stopping",
+                        self.name,
+                        next
+                    );
+                    break;
+                }
+                Some(_) if fetched.len() >= MAX_LIST_PAGES => {
+                    return Err(crate::DoomsdayError::backend(format!(
+                        "CredHub listing did not end after {} pages",
+                        MAX_LIST_PAGES
+                    )));
+                }
+                Some(next) => url = next,
+                None => break,
+            }
+        }
 
-        let certificate_paths: Vec<String> = credentials_response
-            .credentials
-            .into_iter()
-            .filter(|cred| cred.credential_type == "certificate")
-            .map(|cred| cred.name)
-            .collect();
+        // Pages may overlap when credentials are added while listing
+        certificate_paths.sort();
+        certificate_paths.dedup();
 
         Ok(certificate_paths)
     }
 
     async fn get(&self, path: &str) -> crate::Result<Option<CertificateData>> {
-        let mut accessor = self.clone();
-        let auth_header = accessor.get_auth_header().await?;
-
         let url = format!(
             "{}/api/v1/credentials?name={}",
             self.base_url.as_str().trim_end_matches('/'),
             urlencoding::encode(path)
         );
 
-        let response = self
-            .client
-            .get(&url)
-            .header("Authorization", auth_header)
-            .send()
-            .await?;
+        let response = self.send(self.client.get(&url)).await?;
 
-        if !response.status().is_success() {
+        if response.status() == StatusCode::NOT_FOUND {
             return Ok(None);
         }
+        // Anything else must not read as the credential being gone
+        if !response.status().is_success() {
+            return Err(crate::DoomsdayError::backend(format!(
+                "reading {} from CredHub failed with status: {}",
+                path,
+                response.status()
+            )));
+        }
 
         let value_response: CredHubValueResponse = response.json().await?;
 
@@ -205,27 +401,280 @@ impl Accessor for CredHubAccessor {
             return Ok(None);
         }
 
-        let cert_pem = value_response
-            .value
-            .get("certificate")
-            .and_then(|v| v.as_str());
-
-        if let Some(pem_data) = cert_pem {
-            let (_, pem) = parse_x509_pem(pem_data.as_bytes())
-                .map_err(|e| crate::DoomsdayError::x509(format!("Failed to parse PEM: {}", e)))?;
-
-            let (_, cert) = parse_x509_certificate(&pem.contents).map_err(|e| {
-                crate::DoomsdayError::x509(format!("Failed to parse certificate: {}", e))
-            })?;
+        let field = |name: &str| {
+            value_response
+                .value
+                .get(name)
+                .and_then(|v| v.as_str())
+                .filter(|pem| !pem.trim().is_empty())
+        };
+        let (pem_data, ca) = match (field("certificate"), field("ca")) {
+            (Some(certificate), ca) => (certificate, ca),
+            // CAs trusted but not issued by CredHub are stored with only `ca`
+            (None, Some(ca)) => (ca, None),
+            (None, None) => return Ok(None),
+        };
 
-            let cert_data = CertificateData::from_x509(&cert, pem_data)?;
-            Ok(Some(cert_data))
-        } else {
-            Ok(None)
+        // `certificate` may carry its intermediates after the leaf, and `ca`
+        // the issuer or a whole bundle; both end up in the chain, so each
+        // CA's own expiry is tracked
+        let mut cert_data = CertificateData::from_pem_bundle(pem_data)?;
+        if let Some(ca) = ca {
+            if let Err(e) = cert_data.extend_chain_from_pem(ca) {
+                tracing::warn!("Ignoring unparsable CA for {}: {}", path, e);
+            }
         }
+        Ok(Some(cert_data))
     }
 
     fn name(&self) -> &str {
         &self.name
     }
+
+    fn renews_auth(&self) -> bool {
+        matches!(self.auth, CredHubAuth::ClientCredentials { .. })
+    }
+
+    async fn renew_auth(&self) -> crate::Result<Option<Duration>> {
+        if let CredHubAuth::Certificate = self.auth {
+            return Ok(None);
+        }
+        // Client credentials tokens cannot be refreshed, so request a new one
+        let delay = {
+            let _login = self.login_lock.lock().await;
+            self.authenticate().await?
+        };
+        tracing::info!(
+            "CredHub accessor '{}': access token renewed, next renewal in {:?}",
+            self.name,
+            delay
+        );
+        Ok(Some(delay))
+    }
+}
+
+/// The page after `url`, from a `Link: <...>; rel="next"` header, as sent
+/// when a large listing is split into pages.
+fn next_page(url: &Url, headers: &HeaderMap) -> Option<Url> {
+    headers
+        .get_all(LINK)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .find_map(|link| {
+            let (target, params) = link.split_once(';')?;
+            let is_next = params.split(';').any(|param| {
+                param
+                    .trim()
+                    .strip_prefix("rel=")
+                    .is_some_and(|rel| rel.trim_matches('"').split(' ').any(|r| r == "next"))
+            });
+            if !is_next {
+                return None;
+            }
+            let target = target.trim().strip_prefix('<')?.strip_suffix('>')?;
+            url.join(target).ok()
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::extract::Query;
+    use axum::http::HeaderMap as RequestHeaders;
+    use axum::routing::{get, post};
+    use axum::{Json, Router};
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    /// A CredHub and its UAA that accept only the last token issued.
+    #[derive(Default)]
+    struct FakeCredHub {
+        issued: AtomicU32,
+        valid: std::sync::Mutex<Option<String>>,
+    }
+
+    /// Serves the fake, advertising its UAA in `/info` unless `info` is
+    /// false.
+    async fn serve(info: bool) -> (Url, Arc<FakeCredHub>) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = Url::parse(&format!("http://{}", listener.local_addr().unwrap())).unwrap();
+        let fake = Arc::new(FakeCredHub::default());
+
+        let issue = {
+            let fake = fake.clone();
+            move || {
+                let fake = fake.clone();
+                async move {
+                    let token = format!("token-{}", fake.issued.fetch_add(1, Ordering::SeqCst) + 1);
+                    *fake.valid.lock().unwrap() = Some(token.clone());
+                    Json(serde_json::json!({
+                        "access_token": token,
+                        "token_type": "bearer",
+                        "expires_in": 3600,
+                    }))
+                }
+            }
+        };
+        let credentials = {
+            let fake = fake.clone();
+            move |headers: RequestHeaders| {
+                let fake = fake.clone();
+                async move {
+                    let valid = fake.valid.lock().unwrap().clone();
+                    let sent = headers
+                        .get("authorization")
+                        .and_then(|value| value.to_str().ok())
+                        .and_then(|value| value.strip_prefix("Bearer "));
+                    if valid.is_none() || sent != valid.as_deref() {
+                        return Err(axum::http::StatusCode::UNAUTHORIZED);
+                    }
+                    Ok(Json(serde_json::json!({
+                        "credentials": [{"name": "/certs/web", "type": "certificate"}]
+                    })))
+                }
+            }
+        };
+
+        let mut router = Router::new()
+            .route("/uaa/oauth/token", post(issue.clone()))
+            // Where a login would land if discovery fell back to CredHub
+            .route("/oauth/token", post(issue))
+            .route("/api/v1/credentials", get(credentials));
+        if info {
+            let uaa_url = format!("{}uaa", base_url);
+            router =
+                router.route(
+                    "/info",
+                    get(move || async move {
+                        Json(serde_json::json!({"auth-server": {"url": uaa_url}}))
+                    }),
+                );
+        }
+        tokio::spawn(async move { axum::serve(listener, router).await });
+        (base_url, fake)
+    }
+
+    fn accessor(base_url: Url) -> CredHubAccessor {
+        CredHubAccessor::new(
+            "credhub".to_string(),
+            base_url,
+            CredHubAuth::ClientCredentials {
+                client_id: "doomsday".to_string(),
+                client_secret: "secret".to_string(),
+            },
+            None,
+            Client::new(),
+        )
+    }
+
+    #[test]
+    fn test_next_page() {
+        let url = Url::parse("https://credhub:8844/api/v1/credentials?path=%2Fteam").unwrap();
+        let next = |links: &[&str]| {
+            let mut headers = HeaderMap::new();
+            for link in links {
+                headers.append(LINK, link.parse().unwrap());
+            }
+            next_page(&url, &headers).map(String::from)
+        };
+
+        assert_eq!(
+            next(&[
+                r#"</api/v1/credentials?page=1>; rel="prev", </api/v1/credentials?page=3>; rel="next", </x>; rel="last""#
+            ]),
+            Some("https://credhub:8844/api/v1/credentials?page=3".to_string())
+        );
+        // Across several headers, absolute, and among other relations
+        assert_eq!(
+            next(&[
+                r#"</first>; rel="first""#,
+                r#"<https://proxy:8443/page/2>; title="more"; rel="last next""#
+            ]),
+            Some("https://proxy:8443/page/2".to_string())
+        );
+        assert_eq!(
+            next(&["<page2>; rel=next"]),
+            Some("https://credhub:8844/api/v1/page2".to_string())
+        );
+
+        assert_eq!(next(&[]), None);
+        assert_eq!(next(&[r#"</x>; rel="last""#]), None);
+        assert_eq!(next(&[r#"/no-brackets; rel="next""#]), None);
+        assert_eq!(next(&["<page2>"]), None);
+        assert_eq!(next(&["garbage"]), None);
+    }
+
+    #[tokio::test]
+    async fn test_list_follows_pages_of_a_subtree() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = Url::parse(&format!("http://{}", listener.local_addr().unwrap())).unwrap();
+        let last_page = format!("{}api/v1/credentials?path=%2Fteam&page=1", base_url);
+        let credentials = move |Query(query): Query<std::collections::HashMap<String, String>>| {
+            let last_page = last_page.clone();
+            async move {
+                if query.get("path").map(String::as_str) != Some("/team") {
+                    return Err(axum::http::StatusCode::BAD_REQUEST);
+                }
+                // The last page links back to itself, which must not loop
+                let (names, next) = match query.get("page").map(String::as_str) {
+                    None => (
+                        vec!["/team/b", "/team/a"],
+                        "</api/v1/credentials?path=%2Fteam&page=1>".to_string(),
+                    ),
+                    _ => (vec!["/team/c", "/team/a"], format!("<{}>", last_page)),
+                };
+                let credentials: Vec<_> = names
+                    .into_iter()
+                    .map(|name| serde_json::json!({"name": name}))
+                    .collect();
+                Ok((
+                    [("link", format!("{}; rel=\"next\"", next))],
+                    Json(serde_json::json!({ "credentials": credentials })),
+                ))
+            }
+        };
+        let router = Router::new().route("/api/v1/credentials", get(credentials));
+        tokio::spawn(async move { axum::serve(listener, router).await });
+
+        let credhub = CredHubAccessor::new(
+            "credhub".to_string(),
+            base_url,
+            CredHubAuth::Certificate,
+            None,
+            Client::new(),
+        )
+        .with_path(Some("team/".to_string()));
+        assert_eq!(
+            credhub.list().await.unwrap(),
+            vec!["/team/a", "/team/b", "/team/c"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_rejected_token_is_replaced() {
+        let (base_url, fake) = serve(true).await;
+        let credhub = accessor(base_url);
+
+        assert_eq!(credhub.list().await.unwrap(), vec!["/certs/web"]);
+        assert_eq!(fake.issued.load(Ordering::SeqCst), 1);
+
+        // Revoked before it is due for renewal
+        *fake.valid.lock().unwrap() = None;
+        assert_eq!(credhub.access_token.get().await.as_deref(), Some("token-1"));
+        assert_eq!(credhub.list().await.unwrap(), vec!["/certs/web"]);
+        assert_eq!(fake.issued.load(Ordering::SeqCst), 2);
+        assert_eq!(credhub.access_token.get().await.as_deref(), Some("token-2"));
+    }
+
+    #[tokio::test]
+    async fn test_undiscovered_uaa_is_an_error() {
+        let (base_url, fake) = serve(false).await;
+        let credhub = accessor(base_url);
+
+        let error = credhub.list().await.unwrap_err().to_string();
+        assert!(error.contains("set uaa_url"), "{}", error);
+        // The client secret was not sent to CredHub itself
+        assert_eq!(fake.issued.load(Ordering::SeqCst), 0);
+        assert!(credhub.access_token.get().await.is_none());
+    }
 }
@@ -1,11 +1,11 @@
-use crate::storage::Accessor;
+use crate::config::OpsMgrBackendConfig;
+use crate::storage::{Accessor, TokenCache};
 use crate::types::{CertificateData, PathList};
 use async_trait::async_trait;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::time::Duration;
 use url::Url;
-use x509_parser::prelude::*;
 
 #[derive(Debug, Clone)]
 pub struct OpsMgrAccessor {
@@ -14,7 +14,7 @@ pub struct OpsMgrAccessor {
     base_url: Url,
     username: String,
     password: String,
-    access_token: Option<String>,
+    access_token: TokenCache,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -29,6 +29,8 @@ struct OpsMgrTokenResponse {
     access_token: String,
     token_type: String,
     expires_in: u64,
+    #[serde(default)]
+    refresh_token: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -67,9 +69,11 @@ impl OpsMgrAccessor {
         base_url: Url,
         username: String,
         password: String,
+        request_timeout: Duration,
     ) -> crate::Result<Self> {
         let client = Client::builder()
             .danger_accept_invalid_certs(true) // Ops Manager often uses self-signed certs
+            .timeout(request_timeout)
             .build()?;
 
         Ok(OpsMgrAccessor {
@@ -78,44 +82,31 @@ impl OpsMgrAccessor {
             base_url,
             username,
             password,
-            access_token: None,
+            access_token: TokenCache::default(),
         })
     }
 
     pub fn from_config(
         name: String,
-        properties: &HashMap<String, serde_yaml::Value>,
+        config: &OpsMgrBackendConfig,
+        request_timeout: Duration,
     ) -> crate::Result<Self> {
-        let url = properties
-            .get("url")
-            .and_then(|v| v.as_str())
-            .ok_or_else(|| crate::DoomsdayError::config("Ops Manager URL is required"))?;
-
-        let username = properties
-            .get("username")
-            .and_then(|v| v.as_str())
-            .ok_or_else(|| crate::DoomsdayError::config("Ops Manager username is required"))?;
-
-        let password = properties
-            .get("password")
-            .and_then(|v| v.as_str())
-            .ok_or_else(|| crate::DoomsdayError::config("Ops Manager password is required"))?;
-
-        let base_url = Url::parse(url)
+        let base_url = Url::parse(&config.url)
             .map_err(|e| crate::DoomsdayError::config(format!("Invalid Ops Manager URL: {}", e)))?;
 
-        Self::new(name, base_url, username.to_string(), password.to_string())
+        Self::new(
+            name,
+            base_url,
+            config.username.clone(),
+            config.password.clone(),
+            request_timeout,
+        )
     }
 
-    async fn ensure_authenticated(&mut self) -> crate::Result<()> {
-        if self.access_token.is_some() {
-            return Ok(());
-        }
-
-        let token_url = format!(
-            "{}/uaa/oauth/token",
-            self.base_url.as_str().trim_end_matches('/')
-        );
+    /// Logs in with the configured credentials and returns when the new
+    /// token should be renewed.
+    async fn authenticate(&self) -> crate::Result<Duration> {
+        tracing::debug!("Ops Manager accessor '{}': logging in", self.name);
 
         let token_request = OpsMgrTokenRequest {
             grant_type: "password".to_string(),
@@ -125,11 +116,52 @@ impl OpsMgrAccessor {
 
         let response = self
             .client
-            .post(&token_url)
+            .post(self.token_url())
             .form(&token_request)
             .send()
             .await?;
 
+        self.store_token(response).await
+    }
+
+    /// Exchanges the refresh token for a new access token, falling back to a
+    /// full login if there is none or UAA rejects it.
+    async fn refresh(&self) -> crate::Result<Duration> {
+        let Some(refresh_token) = self.access_token.refresh_token().await else {
+            return self.authenticate().await;
+        };
+
+        let response = self
+            .client
+            .post(self.token_url())
+            .form(&[
+                ("grant_type", "refresh_token"),
+                ("refresh_token", refresh_token.as_str()),
+            ])
+            .send()
+            .await?;
+
+        match self.store_token(response).await {
+            Ok(delay) => Ok(delay),
+            Err(e) => {
+                tracing::warn!(
+                    "Ops Manager accessor '{}': token refresh failed ({}), logging in again",
+                    self.name,
+                    e
+                );
+                self.authenticate().await
+            }
+        }
+    }
+
+    fn token_url(&self) -> String {
+        format!(
+            "{}/uaa/oauth/token",
+            self.base_url.as_str().trim_end_matches('/')
+        )
+    }
+
+    async fn store_token(&self, response: reqwest::Response) -> crate::Result<Duration> {
         if !response.status().is_success() {
             return Err(crate::DoomsdayError::auth(
                 "Failed to authenticate with Ops Manager",
@@ -137,17 +169,30 @@ impl OpsMgrAccessor {
         }
 
         let token_response: OpsMgrTokenResponse = response.json().await?;
-        self.access_token = Some(token_response.access_token);
-
-        Ok(())
+        Ok(self
+            .access_token
+            .set(
+                token_response.access_token,
+                token_response.refresh_token,
+                Duration::from_secs(token_response.expires_in),
+            )
+            .await)
     }
 
-    async fn get_auth_header(&mut self) -> crate::Result<String> {
-        self.ensure_authenticated().await?;
-        Ok(format!("Bearer {}", self.access_token.as_ref().unwrap()))
+    async fn get_auth_header(&self) -> crate::Result<String> {
+        let token = match self.access_token.get().await {
+            Some(token) => token,
+            None => {
+                self.refresh().await?;
+                self.access_token.get().await.ok_or_else(|| {
+                    crate::DoomsdayError::auth("Ops Manager issued an already expired token")
+                })?
+            }
+        };
+        Ok(format!("Bearer {}", token))
     }
 
-    async fn get_deployments(&mut self) -> crate::Result<Vec<OpsMgrDeployment>> {
+    async fn get_deployments(&self) -> crate::Result<Vec<OpsMgrDeployment>> {
         let auth_header = self.get_auth_header().await?;
 
         let url = format!(
@@ -176,11 +221,10 @@ impl OpsMgrAccessor {
 #[async_trait]
 impl Accessor for OpsMgrAccessor {
     async fn list(&self) -> crate::Result<PathList> {
-        let mut accessor = self.clone();
-        let deployments = accessor.get_deployments().await?;
+        let deployments = self.get_deployments().await?;
 
         let mut all_paths = Vec::new();
-        let auth_header = accessor.get_auth_header().await?;
+        let auth_header = self.get_auth_header().await?;
 
         for deployment in deployments {
             let url = format!(
@@ -210,9 +254,8 @@ impl Accessor for OpsMgrAccessor {
     }
 
     async fn get(&self, path: &str) -> crate::Result<Option<CertificateData>> {
-        let mut accessor = self.clone();
-        let deployments = accessor.get_deployments().await?;
-        let auth_header = accessor.get_auth_header().await?;
+        let deployments = self.get_deployments().await?;
+        let auth_header = self.get_auth_header().await?;
 
         let parts: Vec<&str> = path.splitn(2, '/').collect();
         if parts.len() != 2 {
@@ -247,17 +290,7 @@ impl Accessor for OpsMgrAccessor {
                     .find(|c| c.property_reference == property_reference);
 
                 if let Some(cert) = cert {
-                    let (_, pem) =
-                        parse_x509_pem(cert.certificate.cert_pem.as_bytes()).map_err(|e| {
-                            crate::DoomsdayError::x509(format!("Failed to parse PEM: {}", e))
-                        })?;
-
-                    let (_, cert_obj) = parse_x509_certificate(&pem.contents).map_err(|e| {
-                        crate::DoomsdayError::x509(format!("Failed to parse certificate: {}", e))
-                    })?;
-
-                    let cert_data =
-                        CertificateData::from_x509(&cert_obj, &cert.certificate.cert_pem)?;
+                    let cert_data = CertificateData::from_pem_bundle(&cert.certificate.cert_pem)?;
                     return Ok(Some(cert_data));
                 }
             }
@@ -269,4 +302,18 @@ impl Accessor for OpsMgrAccessor {
     fn name(&self) -> &str {
         &self.name
     }
+
+    fn renews_auth(&self) -> bool {
+        true
+    }
+
+    async fn renew_auth(&self) -> crate::Result<Option<Duration>> {
+        let delay = self.refresh().await?;
+        tracing::info!(
+            "Ops Manager accessor '{}': access token renewed, next renewal in {:?}",
+            self.name,
+            delay
+        );
+        Ok(Some(delay))
+    }
 }
@@ -1,78 +1,90 @@
+use crate::config::{TlsClientBackendConfig, TlsTargetConfig};
 use crate::storage::Accessor;
 use crate::types::{CertificateData, PathList};
 use async_trait::async_trait;
-use base64::prelude::*;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::net::SocketAddr;
 use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
 use tokio::net::TcpStream;
+use tokio::sync::OnceCell;
 use tokio_rustls::{rustls, TlsConnector};
-use x509_parser::prelude::*;
+
+type ScanResult = Result<Option<CertificateData>, String>;
+type ScanResults = HashMap<(SocketAddr, String, Option<StartTls>), Arc<OnceCell<ScanResult>>>;
 
 #[derive(Debug, Clone)]
 pub struct TlsClientAccessor {
     name: String,
-    targets: Vec<TlsTarget>,
+    targets: Vec<TlsTargetConfig>,
+    /// How long connecting, any STARTTLS exchange and the handshake may take.
+    timeout: Duration,
+    /// Handshake results of a refresh run, keyed by resolved address and SNI
+    /// name, so targets that point at the same endpoint share a single
+    /// handshake. Each run started with `start_run` has its own, so
+    /// overlapping runs do not clobber each other; outside a run every `get`
+    /// handshakes.
+    scan_results: Option<Arc<Mutex<ScanResults>>>,
 }
 
-#[derive(Debug, Clone)]
-struct TlsTarget {
-    host: String,
-    port: u16,
-    server_name: Option<String>,
+/// Plaintext protocols that are upgraded to TLS before the handshake, for
+/// endpoints such as mail servers on port 25 or 587.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StartTls {
+    Smtp,
+}
+
+impl FromStr for StartTls {
+    type Err = crate::DoomsdayError;
+
+    fn from_str(s: &str) -> crate::Result<Self> {
+        match s.to_lowercase().as_str() {
+            "smtp" => Ok(StartTls::Smtp),
+            other => Err(crate::DoomsdayError::invalid_input(format!(
+                "Unsupported STARTTLS protocol: {}",
+                other
+            ))),
+        }
+    }
 }
 
 impl TlsClientAccessor {
-    pub fn new(name: String, targets: Vec<TlsTarget>) -> Self {
-        TlsClientAccessor { name, targets }
+    pub fn new(name: String, targets: Vec<TlsTargetConfig>, timeout: Duration) -> Self {
+        TlsClientAccessor {
+            name,
+            targets,
+            timeout,
+            scan_results: None,
+        }
     }
 
     pub fn from_config(
         name: String,
-        properties: &HashMap<String, serde_yaml::Value>,
+        config: &TlsClientBackendConfig,
+        request_timeout: Duration,
     ) -> crate::Result<Self> {
-        let targets_config = properties
-            .get("targets")
-            .and_then(|v| v.as_sequence())
-            .ok_or_else(|| crate::DoomsdayError::config("TLS client targets are required"))?;
-
-        let mut targets = Vec::new();
-
-        for target_config in targets_config {
-            let target_map = target_config
-                .as_mapping()
-                .ok_or_else(|| crate::DoomsdayError::config("Each target must be a mapping"))?;
-
-            let host = target_map
-                .get(&serde_yaml::Value::String("host".to_string()))
-                .and_then(|v| v.as_str())
-                .ok_or_else(|| crate::DoomsdayError::config("Target host is required"))?;
-
-            let port = target_map
-                .get(&serde_yaml::Value::String("port".to_string()))
-                .and_then(|v| v.as_u64())
-                .unwrap_or(443) as u16;
-
-            let server_name = target_map
-                .get(&serde_yaml::Value::String("server_name".to_string()))
-                .and_then(|v| v.as_str())
-                .map(|s| s.to_string());
-
-            targets.push(TlsTarget {
-                host: host.to_string(),
-                port,
-                server_name,
-            });
-        }
-
-        Ok(Self::new(name, targets))
+        Ok(Self::new(name, config.targets.clone(), request_timeout))
     }
 
-    async fn get_certificate_from_target(
-        &self,
-        target: &TlsTarget,
+    /// Connects to a single endpoint outside of any backend, with the same
+    /// handshake a configured target gets. Used by `doomsday probe`.
+    pub async fn probe(
+        host: &str,
+        port: u16,
+        server_name: Option<&str>,
+        starttls: Option<StartTls>,
+        timeout: Duration,
     ) -> crate::Result<Option<CertificateData>> {
-        let addr = format!("{}:{}", target.host, target.port);
+        let socket_addr = Self::resolve(host, port)?;
+        Self::handshake(socket_addr, server_name.unwrap_or(host), starttls, timeout).await
+    }
+
+    fn resolve(host: &str, port: u16) -> crate::Result<SocketAddr> {
+        let addr = format!("{}:{}", host, port);
         let socket_addr = SocketAddr::from_str(&addr).or_else(|_| {
             // Try to resolve hostname
             std::net::ToSocketAddrs::to_socket_addrs(&addr)?
@@ -81,8 +93,83 @@ impl TlsClientAccessor {
                     std::io::Error::new(std::io::ErrorKind::NotFound, "Could not resolve address")
                 })
         })?;
+        Ok(socket_addr)
+    }
+
+    async fn get_certificate_from_target(
+        &self,
+        target: &TlsTargetConfig,
+    ) -> crate::Result<Option<CertificateData>> {
+        let socket_addr = Self::resolve(&target.host, target.port)?;
+
+        let server_name = target
+            .server_name
+            .clone()
+            .unwrap_or_else(|| target.host.clone());
+
+        let Some(scan_results) = &self.scan_results else {
+            return Self::handshake(socket_addr, &server_name, target.starttls, self.timeout).await;
+        };
+        let cell = scan_results
+            .lock()
+            .unwrap()
+            .entry((socket_addr, server_name.clone(), target.starttls))
+            .or_default()
+            .clone();
+
+        let mut performed = false;
+        let result = cell
+            .get_or_init(|| async {
+                performed = true;
+                Self::handshake(socket_addr, &server_name, target.starttls, self.timeout)
+                    .await
+                    .map_err(|e| e.to_string())
+            })
+            .await;
+
+        if !performed {
+            tracing::debug!(
+                "TLS client '{}': reusing handshake result for {} ({})",
+                self.name,
+                socket_addr,
+                server_name
+            );
+        }
+
+        result.clone().map_err(crate::DoomsdayError::backend)
+    }
+
+    async fn handshake(
+        socket_addr: SocketAddr,
+        server_name: &str,
+        starttls: Option<StartTls>,
+        timeout: Duration,
+    ) -> crate::Result<Option<CertificateData>> {
+        tokio::time::timeout(
+            timeout,
+            Self::fetch_chain(socket_addr, server_name, starttls),
+        )
+        .await
+        .map_err(|_| {
+            std::io::Error::new(
+                std::io::ErrorKind::TimedOut,
+                format!(
+                    "handshake with {} timed out after {:?}",
+                    socket_addr, timeout
+                ),
+            )
+        })?
+    }
 
-        let stream = TcpStream::connect(socket_addr).await?;
+    async fn fetch_chain(
+        socket_addr: SocketAddr,
+        server_name: &str,
+        starttls: Option<StartTls>,
+    ) -> crate::Result<Option<CertificateData>> {
+        let mut stream = TcpStream::connect(socket_addr).await?;
+        if let Some(StartTls::Smtp) = starttls {
+            stream = Self::smtp_starttls(stream).await?;
+        }
 
         let mut root_store = rustls::RootCertStore::empty();
         root_store.add_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.iter().map(|ta| {
@@ -98,9 +185,8 @@ impl TlsClientAccessor {
             .with_root_certificates(root_store)
             .with_no_client_auth();
 
-        let connector = TlsConnector::from(std::sync::Arc::new(config));
+        let connector = TlsConnector::from(Arc::new(config));
 
-        let server_name = target.server_name.as_deref().unwrap_or(&target.host);
         let domain = rustls::ServerName::try_from(server_name)
             .map_err(|e| crate::DoomsdayError::internal(format!("Invalid server name: {}", e)))?;
 
@@ -115,34 +201,68 @@ impl TlsClientAccessor {
             return Ok(None);
         }
 
-        // Use the first certificate in the chain (the server certificate)
-        let cert_der = &peer_certificates[0];
-        let (_, cert) = parse_x509_certificate(cert_der.as_ref()).map_err(|e| {
-            crate::DoomsdayError::x509(format!("Failed to parse certificate: {}", e))
-        })?;
+        // The first certificate is the server's, the rest its chain
+        let mut cert_data = CertificateData::from_der(peer_certificates[0].as_ref())?;
+        for cert_der in &peer_certificates[1..] {
+            cert_data
+                .chain
+                .push(CertificateData::from_der(cert_der.as_ref())?);
+        }
 
-        // Convert DER to PEM for the certificate data
-        let pem_data = format!(
-            "-----BEGIN CERTIFICATE-----\n{}\n-----END CERTIFICATE-----",
-            base64::prelude::BASE64_STANDARD.encode(cert_der.as_ref())
-        );
+        Ok(Some(cert_data))
+    }
 
-        let path = format!("{}:{}", target.host, target.port);
-        let cert_data = CertificateData::from_x509(&cert, &pem_data)?;
+    /// Reads the greeting, sends EHLO and then STARTTLS, leaving the
+    /// connection ready for the TLS handshake.
+    async fn smtp_starttls(stream: TcpStream) -> crate::Result<TcpStream> {
+        let mut reader = BufReader::new(stream);
+        Self::smtp_reply(&mut reader, "220").await?;
+        reader.get_mut().write_all(b"EHLO doomsday\r\n").await?;
+        Self::smtp_reply(&mut reader, "250").await?;
+        reader.get_mut().write_all(b"STARTTLS\r\n").await?;
+        Self::smtp_reply(&mut reader, "220").await?;
+        // The server says nothing more until the handshake, so nothing
+        // buffered is lost
+        Ok(reader.into_inner())
+    }
 
-        Ok(Some(cert_data))
+    /// Reads a possibly multi-line SMTP reply, failing unless it carries
+    /// the expected code.
+    async fn smtp_reply(reader: &mut BufReader<TcpStream>, expected: &str) -> crate::Result<()> {
+        loop {
+            let mut line = String::new();
+            if reader.read_line(&mut line).await? == 0 {
+                return Err(crate::DoomsdayError::backend(
+                    "SMTP server closed the connection before STARTTLS",
+                ));
+            }
+            if !line.starts_with(expected) {
+                return Err(crate::DoomsdayError::backend(format!(
+                    "SMTP server refused STARTTLS: {}",
+                    line.trim_end()
+                )));
+            }
+            // "250-..." continues the reply, "250 ..." ends it
+            if line.as_bytes().get(3) != Some(&b'-') {
+                return Ok(());
+            }
+        }
     }
 }
 
 #[async_trait]
 impl Accessor for TlsClientAccessor {
     async fn list(&self) -> crate::Result<PathList> {
-        let paths: Vec<String> = self
+        let mut paths: Vec<String> = self
             .targets
             .iter()
             .map(|target| format!("{}:{}", target.host, target.port))
             .collect();
 
+        // Duplicate config entries share a path and would be scanned twice
+        paths.sort();
+        paths.dedup();
+
         Ok(paths)
     }
 
@@ -162,4 +282,64 @@ impl Accessor for TlsClientAccessor {
     fn name(&self) -> &str {
         &self.name
     }
+
+    fn start_run(&self) -> Option<Arc<dyn Accessor>> {
+        Some(Arc::new(TlsClientAccessor {
+            scan_results: Some(Arc::new(Mutex::new(HashMap::new()))),
+            ..self.clone()
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tokio::net::TcpListener;
+
+    fn target(host: &str, port: u16) -> TlsTargetConfig {
+        TlsTargetConfig {
+            host: host.to_string(),
+            port,
+            server_name: Some("localhost".to_string()),
+            starttls: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_runs_share_handshakes_only_within_themselves() {
+        // Accepts and drops connections, so every handshake fails quickly
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let connections = Arc::new(AtomicUsize::new(0));
+        let accepted = connections.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                accepted.fetch_add(1, Ordering::SeqCst);
+                drop(stream);
+            }
+        });
+
+        let accessor = TlsClientAccessor::new(
+            "web".to_string(),
+            vec![target("localhost", port), target("127.0.0.1", port)],
+            Duration::from_secs(5),
+        );
+
+        let first = accessor.start_run().unwrap();
+        let paths = first.list().await.unwrap();
+        assert_eq!(paths.len(), 2);
+        assert!(first.get(&paths[0]).await.is_err());
+        assert!(first.get(&paths[1]).await.is_err());
+        assert_eq!(connections.load(Ordering::SeqCst), 1);
+
+        // A run started meanwhile, e.g. a dry run, neither reuses nor clears
+        // the first run's results
+        let second = accessor.start_run().unwrap();
+        second.list().await.unwrap();
+        assert!(first.get(&paths[1]).await.is_err());
+        assert_eq!(connections.load(Ordering::SeqCst), 1);
+        assert!(second.get(&paths[0]).await.is_err());
+        assert_eq!(connections.load(Ordering::SeqCst), 2);
+    }
 }
@@ -1,20 +1,208 @@
-use crate::storage::Accessor;
-use crate::types::{CertificateData, PathList};
+use crate::config::{
+    default_vault_mount_path, default_vault_secret_path, VaultAuthMethod, VaultBackendConfig,
+};
+use crate::scheduler::jitter;
+use crate::storage::{renewal_delay, tls_client_builder, Accessor, RateLimiter, TokenCache};
+use crate::types::{AuthStatus, CertificateData, ListFailure, PathList};
 use async_trait::async_trait;
-use reqwest::Client;
+use futures::stream::{FuturesUnordered, StreamExt};
+use reqwest::{Client, RequestBuilder, Response, StatusCode};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use serde_json::{Map, Value};
+use std::collections::{HashMap, VecDeque};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use tokio::sync::{Mutex, OnceCell};
 use url::Url;
-use x509_parser::prelude::*;
+
+/// How often a token file is checked while nothing else reads it, to keep
+/// the token's reported health current.
+const TOKEN_FILE_CHECK_INTERVAL: Duration = Duration::from_secs(300);
+
+/// Directories listed at once unless configured otherwise.
+const DEFAULT_LIST_CONCURRENCY: usize = 8;
+
+/// Wait before the first retry of a rate limited request, doubling with
+/// every retry up to `MAX_RETRY_BACKOFF`.
+const RETRY_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_RETRY_BACKOFF: Duration = Duration::from_secs(30);
+
+/// Highest `max_retries` accepted, some five minutes of retrying at most.
+const MAX_RETRIES: u32 = 10;
 
 #[derive(Debug, Clone)]
 pub struct VaultAccessor {
     name: String,
     client: Client,
     base_url: Url,
+    auth: VaultAuth,
+    /// The token from the last AppRole login. Kept as the refresh token
+    /// too while Vault allows renewing it.
+    approle_token: TokenCache,
+    /// Held while logging in, so concurrent requests share one login
+    /// rather than each using up the secret ID.
+    login_lock: Arc<Mutex<()>>,
+    /// The token last read from the token file.
+    file_token: Arc<std::sync::Mutex<Option<FileToken>>>,
+    auth_status: Arc<std::sync::Mutex<AuthStatus>>,
+    mounts: Vec<VaultMount>,
+    /// Whether paths start with the mount they are in, as they do when
+    /// `mounts` is configured.
+    qualify_paths: bool,
+    /// The mounts matched by the last listing, to read their paths back.
+    resolved_mounts: Arc<std::sync::Mutex<Vec<String>>>,
+    /// Each mount's KV version, configured or detected on first use and
+    /// shared by every clone.
+    kv_versions: Arc<std::sync::Mutex<HashMap<String, Arc<OnceCell<KvVersion>>>>>,
+    /// Directories listed at once.
+    list_concurrency: usize,
+    max_depth: Option<usize>,
+    /// Directories the last listing could not read.
+    list_failures: Arc<std::sync::Mutex<Vec<ListFailure>>>,
+    rate_limit: Option<RateLimiter>,
+    /// Times a request answered with 429 or 503 is retried.
+    max_retries: u32,
+}
+
+/// A KV mount the accessor lists.
+#[derive(Debug, Clone)]
+pub struct VaultMount {
+    /// The mount's path, or a glob over the KV mounts the token can see.
+    pub path: String,
+    /// Where under the mount to start listing.
+    pub secret_path: String,
+    pub kv_version: Option<KvVersion>,
+}
+
+impl VaultMount {
+    fn is_glob(&self) -> bool {
+        self.path.contains(['*', '?', '['])
+    }
+}
+
+/// Where the accessor's Vault token comes from.
+#[derive(Debug, Clone)]
+pub enum VaultAuth {
+    Token(String),
+    /// Reads the token from a file kept up to date by something else, such
+    /// as a Vault Agent sink.
+    TokenFile(PathBuf),
+    /// Logs in at startup, renews the token it is given, and logs in again
+    /// once the token can no longer be renewed.
+    AppRole {
+        mount: String,
+        role_id: String,
+        secret_id: SecretId,
+    },
+}
+
+#[derive(Debug)]
+struct FileToken {
     token: String,
-    mount_path: String,
-    secret_path: String,
+    modified: Option<SystemTime>,
+}
+
+#[derive(Debug, Clone)]
+pub enum SecretId {
+    Value(String),
+    File(PathBuf),
+}
+
+impl SecretId {
+    fn read(&self) -> crate::Result<String> {
+        match self {
+            SecretId::Value(secret_id) => Ok(secret_id.clone()),
+            SecretId::File(path) => std::fs::read_to_string(path)
+                .map(|secret_id| secret_id.trim().to_string())
+                .map_err(|e| {
+                    crate::DoomsdayError::config(format!(
+                        "Failed to read Vault secret ID from {}: {}",
+                        path.display(),
+                        e
+                    ))
+                }),
+        }
+    }
+}
+
+/// The wait a response's `Retry-After` header asks for, in seconds, kept
+/// within `MAX_RETRY_BACKOFF`.
+fn retry_after(response: &Response) -> Option<Duration> {
+    let seconds: u64 = response
+        .headers()
+        .get(reqwest::header::RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse()
+        .ok()?;
+    Some(Duration::from_secs(seconds).min(MAX_RETRY_BACKOFF))
+}
+
+/// The wait before retry number `retries`, before jitter.
+fn retry_backoff(retries: u32) -> Duration {
+    let exponent = retries.saturating_sub(1).min(31);
+    (RETRY_BACKOFF * (1u32 << exponent)).min(MAX_RETRY_BACKOFF)
+}
+
+fn parse_kv_version(version: Option<u8>) -> crate::Result<Option<KvVersion>> {
+    match version {
+        None => Ok(None),
+        Some(1) => Ok(Some(KvVersion::V1)),
+        Some(2) => Ok(Some(KvVersion::V2)),
+        Some(other) => Err(crate::DoomsdayError::config(format!(
+            "Invalid Vault kv_version: {} (expected 1 or 2)",
+            other
+        ))),
+    }
+}
+
+/// Fields holding a secret's certificate, or a PEM bundle of it and its
+/// chain, in order of preference.
+const CERTIFICATE_FIELDS: &[&str] = &["certificate", "cert", "crt", "tls.crt"];
+
+/// Fields holding the issuing CA or chain beside the certificate.
+const CHAIN_FIELDS: &[&str] = &["issuing_ca", "ca", "ca.crt", "ca_chain", "chain"];
+
+/// The certificate PEM of a secret and the object it was found in: the
+/// secret itself, or else the first nested object holding one, as tools
+/// writing e.g. `{"tls": {"crt": ...}}` do.
+fn find_certificate(fields: &Map<String, Value>) -> Option<(&Map<String, Value>, &str)> {
+    let pem = CERTIFICATE_FIELDS
+        .iter()
+        .find_map(|field| fields.get(*field).and_then(Value::as_str));
+    if let Some(pem) = pem {
+        return Some((fields, pem));
+    }
+    fields.values().find_map(|value| match value {
+        Value::Object(nested) => find_certificate(nested),
+        _ => None,
+    })
+}
+
+/// Versions of the KV secrets engine, which list and read secrets under
+/// different paths.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KvVersion {
+    V1,
+    V2,
+}
+
+impl KvVersion {
+    fn list_path(self, mount_path: &str, path: &str) -> String {
+        match self {
+            KvVersion::V1 => format!("{}/{}", mount_path, path),
+            KvVersion::V2 => format!("{}/metadata/{}", mount_path, path),
+        }
+    }
+
+    fn read_path(self, mount_path: &str, path: &str) -> String {
+        match self {
+            KvVersion::V1 => format!("{}/{}", mount_path, path),
+            KvVersion::V2 => format!("{}/data/{}", mount_path, path),
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -29,154 +217,1011 @@ struct VaultListData {
 
 #[derive(Debug, Serialize, Deserialize)]
 struct VaultSecretResponse {
-    data: HashMap<String, serde_json::Value>,
+    data: Map<String, Value>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct VaultMountResponse {
+    data: VaultMountData,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct VaultMountData {
+    #[serde(rename = "type", default)]
+    mount_type: String,
+    #[serde(default)]
+    options: Option<HashMap<String, String>>,
+}
+
+impl VaultMountData {
+    fn kv_version(&self) -> KvVersion {
+        match self
+            .options
+            .as_ref()
+            .and_then(|options| options.get("version"))
+        {
+            Some(version) if version == "2" => KvVersion::V2,
+            _ => KvVersion::V1,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct VaultMountsResponse {
+    data: VaultMountsData,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct VaultMountsData {
+    /// Secrets engines keyed by path, each ending in `/`.
+    #[serde(default)]
+    secret: HashMap<String, VaultMountData>,
+}
+
+impl VaultMountsData {
+    /// The KV mounts, without their trailing `/`, sorted by path.
+    fn kv_mounts(self) -> Vec<(String, KvVersion)> {
+        let mut kv_mounts: Vec<(String, KvVersion)> = self
+            .secret
+            .into_iter()
+            // `generic` is what KV v1 was called before Vault 0.8
+            .filter(|(_, mount)| mount.mount_type == "kv" || mount.mount_type == "generic")
+            .map(|(path, mount)| (path.trim_end_matches('/').to_string(), mount.kv_version()))
+            .collect();
+        kv_mounts.sort_by(|a, b| a.0.cmp(&b.0));
+        kv_mounts
+    }
+}
+
+/// The mounts among `visible` that the glob `pattern` matches.
+fn matching_mounts<'a>(
+    pattern: &str,
+    visible: &'a [(String, KvVersion)],
+) -> crate::Result<Vec<&'a (String, KvVersion)>> {
+    let pattern = glob::Pattern::new(pattern)
+        .map_err(|e| crate::DoomsdayError::config(format!("Invalid Vault mount pattern: {}", e)))?;
+    Ok(visible
+        .iter()
+        .filter(|(path, _)| pattern.matches(path))
+        .collect())
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct VaultTokenLookupResponse {
+    data: VaultTokenLookupData,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct VaultTokenLookupData {
+    /// Seconds until the token expires; 0 for tokens that never expire.
+    ttl: u64,
+    #[serde(default)]
+    renewable: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct VaultTokenRenewResponse {
+    auth: VaultTokenRenewAuth,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct VaultTokenRenewAuth {
+    lease_duration: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct VaultAppRoleLoginRequest {
+    role_id: String,
+    secret_id: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct VaultLoginResponse {
+    auth: VaultLoginAuth,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct VaultLoginAuth {
+    client_token: String,
+    /// Seconds the token is valid for; 0 for tokens that never expire.
+    lease_duration: u64,
+    #[serde(default)]
+    renewable: bool,
 }
 
 impl VaultAccessor {
     pub fn new(
         name: String,
         base_url: Url,
-        token: String,
-        mount_path: String,
-        secret_path: String,
+        auth: VaultAuth,
+        mounts: Vec<VaultMount>,
+        qualify_paths: bool,
+        client: Client,
     ) -> crate::Result<Self> {
-        let client = Client::builder()
-            .danger_accept_invalid_certs(false)
-            .build()?;
+        let fixed: Vec<&VaultMount> = mounts.iter().filter(|m| !m.is_glob()).collect();
+        let resolved_mounts = fixed.iter().map(|m| m.path.clone()).collect();
+        let kv_versions = fixed
+            .iter()
+            .map(|m| (m.path.clone(), Arc::new(OnceCell::new_with(m.kv_version))))
+            .collect();
 
         Ok(VaultAccessor {
             name,
             client,
             base_url,
-            token,
-            mount_path,
-            secret_path,
+            auth,
+            approle_token: TokenCache::default(),
+            login_lock: Arc::new(Mutex::new(())),
+            file_token: Arc::new(std::sync::Mutex::new(None)),
+            auth_status: Arc::new(std::sync::Mutex::new(AuthStatus::default())),
+            mounts,
+            qualify_paths,
+            resolved_mounts: Arc::new(std::sync::Mutex::new(resolved_mounts)),
+            kv_versions: Arc::new(std::sync::Mutex::new(kv_versions)),
+            list_concurrency: DEFAULT_LIST_CONCURRENCY,
+            max_depth: None,
+            list_failures: Arc::new(std::sync::Mutex::new(Vec::new())),
+            rate_limit: None,
+            max_retries: 0,
         })
     }
 
+    /// Sends at most `max_requests_per_second` requests, and retries those
+    /// Vault answers with 429 or 503 up to `max_retries` times.
+    pub fn with_request_limits(
+        mut self,
+        max_requests_per_second: Option<u32>,
+        max_retries: u32,
+    ) -> Self {
+        self.rate_limit = max_requests_per_second.map(RateLimiter::new);
+        self.max_retries = max_retries;
+        self
+    }
+
+    /// Lists up to `concurrency` directories at once, descending at most
+    /// `max_depth` levels below the secret path.
+    pub fn with_listing(mut self, concurrency: usize, max_depth: Option<usize>) -> Self {
+        self.list_concurrency = concurrency.max(1);
+        self.max_depth = max_depth;
+        self
+    }
+
     pub fn from_config(
         name: String,
-        properties: &HashMap<String, serde_yaml::Value>,
+        config: &VaultBackendConfig,
+        request_timeout: Duration,
     ) -> crate::Result<Self> {
         tracing::info!("Configuring Vault accessor: {}", name);
+        let base_url = Url::parse(&config.url)
+            .map_err(|e| crate::DoomsdayError::config(format!("Invalid Vault URL: {}", e)))?;
 
-        let url = properties
-            .get("url")
-            .and_then(|v| v.as_str())
-            .ok_or_else(|| crate::DoomsdayError::config("Vault URL is required"))?;
-
-        let token = properties
-            .get("token")
-            .and_then(|v| v.as_str())
-            .ok_or_else(|| crate::DoomsdayError::config("Vault token is required"))?;
-
-        let mount_path = properties
-            .get("mount_path")
-            .and_then(|v| v.as_str())
-            .unwrap_or("secret");
+        let mounts = if config.mounts.is_empty() {
+            vec![VaultMount {
+                path: config
+                    .mount_path
+                    .clone()
+                    .unwrap_or_else(default_vault_mount_path),
+                secret_path: config
+                    .secret_path
+                    .clone()
+                    .unwrap_or_else(default_vault_secret_path),
+                kv_version: parse_kv_version(config.kv_version)?,
+            }]
+        } else if config.mount_path.is_some()
+            || config.secret_path.is_some()
+            || config.kv_version.is_some()
+        {
+            return Err(crate::DoomsdayError::config(
+                "Vault mount_path, secret_path and kv_version go in each of mounts when mounts is set",
+            ));
+        } else {
+            config
+                .mounts
+                .iter()
+                .map(|mount| {
+                    glob::Pattern::new(&mount.mount_path).map_err(|e| {
+                        crate::DoomsdayError::config(format!(
+                            "Invalid Vault mount_path pattern {}: {}",
+                            mount.mount_path, e
+                        ))
+                    })?;
+                    Ok(VaultMount {
+                        path: mount.mount_path.trim_matches('/').to_string(),
+                        secret_path: mount.secret_path.clone(),
+                        kv_version: parse_kv_version(mount.kv_version)?,
+                    })
+                })
+                .collect::<crate::Result<Vec<_>>>()?
+        };
+        for mount in &mounts {
+            tracing::debug!(
+                "Vault configuration: url={}, mount_path={}, secret_path={}",
+                config.url,
+                mount.path,
+                mount.secret_path
+            );
+        }
 
-        let secret_path = properties
-            .get("secret_path")
-            .and_then(|v| v.as_str())
-            .unwrap_or("/");
+        let auth = match config.auth {
+            VaultAuthMethod::Token => match (&config.token, &config.token_file) {
+                (Some(token), None) => VaultAuth::Token(token.clone()),
+                (None, Some(path)) => VaultAuth::TokenFile(path.clone()),
+                _ => {
+                    return Err(crate::DoomsdayError::config(
+                        "Vault token auth requires exactly one of token and token_file",
+                    ))
+                }
+            },
+            VaultAuthMethod::AppRole => {
+                let role_id = config.role_id.clone().ok_or_else(|| {
+                    crate::DoomsdayError::config("Vault AppRole auth requires role_id")
+                })?;
+                let secret_id = match (&config.secret_id, &config.secret_id_file) {
+                    (Some(secret_id), None) => SecretId::Value(secret_id.clone()),
+                    (None, Some(path)) => SecretId::File(path.clone()),
+                    _ => return Err(crate::DoomsdayError::config(
+                        "Vault AppRole auth requires exactly one of secret_id and secret_id_file",
+                    )),
+                };
+                VaultAuth::AppRole {
+                    mount: config.approle_mount.clone(),
+                    role_id,
+                    secret_id,
+                }
+            }
+        };
 
-        tracing::debug!(
-            "Vault configuration: url={}, mount_path={}, secret_path={}",
-            url,
-            mount_path,
-            secret_path
-        );
+        if config.max_requests_per_second == Some(0) {
+            return Err(crate::DoomsdayError::config(
+                "Vault max_requests_per_second must be at least 1",
+            ));
+        }
+        if config.max_retries > MAX_RETRIES {
+            return Err(crate::DoomsdayError::config(format!(
+                "Vault max_retries must be at most {}",
+                MAX_RETRIES
+            )));
+        }
+        if config.list_concurrency == 0 {
+            return Err(crate::DoomsdayError::config(
+                "Vault list_concurrency must be at least 1",
+            ));
+        }
 
-        let base_url = Url::parse(url)
-            .map_err(|e| crate::DoomsdayError::config(format!("Invalid Vault URL: {}", e)))?;
+        let client = Self::build_client(&name, config, request_timeout)?;
 
         tracing::info!("Vault accessor configured successfully: {}", name);
 
         Self::new(
             name,
             base_url,
-            token.to_string(),
-            mount_path.to_string(),
-            secret_path.to_string(),
+            auth,
+            mounts,
+            !config.mounts.is_empty(),
+            client,
         )
+        .map(|accessor| {
+            accessor
+                .with_listing(config.list_concurrency, config.max_depth)
+                .with_request_limits(config.max_requests_per_second, config.max_retries)
+        })
     }
 
-    async fn list_recursive(&self, path: &str) -> crate::Result<Vec<String>> {
-        tracing::info!("Starting recursive listing from Vault path: {}", path);
-        let mut all_paths = Vec::new();
-        let mut to_process = vec![path.to_string()];
+    /// An HTTP client trusting the configured CAs and presenting the
+    /// configured client certificate.
+    fn build_client(
+        name: &str,
+        config: &VaultBackendConfig,
+        request_timeout: Duration,
+    ) -> crate::Result<Client> {
+        let mut builder = tls_client_builder(
+            "Vault",
+            config.ca_cert.as_deref(),
+            config.client_cert.as_deref(),
+            config.client_key.as_deref(),
+        )?
+        .timeout(request_timeout);
+
+        if config.skip_verify {
+            tracing::warn!(
+                "Vault accessor '{}': TLS certificate verification is disabled",
+                name
+            );
+            builder = builder.danger_accept_invalid_certs(true);
+        }
+
+        Ok(builder.build()?)
+    }
+
+    fn api_url(&self, path: &str) -> String {
+        format!(
+            "{}/v1/{}",
+            self.base_url.as_str().trim_end_matches('/'),
+            path
+        )
+    }
+
+    /// The token to send with requests, logging in first with an AppRole
+    /// when there is no current token.
+    async fn token(&self) -> crate::Result<String> {
+        match &self.auth {
+            VaultAuth::Token(token) => return Ok(token.clone()),
+            VaultAuth::TokenFile(path) => return self.file_token(path).await,
+            VaultAuth::AppRole { .. } => {}
+        }
+        let _login = self.login_lock.lock().await;
+        if let Some(token) = self.approle_token.get().await {
+            return Ok(token);
+        }
+        self.approle_login().await?;
+        self.approle_token
+            .get()
+            .await
+            .ok_or_else(|| crate::DoomsdayError::auth("Vault issued an already expired token"))
+    }
+
+    /// The token last read from the token file, read first if it has not
+    /// been yet. The file is only checked for changes by `check_file_token`
+    /// and when Vault rejects the token, not on every request.
+    async fn file_token(&self, path: &Path) -> crate::Result<String> {
+        if let Some(cached) = self.file_token.lock().unwrap().as_ref() {
+            return Ok(cached.token.clone());
+        }
+        self.read_token_file(path, false).await
+    }
+
+    /// The token in the token file, read again if the file has changed
+    /// since it was last read, or regardless when `force` is set.
+    async fn read_token_file(&self, path: &Path, force: bool) -> crate::Result<String> {
+        let read_error = |e: std::io::Error| {
+            crate::DoomsdayError::auth(format!(
+                "Failed to read Vault token from {}: {}",
+                path.display(),
+                e
+            ))
+        };
+        let modified = tokio::fs::metadata(path)
+            .await
+            .map_err(read_error)?
+            .modified()
+            .ok();
+
+        if let Some(cached) = self.file_token.lock().unwrap().as_ref() {
+            if !force && cached.modified.is_some() && cached.modified == modified {
+                return Ok(cached.token.clone());
+            }
+        }
+
+        let token = tokio::fs::read_to_string(path)
+            .await
+            .map_err(read_error)?
+            .trim()
+            .to_string();
+        if token.is_empty() {
+            return Err(crate::DoomsdayError::auth(format!(
+                "Vault token file {} is empty",
+                path.display()
+            )));
+        }
+
+        let mut file_token = self.file_token.lock().unwrap();
+        if file_token
+            .as_ref()
+            .is_some_and(|cached| cached.token != token)
+        {
+            tracing::info!(
+                "Vault accessor '{}': token in {} changed, using the new token",
+                self.name,
+                path.display()
+            );
+        }
+        *file_token = Some(FileToken {
+            token: token.clone(),
+            modified,
+        });
+        Ok(token)
+    }
+
+    /// Logs in with the AppRole and returns when to renew the new token,
+    /// or `None` if it never expires.
+    async fn approle_login(&self) -> crate::Result<Option<Duration>> {
+        let VaultAuth::AppRole {
+            mount,
+            role_id,
+            secret_id,
+        } = &self.auth
+        else {
+            return Ok(None);
+        };
+        tracing::debug!("Vault accessor '{}': logging in with AppRole", self.name);
+
+        let request = VaultAppRoleLoginRequest {
+            role_id: role_id.clone(),
+            secret_id: secret_id.read()?,
+        };
+        let response = self
+            .client
+            .post(self.api_url(&format!("auth/{}/login", mount)))
+            .json(&request)
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            let e = crate::DoomsdayError::auth(format!(
+                "Vault AppRole login failed with status: {}",
+                response.status()
+            ));
+            self.record_auth_error(&e);
+            return Err(e);
+        }
+
+        let login: VaultLoginResponse = response.json().await?;
+        self.record_token(login.auth.lease_duration, login.auth.renewable);
+        let token = login.auth.client_token;
+        let refresh_token = login.auth.renewable.then(|| token.clone());
+        if login.auth.lease_duration == 0 {
+            self.approle_token
+                .set(token, None, Duration::from_secs(u64::from(u32::MAX)))
+                .await;
+            return Ok(None);
+        }
+        let delay = self
+            .approle_token
+            .set(
+                token,
+                refresh_token,
+                Duration::from_secs(login.auth.lease_duration),
+            )
+            .await;
+        Ok(Some(delay))
+    }
+
+    /// Renews a token, returning its new lease in seconds.
+    async fn renew_token(&self, token: &str) -> crate::Result<u64> {
+        let response = self
+            .client
+            .post(self.api_url("auth/token/renew-self"))
+            .header("X-Vault-Token", token)
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(crate::DoomsdayError::auth(format!(
+                "Vault token renewal failed with status: {}",
+                response.status()
+            )));
+        }
 
-        while let Some(current_path) = to_process.pop() {
-            tracing::debug!("Vault: listing path {}", current_path);
+        let renewed: VaultTokenRenewResponse = response.json().await?;
+        Ok(renewed.auth.lease_duration)
+    }
+
+    /// Renews a token given in the config, if it expires and Vault allows.
+    async fn renew_static_token(&self, token: &str) -> crate::Result<Option<Duration>> {
+        let lookup = self.lookup_token(token).await?;
+        self.record_token(lookup.ttl, lookup.renewable);
+
+        if lookup.ttl == 0 {
+            tracing::info!(
+                "Vault accessor '{}': token does not expire, no renewal needed",
+                self.name
+            );
+            return Ok(None);
+        }
 
-            let url = format!(
-                "{}/v1/{}/metadata/{}",
-                self.base_url.as_str().trim_end_matches('/'),
-                self.mount_path,
-                current_path.trim_start_matches('/')
+        if !lookup.renewable {
+            tracing::warn!(
+                "Vault accessor '{}': token is not renewable and expires in {}s",
+                self.name,
+                lookup.ttl
             );
+            return Ok(None);
+        }
 
-            tracing::debug!("Vault API request: GET {}", url);
-
-            let response = self
-                .client
-                .get(&url)
-                .header("X-Vault-Token", &self.token)
-                .query(&[("list", "true")])
-                .send()
-                .await?;
-
-            if response.status().is_success() {
-                let vault_response: VaultListResponse = response.json().await?;
-                tracing::debug!(
-                    "Vault returned {} keys for path {}",
-                    vault_response.data.keys.len(),
-                    current_path
+        let lease_duration = self.renew_token(token).await?;
+        self.record_token(lease_duration, true);
+        let delay = renewal_delay(Duration::from_secs(lease_duration));
+        tracing::info!(
+            "Vault accessor '{}': token renewed for {}s, next renewal in {:?}",
+            self.name,
+            lease_duration,
+            delay
+        );
+        Ok(Some(delay))
+    }
+
+    /// Checks the token in the token file. Whatever writes the file, such
+    /// as Vault Agent, renews the token, so this only keeps its reported
+    /// health current.
+    async fn check_file_token(&self, path: &Path) -> crate::Result<Option<Duration>> {
+        let token = self.read_token_file(path, false).await?;
+        let lookup = self.lookup_token(&token).await?;
+        self.record_token(lookup.ttl, lookup.renewable);
+        Ok(Some(TOKEN_FILE_CHECK_INTERVAL))
+    }
+
+    /// Renews the AppRole token while Vault allows it, otherwise logs in
+    /// again.
+    async fn renew_approle_token(&self) -> crate::Result<Option<Duration>> {
+        let _login = self.login_lock.lock().await;
+        if let Some(token) = self.approle_token.refresh_token().await {
+            match self.renew_token(&token).await {
+                Ok(lease_duration) if lease_duration > 0 => {
+                    self.record_token(lease_duration, true);
+                    let delay = self
+                        .approle_token
+                        .set(
+                            token.clone(),
+                            Some(token),
+                            Duration::from_secs(lease_duration),
+                        )
+                        .await;
+                    tracing::info!(
+                        "Vault accessor '{}': AppRole token renewed for {}s, next renewal in {:?}",
+                        self.name,
+                        lease_duration,
+                        delay
+                    );
+                    return Ok(Some(delay));
+                }
+                Ok(_) => {}
+                Err(e) => tracing::info!(
+                    "Vault accessor '{}': logging in again, AppRole token renewal failed: {}",
+                    self.name,
+                    e
+                ),
+            }
+        }
+
+        let delay = self.approle_login().await?;
+        match delay {
+            Some(delay) => tracing::info!(
+                "Vault accessor '{}': logged in with AppRole, next renewal in {:?}",
+                self.name,
+                delay
+            ),
+            None => tracing::info!(
+                "Vault accessor '{}': logged in with AppRole, token does not expire",
+                self.name
+            ),
+        }
+        Ok(delay)
+    }
+
+    /// Sends a request with the current token. When Vault turns the token
+    /// itself away, rather than denying access to the path, an AppRole
+    /// logs in again and the request is sent once more.
+    async fn send(&self, request: RequestBuilder) -> crate::Result<Response> {
+        let retry = request.try_clone();
+        let token = self.token().await?;
+        let response = self
+            .dispatch(request.header("X-Vault-Token", &token))
+            .await?;
+        if response.status() != StatusCode::FORBIDDEN || self.lookup_token(&token).await.is_ok() {
+            return Ok(response);
+        }
+
+        let e = crate::DoomsdayError::auth("Vault rejected the token");
+        self.record_auth_error(&e);
+        let Some(retry) = retry else {
+            return Ok(response);
+        };
+        match &self.auth {
+            VaultAuth::Token(_) => return Ok(response),
+            VaultAuth::TokenFile(path) => {
+                // Unless the file already holds a new token, there is
+                // nothing better to retry with
+                let new_token = self.read_token_file(path, true).await?;
+                if new_token == token {
+                    return Ok(response);
+                }
+                tracing::warn!(
+                    "Vault accessor '{}': token rejected, retrying with the new token in {}",
+                    self.name,
+                    path.display()
                 );
+                if let Ok(lookup) = self.lookup_token(&new_token).await {
+                    self.record_token(lookup.ttl, lookup.renewable);
+                }
+            }
+            VaultAuth::AppRole { .. } => {
+                let _login = self.login_lock.lock().await;
+                // Unless a concurrent request has already replaced it
+                if self.approle_token.get().await.as_deref() == Some(token.as_str()) {
+                    tracing::warn!(
+                        "Vault accessor '{}': token rejected, logging in again",
+                        self.name
+                    );
+                    self.approle_login().await?;
+                }
+            }
+        }
+        self.dispatch(retry.header("X-Vault-Token", self.token().await?))
+            .await
+    }
 
-                for key in vault_response.data.keys {
-                    let full_path = if current_path.is_empty() || current_path == "/" {
-                        key.clone()
-                    } else {
-                        format!("{}/{}", current_path.trim_end_matches('/'), key)
-                    };
-
-                    if key.ends_with('/') {
-                        // It's a directory, add to processing queue
-                        tracing::debug!("Vault: found directory {}, adding to queue", full_path);
-                        to_process.push(full_path.trim_end_matches('/').to_string());
-                    } else {
-                        // It's a secret
-                        tracing::debug!("Vault: found secret {}", full_path);
-                        all_paths.push(full_path);
-                    }
+    /// Sends a request within the rate limit. While Vault answers 429 (a
+    /// rate limit quota) or 503 (sealed, or a standby that cannot serve
+    /// it), it is retried after the `Retry-After` Vault gives or a
+    /// jittered backoff, until `max_retries` runs out.
+    async fn dispatch(&self, mut request: RequestBuilder) -> crate::Result<Response> {
+        let mut retries = 0;
+        loop {
+            let retry = request.try_clone();
+            if let Some(rate_limit) = &self.rate_limit {
+                rate_limit.acquire().await;
+            }
+            let response = request.send().await?;
+
+            let status = response.status();
+            if !matches!(
+                status,
+                StatusCode::TOO_MANY_REQUESTS | StatusCode::SERVICE_UNAVAILABLE
+            ) || retries >= self.max_retries
+            {
+                return Ok(response);
+            }
+            let Some(retry) = retry else {
+                return Ok(response);
+            };
+
+            retries += 1;
+            let delay = retry_after(&response).unwrap_or_else(|| jitter(retry_backoff(retries)));
+            tracing::warn!(
+                "Vault accessor '{}': {} from {}, retrying in {:?} ({}/{})",
+                self.name,
+                status,
+                response.url().path(),
+                delay,
+                retries,
+                self.max_retries
+            );
+            tokio::time::sleep(delay).await;
+            request = retry;
+        }
+    }
+
+    /// Notes a token valid for `ttl` seconds (0 for never expiring).
+    fn record_token(&self, ttl: u64, renewable: bool) {
+        let now = chrono::Utc::now();
+        *self.auth_status.lock().unwrap() = AuthStatus {
+            expires_at: (ttl > 0).then(|| now + chrono::Duration::seconds(ttl as i64)),
+            renewable,
+            checked_at: Some(now),
+            error: None,
+        };
+    }
+
+    fn record_auth_error(&self, e: &crate::DoomsdayError) {
+        self.auth_status.lock().unwrap().error = Some(e.to_string());
+    }
+
+    /// The mount's KV version. When detection fails it is tried again on
+    /// the next use, and v2 is assumed meanwhile.
+    async fn kv_version(&self, mount: &str) -> KvVersion {
+        let cell = self.kv_version_cell(mount);
+        match cell.get_or_try_init(|| self.detect_kv_version(mount)).await {
+            Ok(version) => *version,
+            Err(e) => {
+                tracing::warn!(
+                    "Vault accessor '{}': could not detect the KV version of mount {}, assuming v2: {}",
+                    self.name,
+                    mount,
+                    e
+                );
+                KvVersion::V2
+            }
+        }
+    }
+
+    fn kv_version_cell(&self, mount: &str) -> Arc<OnceCell<KvVersion>> {
+        self.kv_versions
+            .lock()
+            .unwrap()
+            .entry(mount.to_string())
+            .or_default()
+            .clone()
+    }
+
+    /// Reads the mount's options through the endpoint the Vault CLI uses,
+    /// which any token with access to the mount may read, unlike
+    /// `sys/mounts`.
+    async fn detect_kv_version(&self, mount: &str) -> crate::Result<KvVersion> {
+        let response = self
+            .send(
+                self.client
+                    .get(self.api_url(&format!("sys/internal/ui/mounts/{}", mount))),
+            )
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(crate::DoomsdayError::backend(format!(
+                "Vault mount lookup failed with status: {}",
+                response.status()
+            )));
+        }
+
+        let version = response
+            .json::<VaultMountResponse>()
+            .await?
+            .data
+            .kv_version();
+        tracing::info!(
+            "Vault accessor '{}': mount {} is KV {:?}",
+            self.name,
+            mount,
+            version
+        );
+        Ok(version)
+    }
+
+    /// The KV mounts the token can see, with their versions, through the
+    /// same endpoint as `detect_kv_version`.
+    async fn visible_kv_mounts(&self) -> crate::Result<Vec<(String, KvVersion)>> {
+        let response = self
+            .send(self.client.get(self.api_url("sys/internal/ui/mounts")))
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(crate::DoomsdayError::backend(format!(
+                "Vault mount listing failed with status: {}",
+                response.status()
+            )));
+        }
+
+        let mounts: VaultMountsResponse = response.json().await?;
+        Ok(mounts.data.kv_mounts())
+    }
+
+    /// The mounts to list, with where to start in each. Globs are matched
+    /// against the mounts the token can see, so mounts added since the last
+    /// listing are picked up.
+    async fn resolve_mounts(&self) -> crate::Result<Vec<(String, String)>> {
+        let mut resolved: Vec<(String, String)> = Vec::new();
+        let mut visible = None;
+
+        for mount in &self.mounts {
+            if !mount.is_glob() {
+                if !resolved.iter().any(|(path, _)| *path == mount.path) {
+                    resolved.push((mount.path.clone(), mount.secret_path.clone()));
                 }
-            } else {
+                continue;
+            }
+
+            let visible = match &visible {
+                Some(visible) => visible,
+                None => visible.insert(self.visible_kv_mounts().await?),
+            };
+            let matched = matching_mounts(&mount.path, visible)?;
+            if matched.is_empty() {
                 tracing::warn!(
-                    "Vault API request failed with status: {} for path: {}",
-                    response.status(),
-                    current_path
+                    "Vault accessor '{}': no KV mounts match {}",
+                    self.name,
+                    mount.path
                 );
             }
+
+            for (path, version) in matched {
+                // Already known from the listing, sparing a lookup per mount
+                let _ = self
+                    .kv_version_cell(path)
+                    .set(mount.kv_version.unwrap_or(*version));
+                if !resolved.iter().any(|(resolved, _)| resolved == path) {
+                    resolved.push((path.clone(), mount.secret_path.clone()));
+                }
+            }
         }
 
+        *self.resolved_mounts.lock().unwrap() =
+            resolved.iter().map(|(path, _)| path.clone()).collect();
+        Ok(resolved)
+    }
+
+    /// The mount a listed path is in, and the path within it.
+    fn locate<'a>(&self, path: &'a str) -> crate::Result<(String, &'a str)> {
+        if !self.qualify_paths {
+            return Ok((self.mounts[0].path.clone(), path));
+        }
+        self.resolved_mounts
+            .lock()
+            .unwrap()
+            .iter()
+            .filter_map(|mount| {
+                let rest = path.strip_prefix(mount.as_str())?.strip_prefix('/')?;
+                Some((mount.clone(), rest))
+            })
+            .max_by_key(|(mount, _)| mount.len())
+            .ok_or_else(|| {
+                crate::DoomsdayError::not_found(format!(
+                    "{} is not in any of the backend's Vault mounts",
+                    path
+                ))
+            })
+    }
+
+    /// A path within a mount as the accessor lists it.
+    fn qualify(&self, mount: &str, path: &str) -> String {
+        if self.qualify_paths {
+            format!("{}/{}", mount, path.trim_start_matches('/'))
+        } else {
+            path.to_string()
+        }
+    }
+
+    async fn lookup_token(&self, token: &str) -> crate::Result<VaultTokenLookupData> {
+        let response = self
+            .client
+            .get(self.api_url("auth/token/lookup-self"))
+            .header("X-Vault-Token", token)
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(crate::DoomsdayError::auth(format!(
+                "Vault token lookup failed with status: {}",
+                response.status()
+            )));
+        }
+
+        let lookup: VaultTokenLookupResponse = response.json().await?;
+        Ok(lookup.data)
+    }
+
+    /// Walks the tree below the secret path of each of `roots`, given as
+    /// (mount, secret path), listing up to `list_concurrency` directories
+    /// at once across all of them. A directory that cannot be listed is
+    /// recorded in `list_failures` and skipped. A secret path that cannot
+    /// be listed fails the listing, unless other mounts could be listed.
+    async fn list_recursive(&self, roots: &[(String, String)]) -> crate::Result<Vec<String>> {
+        let mut to_process = VecDeque::new();
+        for (mount, secret_path) in roots {
+            tracing::info!(
+                "Starting recursive listing from Vault path: {} in mount {}",
+                secret_path,
+                mount
+            );
+            let kv_version = self.kv_version(mount).await;
+            to_process.push_back((mount.as_str(), kv_version, secret_path.clone(), 0));
+        }
+
+        let mut all_paths = Vec::new();
+        let mut failures = Vec::new();
+        let mut root_errors = Vec::new();
+        let mut num_dirs = 0;
+        let mut too_deep = 0;
+        let mut listing = FuturesUnordered::new();
+
+        loop {
+            while listing.len() < self.list_concurrency {
+                let Some((mount, kv_version, dir, depth)) = to_process.pop_front() else {
+                    break;
+                };
+                listing.push(async move {
+                    let keys = self.list_directory(mount, kv_version, &dir).await;
+                    (mount, kv_version, dir, depth, keys)
+                });
+            }
+
+            let Some((mount, kv_version, current_path, depth, keys)) = listing.next().await else {
+                break;
+            };
+            num_dirs += 1;
+
+            let keys = match keys {
+                Ok(keys) => keys,
+                Err(e) => {
+                    tracing::warn!(
+                        "Vault accessor '{}': skipping unreadable directory {} in mount {}: {}",
+                        self.name,
+                        current_path,
+                        mount,
+                        e
+                    );
+                    failures.push(ListFailure {
+                        path: self.qualify(mount, &current_path),
+                        error: e.to_string(),
+                    });
+                    if depth == 0 {
+                        root_errors.push(e);
+                    }
+                    continue;
+                }
+            };
+
+            for key in keys {
+                let full_path = if current_path.is_empty() || current_path == "/" {
+                    key.clone()
+                } else {
+                    format!("{}/{}", current_path.trim_end_matches('/'), key)
+                };
+
+                if !key.ends_with('/') {
+                    tracing::debug!("Vault: found secret {} in mount {}", full_path, mount);
+                    all_paths.push(self.qualify(mount, &full_path));
+                } else if self.max_depth.is_some_and(|max| depth >= max) {
+                    too_deep += 1;
+                } else {
+                    tracing::debug!("Vault: found directory {}, adding to queue", full_path);
+                    let dir = full_path.trim_end_matches('/').to_string();
+                    to_process.push_back((mount, kv_version, dir, depth + 1));
+                }
+            }
+        }
+
+        // A single mount's secret path failing, or every mount's, is the
+        // backend failing rather than part of it
+        if !root_errors.is_empty() && (!self.qualify_paths || root_errors.len() == roots.len()) {
+            return Err(root_errors.remove(0));
+        }
+
+        if too_deep > 0 {
+            tracing::info!(
+                "Vault accessor '{}': left out {} directories deeper than max_depth",
+                self.name,
+                too_deep
+            );
+        }
         tracing::info!(
-            "Vault recursive listing completed: {} total paths found",
-            all_paths.len()
+            "Vault recursive listing completed: {} total paths found in {} directories, {} unreadable",
+            all_paths.len(),
+            num_dirs,
+            failures.len()
         );
+
+        // Directories finish in any order
+        all_paths.sort();
+        *self.list_failures.lock().unwrap() = failures;
         Ok(all_paths)
     }
+
+    /// The keys of one directory, with subdirectories ending in `/`.
+    async fn list_directory(
+        &self,
+        mount: &str,
+        kv_version: KvVersion,
+        dir: &str,
+    ) -> crate::Result<Vec<String>> {
+        let url = self.api_url(&kv_version.list_path(mount, dir.trim_start_matches('/')));
+        tracing::debug!("Vault API request: GET {}", url);
+
+        let response = self
+            .send(self.client.get(&url).query(&[("list", "true")]))
+            .await?;
+
+        // Vault answers 404 for a directory with nothing in it
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(Vec::new());
+        }
+        if !response.status().is_success() {
+            return Err(crate::DoomsdayError::backend(format!(
+                "listing {} failed with status: {}",
+                dir,
+                response.status()
+            )));
+        }
+
+        let vault_response: VaultListResponse = response.json().await?;
+        tracing::debug!(
+            "Vault returned {} keys for path {}",
+            vault_response.data.keys.len(),
+            dir
+        );
+        Ok(vault_response.data.keys)
+    }
 }
 
 #[async_trait]
 impl Accessor for VaultAccessor {
     async fn list(&self) -> crate::Result<PathList> {
         tracing::info!(
-            "Vault accessor '{}': listing secrets from path: {}",
+            "Vault accessor '{}': listing secrets from {} configured mounts",
             self.name,
-            self.secret_path
+            self.mounts.len()
         );
-        let result = self.list_recursive(&self.secret_path).await;
+        let result = match self.resolve_mounts().await {
+            Ok(roots) => self.list_recursive(&roots).await,
+            Err(e) => Err(e),
+        };
         match &result {
             Ok(paths) => tracing::info!(
                 "Vault accessor '{}': found {} secrets",
@@ -195,70 +1240,78 @@ impl Accessor for VaultAccessor {
             path
         );
 
-        let url = format!(
-            "{}/v1/{}/data/{}",
-            self.base_url.as_str().trim_end_matches('/'),
-            self.mount_path,
-            path.trim_start_matches('/')
-        );
+        let (mount, secret) = self.locate(path)?;
+        let kv_version = self.kv_version(&mount).await;
+        let url = self.api_url(&kv_version.read_path(&mount, secret.trim_start_matches('/')));
 
         tracing::debug!("Vault API request: GET {}", url);
 
-        let response = self
-            .client
-            .get(&url)
-            .header("X-Vault-Token", &self.token)
-            .send()
-            .await?;
+        let response = self.send(self.client.get(&url)).await?;
 
-        if !response.status().is_success() {
+        if response.status() == StatusCode::NOT_FOUND {
             tracing::debug!(
-                "Vault accessor '{}': no certificate found at path {} (status: {})",
+                "Vault accessor '{}': no certificate found at path {}",
                 self.name,
-                path,
-                response.status()
+                path
             );
             return Ok(None);
         }
+        // Anything else, e.g. a rate limit outlasting the retries, must not
+        // read as the secret being gone
+        if !response.status().is_success() {
+            return Err(crate::DoomsdayError::backend(format!(
+                "reading {} failed with status: {}",
+                path,
+                response.status()
+            )));
+        }
 
-        let vault_response: VaultSecretResponse = response.json().await?;
-
-        // Look for certificate data in common fields
-        let cert_pem = vault_response
-            .data
-            .get("certificate")
-            .or_else(|| vault_response.data.get("cert"))
-            .or_else(|| vault_response.data.get("crt"))
-            .and_then(|v| v.as_str());
+        let mut vault_response: VaultSecretResponse = response.json().await?;
+        // KV v2 nests the secret under data.data, beside its metadata
+        let fields = match kv_version {
+            KvVersion::V1 => vault_response.data,
+            KvVersion::V2 => match vault_response.data.remove("data") {
+                Some(Value::Object(fields)) => fields,
+                _ => Map::new(),
+            },
+        };
 
-        if let Some(pem_data) = cert_pem {
+        if let Some((fields, pem_data)) = find_certificate(&fields) {
             tracing::debug!(
                 "Vault accessor '{}': found certificate data at path: {}",
                 self.name,
                 path
             );
 
-            let (_, pem) = parse_x509_pem(pem_data.as_bytes()).map_err(|e| {
-                tracing::error!(
-                    "Vault accessor '{}': failed to parse PEM at {}: {}",
-                    self.name,
-                    path,
-                    e
-                );
-                crate::DoomsdayError::x509(format!("Failed to parse PEM: {}", e))
-            })?;
-
-            let (_, cert) = parse_x509_certificate(&pem.contents).map_err(|e| {
+            let mut cert_data = CertificateData::from_pem_bundle(pem_data).map_err(|e| {
                 tracing::error!(
                     "Vault accessor '{}': failed to parse certificate at {}: {}",
                     self.name,
                     path,
                     e
                 );
-                crate::DoomsdayError::x509(format!("Failed to parse certificate: {}", e))
+                e
             })?;
 
-            let cert_data = CertificateData::from_x509(&cert, pem_data)?;
+            // PKI-style secrets keep the issuing CA and chain in separate fields
+            for field in CHAIN_FIELDS {
+                let pems: Vec<&str> = match fields.get(*field) {
+                    Some(Value::String(pem)) => vec![pem.as_str()],
+                    Some(Value::Array(pems)) => pems.iter().filter_map(|v| v.as_str()).collect(),
+                    _ => continue,
+                };
+                for pem in pems {
+                    if let Err(e) = cert_data.extend_chain_from_pem(pem) {
+                        tracing::warn!(
+                            "Vault accessor '{}': ignoring unparsable {} at {}: {}",
+                            self.name,
+                            field,
+                            path,
+                            e
+                        );
+                    }
+                }
+            }
             tracing::info!(
                 "Vault accessor '{}': successfully parsed certificate from path: {} (subject: {})",
                 self.name,
@@ -279,4 +1332,337 @@ impl Accessor for VaultAccessor {
     fn name(&self) -> &str {
         &self.name
     }
+
+    fn renews_auth(&self) -> bool {
+        true
+    }
+
+    async fn renew_auth(&self) -> crate::Result<Option<Duration>> {
+        let result = match &self.auth {
+            VaultAuth::Token(token) => self.renew_static_token(token).await,
+            VaultAuth::TokenFile(path) => self.check_file_token(path).await,
+            VaultAuth::AppRole { .. } => self.renew_approle_token().await,
+        };
+        if let Err(e) = &result {
+            self.record_auth_error(e);
+        }
+        result
+    }
+
+    fn list_failures(&self) -> Vec<ListFailure> {
+        self.list_failures.lock().unwrap().clone()
+    }
+
+    fn auth_status(&self) -> Option<AuthStatus> {
+        Some(self.auth_status.lock().unwrap().clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderMap as RequestHeaders;
+    use axum::routing::post;
+    use axum::{Json, Router};
+    use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+
+    /// A Vault that issues AppRole tokens valid for an hour and renews
+    /// them for half an hour, unless `refuse_renewal` is set.
+    #[derive(Default)]
+    struct FakeVault {
+        logins: AtomicU32,
+        renewals: AtomicU32,
+        refuse_renewal: AtomicBool,
+    }
+
+    async fn serve() -> (Url, Arc<FakeVault>) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = Url::parse(&format!("http://{}", listener.local_addr().unwrap())).unwrap();
+        let fake = Arc::new(FakeVault::default());
+
+        let login = {
+            let fake = fake.clone();
+            move |Json(request): Json<VaultAppRoleLoginRequest>| {
+                let fake = fake.clone();
+                async move {
+                    if request.role_id != "role" || request.secret_id != "secret" {
+                        return Err(axum::http::StatusCode::BAD_REQUEST);
+                    }
+                    let login = fake.logins.fetch_add(1, Ordering::SeqCst) + 1;
+                    Ok(Json(serde_json::json!({
+                        "auth": {
+                            "client_token": format!("hvs.{}", login),
+                            "lease_duration": 3600,
+                            "renewable": true,
+                        }
+                    })))
+                }
+            }
+        };
+        let renew = {
+            let fake = fake.clone();
+            move |headers: RequestHeaders| {
+                let fake = fake.clone();
+                async move {
+                    if headers.get("x-vault-token").is_none()
+                        || fake.refuse_renewal.load(Ordering::SeqCst)
+                    {
+                        return Err(axum::http::StatusCode::FORBIDDEN);
+                    }
+                    fake.renewals.fetch_add(1, Ordering::SeqCst);
+                    Ok(Json(serde_json::json!({"auth": {"lease_duration": 1800}})))
+                }
+            }
+        };
+
+        let router = Router::new()
+            .route("/v1/auth/approle/login", post(login))
+            .route("/v1/auth/token/renew-self", post(renew));
+        tokio::spawn(async move { axum::serve(listener, router).await });
+        (base_url, fake)
+    }
+
+    fn approle_accessor(base_url: Url, secret_id: SecretId) -> VaultAccessor {
+        VaultAccessor::new(
+            "vault".to_string(),
+            base_url,
+            VaultAuth::AppRole {
+                mount: "approle".to_string(),
+                role_id: "role".to_string(),
+                secret_id,
+            },
+            Vec::new(),
+            false,
+            Client::new(),
+        )
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_approle_token_is_renewed() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("secret-id");
+        std::fs::write(&path, "secret\n").unwrap();
+        let (base_url, fake) = serve().await;
+        let vault = approle_accessor(base_url, SecretId::File(path));
+
+        // Logs in at startup, and renews after two thirds of the lease
+        assert_eq!(
+            vault.renew_auth().await.unwrap(),
+            Some(Duration::from_secs(2400))
+        );
+        assert_eq!(vault.token().await.unwrap(), "hvs.1");
+        let status = vault.auth_status().unwrap();
+        assert!(status.renewable && status.expires_at.is_some());
+
+        assert_eq!(
+            vault.renew_auth().await.unwrap(),
+            Some(Duration::from_secs(1200))
+        );
+        assert_eq!(vault.token().await.unwrap(), "hvs.1");
+        assert_eq!(fake.logins.load(Ordering::SeqCst), 1);
+        assert_eq!(fake.renewals.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_approle_logs_in_again_when_renewal_fails() {
+        let (base_url, fake) = serve().await;
+        let vault = approle_accessor(base_url, SecretId::Value("secret".to_string()));
+        assert_eq!(vault.token().await.unwrap(), "hvs.1");
+
+        fake.refuse_renewal.store(true, Ordering::SeqCst);
+        // A fresh token, renewed on the schedule of a new login's lease
+        assert_eq!(
+            vault.renew_auth().await.unwrap(),
+            Some(Duration::from_secs(2400))
+        );
+        assert_eq!(vault.token().await.unwrap(), "hvs.2");
+        assert_eq!(fake.logins.load(Ordering::SeqCst), 2);
+        assert_eq!(fake.renewals.load(Ordering::SeqCst), 0);
+        assert!(vault.auth_status().unwrap().error.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_rotated_token_file_is_reloaded() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("token");
+        let write = |token: &str, modified: SystemTime| {
+            std::fs::write(&path, token).unwrap();
+            std::fs::File::options()
+                .write(true)
+                .open(&path)
+                .unwrap()
+                .set_modified(modified)
+                .unwrap();
+        };
+        let start = SystemTime::now();
+        write("hvs.first\n", start);
+
+        let vault = VaultAccessor::new(
+            "vault".to_string(),
+            Url::parse("http://127.0.0.1:8200").unwrap(),
+            VaultAuth::TokenFile(path.clone()),
+            Vec::new(),
+            false,
+            Client::new(),
+        )
+        .unwrap();
+        assert_eq!(vault.token().await.unwrap(), "hvs.first");
+
+        // Requests keep the token read last, without touching the file
+        write("hvs.second", start + Duration::from_secs(60));
+        assert_eq!(vault.token().await.unwrap(), "hvs.first");
+
+        // The periodic check notices the rotation
+        assert_eq!(
+            vault.read_token_file(&path, false).await.unwrap(),
+            "hvs.second"
+        );
+        assert_eq!(vault.token().await.unwrap(), "hvs.second");
+
+        // Rewritten without a new modification time, as only a rejected
+        // token forces a read to find
+        write("hvs.third", start + Duration::from_secs(60));
+        assert_eq!(
+            vault.read_token_file(&path, false).await.unwrap(),
+            "hvs.second"
+        );
+        assert_eq!(
+            vault.read_token_file(&path, true).await.unwrap(),
+            "hvs.third"
+        );
+        assert_eq!(vault.token().await.unwrap(), "hvs.third");
+    }
+
+    #[test]
+    fn test_mount_lookup_detects_kv_version() {
+        let version = |body: Value| {
+            serde_json::from_value::<VaultMountResponse>(body)
+                .unwrap()
+                .data
+                .kv_version()
+        };
+        assert_eq!(
+            version(serde_json::json!({
+                "data": {"type": "kv", "path": "secret/", "options": {"version": "2"}}
+            })),
+            KvVersion::V2
+        );
+        assert_eq!(
+            version(serde_json::json!({
+                "data": {"type": "kv", "path": "kv/", "options": {"version": "1"}}
+            })),
+            KvVersion::V1
+        );
+        // Mounts made before KV v2 existed have no options at all
+        assert_eq!(
+            version(serde_json::json!({
+                "data": {"type": "generic", "path": "legacy/", "options": null}
+            })),
+            KvVersion::V1
+        );
+        assert_eq!(
+            version(serde_json::json!({"data": {"type": "kv"}})),
+            KvVersion::V1
+        );
+    }
+
+    #[test]
+    fn test_mount_listing_keeps_kv_mounts() {
+        let listing: VaultMountsResponse = serde_json::from_value(serde_json::json!({
+            "data": {
+                "secret": {
+                    "secret/": {"type": "kv", "options": {"version": "2"}},
+                    "kv-prod/": {"type": "kv", "options": {"version": "1"}},
+                    "legacy/": {"type": "generic", "options": null},
+                    "pki/": {"type": "pki", "options": null},
+                    "cubbyhole/": {"type": "cubbyhole", "options": null}
+                },
+                "auth": {"token/": {"type": "token"}}
+            }
+        }))
+        .unwrap();
+        assert_eq!(
+            listing.data.kv_mounts(),
+            vec![
+                ("kv-prod".to_string(), KvVersion::V1),
+                ("legacy".to_string(), KvVersion::V1),
+                ("secret".to_string(), KvVersion::V2),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_mount_globs() {
+        let visible = vec![
+            ("kv-dev".to_string(), KvVersion::V2),
+            ("kv-prod".to_string(), KvVersion::V1),
+            ("secret".to_string(), KvVersion::V2),
+        ];
+        let matched = |pattern: &str| -> Vec<String> {
+            matching_mounts(pattern, &visible)
+                .unwrap()
+                .into_iter()
+                .map(|(path, _)| path.clone())
+                .collect()
+        };
+        assert_eq!(matched("kv-*"), vec!["kv-dev", "kv-prod"]);
+        assert_eq!(matched("kv-[!p]*"), vec!["kv-dev"]);
+        assert_eq!(matched("s?cret"), vec!["secret"]);
+        assert!(matched("team-*").is_empty());
+
+        let e = matching_mounts("kv-[", &visible).unwrap_err();
+        assert!(e.to_string().contains("Invalid Vault mount pattern"));
+    }
+
+    #[test]
+    fn test_locate_picks_the_longest_mount() {
+        let mount = |path: &str| VaultMount {
+            path: path.to_string(),
+            secret_path: String::new(),
+            kv_version: None,
+        };
+        let vault = VaultAccessor::new(
+            "vault".to_string(),
+            Url::parse("http://127.0.0.1:8200").unwrap(),
+            VaultAuth::Token("hvs.test".to_string()),
+            vec![mount("kv"), mount("kv/team"), mount("kv-*")],
+            true,
+            Client::new(),
+        )
+        .unwrap();
+        // Globs are only located once a listing has resolved them
+        assert!(vault.locate("kv-prod/certs/api").is_err());
+        vault
+            .resolved_mounts
+            .lock()
+            .unwrap()
+            .push("kv-prod".to_string());
+
+        assert_eq!(
+            vault.locate("kv/team/certs/api").unwrap(),
+            ("kv/team".to_string(), "certs/api")
+        );
+        assert_eq!(
+            vault.locate("kv/other/api").unwrap(),
+            ("kv".to_string(), "other/api")
+        );
+        assert_eq!(
+            vault.locate("kv-prod/certs/api").unwrap(),
+            ("kv-prod".to_string(), "certs/api")
+        );
+        // A prefix of the path that is not a whole mount
+        assert!(vault.locate("kvx/certs/api").is_err());
+        assert!(vault.locate("kv").is_err());
+    }
+
+    #[test]
+    fn test_retry_backoff_is_capped() {
+        assert_eq!(retry_backoff(1), RETRY_BACKOFF);
+        assert_eq!(retry_backoff(3), RETRY_BACKOFF * 4);
+        assert_eq!(retry_backoff(6), MAX_RETRY_BACKOFF);
+        // Far past where doubling overflows a u32
+        assert_eq!(retry_backoff(40), MAX_RETRY_BACKOFF);
+        assert_eq!(retry_backoff(u32::MAX), MAX_RETRY_BACKOFF);
+    }
 }
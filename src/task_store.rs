@@ -0,0 +1,247 @@
+use crate::types::TaskInfo;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use tokio::sync::Notify;
+
+/// Lines the log may reach before it is compacted, however few tasks it
+/// holds.
+const MIN_COMPACT_LINES: usize = 1000;
+
+/// File-backed scheduler task history, kept as a log with one JSON line per
+/// changed or removed task. Changes are collected as they happen and
+/// written in batches by `flush`, which blocks and so is run off the async
+/// workers. Once the log has grown well past the tasks it holds, it is
+/// rewritten with just those.
+#[derive(Debug)]
+pub struct TaskStore {
+    path: PathBuf,
+    /// Changes not yet written, by task ID: the task, or `None` once removed.
+    pending: Mutex<HashMap<String, Option<TaskInfo>>>,
+    changed: Notify,
+    // Serializes writers so an older batch never lands after a newer one
+    log: Mutex<LogState>,
+}
+
+#[derive(Debug, Default)]
+struct LogState {
+    lines: usize,
+    compact_at: usize,
+}
+
+/// A line of the log.
+#[derive(Serialize, Deserialize)]
+struct LogEntry {
+    id: String,
+    /// Absent for a removed task.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    task: Option<TaskInfo>,
+}
+
+impl TaskStore {
+    pub fn new(path: PathBuf) -> Self {
+        TaskStore {
+            path,
+            pending: Mutex::new(HashMap::new()),
+            changed: Notify::new(),
+            log: Mutex::new(LogState::default()),
+        }
+    }
+
+    /// Reads the saved history, oldest first. Also reads the single JSON
+    /// array earlier versions wrote.
+    pub fn load(&self) -> crate::Result<Vec<TaskInfo>> {
+        if !self.path.exists() {
+            return Ok(Vec::new());
+        }
+
+        let content = fs::read_to_string(&self.path)?;
+        let mut tasks: Vec<TaskInfo> = if content.trim_start().starts_with('[') {
+            serde_json::from_str(&content)?
+        } else {
+            let lines: Vec<&str> = content.lines().filter(|l| !l.trim().is_empty()).collect();
+            let mut tasks = HashMap::new();
+            for (index, line) in lines.iter().enumerate() {
+                match serde_json::from_str::<LogEntry>(line) {
+                    Ok(LogEntry {
+                        id,
+                        task: Some(task),
+                    }) => {
+                        tasks.insert(id, task);
+                    }
+                    Ok(LogEntry { id, task: None }) => {
+                        tasks.remove(&id);
+                    }
+                    // Cut short by a crash while appending
+                    Err(e) if index + 1 == lines.len() => {
+                        tracing::warn!(
+                            "Ignoring incomplete last line of {}: {}",
+                            self.path.display(),
+                            e
+                        );
+                    }
+                    Err(e) => return Err(e.into()),
+                }
+            }
+            tasks.into_values().collect()
+        };
+        tasks.sort_by_key(|t| t.created_at);
+
+        tracing::info!(
+            "Loaded {} scheduler tasks from {}",
+            tasks.len(),
+            self.path.display()
+        );
+        Ok(tasks)
+    }
+
+    /// Queues a new or updated task to be written by the next `flush`.
+    pub fn changed(&self, task: &TaskInfo) {
+        self.pending
+            .lock()
+            .unwrap()
+            .insert(task.id.clone(), Some(task.clone()));
+        self.changed.notify_one();
+    }
+
+    /// Queues a task's removal to be written by the next `flush`.
+    pub fn removed(&self, id: &str) {
+        self.pending.lock().unwrap().insert(id.to_string(), None);
+        self.changed.notify_one();
+    }
+
+    /// Waits until there are changes to flush.
+    pub async fn wait_for_changes(&self) {
+        self.changed.notified().await;
+    }
+
+    /// Appends the queued changes to the log, or rewrites it from
+    /// `snapshot` once it is due for compaction.
+    pub fn flush<F>(&self, snapshot: F) -> crate::Result<()>
+    where
+        F: FnOnce() -> Vec<TaskInfo>,
+    {
+        let mut log = self.log.lock().unwrap();
+        let batch = std::mem::take(&mut *self.pending.lock().unwrap());
+        if batch.is_empty() {
+            return Ok(());
+        }
+        if log.lines + batch.len() >= log.compact_at {
+            // Holds every change in the batch, and possibly later ones
+            return self.write_all(&mut log, snapshot());
+        }
+
+        let count = batch.len();
+        let mut lines = String::new();
+        for (id, task) in batch {
+            lines.push_str(&serde_json::to_string(&LogEntry { id, task })?);
+            lines.push('\n');
+        }
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        file.write_all(lines.as_bytes())?;
+        log.lines += count;
+        Ok(())
+    }
+
+    /// Replaces the log with `tasks`, dropping changes queued before.
+    pub fn rewrite(&self, tasks: Vec<TaskInfo>) -> crate::Result<()> {
+        let mut log = self.log.lock().unwrap();
+        self.pending.lock().unwrap().clear();
+        self.write_all(&mut log, tasks)
+    }
+
+    fn write_all(&self, log: &mut LogState, mut tasks: Vec<TaskInfo>) -> crate::Result<()> {
+        tasks.sort_by_key(|t| t.created_at);
+
+        let mut lines = String::new();
+        for task in &tasks {
+            lines.push_str(&serde_json::to_string(&LogEntry {
+                id: task.id.clone(),
+                task: Some(task.clone()),
+            })?);
+            lines.push('\n');
+        }
+
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        // Write then rename so a crash mid-write cannot truncate the history
+        let tmp = self.path.with_extension("json.tmp");
+        fs::write(&tmp, lines)?;
+        fs::rename(&tmp, &self.path)?;
+
+        log.lines = tasks.len();
+        log.compact_at = (tasks.len() * 2).max(MIN_COMPACT_LINES);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{Task, TaskStatus};
+    use chrono::Utc;
+
+    fn task(id: &str, status: TaskStatus) -> TaskInfo {
+        TaskInfo {
+            id: id.to_string(),
+            task: Task::RefreshBackend {
+                backend_name: "vault".to_string(),
+            },
+            created_at: Utc::now(),
+            started_at: None,
+            completed_at: None,
+            status,
+            error: None,
+            stats: None,
+            attempts: 0,
+            next_retry_at: None,
+        }
+    }
+
+    #[test]
+    fn test_flush_appends_only_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tasks.json");
+        let store = TaskStore::new(path.clone());
+        store
+            .rewrite(vec![
+                task("a", TaskStatus::Completed),
+                task("b", TaskStatus::Pending),
+            ])
+            .unwrap();
+
+        store.changed(&task("b", TaskStatus::Running));
+        store.changed(&task("b", TaskStatus::Completed));
+        store.removed("a");
+        store
+            .flush(|| unreachable!("not due for compaction"))
+            .unwrap();
+        // The two tasks, then one line for each task changed since
+        assert_eq!(fs::read_to_string(&path).unwrap().lines().count(), 4);
+
+        // A crash while appending leaves a partial line behind
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(b"{\"id\":\"c\",\"ta").unwrap();
+        let tasks = TaskStore::new(path.clone()).load().unwrap();
+        assert_eq!(tasks.len(), 1);
+        assert_eq!(tasks[0].id, "b");
+        assert!(matches!(tasks[0].status, TaskStatus::Completed));
+
+        // The single array written by earlier versions
+        let legacy = vec![task("old", TaskStatus::Failed)];
+        fs::write(&path, serde_json::to_string_pretty(&legacy).unwrap()).unwrap();
+        let tasks = TaskStore::new(path).load().unwrap();
+        assert_eq!(tasks.len(), 1);
+        assert_eq!(tasks[0].id, "old");
+    }
+}
@@ -1,29 +1,196 @@
-use chrono::{DateTime, Utc};
+use crate::alerts::AlertAck;
+use crate::config::StatusThresholds;
+use base64::prelude::*;
+use chrono::{DateTime, Duration, Utc};
 use serde::{Deserialize, Serialize};
 use sha1::{Digest, Sha1};
 use sha2::Sha256;
 use std::collections::HashMap;
+use std::sync::Arc;
 use x509_parser::prelude::*;
+use x509_parser::public_key::PublicKey;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CacheItem {
+    #[serde(default)]
+    pub sha1: String,
     pub subject: String,
+    /// Absent for certificates cached before it was recorded.
+    #[serde(default)]
+    pub not_before: Option<DateTime<Utc>>,
     pub not_after: DateTime<Utc>,
     pub paths: Vec<PathObject>,
+    #[serde(default)]
+    pub status: CertificateStatus,
+    /// Every backend serving this certificate has been failing for longer
+    /// than `status.stale_after_hours`, so it may no longer be current.
+    #[serde(default)]
+    pub stale: bool,
+    /// Attached by the configured label rules.
+    #[serde(default)]
+    pub labels: HashMap<String, String>,
+    /// Set while an operator has silenced the certificate's notifications.
+    #[serde(default)]
+    pub acknowledged: Option<AlertAck>,
+    #[serde(flatten)]
+    pub metadata: CertificateMetadata,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CacheObject {
     pub subject: String,
+    #[serde(default)]
+    pub not_before: Option<DateTime<Utc>>,
     pub not_after: DateTime<Utc>,
     pub sha1: String,
     pub paths: Vec<PathObject>,
+    #[serde(flatten)]
+    pub metadata: CertificateMetadata,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Certificate details beyond subject and validity, kept in the cache so API
+/// consumers don't need to fetch the certificate from its backend. Empty for
+/// entries cached before these were recorded.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CertificateMetadata {
+    pub issuer: String,
+    pub serial_number: String,
+    pub subject_alt_names: Vec<String>,
+    pub fingerprint_sha256: String,
+    pub key_algorithm: String,
+    /// Key size in bits, 0 when unknown.
+    pub key_size: usize,
+    pub key_usage: Vec<String>,
+    pub ext_key_usage: Vec<String>,
+    pub is_ca: bool,
+    /// Hex key identifiers, used to match certificates to their issuers.
+    pub subject_key_id: String,
+    pub authority_key_id: String,
+}
+
+/// Where a certificate was found. Backend names and paths are shared
+/// between the certificates that carry them (see `Cache::put`), since large
+/// fleets repeat them across hundreds of thousands of entries.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct PathObject {
-    pub backend: String,
-    pub path: String,
+    pub backend: Arc<str>,
+    pub path: Arc<str>,
+}
+
+impl PathObject {
+    pub fn new(backend: impl Into<Arc<str>>, path: impl Into<Arc<str>>) -> Self {
+        PathObject {
+            backend: backend.into(),
+            path: path.into(),
+        }
+    }
+}
+
+impl CacheObject {
+    pub fn from_certificate(cert: CertificateData, path: PathObject) -> Self {
+        let metadata = cert.metadata();
+        CacheObject {
+            subject: cert.subject,
+            not_before: Some(cert.not_before),
+            not_after: cert.not_after,
+            sha1: cert.fingerprint_sha1,
+            paths: vec![path],
+            metadata,
+        }
+    }
+}
+
+impl CacheItem {
+    pub fn new(obj: &CacheObject, status: CertificateStatus) -> Self {
+        CacheItem {
+            sha1: obj.sha1.clone(),
+            subject: obj.subject.clone(),
+            not_before: obj.not_before,
+            not_after: obj.not_after,
+            paths: obj.paths.clone(),
+            status,
+            stale: false,
+            labels: HashMap::new(),
+            acknowledged: None,
+            metadata: obj.metadata.clone(),
+        }
+    }
+}
+
+/// Where a certificate is in its lifecycle. Computed in one place
+/// (`CertificateStatus::evaluate`) so the API, stats, notifications and
+/// dashboards all agree.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CertificateStatus {
+    #[default]
+    Ok,
+    ExpiringSoon,
+    Expired,
+    NotYetValid,
+    /// Reserved for backends that can report revocation; none currently do.
+    Revoked,
+    /// A newer, currently valid certificate with the same subject is also
+    /// present, so this one no longer needs attention.
+    Superseded,
+}
+
+impl CertificateStatus {
+    pub fn evaluate(
+        object: &CacheObject,
+        superseded: bool,
+        now: DateTime<Utc>,
+        thresholds: &StatusThresholds,
+    ) -> Self {
+        if object.not_before.is_some_and(|not_before| not_before > now) {
+            CertificateStatus::NotYetValid
+        } else if superseded {
+            CertificateStatus::Superseded
+        } else if object.not_after <= now {
+            CertificateStatus::Expired
+        } else if object.not_after - now <= Duration::days(thresholds.expiring_soon_days) {
+            CertificateStatus::ExpiringSoon
+        } else {
+            CertificateStatus::Ok
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CertificateStatus::Ok => "ok",
+            CertificateStatus::ExpiringSoon => "expiring_soon",
+            CertificateStatus::Expired => "expired",
+            CertificateStatus::NotYetValid => "not_yet_valid",
+            CertificateStatus::Revoked => "revoked",
+            CertificateStatus::Superseded => "superseded",
+        }
+    }
+}
+
+impl std::str::FromStr for CertificateStatus {
+    type Err = crate::DoomsdayError;
+
+    fn from_str(s: &str) -> crate::Result<Self> {
+        match s {
+            "ok" => Ok(CertificateStatus::Ok),
+            "expiring_soon" => Ok(CertificateStatus::ExpiringSoon),
+            "expired" => Ok(CertificateStatus::Expired),
+            "not_yet_valid" => Ok(CertificateStatus::NotYetValid),
+            "revoked" => Ok(CertificateStatus::Revoked),
+            "superseded" => Ok(CertificateStatus::Superseded),
+            other => Err(crate::DoomsdayError::invalid_input(format!(
+                "Unknown certificate status: {}",
+                other
+            ))),
+        }
+    }
+}
+
+impl std::fmt::Display for CertificateStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -37,6 +204,93 @@ pub struct PopulateStats {
 pub struct InfoResponse {
     pub version: String,
     pub auth_required: bool,
+    /// True when one or more configured backends could not be initialized.
+    #[serde(default)]
+    pub degraded: bool,
+    /// Summary of the most recent refresh, absent until the first one completes.
+    #[serde(default)]
+    pub last_refresh: Option<RefreshSummary>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RefreshSummary {
+    pub finished_at: DateTime<Utc>,
+    pub duration_ms: u64,
+    pub num_certs: usize,
+    pub num_paths: usize,
+    pub backends: Vec<BackendRefreshStats>,
+}
+
+impl RefreshSummary {
+    pub fn failing_backends(&self) -> usize {
+        self.backends.iter().filter(|b| b.error.is_some()).count()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackendRefreshStats {
+    pub name: String,
+    pub finished_at: DateTime<Utc>,
+    /// Time spent listing and fetching from this backend. Fetches run
+    /// concurrently, so this can exceed the wall-clock refresh duration.
+    pub duration_ms: u64,
+    pub num_certs: usize,
+    pub num_paths: usize,
+    pub error: Option<String>,
+    /// Directories the listing could not read. Their certificates are kept
+    /// from the previous refresh.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub list_failures: Vec<ListFailure>,
+}
+
+/// A directory left out of an otherwise successful listing.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ListFailure {
+    pub path: String,
+    pub error: String,
+}
+
+impl BackendRefreshStats {
+    pub fn new(name: &str) -> Self {
+        BackendRefreshStats {
+            name: name.to_string(),
+            finished_at: Utc::now(),
+            duration_ms: 0,
+            num_certs: 0,
+            num_paths: 0,
+            error: None,
+            list_failures: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackendStatus {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub backend_type: String,
+    pub healthy: bool,
+    pub error: Option<String>,
+    /// When the current run of failed refreshes started.
+    pub failing_since: Option<DateTime<Utc>>,
+    /// Failing for longer than `status.stale_after_hours`.
+    pub stale: bool,
+    /// The state of the backend's token, for backends whose tokens expire.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auth: Option<AuthStatus>,
+}
+
+/// How a backend's token is holding up.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AuthStatus {
+    /// When the current token expires. Unset for tokens that never expire,
+    /// or before one has been obtained.
+    pub expires_at: Option<DateTime<Utc>>,
+    pub renewable: bool,
+    /// When the token was last obtained, renewed or checked.
+    pub checked_at: Option<DateTime<Utc>>,
+    /// Why the last renewal or login failed, until one succeeds.
+    pub error: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -56,13 +310,75 @@ pub struct SchedulerInfo {
     pub workers: usize,
     pub pending_tasks: usize,
     pub running_tasks: usize,
+    /// Whether scheduled refreshes are paused.
+    #[serde(default)]
+    pub paused: bool,
+    /// Execution statistics per task type and backend.
+    #[serde(default)]
+    pub task_stats: Vec<TaskTypeStats>,
 }
 
+/// Outcomes and durations of task executions (each retry attempt counts as
+/// one execution) for one task type against one backend.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TaskTypeStats {
+    pub task_type: String,
+    pub backend: String,
+    pub succeeded: u64,
+    pub failed: u64,
+    /// Sum of all execution durations, for computing averages.
+    pub total_duration_ms: u64,
+    pub avg_duration_ms: u64,
+    /// Percentiles over the most recent executions.
+    pub p50_duration_ms: u64,
+    pub p95_duration_ms: u64,
+    pub p99_duration_ms: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 #[serde(tag = "type")]
 pub enum Task {
-    RefreshBackend { backend_name: String },
-    RenewAuthToken { backend_name: String },
+    RefreshBackend {
+        backend_name: String,
+    },
+    RenewAuthToken {
+        backend_name: String,
+    },
+    /// Runs a `task` hook's command for the certificate `sha1`.
+    RunHook {
+        hook_name: String,
+        sha1: String,
+    },
+}
+
+impl Task {
+    /// Stable name of the task type, used as a metrics label.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Task::RefreshBackend { .. } => "refresh_backend",
+            Task::RenewAuthToken { .. } => "renew_auth_token",
+            Task::RunHook { .. } => "run_hook",
+        }
+    }
+
+    /// The backend the task works on, or the hook name for hook tasks.
+    pub fn backend_name(&self) -> &str {
+        match self {
+            Task::RefreshBackend { backend_name } | Task::RenewAuthToken { backend_name } => {
+                backend_name
+            }
+            Task::RunHook { hook_name, .. } => hook_name,
+        }
+    }
+
+    /// Whether a failed run is retried with backoff, so an outage does not
+    /// leave the cache stale until the next interval or let a token lapse.
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            Task::RefreshBackend { .. } | Task::RenewAuthToken { .. } | Task::RunHook { .. }
+        )
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -74,6 +390,15 @@ pub struct TaskInfo {
     pub completed_at: Option<DateTime<Utc>>,
     pub status: TaskStatus,
     pub error: Option<String>,
+    /// Statistics from a completed refresh task.
+    #[serde(default)]
+    pub stats: Option<PopulateStats>,
+    /// Number of times the task has been started.
+    #[serde(default)]
+    pub attempts: u32,
+    /// When a failed task is due to be retried.
+    #[serde(default)]
+    pub next_retry_at: Option<DateTime<Utc>>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -82,6 +407,8 @@ pub enum TaskStatus {
     Running,
     Completed,
     Failed,
+    /// Dropped from the queue because the scheduler shut down first.
+    Cancelled,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -89,6 +416,51 @@ pub struct RefreshRequest {
     pub backends: Option<Vec<String>>,
 }
 
+/// A hypothetical certificate to run through the notification routing.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NotificationSimulationRequest {
+    pub subject: String,
+    /// Labels the certificate would carry, matched against the label
+    /// selectors of the notification backend and webhook endpoints.
+    #[serde(default)]
+    pub labels: HashMap<String, String>,
+    pub days_to_expiry: i64,
+    /// Where the certificate would be found, matched against notification
+    /// routes. Routes on backend or path do not match without it.
+    #[serde(default)]
+    pub backend: Option<String>,
+    #[serde(default)]
+    pub path: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NotificationSimulation {
+    pub subject: String,
+    pub not_after: DateTime<Utc>,
+    pub labels: HashMap<String, String>,
+    /// Everything that would fire, empty when the certificate is not alerted on.
+    pub matches: Vec<SimulatedNotification>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SimulatedNotification {
+    pub rule: String,
+    /// Notification backend type, or `webhook`.
+    pub backend: String,
+    pub urgency: Option<String>,
+    pub recipients: Vec<String>,
+}
+
+/// The outcome of sending a test notification via one backend.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NotificationTestResult {
+    /// The notification backend's name, or its type when unnamed.
+    pub backend: String,
+    pub recipients: Vec<String>,
+    pub success: bool,
+    pub error: Option<String>,
+}
+
 pub type PathList = Vec<String>;
 
 #[derive(Debug, Clone)]
@@ -108,9 +480,17 @@ pub struct CertificateData {
     pub key_usage: Vec<String>,
     pub ext_key_usage: Vec<String>,
     pub is_ca: bool,
+    pub key_algorithm: String,
+    pub key_size: usize,
+    pub subject_key_id: String,
+    pub authority_key_id: String,
     pub fingerprint_sha1: String,
     pub fingerprint_sha256: String,
     pub pem_data: String,
+    /// Issuing certificates delivered alongside this one (e.g. the rest of a
+    /// PEM bundle or a TLS handshake), nearest issuer first.
+    #[serde(default)]
+    pub chain: Vec<CertificateData>,
 }
 
 impl CertificateData {
@@ -157,12 +537,81 @@ impl CertificateData {
             .flatten()
             .collect();
 
-        let key_usage = vec![]; // TODO: Parse key usage extensions
-        let ext_key_usage = vec![]; // TODO: Parse extended key usage
+        let key_usage = match cert.key_usage() {
+            Ok(Some(ext)) => {
+                let usage = ext.value;
+                [
+                    (usage.digital_signature(), "digital_signature"),
+                    (usage.non_repudiation(), "non_repudiation"),
+                    (usage.key_encipherment(), "key_encipherment"),
+                    (usage.data_encipherment(), "data_encipherment"),
+                    (usage.key_agreement(), "key_agreement"),
+                    (usage.key_cert_sign(), "key_cert_sign"),
+                    (usage.crl_sign(), "crl_sign"),
+                    (usage.encipher_only(), "encipher_only"),
+                    (usage.decipher_only(), "decipher_only"),
+                ]
+                .into_iter()
+                .filter(|(set, _)| *set)
+                .map(|(_, name)| name.to_string())
+                .collect()
+            }
+            _ => vec![],
+        };
+
+        let ext_key_usage = match cert.extended_key_usage() {
+            Ok(Some(ext)) => {
+                let usage = ext.value;
+                let mut names: Vec<String> = [
+                    (usage.any, "any"),
+                    (usage.server_auth, "server_auth"),
+                    (usage.client_auth, "client_auth"),
+                    (usage.code_signing, "code_signing"),
+                    (usage.email_protection, "email_protection"),
+                    (usage.time_stamping, "time_stamping"),
+                    (usage.ocsp_signing, "ocsp_signing"),
+                ]
+                .into_iter()
+                .filter(|(set, _)| *set)
+                .map(|(_, name)| name.to_string())
+                .collect();
+                names.extend(usage.other.iter().map(|oid| oid.to_id_string()));
+                names
+            }
+            _ => vec![],
+        };
+
+        let (key_algorithm, key_size) = match cert.public_key().parsed() {
+            Ok(key) => {
+                let algorithm = match key {
+                    PublicKey::RSA(_) => "RSA",
+                    PublicKey::EC(_) => "EC",
+                    PublicKey::DSA(_) => "DSA",
+                    PublicKey::GostR3410(_) | PublicKey::GostR3410_2012(_) => "GOST",
+                    PublicKey::Unknown(_) => "unknown",
+                };
+                (algorithm.to_string(), key.key_size())
+            }
+            Err(_) => ("unknown".to_string(), 0),
+        };
         let is_ca = cert.extensions().iter().any(
             |ext| matches!(ext.parsed_extension(), ParsedExtension::BasicConstraints(bc) if bc.ca),
         );
 
+        let mut subject_key_id = String::new();
+        let mut authority_key_id = String::new();
+        for ext in cert.extensions() {
+            match ext.parsed_extension() {
+                ParsedExtension::SubjectKeyIdentifier(id) => subject_key_id = hex::encode(id.0),
+                ParsedExtension::AuthorityKeyIdentifier(aki) => {
+                    if let Some(id) = &aki.key_identifier {
+                        authority_key_id = hex::encode(id.0);
+                    }
+                }
+                _ => {}
+            }
+        }
+
         Ok(CertificateData {
             subject,
             not_before: not_before_dt,
@@ -173,9 +622,94 @@ impl CertificateData {
             key_usage,
             ext_key_usage,
             is_ca,
+            key_algorithm,
+            key_size,
+            subject_key_id,
+            authority_key_id,
             fingerprint_sha1,
             fingerprint_sha256,
             pem_data: pem_data.to_string(),
+            chain: Vec::new(),
         })
     }
+
+    pub fn from_der(der: &[u8]) -> crate::Result<Self> {
+        let (_, cert) = parse_x509_certificate(der).map_err(|e| {
+            crate::DoomsdayError::x509(format!("Failed to parse certificate: {}", e))
+        })?;
+        let pem_data = format!(
+            "-----BEGIN CERTIFICATE-----\n{}\n-----END CERTIFICATE-----",
+            BASE64_STANDARD.encode(der)
+        );
+        Self::from_x509(&cert, &pem_data)
+    }
+
+    /// Parses PEM data holding one or more certificates. The first is the
+    /// certificate itself and any that follow become its `chain`; other
+    /// blocks such as private keys are skipped.
+    pub fn from_pem_bundle(pem_data: &str) -> crate::Result<Self> {
+        let mut certs = Self::parse_pem_certificates(pem_data)?.into_iter();
+        let mut cert = certs
+            .next()
+            .ok_or_else(|| crate::DoomsdayError::x509("No certificate found in PEM data"))?;
+        cert.chain = certs.collect();
+        Ok(cert)
+    }
+
+    /// Every certificate in a PEM file or bundle, or the single certificate
+    /// in a DER file. Bundled certificates are returned on their own rather
+    /// than as a chain, for auditing each one.
+    pub fn parse_file(data: &[u8]) -> crate::Result<Vec<Self>> {
+        match std::str::from_utf8(data) {
+            Ok(pem_data) if pem_data.contains("-----BEGIN") => {
+                Self::parse_pem_certificates(pem_data)
+            }
+            _ => Ok(vec![Self::from_der(data)?]),
+        }
+    }
+
+    /// Adds the certificates in `pem_data` (e.g. a separately stored CA) to
+    /// the chain, skipping any already present.
+    pub fn extend_chain_from_pem(&mut self, pem_data: &str) -> crate::Result<()> {
+        for cert in Self::parse_pem_certificates(pem_data)? {
+            let known = cert.fingerprint_sha1 == self.fingerprint_sha1
+                || self
+                    .chain
+                    .iter()
+                    .any(|c| c.fingerprint_sha1 == cert.fingerprint_sha1);
+            if !known {
+                self.chain.push(cert);
+            }
+        }
+        Ok(())
+    }
+
+    fn parse_pem_certificates(pem_data: &str) -> crate::Result<Vec<Self>> {
+        let mut certs = Vec::new();
+        for pem in x509_parser::pem::Pem::iter_from_buffer(pem_data.as_bytes()) {
+            let pem =
+                pem.map_err(|e| crate::DoomsdayError::x509(format!("Failed to parse PEM: {}", e)))?;
+            if pem.label != "CERTIFICATE" {
+                continue;
+            }
+            certs.push(Self::from_der(&pem.contents)?);
+        }
+        Ok(certs)
+    }
+
+    pub fn metadata(&self) -> CertificateMetadata {
+        CertificateMetadata {
+            issuer: self.issuer.clone(),
+            serial_number: self.serial_number.clone(),
+            subject_alt_names: self.subject_alt_names.clone(),
+            fingerprint_sha256: self.fingerprint_sha256.clone(),
+            key_algorithm: self.key_algorithm.clone(),
+            key_size: self.key_size,
+            key_usage: self.key_usage.clone(),
+            ext_key_usage: self.ext_key_usage.clone(),
+            is_ca: self.is_ca,
+            subject_key_id: self.subject_key_id.clone(),
+            authority_key_id: self.authority_key_id.clone(),
+        }
+    }
 }
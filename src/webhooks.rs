@@ -0,0 +1,248 @@
+use crate::cache::{Cache, CacheEvent};
+use crate::config::{WebhookConfig, WebhookEndpointConfig};
+use crate::labels::matches_selector;
+use crate::types::SimulatedNotification;
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::collections::HashMap;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::time::{sleep, Duration};
+
+type HmacSha256 = Hmac<Sha256>;
+
+const DEFAULT_MAX_RETRIES: u32 = 3;
+
+/// Delivers cache events to the configured outbound webhooks.
+///
+/// Each delivery is a JSON `POST` carrying the event name in the `event` field
+/// and the `X-Doomsday-Event` header. When a secret is configured the body is
+/// signed with HMAC-SHA256 and sent as `X-Doomsday-Signature: sha256=<hex>`.
+pub struct WebhookDispatcher {
+    config: WebhookConfig,
+    cache: Cache,
+    client: reqwest::Client,
+}
+
+impl WebhookDispatcher {
+    pub fn new(config: WebhookConfig, cache: Cache) -> Self {
+        WebhookDispatcher {
+            config,
+            cache,
+            client: reqwest::Client::new(),
+        }
+    }
+
+    pub fn spawn(self) -> tokio::task::JoinHandle<()> {
+        tracing::info!(
+            "Starting webhook dispatcher for {} endpoints",
+            self.config.endpoints.len()
+        );
+        tokio::spawn(self.run())
+    }
+
+    async fn run(self) {
+        let mut events = self.cache.subscribe();
+
+        loop {
+            match events.recv().await {
+                Ok(event) => self.dispatch(&event),
+                Err(RecvError::Lagged(skipped)) => {
+                    tracing::warn!(
+                        "Webhook dispatcher lagged, {} cache events were dropped",
+                        skipped
+                    );
+                }
+                Err(RecvError::Closed) => break,
+            }
+        }
+
+        tracing::warn!("Webhook dispatcher stopped");
+    }
+
+    fn dispatch(&self, event: &CacheEvent) {
+        let mut payload = match serde_json::to_value(event) {
+            Ok(payload) => payload,
+            Err(e) => {
+                tracing::error!("Failed to serialize webhook event: {}", e);
+                return;
+            }
+        };
+        let labels = self.cache.label_rules().labels_for(event.certificate());
+        payload["timestamp"] = serde_json::json!(Utc::now().to_rfc3339());
+        payload["labels"] = serde_json::json!(labels);
+        let body = payload.to_string();
+
+        for endpoint in &self.config.endpoints {
+            if !endpoint.accepts(event.name()) || !matches_selector(&endpoint.labels, &labels) {
+                continue;
+            }
+
+            tokio::spawn(deliver(
+                self.client.clone(),
+                endpoint.clone(),
+                event.name(),
+                body.clone(),
+            ));
+        }
+    }
+}
+
+async fn deliver(
+    client: reqwest::Client,
+    endpoint: WebhookEndpointConfig,
+    event_name: &'static str,
+    body: String,
+) {
+    let max_retries = endpoint.max_retries.unwrap_or(DEFAULT_MAX_RETRIES);
+
+    for attempt in 0..=max_retries {
+        if attempt > 0 {
+            let backoff = Duration::from_secs(1 << (attempt - 1).min(6));
+            tracing::debug!(
+                "Retrying webhook {} in {:?} (attempt {}/{})",
+                endpoint.name,
+                backoff,
+                attempt + 1,
+                max_retries + 1
+            );
+            sleep(backoff).await;
+        }
+
+        let mut request = client
+            .post(&endpoint.url)
+            .header("Content-Type", "application/json")
+            .header("X-Doomsday-Event", event_name)
+            .body(body.clone());
+
+        if let Some(secret) = &endpoint.secret {
+            request = request.header(
+                "X-Doomsday-Signature",
+                format!("sha256={}", sign(secret, body.as_bytes())),
+            );
+        }
+
+        match request.send().await {
+            Ok(response) if response.status().is_success() => {
+                tracing::debug!("Delivered {} webhook to {}", event_name, endpoint.name);
+                return;
+            }
+            Ok(response) => tracing::warn!(
+                "Webhook {} rejected {} event: {}",
+                endpoint.name,
+                event_name,
+                response.status()
+            ),
+            Err(e) => tracing::warn!(
+                "Webhook {} delivery of {} event failed: {}",
+                endpoint.name,
+                event_name,
+                e
+            ),
+        }
+    }
+
+    tracing::error!(
+        "Giving up on {} webhook to {} after {} attempts",
+        event_name,
+        endpoint.name,
+        max_retries + 1
+    );
+}
+
+/// Reports which endpoints would receive a `threshold_crossed` event for the
+/// tightest of `thresholds` a certificate expiring in `days_until_expiry`
+/// days and carrying `labels` has crossed.
+pub fn simulate(
+    config: &WebhookConfig,
+    thresholds: &[i64],
+    days_until_expiry: i64,
+    labels: &HashMap<String, String>,
+) -> Option<SimulatedNotification> {
+    let threshold = tightest_threshold(thresholds, days_until_expiry)?;
+
+    let event = "threshold_crossed";
+    let recipients: Vec<String> = config
+        .endpoints
+        .iter()
+        .filter(|endpoint| endpoint.accepts(event) && matches_selector(&endpoint.labels, labels))
+        .map(|endpoint| format!("{} ({})", endpoint.name, endpoint.url))
+        .collect();
+
+    if recipients.is_empty() {
+        return None;
+    }
+
+    Some(SimulatedNotification {
+        rule: format!("{} ({} days)", event, threshold),
+        backend: "webhook".to_string(),
+        urgency: None,
+        recipients,
+    })
+}
+
+/// The smallest threshold a certificate expiring in `days_until_expiry` days
+/// has reached.
+pub fn tightest_threshold(thresholds: &[i64], days_until_expiry: i64) -> Option<i64> {
+    thresholds
+        .iter()
+        .copied()
+        .filter(|threshold| days_until_expiry <= *threshold)
+        .min()
+}
+
+/// Hex-encoded HMAC-SHA256 of `body` keyed with `secret`.
+pub fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac =
+        HmacSha256::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(body);
+    hex::encode(mac.finalize().into_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sign() {
+        // RFC 4231 test case 2
+        assert_eq!(
+            sign("Jefe", b"what do ya want for nothing?"),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[test]
+    fn test_simulate_routes_to_subscribed_endpoints() {
+        let endpoint = |name: &str, events: &[&str]| WebhookEndpointConfig {
+            name: name.to_string(),
+            url: format!("https://{}.example.com", name),
+            secret: None,
+            events: events.iter().map(|e| e.to_string()).collect(),
+            max_retries: None,
+            labels: HashMap::new(),
+        };
+        let thresholds = [30, 7, 1];
+        let config = WebhookConfig {
+            thresholds: None,
+            check_interval: None,
+            endpoints: vec![
+                endpoint("all", &[]),
+                endpoint("removals", &["certificate_removed"]),
+            ],
+        };
+
+        let labels = HashMap::new();
+        let simulated = simulate(&config, &thresholds, 5, &labels).unwrap();
+        assert_eq!(simulated.rule, "threshold_crossed (7 days)");
+        assert_eq!(simulated.recipients, vec!["all (https://all.example.com)"]);
+        assert!(simulate(&config, &thresholds, 90, &labels).is_none());
+
+        // Endpoints with a label selector only hear about matching certificates
+        let mut config = config;
+        config.endpoints[0].labels = HashMap::from([("team".to_string(), "web".to_string())]);
+        assert!(simulate(&config, &thresholds, 5, &labels).is_none());
+        let labels = HashMap::from([("team".to_string(), "web".to_string())]);
+        assert!(simulate(&config, &thresholds, 5, &labels).is_some());
+    }
+}
@@ -0,0 +1,323 @@
+(function () {
+    'use strict';
+
+    const TOKEN_KEY = 'doomsday-token';
+    // Row styling per server-computed certificate status
+    const STATUS_CLASSES = {
+        ok: 'ok',
+        expiring_soon: 'expiring',
+        expired: 'expired',
+        revoked: 'expired',
+        not_yet_valid: 'pending',
+        superseded: 'superseded',
+    };
+
+    const state = {
+        certificates: [],
+        backend: '',
+        sortKey: 'not_after',
+        sortAscending: true,
+    };
+
+    const $ = (id) => document.getElementById(id);
+
+    function getToken() {
+        return localStorage.getItem(TOKEN_KEY);
+    }
+
+    function setToken(token) {
+        if (token) {
+            localStorage.setItem(TOKEN_KEY, token);
+        } else {
+            localStorage.removeItem(TOKEN_KEY);
+        }
+    }
+
+    async function api(path, options) {
+        const headers = { 'Content-Type': 'application/json' };
+        const token = getToken();
+        if (token) {
+            headers['X-Doomsday-Token'] = token;
+        }
+
+        const response = await fetch(path, Object.assign({ headers }, options || {}));
+        if (response.status === 401) {
+            setToken(null);
+            throw new AuthRequired();
+        }
+        if (!response.ok) {
+            throw new Error(`${path} failed: ${response.status} ${response.statusText}`);
+        }
+        return response.json();
+    }
+
+    class AuthRequired extends Error {}
+
+    function showError(message) {
+        const banner = $('error');
+        banner.textContent = message;
+        banner.hidden = !message;
+    }
+
+    function showLogin() {
+        $('login').hidden = false;
+        $('content').hidden = true;
+        $('logout-button').hidden = true;
+    }
+
+    function showContent(authRequired) {
+        $('login').hidden = true;
+        $('content').hidden = false;
+        $('logout-button').hidden = !authRequired;
+    }
+
+    function statusOf(cert) {
+        return cert.status || 'ok';
+    }
+
+    function formatRemaining(notAfter) {
+        let seconds = Math.floor((new Date(notAfter) - new Date()) / 1000);
+        if (seconds < 0) {
+            return 'expired';
+        }
+        const parts = [];
+        const units = [['y', 365 * 24 * 3600], ['d', 24 * 3600], ['h', 3600], ['m', 60]];
+        for (const [unit, size] of units) {
+            if (seconds >= size) {
+                parts.push(`${Math.floor(seconds / size)}${unit}`);
+                seconds %= size;
+            }
+        }
+        return parts.length ? parts.slice(0, 2).join('') : `${seconds}s`;
+    }
+
+    function formatAgo(timestamp) {
+        const seconds = Math.max(0, Math.floor((new Date() - new Date(timestamp)) / 1000));
+        if (seconds < 60) {
+            return 'just now';
+        }
+        const units = [['d', 24 * 3600], ['h', 3600], ['m', 60]];
+        for (const [unit, size] of units) {
+            if (seconds >= size) {
+                return `${Math.floor(seconds / size)}${unit} ago`;
+            }
+        }
+        return 'just now';
+    }
+
+    function renderRefreshSummary(summary) {
+        const element = $('refresh-summary');
+        if (!summary) {
+            element.textContent = 'no scan yet';
+            element.title = '';
+            element.className = 'refresh-summary';
+            return;
+        }
+
+        const failing = summary.backends.filter((b) => b.error).length;
+        const parts = [
+            `last scan ${formatAgo(summary.finished_at)}`,
+            `${summary.backends.length} backend${summary.backends.length === 1 ? '' : 's'}`,
+        ];
+        if (failing) {
+            parts.push(`${failing} failing`);
+        }
+        element.textContent = parts.join(', ');
+        element.title = summary.backends
+            .map((b) => `${b.name}: ${b.error ? `failed (${b.error})` : `${b.num_certs} certs in ${b.duration_ms}ms`}`)
+            .join('\n');
+        element.className = failing ? 'refresh-summary failing' : 'refresh-summary';
+    }
+
+    function backendsOf(cert) {
+        return [...new Set(cert.paths.map((p) => p.backend))].sort();
+    }
+
+    function renderStats(stats) {
+        $('stat-expired').textContent = stats.expired;
+        $('stat-expiring').textContent = stats.expiring_soon;
+        $('stat-ok').textContent = stats.ok;
+        $('stat-total').textContent = stats.total;
+    }
+
+    function renderBackendTabs() {
+        const backends = new Set();
+        state.certificates.forEach((cert) => backendsOf(cert).forEach((b) => backends.add(b)));
+
+        const tabs = $('backend-tabs');
+        tabs.innerHTML = '';
+        ['', ...[...backends].sort()].forEach((backend) => {
+            const button = document.createElement('button');
+            button.type = 'button';
+            button.textContent = backend || 'All backends';
+            button.className = backend === state.backend ? 'active' : '';
+            button.addEventListener('click', () => {
+                state.backend = backend;
+                renderBackendTabs();
+                renderTable();
+            });
+            tabs.appendChild(button);
+        });
+    }
+
+    function sortValue(cert, key) {
+        switch (key) {
+            case 'subject':
+                return cert.subject.toLowerCase();
+            case 'backends':
+                return backendsOf(cert).join(',');
+            case 'paths':
+                return cert.paths.length;
+            case 'status':
+                return statusOf(cert);
+            default:
+                return new Date(cert.not_after).getTime();
+        }
+    }
+
+    function renderTable() {
+        const search = $('search').value.trim().toLowerCase();
+        const status = $('status-filter').value;
+
+        const rows = state.certificates
+            .filter((cert) => !state.backend || backendsOf(cert).includes(state.backend))
+            .filter((cert) => !status || statusOf(cert) === status)
+            .filter((cert) => {
+                if (!search) {
+                    return true;
+                }
+                return cert.subject.toLowerCase().includes(search)
+                    || cert.paths.some((p) => p.path.toLowerCase().includes(search));
+            })
+            .sort((a, b) => {
+                const left = sortValue(a, state.sortKey);
+                const right = sortValue(b, state.sortKey);
+                const order = left < right ? -1 : left > right ? 1 : 0;
+                return state.sortAscending ? order : -order;
+            });
+
+        const body = $('certificate-rows');
+        body.innerHTML = '';
+        rows.forEach((cert) => {
+            const row = document.createElement('tr');
+            row.className = STATUS_CLASSES[statusOf(cert)] || '';
+            const cells = [
+                cert.subject,
+                new Date(cert.not_after).toISOString().replace('T', ' ').slice(0, 16) + ' UTC',
+                formatRemaining(cert.not_after),
+                statusOf(cert).replace(/_/g, ' '),
+                backendsOf(cert).join(', '),
+                cert.paths.map((p) => `${p.backend}:${p.path}`).join('\n'),
+            ];
+            cells.forEach((text) => {
+                const cell = document.createElement('td');
+                cell.textContent = text;
+                cell.title = text;
+                row.appendChild(cell);
+            });
+            body.appendChild(row);
+        });
+
+        $('empty').hidden = rows.length > 0;
+
+        document.querySelectorAll('th[data-sort]').forEach((th) => {
+            th.classList.remove('sorted-asc', 'sorted-desc');
+            if (th.dataset.sort === state.sortKey) {
+                th.classList.add(state.sortAscending ? 'sorted-asc' : 'sorted-desc');
+            }
+        });
+    }
+
+    async function load() {
+        showError('');
+        let info;
+        try {
+            info = await api('/v1/info');
+            $('version').textContent = `v${info.version}`;
+            renderRefreshSummary(info.last_refresh);
+        } catch (e) {
+            showError(`Could not reach the Doomsday server: ${e.message}`);
+            return;
+        }
+
+        if (info.auth_required && !getToken()) {
+            showLogin();
+            return;
+        }
+
+        try {
+            const [certificates, stats] = await Promise.all([
+                api('/v1/cache'),
+                api('/v1/cache/stats'),
+            ]);
+            state.certificates = certificates;
+            renderStats(stats);
+            renderBackendTabs();
+            renderTable();
+            showContent(info.auth_required);
+        } catch (e) {
+            if (e instanceof AuthRequired) {
+                showLogin();
+            } else {
+                showError(e.message);
+            }
+        }
+    }
+
+    async function login(event) {
+        event.preventDefault();
+        const form = event.target;
+        try {
+            const response = await api('/v1/auth', {
+                method: 'POST',
+                body: JSON.stringify({
+                    username: form.username.value,
+                    password: form.password.value,
+                }),
+            });
+            setToken(response.token);
+            form.reset();
+            await load();
+        } catch (e) {
+            showError('Authentication failed');
+        }
+    }
+
+    async function refresh() {
+        showError('');
+        try {
+            await api('/v1/cache/refresh', { method: 'POST', body: JSON.stringify({}) });
+            await load();
+        } catch (e) {
+            if (e instanceof AuthRequired) {
+                showLogin();
+            } else {
+                showError(e.message);
+            }
+        }
+    }
+
+    document.addEventListener('DOMContentLoaded', () => {
+        $('login-form').addEventListener('submit', login);
+        $('refresh-button').addEventListener('click', refresh);
+        $('logout-button').addEventListener('click', () => {
+            setToken(null);
+            showLogin();
+        });
+        $('search').addEventListener('input', renderTable);
+        $('status-filter').addEventListener('change', renderTable);
+        document.querySelectorAll('th[data-sort]').forEach((th) => {
+            th.addEventListener('click', () => {
+                if (state.sortKey === th.dataset.sort) {
+                    state.sortAscending = !state.sortAscending;
+                } else {
+                    state.sortKey = th.dataset.sort;
+                    state.sortAscending = true;
+                }
+                renderTable();
+            });
+        });
+
+        load();
+    });
+})();